@@ -0,0 +1,164 @@
+//! Derive macro for cj_bitmask_vec. Use it through the `derive` feature of cj_bitmask_vec.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, ExprLit, Fields, Ident, Lit};
+
+/// Turns a fieldless enum into named bits of an unsigned integer bitmask.<br>
+///
+/// Each variant becomes one bit: its explicit discriminant, or else one more than the bit of the
+/// variant before it (0 for the first), the way rustc numbers discriminants. Two variants
+/// cannot share a bit.
+/// The bitmask type comes from `#[repr(u8)]` through `#[repr(u128)]` and defaults to u32.
+///
+/// Generated for the enum:
+/// - `bit(self)` returning the variant's bitmask, and `ALL` with every variant's bit set
+/// - `From<Enum>` for the bitmask type
+/// - `Enum | Enum`, `bitmask | Enum` and `bitmask & Enum`, all producing the bitmask type
+/// - `bitmask |= Enum`
+#[proc_macro_derive(BitmaskFlags)]
+pub fn derive_bitmask_flags(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let name = &input.ident;
+    let data = match &input.data {
+        Data::Enum(x) => x,
+        _ => return Err(Error::new_spanned(name, "BitmaskFlags requires an enum")),
+    };
+
+    let repr = repr_type(&input)?;
+    let width = match repr.to_string().as_str() {
+        "u8" => 8,
+        "u16" => 16,
+        "u32" => 32,
+        "u64" => 64,
+        "u128" => 128,
+        _ => {
+            return Err(Error::new_spanned(
+                &repr,
+                "BitmaskFlags requires an unsigned repr from u8 to u128",
+            ))
+        }
+    };
+
+    let mut arms = Vec::new();
+    let mut bits = Vec::new();
+    let mut next = 0u32;
+    for variant in data.variants.iter() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                variant,
+                "BitmaskFlags variants cannot have fields",
+            ));
+        }
+        let bit = match &variant.discriminant {
+            Some((
+                _,
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(x), ..
+                }),
+            )) => x.base10_parse::<u32>()?,
+            Some((_, x)) => {
+                return Err(Error::new_spanned(
+                    x,
+                    "BitmaskFlags discriminants must be integer literals",
+                ))
+            }
+            None => next,
+        };
+        if bit >= width {
+            return Err(Error::new_spanned(
+                variant,
+                format!("bit {bit} does not fit in {repr}"),
+            ));
+        }
+        if bits.contains(&bit) {
+            return Err(Error::new_spanned(
+                variant,
+                format!("bit {bit} is already used by another variant"),
+            ));
+        }
+        next = bit + 1;
+        let ident = &variant.ident;
+        arms.push(quote! { #name::#ident => 1 << #bit });
+        bits.push(bit);
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Bitmask with every variant's bit set
+            pub const ALL: #repr = 0 #(| (1 << #bits))*;
+
+            /// Returns the bitmask with only this variant's bit set
+            #[inline]
+            pub const fn bit(self) -> #repr {
+                match self {
+                    #(#arms,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::core::convert::From<#name #ty_generics> for #repr #where_clause {
+            #[inline]
+            fn from(value: #name #ty_generics) -> Self {
+                value.bit()
+            }
+        }
+
+        impl #impl_generics ::core::ops::BitOr for #name #ty_generics #where_clause {
+            type Output = #repr;
+
+            #[inline]
+            fn bitor(self, rhs: Self) -> #repr {
+                self.bit() | rhs.bit()
+            }
+        }
+
+        impl #impl_generics ::core::ops::BitOr<#name #ty_generics> for #repr #where_clause {
+            type Output = #repr;
+
+            #[inline]
+            fn bitor(self, rhs: #name #ty_generics) -> #repr {
+                self | rhs.bit()
+            }
+        }
+
+        impl #impl_generics ::core::ops::BitAnd<#name #ty_generics> for #repr #where_clause {
+            type Output = #repr;
+
+            #[inline]
+            fn bitand(self, rhs: #name #ty_generics) -> #repr {
+                self & rhs.bit()
+            }
+        }
+
+        impl #impl_generics ::core::ops::BitOrAssign<#name #ty_generics> for #repr #where_clause {
+            #[inline]
+            fn bitor_assign(&mut self, rhs: #name #ty_generics) {
+                *self |= rhs.bit();
+            }
+        }
+    })
+}
+
+fn repr_type(input: &DeriveInput) -> Result<Ident, Error> {
+    let mut repr = None;
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                if ident.to_string().starts_with('u') {
+                    repr = Some(ident.clone());
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr.unwrap_or_else(|| Ident::new("u32", Span::call_site())))
+}
@@ -0,0 +1,73 @@
+use cj_bitmask_vec_derive::BitmaskFlags;
+
+#[derive(BitmaskFlags, Clone, Copy)]
+#[repr(u8)]
+enum State {
+    Queued,
+    Running,
+    Failed,
+}
+
+#[derive(BitmaskFlags, Clone, Copy)]
+enum Sparse {
+    Low = 1,
+    High = 31,
+}
+
+#[derive(BitmaskFlags, Clone, Copy)]
+#[repr(u16)]
+enum Mixed {
+    First,
+    Second = 4,
+    Third,
+    Fourth = 9,
+    Fifth,
+}
+
+#[derive(BitmaskFlags, Clone, Copy)]
+#[repr(u128)]
+enum Wide {
+    Top = 127,
+}
+
+#[test]
+fn test_derive_bit() {
+    assert_eq!(State::Queued.bit(), 0b001u8);
+    assert_eq!(State::Running.bit(), 0b010u8);
+    assert_eq!(State::Failed.bit(), 0b100u8);
+    assert_eq!(State::ALL, 0b111u8);
+
+    assert_eq!(Sparse::Low.bit(), 0b10u32);
+    assert_eq!(Sparse::High.bit(), 1u32 << 31);
+    assert_eq!(Wide::Top.bit(), 1u128 << 127);
+}
+
+#[test]
+fn test_derive_mixed_discriminants() {
+    // implicit bits follow the previous variant, as rustc numbers discriminants
+    for x in [
+        Mixed::First,
+        Mixed::Second,
+        Mixed::Third,
+        Mixed::Fourth,
+        Mixed::Fifth,
+    ] {
+        assert_eq!(x.bit(), 1u16 << (x as u16));
+    }
+    assert_eq!(Mixed::Third.bit(), 1u16 << 5);
+    assert_eq!(Mixed::Fifth.bit(), 1u16 << 10);
+    assert_eq!(Mixed::ALL, 0b11000110001);
+}
+
+#[test]
+fn test_derive_ops() {
+    let mut mask: u8 = State::Queued | State::Failed;
+    assert_eq!(mask, 0b101);
+    assert_eq!(mask & State::Failed, 0b100);
+    assert_eq!(mask & State::Running, 0);
+
+    mask |= State::Running;
+    assert_eq!(mask, State::ALL);
+    assert_eq!(0u8 | State::Running, 0b010);
+    assert_eq!(u8::from(State::Failed), 0b100);
+}
@@ -0,0 +1,75 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use arbitrary::{size_hint, Arbitrary, Result, Unstructured};
+use cj_common::cj_binary::bitbuf::*;
+
+impl<'a, B, T> Arbitrary<'a> for BitmaskItem<B, T>
+where
+    B: Bitflag + Arbitrary<'a>,
+    T: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            bitmask: B::arbitrary(u)?,
+            item: T::arbitrary(u)?,
+        })
+    }
+
+    #[inline]
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        size_hint::and(B::size_hint(depth), T::size_hint(depth))
+    }
+}
+
+impl<'a, B, T> Arbitrary<'a> for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Arbitrary<'a>,
+    T: Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut v = Self::new();
+        *v.as_mut_vec() = Vec::<BitmaskItem<B, T>>::arbitrary(u)?;
+        Ok(v)
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        let mut v = Self::new();
+        *v.as_mut_vec() = Vec::<BitmaskItem<B, T>>::arbitrary_take_rest(u)?;
+        Ok(v)
+    }
+
+    #[inline]
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        Vec::<BitmaskItem<B, T>>::size_hint(depth)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_item::BitmaskItem;
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use arbitrary::{Arbitrary, Unstructured};
+
+    #[test]
+    fn test_bitmask_arbitrary_item() {
+        let mut u = Unstructured::new(&[0b00000010, 1, 0, 0, 0]);
+        let x = BitmaskItem::<u8, u32>::arbitrary(&mut u).unwrap();
+
+        assert_eq!(x.bitmask, 0b00000010);
+        assert_eq!(BitmaskItem::<u8, u32>::size_hint(0), (5, Some(5)));
+    }
+
+    #[test]
+    fn test_bitmask_arbitrary_vec() {
+        let data = vec![0xFFu8; 64];
+        let v = BitmaskVec::<u8, u16>::arbitrary_take_rest(Unstructured::new(&data)).unwrap();
+
+        assert!(!v.is_empty());
+        assert_eq!(v.count_matching(&0b00000001), v.len());
+
+        let mut u = Unstructured::new(&data);
+        let v2 = BitmaskVec::<u16, u8>::arbitrary(&mut u).unwrap();
+        assert!(v2.len() <= data.len() / 3);
+    }
+}
@@ -0,0 +1,436 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVecIter, BitmaskVecIterMut, BitmaskVecIterWithMask, BitmaskVecIterWithMaskMut,
+};
+use cj_common::cj_binary::bitbuf::*;
+use core::fmt::{Debug, Display, Formatter};
+use core::mem::MaybeUninit;
+use core::ops::{Index, IndexMut};
+use core::ptr;
+
+/// Error returned when pushing to a full BitmaskArrayVec. Holds the rejected BitmaskItem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityError<E> {
+    element: E,
+}
+
+impl<E> CapacityError<E> {
+    pub fn new(element: E) -> Self {
+        Self { element }
+    }
+
+    /// Returns the element that could not be added.
+    pub fn into_inner(self) -> E {
+        self.element
+    }
+}
+
+impl<E> Display for CapacityError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "insufficient capacity")
+    }
+}
+
+impl<E: Debug> std::error::Error for CapacityError<E> {}
+
+/// BitmaskArrayVec is a fixed capacity, stack allocated vec that pairs bitmasks with T.<br>
+///
+/// It offers the same push/pop/filter API as BitmaskVec without any heap usage.
+/// push() panics when full, try_push() returns a CapacityError instead.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskArrayVec::<u8, i32, 4>::new();
+/// v.push_with_mask(0b00000000, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000011, 102);
+/// v.push(103);
+/// assert!(v.is_full());
+///
+/// let err = v.try_push_with_mask(0b00000010, 104).unwrap_err();
+/// assert_eq!(err.into_inner().item, 104);
+///
+/// let mut count = 0;
+/// let mut iter = v.iter_with_mask();
+/// while let Some(pair) = iter.filter_mask(&0b00000010) {
+///     assert!([101, 102].contains(&pair.item));
+///     count += 1;
+/// }
+/// assert_eq!(count, 2);
+/// ```
+pub struct BitmaskArrayVec<B, T, const N: usize>
+where
+    B: Bitflag,
+{
+    inner: [MaybeUninit<BitmaskItem<B, T>>; N],
+    len: usize,
+}
+
+impl<'a, B, T, const N: usize> BitmaskArrayVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            inner: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the fixed capacity N.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of elements that can still be added.
+    #[inline]
+    pub fn remaining_capacity(&self) -> usize {
+        N - self.len
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if the vector is at capacity.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        // the first len elements are always initialized
+        unsafe { core::slice::from_raw_parts(self.inner.as_ptr() as *const _, self.len) }
+    }
+
+    /// Extracts a mutable slice containing the entire vector.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [BitmaskItem<B, T>] {
+        // the first len elements are always initialized
+        unsafe { core::slice::from_raw_parts_mut(self.inner.as_mut_ptr() as *mut _, self.len) }
+    }
+
+    /// Pushes a BitmaskItem, returning it inside a CapacityError if the vector is full.
+    #[inline]
+    pub fn try_push_bitmask_item(
+        &mut self,
+        value: BitmaskItem<B, T>,
+    ) -> Result<(), CapacityError<BitmaskItem<B, T>>> {
+        if self.len == N {
+            return Err(CapacityError::new(value));
+        }
+        self.inner[self.len].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Pushes T and a default bitmask of zero, or returns a CapacityError if the vector is full.
+    #[inline]
+    pub fn try_push(&mut self, value: T) -> Result<(), CapacityError<BitmaskItem<B, T>>> {
+        self.try_push_bitmask_item(BitmaskItem::new(B::default(), value))
+    }
+
+    /// Pushes T and the supplied bitmask, or returns a CapacityError if the vector is full.
+    #[inline]
+    pub fn try_push_with_mask(
+        &mut self,
+        bitmask: B,
+        value: T,
+    ) -> Result<(), CapacityError<BitmaskItem<B, T>>> {
+        self.try_push_bitmask_item(BitmaskItem::new(bitmask, value))
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    /// * panics if the vector is full. Use try_push() to handle this case.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        if self.try_push(value).is_err() {
+            panic!("BitmaskArrayVec is full (capacity {N})");
+        }
+    }
+
+    /// Pushes T and the supplied bitmask.
+    /// * panics if the vector is full. Use try_push_with_mask() to handle this case.
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        if self.try_push_with_mask(bitmask, value).is_err() {
+            panic!("BitmaskArrayVec is full (capacity {N})");
+        }
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // the element at len was initialized and is no longer tracked
+        Some(unsafe { self.inner[self.len].assume_init_read() })
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    /// * panics if index > len or the vector is full.
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        assert!(index <= self.len, "insertion index out of bounds");
+        self.push_with_mask(bitmask, value);
+        self.as_mut_slice()[index..].rotate_right(1);
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    /// * panics if index > len or the vector is full.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        assert!(index < self.len, "removal index out of bounds");
+        self.as_mut_slice()[index..].rotate_left(1);
+        self.pop_with_mask().unwrap()
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes an element and bitmask from the vector and returns it, replacing it with the last element.
+    pub fn swap_with_mask_remove(&mut self, index: usize) -> BitmaskItem<B, T> {
+        assert!(index < self.len, "removal index out of bounds");
+        let last = self.len - 1;
+        self.as_mut_slice().swap(index, last);
+        self.pop_with_mask().unwrap()
+    }
+
+    /// Removes an element without bitmask from the vector and returns it, replacing it with the last element.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.swap_with_mask_remove(index).item
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(
+            self.inner[len..self.len].as_mut_ptr() as *mut BitmaskItem<B, T>,
+            self.len - len,
+        );
+        self.len = len;
+        // the tail elements were initialized and are no longer tracked
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    /// * this iter excludes bitmask. Use iter_with_mask() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter(&'a mut self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.as_slice().iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a mut self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.as_slice().iter())
+    }
+
+    /// Returns a BitmaskVecIterMut for mutable iteration over T.
+    /// * this iter excludes bitmask. Use iter_with_mask_mut() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecIterMut<'a, B, T> {
+        BitmaskVecIterMut::new(self.as_mut_slice().iter_mut())
+    }
+
+    /// Returns a BitmaskVecIterWithMaskMut for mutable iteration over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask_mut(&'a mut self) -> BitmaskVecIterWithMaskMut<'a, B, T> {
+        BitmaskVecIterWithMaskMut::new(self.as_mut_slice().iter_mut())
+    }
+}
+
+impl<B, T, const N: usize> Drop for BitmaskArrayVec<B, T, N>
+where
+    B: Bitflag,
+{
+    fn drop(&mut self) {
+        let items = ptr::slice_from_raw_parts_mut(
+            self.inner.as_mut_ptr() as *mut BitmaskItem<B, T>,
+            self.len,
+        );
+        // the first len elements are always initialized
+        unsafe { ptr::drop_in_place(items) };
+    }
+}
+
+impl<'a, B, T, const N: usize> Default for BitmaskArrayVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T, const N: usize> Index<usize> for BitmaskArrayVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.as_slice()[index].item
+    }
+}
+
+impl<'a, B, T, const N: usize> IndexMut<usize> for BitmaskArrayVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.as_mut_slice()[index].item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_array_vec::BitmaskArrayVec;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_bitmask_array_vec_push() {
+        let mut v = BitmaskArrayVec::<u8, i32, 4>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push(0);
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.remaining_capacity(), 1);
+        assert_eq!(v[1], 400);
+    }
+
+    #[test]
+    fn test_bitmask_array_vec_try_push() {
+        let mut v = BitmaskArrayVec::<u8, i32, 2>::new();
+        assert!(v.try_push_with_mask(0b00000001, 100).is_ok());
+        assert!(v.try_push(101).is_ok());
+
+        let err = v.try_push_with_mask(0b00000010, 102).unwrap_err();
+        let x = err.into_inner();
+        assert_eq!(x.bitmask, 0b00000010);
+        assert_eq!(x.item, 102);
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_array_vec_push_full() {
+        let mut v = BitmaskArrayVec::<u8, i32, 1>::new();
+        v.push(100);
+        v.push(101);
+    }
+
+    #[test]
+    fn test_bitmask_array_vec_pop_with_mask() {
+        let mut v = BitmaskArrayVec::<u8, i32, 4>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(2, 999);
+
+        let x = v.pop_with_mask().unwrap();
+        assert_eq!(x.bitmask, 0b00000010);
+        assert_eq!(x.item, 999);
+        assert_eq!(v.pop(), Some(100));
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn test_bitmask_array_vec_insert_remove() {
+        let mut v = BitmaskArrayVec::<u8, i32, 4>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.insert(1, 500);
+        v.insert_with_mask(0, 0b11000000, 600);
+
+        assert_eq!(
+            v.as_slice().iter().map(|x| x.item).collect::<Vec<_>>(),
+            vec![600, 100, 500, 101]
+        );
+        assert_eq!(v.remove(2), 500);
+        let x = v.remove_with_mask(0);
+        assert_eq!(x.bitmask, 0b11000000);
+        assert_eq!(x.item, 600);
+        assert_eq!(v.swap_remove(0), 100);
+        assert_eq!(v[0], 101);
+    }
+
+    #[test]
+    fn test_bitmask_array_vec_filter() {
+        let mut v = BitmaskArrayVec::<u8, i32, 8>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        let mut count = 0;
+        let mut z = v.iter_with_mask();
+        while let Some(pair) = z.filter_mask(&0b00000010) {
+            assert!([101, 102, 104].contains(&pair.item));
+            count += 1;
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_bitmask_array_vec_iter_mut() {
+        let mut v = BitmaskArrayVec::<u8, i32, 4>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        for x in v.iter_mut() {
+            *x *= 2;
+        }
+        let total: i32 = v.iter().sum();
+        assert_eq!(total, 402);
+    }
+
+    #[test]
+    fn test_bitmask_array_vec_drop() {
+        let rc = Rc::new(0);
+        {
+            let mut v = BitmaskArrayVec::<u8, Rc<i32>, 4>::new();
+            v.push(rc.clone());
+            v.push(rc.clone());
+            v.push(rc.clone());
+            assert_eq!(Rc::strong_count(&rc), 4);
+            v.truncate(1);
+            assert_eq!(Rc::strong_count(&rc), 2);
+        }
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::cj_bitmask_vec::BitmaskVec;
+use arrow_array::types::*;
+use arrow_array::{Array, ArrayRef, ArrowPrimitiveType, PrimitiveArray, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema};
+use cj_common::cj_binary::bitbuf::*;
+use std::sync::Arc;
+
+/// Name of the RecordBatch column holding the bitmasks
+pub const MASK_COLUMN: &str = "mask";
+/// Name of the RecordBatch column holding T
+pub const ITEM_COLUMN: &str = "item";
+
+/// Bitmask types that can be stored in an Arrow column. Implemented for u8 through u64.
+pub trait ArrowMask: Sized + Copy {
+    type ArrowType: ArrowPrimitiveType<Native = Self>;
+}
+
+macro_rules! impl_arrow_mask {
+    ($($t:ty => $a:ty),*) => {$(
+        impl ArrowMask for $t {
+            type ArrowType = $a;
+        }
+    )*};
+}
+
+impl_arrow_mask!(u8 => UInt8Type, u16 => UInt16Type, u32 => UInt32Type, u64 => UInt64Type);
+
+/// Item types that can be stored in an Arrow column. Implemented for primitive numbers and String.
+pub trait ArrowItem: Sized {
+    /// Arrow DataType of the item column
+    fn data_type() -> DataType;
+
+    /// Builds an Arrow array from the items
+    fn to_array<I>(items: I) -> ArrayRef
+    where
+        I: IntoIterator<Item = Self>;
+
+    /// Reads items back out of an Arrow array. Nulls are rejected.
+    fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError>;
+}
+
+macro_rules! impl_arrow_item {
+    ($($t:ty => $a:ty),*) => {$(
+        impl ArrowItem for $t {
+            #[inline]
+            fn data_type() -> DataType {
+                <$a as ArrowPrimitiveType>::DATA_TYPE
+            }
+
+            fn to_array<I>(items: I) -> ArrayRef
+            where
+                I: IntoIterator<Item = Self>,
+            {
+                Arc::new(PrimitiveArray::<$a>::from_iter_values(items))
+            }
+
+            fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError> {
+                let array = downcast::<PrimitiveArray<$a>>(array, ITEM_COLUMN)?;
+                Ok(array.values().to_vec())
+            }
+        }
+    )*};
+}
+
+impl_arrow_item!(
+    i8 => Int8Type, i16 => Int16Type, i32 => Int32Type, i64 => Int64Type,
+    u8 => UInt8Type, u16 => UInt16Type, u32 => UInt32Type, u64 => UInt64Type,
+    f32 => Float32Type, f64 => Float64Type
+);
+
+impl ArrowItem for String {
+    #[inline]
+    fn data_type() -> DataType {
+        DataType::Utf8
+    }
+
+    fn to_array<I>(items: I) -> ArrayRef
+    where
+        I: IntoIterator<Item = Self>,
+    {
+        Arc::new(StringArray::from_iter_values(items))
+    }
+
+    fn from_array(array: &dyn Array) -> Result<Vec<Self>, ArrowError> {
+        let array = downcast::<StringArray>(array, ITEM_COLUMN)?;
+        Ok(array.iter().flatten().map(String::from).collect())
+    }
+}
+
+fn downcast<'b, A>(array: &'b dyn Array, column: &str) -> Result<&'b A, ArrowError>
+where
+    A: Array + 'static,
+{
+    if array.null_count() > 0 {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "column '{column}' contains nulls"
+        )));
+    }
+    array.as_any().downcast_ref::<A>().ok_or_else(|| {
+        ArrowError::SchemaError(format!(
+            "column '{column}' has unexpected type {}",
+            array.data_type()
+        ))
+    })
+}
+
+fn column<'b>(batch: &'b RecordBatch, name: &str) -> Result<&'b ArrayRef, ArrowError> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| ArrowError::SchemaError(format!("missing column '{name}'")))
+}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + ArrowMask,
+    T: ArrowItem,
+{
+    /// Returns the Arrow Schema used for RecordBatch conversion: a non-null mask column and a non-null item column.
+    pub fn arrow_schema() -> Schema {
+        Schema::new(vec![
+            Field::new(
+                MASK_COLUMN,
+                <B::ArrowType as ArrowPrimitiveType>::DATA_TYPE,
+                false,
+            ),
+            Field::new(ITEM_COLUMN, T::data_type(), false),
+        ])
+    }
+
+    /// Consumes the vector, moving bitmasks and T into an Arrow RecordBatch.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    ///
+    /// let batch = v.into_record_batch().unwrap();
+    /// assert_eq!(batch.num_rows(), 2);
+    ///
+    /// let v = BitmaskVec::<u8, i32>::try_from_record_batch(&batch).unwrap();
+    /// assert_eq!(v[1], 101);
+    /// ```
+    pub fn into_record_batch(mut self) -> Result<RecordBatch, ArrowError> {
+        let len = self.len();
+        let mut masks = Vec::with_capacity(len);
+        let mut items = Vec::with_capacity(len);
+        for x in self.drain(..) {
+            masks.push(x.bitmask);
+            items.push(x.item);
+        }
+        let masks: ArrayRef = Arc::new(PrimitiveArray::<B::ArrowType>::from_iter_values(masks));
+        RecordBatch::try_new(
+            Arc::new(Self::arrow_schema()),
+            vec![masks, T::to_array(items)],
+        )
+    }
+
+    /// Copies bitmasks and T into an Arrow RecordBatch, leaving the vector untouched.
+    pub fn to_record_batch(&self) -> Result<RecordBatch, ArrowError>
+    where
+        T: Clone,
+    {
+        let masks: ArrayRef = Arc::new(PrimitiveArray::<B::ArrowType>::from_iter_values(
+            self.as_slice().iter().map(|x| x.bitmask),
+        ));
+        let items = T::to_array(self.as_slice().iter().map(|x| x.item.clone()));
+        RecordBatch::try_new(Arc::new(Self::arrow_schema()), vec![masks, items])
+    }
+
+    /// Builds a vector from a RecordBatch containing a mask column and an item column.
+    pub fn try_from_record_batch(batch: &RecordBatch) -> Result<Self, ArrowError> {
+        let masks = downcast::<PrimitiveArray<B::ArrowType>>(
+            column(batch, MASK_COLUMN)?.as_ref(),
+            MASK_COLUMN,
+        )?;
+        let items = T::from_array(column(batch, ITEM_COLUMN)?.as_ref())?;
+
+        let mut v = Self::with_capacity(items.len());
+        for (bitmask, item) in masks.iter().flatten().zip(items) {
+            v.push_with_mask(bitmask, item);
+        }
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_arrow::{ITEM_COLUMN, MASK_COLUMN};
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use arrow_array::{ArrayRef, Int32Array, RecordBatch, UInt8Array};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_bitmask_arrow_into_record_batch() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        let batch = v.into_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.num_columns(), 2);
+    }
+
+    #[test]
+    fn test_bitmask_arrow_to_record_batch() {
+        let mut v = BitmaskVec::<u16, String>::new();
+        v.push_with_mask(0b00000000, "a".to_string());
+        v.push_with_mask(0b00000010, "b".to_string());
+
+        let batch = v.to_record_batch().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn test_bitmask_arrow_round_trip() {
+        let mut v = BitmaskVec::<u8, String>::new();
+        v.push_with_mask(0b00000001, "a".to_string());
+        v.push_with_mask(0b00000010, "b".to_string());
+        v.push_with_mask(0b00000100, "c".to_string());
+
+        let batch = v.to_record_batch().unwrap();
+        let mut v2 = BitmaskVec::<u8, String>::try_from_record_batch(&batch).unwrap();
+
+        assert_eq!(v2.len(), 3);
+        assert_eq!(v2[2], "c");
+        assert_eq!(v2.iter_with_mask().nth(1).unwrap().bitmask, 0b00000010);
+    }
+
+    #[test]
+    fn test_bitmask_arrow_wrong_type() {
+        let masks: ArrayRef = Arc::new(UInt8Array::from(vec![1, 2]));
+        let items: ArrayRef = Arc::new(Int32Array::from(vec![1, 2]));
+        let batch =
+            RecordBatch::try_from_iter(vec![(MASK_COLUMN, masks), (ITEM_COLUMN, items)]).unwrap();
+
+        assert!(BitmaskVec::<u16, i32>::try_from_record_batch(&batch).is_err());
+        assert!(BitmaskVec::<u8, i64>::try_from_record_batch(&batch).is_err());
+        assert!(BitmaskVec::<u8, i32>::try_from_record_batch(&batch).is_ok());
+    }
+
+    #[test]
+    fn test_bitmask_arrow_nulls_rejected() {
+        let masks: ArrayRef = Arc::new(UInt8Array::from(vec![1, 2]));
+        let items: ArrayRef = Arc::new(Int32Array::from(vec![Some(1), None]));
+        let batch =
+            RecordBatch::try_from_iter(vec![(MASK_COLUMN, masks), (ITEM_COLUMN, items)]).unwrap();
+
+        assert!(BitmaskVec::<u8, i32>::try_from_record_batch(&batch).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use bitflags::Flags;
+use cj_common::cj_binary::bitbuf::*;
+
+impl<'a, B, T> BitmaskItem<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone,
+{
+    /// Creates a BitmaskItem from a bitflags type, storing its raw bits as the bitmask.
+    #[inline]
+    pub fn from_flags<F>(flags: F, item: T) -> Self
+    where
+        F: Flags<Bits = B>,
+    {
+        Self::new(flags.bits(), item)
+    }
+
+    /// Returns the bitmask as a bitflags type. Unknown bits are retained.
+    #[inline]
+    pub fn flags<F>(&self) -> F
+    where
+        F: Flags<Bits = B>,
+    {
+        F::from_bits_retain(self.bitmask.clone())
+    }
+
+    /// Replaces the bitmask with the raw bits of a bitflags type.
+    #[inline]
+    pub fn set_flags<F>(&mut self, flags: F)
+    where
+        F: Flags<Bits = B>,
+    {
+        self.bitmask = flags.bits();
+    }
+
+    /// Returns true if all flags in flags are set in bitmask.
+    #[inline]
+    pub fn contains_flags<F>(&self, flags: F) -> bool
+    where
+        F: Flags<Bits = B>,
+    {
+        self.flags::<F>().contains(flags)
+    }
+}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Pushes T with the raw bits of a bitflags type as its bitmask.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// bitflags::bitflags! {
+    ///     #[derive(Clone, Copy)]
+    ///     struct State: u8 {
+    ///         const QUEUED = 0b00000001;
+    ///         const RUNNING = 0b00000010;
+    ///     }
+    /// }
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_flags(State::QUEUED, 100);
+    /// v.push_with_flags(State::QUEUED | State::RUNNING, 101);
+    ///
+    /// assert_eq!(v.count_matching_flags(State::RUNNING), 1);
+    /// assert!(v.as_slice()[1].flags::<State>().contains(State::QUEUED));
+    /// ```
+    #[inline]
+    pub fn push_with_flags<F>(&mut self, flags: F, value: T)
+    where
+        F: Flags<Bits = B>,
+    {
+        self.push_with_mask(flags.bits(), value);
+    }
+
+    /// Inserts T with the raw bits of a bitflags type at position index, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert_with_flags<F>(&mut self, index: usize, flags: F, value: T)
+    where
+        F: Flags<Bits = B>,
+    {
+        self.insert_with_mask(index, flags.bits(), value);
+    }
+
+    /// Returns an iterator over the BitmaskItems whose bitmask contains all of flags.
+    pub fn iter_matching_flags<F>(&self, flags: F) -> impl Iterator<Item = &BitmaskItem<B, T>>
+    where
+        F: Flags<Bits = B> + Copy,
+    {
+        self.as_slice()
+            .iter()
+            .filter(move |x| x.contains_flags(flags))
+    }
+
+    /// Returns the number of items whose bitmask contains all of flags.
+    pub fn count_matching_flags<F>(&self, flags: F) -> usize
+    where
+        F: Flags<Bits = B> + Copy,
+    {
+        self.iter_matching_flags(flags).count()
+    }
+
+    /// Returns the indices of the items whose bitmask contains all of flags.
+    pub fn indices_matching_flags<F>(&self, flags: F) -> Vec<usize>
+    where
+        F: Flags<Bits = B> + Copy,
+    {
+        self.as_slice()
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.contains_flags(flags))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_item::BitmaskItem;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    bitflags::bitflags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct State: u16 {
+            const QUEUED = 0b0001;
+            const RUNNING = 0b0010;
+            const FAILED = 0b0100;
+        }
+    }
+
+    #[test]
+    fn test_bitmask_bitflags_item() {
+        let mut x = BitmaskItem::from_flags(State::QUEUED | State::FAILED, 100);
+        assert_eq!(x.bitmask, 0b0101);
+        assert!(x.contains_flags(State::FAILED));
+        assert!(!x.contains_flags(State::RUNNING));
+
+        x.set_flags(State::RUNNING);
+        assert_eq!(x.flags::<State>(), State::RUNNING);
+    }
+
+    #[test]
+    fn test_bitmask_bitflags_unknown_bits() {
+        let x = BitmaskItem::new(0b1000_0010u16, 100);
+        assert_eq!(x.flags::<State>().bits(), 0b1000_0010);
+        assert!(x.contains_flags(State::RUNNING));
+    }
+
+    #[test]
+    fn test_bitmask_bitflags_matching() {
+        let mut v = BitmaskVec::<u16, i32>::new();
+        v.push_with_flags(State::QUEUED, 100);
+        v.push_with_flags(State::RUNNING, 101);
+        v.push_with_flags(State::RUNNING | State::FAILED, 102);
+        v.insert_with_flags(0, State::empty(), 99);
+
+        assert_eq!(v.count_matching_flags(State::RUNNING), 2);
+        assert_eq!(v.indices_matching_flags(State::RUNNING), vec![2, 3]);
+        assert_eq!(v.count_matching_flags(State::empty()), 4);
+
+        let x: Vec<_> = v
+            .iter_matching_flags(State::RUNNING | State::FAILED)
+            .map(|x| x.item)
+            .collect();
+        assert_eq!(x, vec![102]);
+    }
+}
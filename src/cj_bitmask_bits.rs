@@ -0,0 +1,119 @@
+use core::ops::{BitAnd, BitOr, BitXor, Not};
+
+/// Bit level operations on bitmask types, used by the containers that need to look at
+/// individual bits. Implemented for u8 through u128.
+pub trait BitmaskBits:
+    Copy
+    + Default
+    + PartialEq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+{
+    /// Number of bits in the bitmask
+    const BITS: usize;
+
+    /// Returns a bitmask with only the given bit set
+    fn bit(bit: usize) -> Self;
+
+    /// Returns true if the given bit is set
+    fn has_bit(&self, bit: usize) -> bool;
+
+    /// Returns the number of set bits
+    fn count_bits(&self) -> u32;
+
+    /// Returns true if no bits are set
+    #[inline]
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns an iterator over the positions of the set bits, lowest first
+    #[inline]
+    fn set_bits(&self) -> SetBits<Self> {
+        SetBits {
+            mask: *self,
+            bit: 0,
+        }
+    }
+}
+
+macro_rules! impl_bitmask_bits {
+    ($($t:ty),*) => {$(
+        impl BitmaskBits for $t {
+            const BITS: usize = <$t>::BITS as usize;
+
+            #[inline]
+            fn bit(bit: usize) -> Self {
+                1 << bit
+            }
+
+            #[inline]
+            fn has_bit(&self, bit: usize) -> bool {
+                (*self >> bit) & 1 == 1
+            }
+
+            #[inline]
+            fn count_bits(&self) -> u32 {
+                self.count_ones()
+            }
+        }
+    )*};
+}
+
+impl_bitmask_bits!(u8, u16, u32, u64, u128);
+
+/// Iter over the positions of the set bits of a bitmask
+pub struct SetBits<B>
+where
+    B: BitmaskBits,
+{
+    mask: B,
+    bit: usize,
+}
+
+impl<B> Iterator for SetBits<B>
+where
+    B: BitmaskBits,
+{
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.bit < B::BITS {
+            let bit = self.bit;
+            self.bit += 1;
+            if self.mask.has_bit(bit) {
+                return Some(bit);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_bits::BitmaskBits;
+
+    #[test]
+    fn test_bitmask_bits_bit() {
+        assert_eq!(u8::bit(1), 0b00000010);
+        assert_eq!(u128::bit(127), 1u128 << 127);
+        assert_eq!(<u16 as BitmaskBits>::BITS, 16);
+    }
+
+    #[test]
+    fn test_bitmask_bits_has_bit() {
+        assert!(0b00000100u8.has_bit(2));
+        assert!(!0b00000100u8.has_bit(1));
+        assert_eq!(0b10000110u8.count_bits(), 3);
+        assert!(0u32.is_zero());
+    }
+
+    #[test]
+    fn test_bitmask_bits_set_bits() {
+        let x: Vec<_> = 0b10000110u8.set_bits().collect();
+        assert_eq!(x, vec![1, 2, 7]);
+        assert_eq!(0u64.set_bits().count(), 0);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMatching, BitmaskVecIterMut, BitmaskVecIterWithMask,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Index, IndexMut};
+
+/// BitmaskCountedVec is a vec that pairs bitmasks with T and keeps a running count of how
+/// many elements have each bit set.<br>
+///
+/// count_with_bit() is O(1). The counts are updated on every push, pop, removal and set_mask(),
+/// so bitmasks can only be changed through set_mask().
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// const RUNNING: u8 = 0b00000010;
+///
+/// let mut v = BitmaskCountedVec::<u8, i32>::new();
+/// v.push_with_mask(0, 100);
+/// v.push_with_mask(RUNNING, 101);
+/// v.push_with_mask(RUNNING, 102);
+/// assert_eq!(v.count_with_bit(1), 2);
+///
+/// v.set_mask(1, 0);
+/// assert_eq!(v.count_with_bit(1), 1);
+/// ```
+pub struct BitmaskCountedVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Vec<BitmaskItem<B, T>>,
+    counts: Vec<usize>,
+}
+
+impl<'a, B, T> BitmaskCountedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Vec::with_capacity(capacity),
+            counts: vec![0; B::BITS],
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Returns how many elements have bit set. O(1).
+    #[inline]
+    pub fn count_with_bit(&self, bit: usize) -> usize {
+        self.counts[bit]
+    }
+
+    /// Returns the per bit counts, indexed by bit position.
+    #[inline]
+    pub fn bit_counts(&self) -> &[usize] {
+        self.counts.as_slice()
+    }
+
+    /// Clears the vector and resets the counts.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.counts.fill(0);
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest
+    pub fn truncate(&mut self, len: usize) {
+        while self.inner.len() > len {
+            self.pop_with_mask();
+        }
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.add_counts(&bitmask);
+        self.inner.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.inner.insert(index, BitmaskItem::new(bitmask, value));
+        self.add_counts(&bitmask);
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        let x = self.inner.pop()?;
+        self.sub_counts(&x.bitmask);
+        Some(x)
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let x = self.inner.remove(index);
+        self.sub_counts(&x.bitmask);
+        x
+    }
+
+    /// Removes an element without bitmask from the vector and returns it.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.swap_with_mask_remove(index).item
+    }
+
+    /// Removes an element and bitmask from the vector and returns it.
+    #[inline]
+    pub fn swap_with_mask_remove(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let x = self.inner.swap_remove(index);
+        self.sub_counts(&x.bitmask);
+        x
+    }
+
+    /// Replaces the bitmask at index, updating the counts of the bits that changed.
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        let old = self.inner[index].bitmask;
+        self.sub_counts(&(old & !bitmask));
+        self.add_counts(&(bitmask & !old));
+        self.inner[index].bitmask = bitmask;
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMut for mutable iteration over T. Bitmasks can only be changed with set_mask().
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecIterMut<'a, B, T> {
+        BitmaskVecIterMut::new(self.inner.iter_mut())
+    }
+
+    /// Returns a BitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecIterMatching<'a, B, T> {
+        BitmaskVecIterMatching::new(self.inner.iter(), mask)
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    /// This is O(1) when mask has at most one bit set.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        let mut bits = mask.set_bits();
+        match (bits.next(), bits.next()) {
+            (None, _) => self.inner.len(),
+            (Some(bit), None) => self.counts[bit],
+            _ => self.inner.iter().filter(|x| x.matches_mask(mask)).count(),
+        }
+    }
+
+    /// Moves the items into a BitmaskVec, dropping the counts.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.inner.len());
+        for x in self.inner {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+
+    #[inline]
+    fn add_counts(&mut self, bitmask: &B) {
+        for bit in bitmask.set_bits() {
+            self.counts[bit] += 1;
+        }
+    }
+
+    #[inline]
+    fn sub_counts(&mut self, bitmask: &B) {
+        for bit in bitmask.set_bits() {
+            self.counts[bit] -= 1;
+        }
+    }
+}
+
+impl<'a, B, T> Default for BitmaskCountedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskCountedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let mut v = Self::with_capacity(value.len());
+        for x in value.drain(..) {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskCountedVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<B, T> IndexMut<usize> for BitmaskCountedVec<B, T>
+where
+    B: Bitflag,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index].item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_counted_vec::BitmaskCountedVec;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_bitmask_counted_vec_push() {
+        let mut v = BitmaskCountedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push(102);
+
+        assert_eq!(v.count_with_bit(0), 1);
+        assert_eq!(v.count_with_bit(1), 2);
+        assert_eq!(v.count_with_bit(7), 0);
+        assert_eq!(v.bit_counts().len(), 8);
+    }
+
+    #[test]
+    fn test_bitmask_counted_vec_pop_remove() {
+        let mut v = BitmaskCountedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000110, 102);
+        v.push_with_mask(0b00000011, 103);
+
+        v.pop();
+        assert_eq!(v.count_with_bit(0), 1);
+        assert_eq!(v.count_with_bit(1), 2);
+
+        assert_eq!(v.remove(1), 101);
+        assert_eq!(v.count_with_bit(1), 1);
+        assert_eq!(v.swap_remove(0), 100);
+        assert_eq!(v.count_with_bit(0), 0);
+        assert_eq!(v.count_with_bit(2), 1);
+
+        v.clear();
+        assert!(v.bit_counts().iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn test_bitmask_counted_vec_insert_truncate() {
+        let mut v = BitmaskCountedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.insert_with_mask(0, 0b10000001, 99);
+        assert_eq!(v.count_with_bit(7), 1);
+        assert_eq!(v.count_with_bit(0), 2);
+
+        v.truncate(1);
+        assert_eq!(v.count_with_bit(0), 1);
+        assert_eq!(v.count_with_bit(1), 0);
+        assert_eq!(v.count_with_bit(7), 1);
+    }
+
+    #[test]
+    fn test_bitmask_counted_vec_set_mask() {
+        let mut v = BitmaskCountedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        v.set_mask(0, 0b00000110);
+        assert_eq!(v.count_with_bit(0), 0);
+        assert_eq!(v.count_with_bit(1), 2);
+        assert_eq!(v.count_with_bit(2), 1);
+    }
+
+    #[test]
+    fn test_bitmask_counted_vec_count_matching() {
+        let mut v = BitmaskCountedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000001, 102);
+
+        assert_eq!(v.count_matching(&0), 3);
+        assert_eq!(v.count_matching(&0b00000010), 2);
+        assert_eq!(v.count_matching(&0b00000011), 1);
+        assert_eq!(v.iter_matching(&0b00000011).next().unwrap().item, 101);
+    }
+
+    #[test]
+    fn test_bitmask_counted_vec_from_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+
+        let v = BitmaskCountedVec::from(v);
+        assert_eq!(v.count_with_bit(0), 2);
+        assert_eq!(v.into_bitmask_vec().len(), 2);
+    }
+}
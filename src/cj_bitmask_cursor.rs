@@ -0,0 +1,289 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use cj_common::cj_binary::bitbuf::*;
+
+/// BitmaskVecCursorMut walks a BitmaskVec and allows structural edits at its position.<br>
+///
+/// Created by BitmaskVec::cursor_mut(). The cursor points either at an element or one past the
+/// end of the vec, in which case current() returns None. Removing the current element leaves the
+/// cursor on the element that followed it, so matching items can be deleted in a single pass.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(0b00000001, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000011, 102);
+/// v.push_with_mask(0b00000100, 103);
+///
+/// let mut cursor = v.cursor_mut();
+/// while let Some(x) = cursor.current() {
+///     if x.matches_mask(&0b00000010) {
+///         cursor.remove_current();
+///     } else {
+///         cursor.move_next();
+///     }
+/// }
+/// assert_eq!(v.len(), 2);
+/// assert_eq!(v[1], 103);
+/// ```
+pub struct BitmaskVecCursorMut<'v, B, T>
+where
+    B: Bitflag,
+{
+    vec: &'v mut BitmaskVec<B, T>,
+    index: usize,
+}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Returns a cursor positioned at the first element.
+    #[inline]
+    pub fn cursor_mut(&mut self) -> BitmaskVecCursorMut<'_, B, T> {
+        BitmaskVecCursorMut {
+            vec: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a, 'v, B, T> BitmaskVecCursorMut<'v, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Returns the index of the current element, or None if the cursor is past the end.
+    #[inline]
+    pub fn index(&self) -> Option<usize> {
+        if self.index < self.vec.len() {
+            Some(self.index)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the cursor to the next element, or past the end from the last element.
+    /// Returns false if the cursor was already past the end.
+    #[inline]
+    pub fn move_next(&mut self) -> bool {
+        if self.index < self.vec.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves the cursor to the previous element. Returns false if the cursor was already at the first element.
+    #[inline]
+    pub fn move_prev(&mut self) -> bool {
+        if self.index > 0 {
+            self.index -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the current BitmaskItem, or None if the cursor is past the end.
+    #[inline]
+    pub fn current(&self) -> Option<&BitmaskItem<B, T>> {
+        self.vec.as_slice().get(self.index)
+    }
+
+    /// Returns the current BitmaskItem mutably, or None if the cursor is past the end.
+    #[inline]
+    pub fn current_mut(&mut self) -> Option<&mut BitmaskItem<B, T>> {
+        self.vec.as_mut_vec().get_mut(self.index)
+    }
+
+    /// Returns the bitmask of the current element.
+    #[inline]
+    pub fn mask(&self) -> Option<&B> {
+        self.current().map(|x| &x.bitmask)
+    }
+
+    /// Returns T of the current element.
+    #[inline]
+    pub fn item(&self) -> Option<&T> {
+        self.current().map(|x| &x.item)
+    }
+
+    /// Returns T of the current element mutably.
+    #[inline]
+    pub fn item_mut(&mut self) -> Option<&mut T> {
+        self.vec
+            .as_mut_vec()
+            .get_mut(self.index)
+            .map(|x| &mut x.item)
+    }
+
+    /// Replaces the bitmask of the current element. Returns false if the cursor is past the end.
+    /// # Panics
+    /// Panics if BitmaskVec::set_mask() would panic for the new bitmask.
+    #[inline]
+    pub fn set_mask(&mut self, bitmask: B) -> bool {
+        if self.index < self.vec.len() {
+            self.vec.set_mask(self.index, bitmask);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes and returns T of the current element. The cursor moves to the element that followed it.
+    #[inline]
+    pub fn remove_current(&mut self) -> Option<T> {
+        self.remove_current_with_mask().map(|x| x.item)
+    }
+
+    /// Removes and returns the current BitmaskItem. The cursor moves to the element that followed it.
+    #[inline]
+    pub fn remove_current_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        if self.index < self.vec.len() {
+            Some(self.vec.remove_with_mask(self.index))
+        } else {
+            None
+        }
+    }
+
+    /// Inserts T with a default bitmask of zero before the current element. The cursor stays on the current element.
+    #[inline]
+    pub fn insert_before(&mut self, value: T) {
+        self.insert_before_with_mask(B::default(), value);
+    }
+
+    /// Inserts T and bitmask before the current element. The cursor stays on the current element.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema of the vec.
+    #[inline]
+    pub fn insert_before_with_mask(&mut self, bitmask: B, value: T) {
+        self.vec.insert_with_mask(self.index, bitmask, value);
+        self.index += 1;
+    }
+
+    /// Inserts T with a default bitmask of zero after the current element. If the cursor is past the end,
+    /// T is appended and the cursor stays past the end.
+    #[inline]
+    pub fn insert_after(&mut self, value: T) {
+        self.insert_after_with_mask(B::default(), value);
+    }
+
+    /// Inserts T and bitmask after the current element. If the cursor is past the end,
+    /// T is appended and the cursor stays past the end.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema of the vec.
+    pub fn insert_after_with_mask(&mut self, bitmask: B, value: T) {
+        if self.index < self.vec.len() {
+            self.vec.insert_with_mask(self.index + 1, bitmask, value);
+        } else {
+            self.vec.push_with_mask(bitmask, value);
+            self.index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_bitmask_cursor_move() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+        let mut c = v.cursor_mut();
+
+        assert_eq!(c.index(), Some(0));
+        assert!(!c.move_prev());
+        assert!(c.move_next());
+        assert_eq!(c.item(), Some(&101));
+        assert_eq!(c.mask(), Some(&0b00000010));
+
+        while c.move_next() {}
+        assert_eq!(c.index(), None);
+        assert!(c.current().is_none());
+        assert!(c.move_prev());
+        assert_eq!(c.item(), Some(&102));
+    }
+
+    #[test]
+    fn test_bitmask_cursor_mutate() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000001, 101);
+        let mut c = v.cursor_mut();
+        c.move_next();
+        *c.item_mut().unwrap() = 201;
+        assert!(c.set_mask(0b10000000));
+        c.move_next();
+        assert!(!c.set_mask(0b10000000));
+
+        assert_eq!(v[1], 201);
+        assert_eq!(v.indices_matching(&0b10000000), vec![1]);
+    }
+
+    #[test]
+    fn test_bitmask_cursor_remove_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000001, 103);
+        let mut c = v.cursor_mut();
+        while let Some(x) = c.current() {
+            if x.matches_mask(&0b00000001) {
+                c.remove_current();
+            } else {
+                c.move_next();
+            }
+        }
+        assert!(c.remove_current_with_mask().is_none());
+
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0], 102);
+    }
+
+    #[test]
+    fn test_bitmask_cursor_insert() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        v.push(101);
+        let mut c = v.cursor_mut();
+        c.move_next();
+        c.insert_before_with_mask(0b01000000, 50);
+        assert_eq!(c.item(), Some(&101));
+        c.insert_after(60);
+        assert_eq!(c.item(), Some(&101));
+
+        while c.move_next() {}
+        c.insert_after_with_mask(0b00100000, 70);
+        assert!(c.current().is_none());
+
+        let x: Vec<_> = v.iter().copied().collect();
+        assert_eq!(x, vec![100, 50, 101, 60, 70]);
+        assert_eq!(v.indices_matching(&0b01000000), vec![1]);
+        assert_eq!(v.indices_matching(&0b00100000), vec![4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_cursor_set_mask_schema() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        v.set_schema(0b00001111);
+        v.cursor_mut().set_mask(0b10000000);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+
+/// DeferredOps queues pushes, removals and mask updates for a BitmaskVec while it is borrowed,
+/// for example during a filtered iteration.<br>
+///
+/// The queued changes are applied together by BitmaskVec::apply_deferred(). Indices refer to the
+/// vec as it was before apply_deferred() was called: mask updates are applied first in queued
+/// order, then the removals, then the pushes.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(0b00000001, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000001, 102);
+///
+/// let mut ops = DeferredOps::new();
+/// for (i, x) in v.iter_with_mask().enumerate() {
+///     if x.matches_mask(&0b00000001) {
+///         ops.remove(i);
+///         ops.push_with_mask(0b00000100, x.item + 100);
+///     }
+/// }
+/// v.apply_deferred(&mut ops);
+///
+/// assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![101, 200, 202]);
+/// assert!(ops.is_empty());
+/// ```
+pub struct DeferredOps<B, T>
+where
+    B: Bitflag,
+{
+    masks: Vec<(usize, B)>,
+    removals: Vec<usize>,
+    pushes: Vec<BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> DeferredOps<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            masks: Vec::new(),
+            removals: Vec::new(),
+            pushes: Vec::new(),
+        }
+    }
+
+    /// Returns the number of queued operations.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.masks.len() + self.removals.len() + self.pushes.len()
+    }
+
+    /// Returns true if no operations are queued.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Discards all queued operations.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.masks.clear();
+        self.removals.clear();
+        self.pushes.clear();
+    }
+
+    /// Queues T to be appended with a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Queues T and bitmask to be appended.
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.pushes.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Queues the removal of the item at index. Queuing the same index twice removes it once.
+    #[inline]
+    pub fn remove(&mut self, index: usize) {
+        self.removals.push(index);
+    }
+
+    /// Queues replacing the bitmask of the item at index.
+    #[inline]
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        self.masks.push((index, bitmask));
+    }
+}
+
+impl<'a, B, T> Default for DeferredOps<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Applies and drains the operations queued in ops, leaving it empty for reuse.
+    /// # Panics
+    /// Panics if a queued removal or mask update refers to an index out of bounds, or a queued
+    /// mask update or push sets a bitmask outside the schema of the vec, in which case the vec
+    /// is left unchanged.
+    pub fn apply_deferred(&mut self, ops: &mut DeferredOps<B, T>) {
+        let len = self.len();
+        if let Some(i) = ops
+            .masks
+            .iter()
+            .map(|(i, _)| *i)
+            .chain(ops.removals.iter().copied())
+            .find(|i| *i >= len)
+        {
+            panic!("deferred index {i} is out of bounds for length {len}");
+        }
+        for bitmask in ops
+            .masks
+            .iter()
+            .map(|(_, bitmask)| bitmask)
+            .chain(ops.pushes.iter().map(|x| &x.bitmask))
+        {
+            self.assert_schema(bitmask);
+        }
+
+        let inner = self.as_mut_vec();
+
+        for (i, bitmask) in ops.masks.drain(..) {
+            inner[i].bitmask = bitmask;
+        }
+
+        if !ops.removals.is_empty() {
+            let mut removed = alloc::vec![false; len];
+            for i in ops.removals.drain(..) {
+                removed[i] = true;
+            }
+            let mut flags = removed.into_iter();
+            inner.retain(|_| !flags.next().unwrap_or(false));
+        }
+
+        inner.append(&mut ops.pushes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_deferred::DeferredOps;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_deferred_apply() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000100, 103);
+        let mut ops = DeferredOps::new();
+        for i in v.indices_matching(&0b00000010) {
+            ops.push(v[i] + 100);
+            ops.set_mask(i, 0b10000000);
+        }
+        // indices refer to the vec before any queued change
+        ops.remove(0);
+        ops.remove(3);
+        assert_eq!(ops.len(), 6);
+
+        v.apply_deferred(&mut ops);
+        assert!(ops.is_empty());
+        assert_eq!(
+            v.iter().copied().collect::<Vec<_>>(),
+            vec![101, 102, 201, 202]
+        );
+        assert_eq!(v.indices_matching(&0b10000000), vec![0, 1]);
+        assert_eq!(v.indices_matching(&0b00000010), vec![]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_deferred_out_of_bounds() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        let mut ops = DeferredOps::new();
+        ops.set_mask(0, 0b10000000);
+        ops.remove(1);
+        v.apply_deferred(&mut ops);
+    }
+
+    #[test]
+    fn test_deferred_clear() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        v.push(101);
+        let mut ops = DeferredOps::<u8, i32>::default();
+        ops.push_with_mask(0b00000001, 500);
+        ops.remove(1);
+        ops.clear();
+
+        v.apply_deferred(&mut ops);
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![100, 101]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_deferred_schema() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.set_schema(0b00001111);
+        let mut ops = DeferredOps::new();
+        ops.push_with_mask(0b10000000, 100);
+        v.apply_deferred(&mut ops);
+    }
+}
@@ -0,0 +1,283 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use crate::cj_error::Error;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::fmt::{Display, Formatter};
+
+/// A single recorded change in a BitmaskVecDiff.
+#[derive(Debug, Clone)]
+pub enum BitmaskVecChange<B, T>
+where
+    B: Bitflag,
+{
+    /// The BitmaskItem at index changed from old to new.
+    Replace {
+        index: usize,
+        old: BitmaskItem<B, T>,
+        new: BitmaskItem<B, T>,
+    },
+    /// A BitmaskItem was pushed.
+    Push(BitmaskItem<B, T>),
+    /// The last BitmaskItem was popped.
+    Pop(BitmaskItem<B, T>),
+}
+
+/// BitmaskVecDiff is the list of changes that turns one BitmaskVec into another.<br>
+///
+/// Built by BitmaskVec::diff() and replayed by BitmaskVec::apply(). Every change records the
+/// value it expects to overwrite, so a patch is only applied to the state it was made from.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut a = BitmaskVec::<u8, i32>::new();
+/// a.push_with_mask(0b00000000, 100);
+/// a.push_with_mask(0b00000010, 101);
+///
+/// let mut b = BitmaskVec::<u8, i32>::new();
+/// b.push_with_mask(0b00000001, 100);
+/// b.push_with_mask(0b00000010, 101);
+/// b.push_with_mask(0b00000100, 102);
+///
+/// let patch = a.diff(&b);
+/// assert_eq!(patch.len(), 2);
+///
+/// let mut c = BitmaskVec::<u8, i32>::new();
+/// c.push_with_mask(0b00000000, 100);
+/// c.push_with_mask(0b00000010, 101);
+/// c.apply(&patch).unwrap();
+/// assert_eq!(c.diff(&b).len(), 0);
+///
+/// // the patch no longer applies to the patched vec
+/// assert!(c.apply(&patch).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct BitmaskVecDiff<B, T>
+where
+    B: Bitflag,
+{
+    changes: Vec<BitmaskVecChange<B, T>>,
+}
+
+impl<B, T> BitmaskVecDiff<B, T>
+where
+    B: Bitflag,
+{
+    /// Returns the recorded changes in the order they are applied.
+    #[inline]
+    pub fn changes(&self) -> &[BitmaskVecChange<B, T>] {
+        &self.changes
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Returns true if the diff contains no changes.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Error returned when a BitmaskVecDiff does not apply. The vec is left unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// A change refers to an index past the end of the vec.
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The BitmaskItem at index is not the one the change expects.
+    Mismatch { index: usize },
+    /// The vec does not allow the bitmask a change sets at index, for the reason in error.
+    Rejected { index: usize, error: Error },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PatchError::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for length {len}")
+            }
+            PatchError::Mismatch { index } => write!(f, "unexpected item at index {index}"),
+            PatchError::Rejected { index, error } => {
+                write!(f, "change at index {index} rejected: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + PartialEq,
+    T: Clone + PartialEq,
+{
+    /// Returns the BitmaskVecDiff that turns self into other.
+    pub fn diff(&self, other: &Self) -> BitmaskVecDiff<B, T> {
+        let a = self.as_slice();
+        let b = other.as_slice();
+        let mut changes = Vec::new();
+        for (index, (old, new)) in a.iter().zip(b).enumerate() {
+            if old != new {
+                changes.push(BitmaskVecChange::Replace {
+                    index,
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+        for x in a.iter().skip(b.len()).rev() {
+            changes.push(BitmaskVecChange::Pop(x.clone()));
+        }
+        for x in b.iter().skip(a.len()) {
+            changes.push(BitmaskVecChange::Push(x.clone()));
+        }
+        BitmaskVecDiff { changes }
+    }
+
+    /// Replays the changes in patch. If any change does not match the current contents, or sets
+    /// a bitmask the schema does not allow, a PatchError is returned and the vec is left
+    /// unchanged.
+    pub fn apply(&mut self, patch: &BitmaskVecDiff<B, T>) -> Result<(), PatchError> {
+        let mut tx = self.begin();
+        for change in patch.changes() {
+            match change {
+                BitmaskVecChange::Replace { index, old, new } => {
+                    let index = *index;
+                    let len = tx.len();
+                    let current = tx
+                        .as_slice()
+                        .get(index)
+                        .ok_or(PatchError::IndexOutOfBounds { index, len })?;
+                    if current != old {
+                        return Err(PatchError::Mismatch { index });
+                    }
+                    tx.vec()
+                        .check_set_mask(index, &new.bitmask)
+                        .map_err(|error| PatchError::Rejected { index, error })?;
+                    tx.set_mask(index, new.bitmask.clone());
+                    tx.replace(index, new.item.clone());
+                }
+                BitmaskVecChange::Push(x) => {
+                    if !tx.vec().allows_mask(&x.bitmask) {
+                        return Err(PatchError::Rejected {
+                            index: tx.len(),
+                            error: Error::OutsideSchema,
+                        });
+                    }
+                    tx.push_with_mask(x.bitmask.clone(), x.item.clone());
+                }
+                BitmaskVecChange::Pop(x) => {
+                    let len = tx.len();
+                    let current = tx
+                        .as_slice()
+                        .last()
+                        .ok_or(PatchError::IndexOutOfBounds { index: 0, len })?;
+                    if current != x {
+                        return Err(PatchError::Mismatch { index: len - 1 });
+                    }
+                    tx.pop_with_mask();
+                }
+            }
+        }
+        tx.commit();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_diff::{BitmaskVecChange, PatchError};
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_error::Error;
+
+    fn pairs(v: &BitmaskVec<u8, i32>) -> Vec<(u8, i32)> {
+        v.as_slice().iter().map(|x| (x.bitmask, x.item)).collect()
+    }
+
+    #[test]
+    fn test_bitmask_diff_diff() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push_with_mask(0b00000001, 100);
+        a.push_with_mask(0b00000010, 101);
+        a.push_with_mask(0b00000100, 102);
+        let mut b = a.clone();
+        assert!(a.diff(&b).is_empty());
+
+        b.set_mask(0, 0b00000011);
+        b.push_with_mask(0b00001000, 103);
+        let d = a.diff(&b);
+        assert_eq!(d.len(), 2);
+        assert!(matches!(
+            d.changes()[0],
+            BitmaskVecChange::Replace { index: 0, .. }
+        ));
+        assert!(matches!(&d.changes()[1], BitmaskVecChange::Push(x) if x.item == 103));
+
+        // pops come last first
+        b.truncate(1);
+        let d = a.diff(&b);
+        assert_eq!(d.len(), 3);
+        assert!(matches!(&d.changes()[1], BitmaskVecChange::Pop(x) if x.item == 102));
+        assert!(matches!(&d.changes()[2], BitmaskVecChange::Pop(x) if x.item == 101));
+    }
+
+    #[test]
+    fn test_bitmask_diff_apply() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push_with_mask(0b00000001, 100);
+        a.push_with_mask(0b00000010, 101);
+        let mut b = a.clone();
+        b.set_mask(0, 0b10000000);
+        b.push_with_mask(0b00000001, 102);
+
+        let mut c = a.clone();
+        c.apply(&a.diff(&b)).unwrap();
+        assert_eq!(pairs(&c), pairs(&b));
+
+        c.apply(&b.diff(&a)).unwrap();
+        assert_eq!(pairs(&c), pairs(&a));
+    }
+
+    #[test]
+    fn test_bitmask_diff_apply_mismatch() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push(100);
+        a.push(101);
+        let mut b = a.clone();
+        b[1] = 7;
+        b.push(8);
+        let d = a.diff(&b);
+
+        let mut c = a.clone();
+        c[1] = 9;
+        assert_eq!(c.apply(&d), Err(PatchError::Mismatch { index: 1 }));
+        assert_eq!(c.iter().copied().collect::<Vec<_>>(), vec![100, 9]);
+
+        let mut c = BitmaskVec::<u8, i32>::new();
+        c.push(100);
+        assert_eq!(
+            c.apply(&d),
+            Err(PatchError::IndexOutOfBounds { index: 1, len: 1 })
+        );
+    }
+
+    #[test]
+    fn test_bitmask_diff_apply_rejected() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push_with_mask(0b00000001, 100);
+        let mut b = a.clone();
+        b.push_with_mask(0b10000000, 101);
+        let d = a.diff(&b);
+
+        a.set_schema(0b00001111);
+        assert_eq!(
+            a.apply(&d),
+            Err(PatchError::Rejected {
+                index: 1,
+                error: Error::OutsideSchema
+            })
+        );
+        assert_eq!(a.len(), 1);
+    }
+}
@@ -0,0 +1,318 @@
+//! C interface to BitmaskVec with byte payloads.
+//!
+//! Each supported bitmask width has its own opaque handle type and set of functions, named
+//! cj_bitmask_vec_u8_* through cj_bitmask_vec_u64_*. A handle is created with *_new() and must
+//! be released with *_free(). Payloads are copied in by *_push_with_mask() and stay owned by the
+//! vec; pointers returned by *_get() and passed to callbacks are valid until the vec is next
+//! modified or freed.
+//!
+//! The functions are exported unmangled, so linking this crate into a staticlib or cdylib makes
+//! them callable from C:
+//! ```c
+//! typedef struct BitmaskVecU8 BitmaskVecU8;
+//!
+//! bool print_match(void *user, size_t index, uint8_t mask, const uint8_t *data, size_t len);
+//!
+//! BitmaskVecU8 *v = cj_bitmask_vec_u8_new();
+//! cj_bitmask_vec_u8_push_with_mask(v, 0x02, (const uint8_t *)"abc", 3);
+//! cj_bitmask_vec_u8_for_each_matching(v, 0x02, print_match, NULL);
+//! cj_bitmask_vec_u8_free(v);
+//! ```
+
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use core::ptr;
+use core::slice;
+
+/// Opaque handle to a BitmaskVec with u8 bitmasks and byte payloads.
+pub type BitmaskVecU8 = BitmaskVec<u8, Box<[u8]>>;
+/// Opaque handle to a BitmaskVec with u16 bitmasks and byte payloads.
+pub type BitmaskVecU16 = BitmaskVec<u16, Box<[u8]>>;
+/// Opaque handle to a BitmaskVec with u32 bitmasks and byte payloads.
+pub type BitmaskVecU32 = BitmaskVec<u32, Box<[u8]>>;
+/// Opaque handle to a BitmaskVec with u64 bitmasks and byte payloads.
+pub type BitmaskVecU64 = BitmaskVec<u64, Box<[u8]>>;
+
+/// Callback for *_for_each_matching(), given the user pointer, the index, bitmask and payload of
+/// a matching element. Return false to stop the iteration.
+pub type MatchCallback<B> =
+    Option<unsafe extern "C" fn(*mut c_void, usize, B, *const u8, usize) -> bool>;
+
+/// Returns the payload slice for data and len, treating a null data pointer as empty.
+unsafe fn payload<'p>(data: *const u8, len: usize) -> &'p [u8] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(data, len) }
+    }
+}
+
+macro_rules! ffi_bitmask_vec {
+    (
+        $b:ty,
+        $vec:ty,
+        $new:ident,
+        $free:ident,
+        $clear:ident,
+        $push:ident,
+        $len:ident,
+        $get:ident,
+        $count:ident,
+        $for_each:ident
+    ) => {
+        /// Creates an empty vec. Release it with the matching *_free().
+        #[no_mangle]
+        pub extern "C" fn $new() -> *mut $vec {
+            Box::into_raw(Box::new(BitmaskVec::new()))
+        }
+
+        /// Releases a vec created by the matching *_new(). A null v is ignored.
+        ///
+        /// # Safety
+        /// v must be null or a handle from *_new() that has not been freed.
+        #[no_mangle]
+        pub unsafe extern "C" fn $free(v: *mut $vec) {
+            if !v.is_null() {
+                drop(unsafe { Box::from_raw(v) });
+            }
+        }
+
+        /// Removes every element.
+        ///
+        /// # Safety
+        /// v must be a live handle.
+        #[no_mangle]
+        pub unsafe extern "C" fn $clear(v: *mut $vec) {
+            unsafe { &mut *v }.clear();
+        }
+
+        /// Appends a copy of the len bytes at data with bitmask. Returns false, without
+        /// appending, if data is null while len isn't 0, bitmask is outside the schema of the
+        /// vec or memory for the copy runs out.
+        ///
+        /// # Safety
+        /// v must be a live handle, and data must be null or point to len readable bytes.
+        #[no_mangle]
+        pub unsafe extern "C" fn $push(
+            v: *mut $vec,
+            bitmask: $b,
+            data: *const u8,
+            len: usize,
+        ) -> bool {
+            if data.is_null() && len != 0 {
+                return false;
+            }
+            let mut value = Vec::new();
+            if value.try_reserve_exact(len).is_err() {
+                return false;
+            }
+            value.extend_from_slice(unsafe { payload(data, len) });
+            unsafe { &mut *v }
+                .try_push_with_mask(bitmask, value.into_boxed_slice())
+                .is_ok()
+        }
+
+        /// Returns the number of elements.
+        ///
+        /// # Safety
+        /// v must be a live handle.
+        #[no_mangle]
+        pub unsafe extern "C" fn $len(v: *const $vec) -> usize {
+            unsafe { &*v }.len()
+        }
+
+        /// Returns the payload of the element at index and writes its bitmask to out_mask and
+        /// its length to out_len. Returns null, writing nothing, if index is out of bounds.
+        ///
+        /// # Safety
+        /// v must be a live handle. out_mask and out_len must each be null or writable.
+        #[no_mangle]
+        pub unsafe extern "C" fn $get(
+            v: *const $vec,
+            index: usize,
+            out_mask: *mut $b,
+            out_len: *mut usize,
+        ) -> *const u8 {
+            let Some(x) = unsafe { &*v }.as_slice().get(index) else {
+                return ptr::null();
+            };
+            if !out_mask.is_null() {
+                unsafe { *out_mask = x.bitmask };
+            }
+            if !out_len.is_null() {
+                unsafe { *out_len = x.item.len() };
+            }
+            x.item.as_ptr()
+        }
+
+        /// Returns the number of elements whose bitmask matches mask.
+        ///
+        /// # Safety
+        /// v must be a live handle.
+        #[no_mangle]
+        pub unsafe extern "C" fn $count(v: *const $vec, mask: $b) -> usize {
+            unsafe { &*v }.count_matching(&mask)
+        }
+
+        /// Calls callback with user for each element whose bitmask matches mask, in order, until
+        /// it returns false. Returns the number of calls made.
+        ///
+        /// # Safety
+        /// v must be a live handle, and callback must not modify or free it.
+        #[no_mangle]
+        pub unsafe extern "C" fn $for_each(
+            v: *const $vec,
+            mask: $b,
+            callback: MatchCallback<$b>,
+            user: *mut c_void,
+        ) -> usize {
+            let Some(callback) = callback else {
+                return 0;
+            };
+            let mut calls = 0;
+            for i in unsafe { &*v }.indices_matching(&mask) {
+                let x = &unsafe { &*v }.as_slice()[i];
+                calls += 1;
+                if !unsafe { callback(user, i, x.bitmask, x.item.as_ptr(), x.item.len()) } {
+                    break;
+                }
+            }
+            calls
+        }
+    };
+}
+
+ffi_bitmask_vec!(
+    u8,
+    BitmaskVecU8,
+    cj_bitmask_vec_u8_new,
+    cj_bitmask_vec_u8_free,
+    cj_bitmask_vec_u8_clear,
+    cj_bitmask_vec_u8_push_with_mask,
+    cj_bitmask_vec_u8_len,
+    cj_bitmask_vec_u8_get,
+    cj_bitmask_vec_u8_count_matching,
+    cj_bitmask_vec_u8_for_each_matching
+);
+ffi_bitmask_vec!(
+    u16,
+    BitmaskVecU16,
+    cj_bitmask_vec_u16_new,
+    cj_bitmask_vec_u16_free,
+    cj_bitmask_vec_u16_clear,
+    cj_bitmask_vec_u16_push_with_mask,
+    cj_bitmask_vec_u16_len,
+    cj_bitmask_vec_u16_get,
+    cj_bitmask_vec_u16_count_matching,
+    cj_bitmask_vec_u16_for_each_matching
+);
+ffi_bitmask_vec!(
+    u32,
+    BitmaskVecU32,
+    cj_bitmask_vec_u32_new,
+    cj_bitmask_vec_u32_free,
+    cj_bitmask_vec_u32_clear,
+    cj_bitmask_vec_u32_push_with_mask,
+    cj_bitmask_vec_u32_len,
+    cj_bitmask_vec_u32_get,
+    cj_bitmask_vec_u32_count_matching,
+    cj_bitmask_vec_u32_for_each_matching
+);
+ffi_bitmask_vec!(
+    u64,
+    BitmaskVecU64,
+    cj_bitmask_vec_u64_new,
+    cj_bitmask_vec_u64_free,
+    cj_bitmask_vec_u64_clear,
+    cj_bitmask_vec_u64_push_with_mask,
+    cj_bitmask_vec_u64_len,
+    cj_bitmask_vec_u64_get,
+    cj_bitmask_vec_u64_count_matching,
+    cj_bitmask_vec_u64_for_each_matching
+);
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_ffi::*;
+    use core::ffi::c_void;
+    use core::ptr;
+
+    unsafe extern "C" fn collect(
+        user: *mut c_void,
+        index: usize,
+        mask: u8,
+        data: *const u8,
+        len: usize,
+    ) -> bool {
+        let seen = unsafe { &mut *(user as *mut Vec<(usize, u8, Vec<u8>)>) };
+        seen.push((
+            index,
+            mask,
+            unsafe { core::slice::from_raw_parts(data, len) }.to_vec(),
+        ));
+        seen.len() < 2
+    }
+
+    #[test]
+    fn test_ffi_u8() {
+        unsafe {
+            let v = cj_bitmask_vec_u8_new();
+            assert!(cj_bitmask_vec_u8_push_with_mask(v, 0b01, b"ab".as_ptr(), 2));
+            assert!(cj_bitmask_vec_u8_push_with_mask(v, 0b11, ptr::null(), 0));
+            assert!(cj_bitmask_vec_u8_push_with_mask(v, 0b10, b"c".as_ptr(), 1));
+            assert!(cj_bitmask_vec_u8_push_with_mask(v, 0b11, b"de".as_ptr(), 2));
+            assert_eq!(cj_bitmask_vec_u8_len(v), 4);
+            assert_eq!(cj_bitmask_vec_u8_count_matching(v, 0b10), 3);
+
+            let mut mask = 0u8;
+            let mut len = 0usize;
+            let data = cj_bitmask_vec_u8_get(v, 3, &mut mask, &mut len);
+            assert_eq!(
+                (mask, core::slice::from_raw_parts(data, len)),
+                (0b11, &b"de"[..])
+            );
+            assert!(cj_bitmask_vec_u8_get(v, 4, &mut mask, &mut len).is_null());
+
+            let mut seen: Vec<(usize, u8, Vec<u8>)> = Vec::new();
+            let calls = cj_bitmask_vec_u8_for_each_matching(
+                v,
+                0b10,
+                Some(collect),
+                &mut seen as *mut _ as *mut c_void,
+            );
+            assert_eq!(calls, 2);
+            assert_eq!(seen, vec![(1, 0b11, vec![]), (2, 0b10, b"c".to_vec())]);
+            assert_eq!(
+                cj_bitmask_vec_u8_for_each_matching(v, 0, None, ptr::null_mut()),
+                0
+            );
+
+            (*v).set_schema(0b01);
+            assert!(!cj_bitmask_vec_u8_push_with_mask(v, 0b10, ptr::null(), 0));
+            assert!(!cj_bitmask_vec_u8_push_with_mask(v, 0b01, ptr::null(), 3));
+            assert_eq!(cj_bitmask_vec_u8_len(v), 4);
+            cj_bitmask_vec_u8_clear(v);
+            assert_eq!(cj_bitmask_vec_u8_len(v), 0);
+            cj_bitmask_vec_u8_free(v);
+            cj_bitmask_vec_u8_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_ffi_u64() {
+        unsafe {
+            let v = cj_bitmask_vec_u64_new();
+            assert!(cj_bitmask_vec_u64_push_with_mask(
+                v,
+                1 << 40,
+                b"x".as_ptr(),
+                1
+            ));
+            assert_eq!(cj_bitmask_vec_u64_count_matching(v, 1 << 40), 1);
+            assert!(!cj_bitmask_vec_u64_get(v, 0, ptr::null_mut(), ptr::null_mut()).is_null());
+            cj_bitmask_vec_u64_free(v);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::cj_bitmask_slab::*;
+
+/// Key returned by BitmaskGenSlab. Embeds the generation of the slot it was issued for, so a
+/// key to a removed entry never resolves to an entry inserted later in the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BitmaskKey {
+    index: u32,
+    generation: u32,
+}
+
+impl BitmaskKey {
+    /// Returns the slot index of the key.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    /// Returns the generation of the key.
+    #[inline]
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl SlabKey for BitmaskKey {
+    /// Panics if index doesn't fit in a u32.
+    #[inline]
+    fn from_slot(index: usize, generation: u32) -> Self {
+        Self {
+            index: u32::try_from(index).expect("too many slab entries"),
+            generation,
+        }
+    }
+
+    #[inline]
+    fn slot(&self) -> usize {
+        self.index()
+    }
+
+    #[inline]
+    fn is_current(&self, generation: u32) -> bool {
+        self.generation == generation
+    }
+}
+
+/// BitmaskGenSlab is an arena that pairs bitmasks with T and hands out generational keys.<br>
+///
+/// Like BitmaskSlab, entries never move. Each slot also counts how many times it has been
+/// freed, and a BitmaskKey only resolves while its generation matches, so stale keys return
+/// None instead of the item that reused the slot.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut slab = BitmaskGenSlab::<u8, i32>::new();
+/// let a = slab.insert_with_mask(0b00000001, 100);
+/// slab.remove(a);
+///
+/// let b = slab.insert_with_mask(0b00000010, 101);
+/// assert_eq!(a.index(), b.index());
+/// assert_eq!(slab.get(a), None);
+/// assert_eq!(slab.get(b), Some(&101));
+/// ```
+pub type BitmaskGenSlab<B, T> = BitmaskSlab<B, T, BitmaskKey>;
+
+/// Iter that returns (key, BitmaskItem) for every occupied entry.
+pub type BitmaskGenSlabIter<'a, B, T> = BitmaskSlabIter<'a, B, T, BitmaskKey>;
+
+/// Iter that returns (key, mutable BitmaskItem) for every occupied entry.
+pub type BitmaskGenSlabIterMut<'a, B, T> = BitmaskSlabIterMut<'a, B, T, BitmaskKey>;
+
+/// Iter that returns (key, BitmaskItem) for occupied entries whose bitmask matches a mask.
+pub type BitmaskGenSlabIterMatching<'a, B, T> = BitmaskSlabIterMatching<'a, B, T, BitmaskKey>;
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_gen_slab::BitmaskGenSlab;
+
+    #[test]
+    fn test_bitmask_gen_slab_insert_remove() {
+        let mut slab = BitmaskGenSlab::<u8, i32>::new();
+        let a = slab.insert_with_mask(0b00000001, 100);
+        let b = slab.insert(101);
+        assert_eq!(slab.len(), 2);
+
+        assert_eq!(slab.remove(b), Some(101));
+        assert_eq!(slab.remove(b), None);
+        assert!(!slab.contains(b));
+        assert_eq!(slab[a], 100);
+        assert_eq!(slab.len(), 1);
+    }
+
+    #[test]
+    fn test_bitmask_gen_slab_stale_key() {
+        let mut slab = BitmaskGenSlab::<u8, i32>::new();
+        let a = slab.insert(100);
+        slab.remove(a);
+        let b = slab.insert(101);
+
+        assert_eq!(a.index(), b.index());
+        assert_eq!(b.generation(), a.generation() + 1);
+        assert!(slab.get(a).is_none());
+        assert!(slab.get_mut(a).is_none());
+        assert!(slab.remove(a).is_none());
+        assert_eq!(slab[b], 101);
+    }
+
+    #[test]
+    fn test_bitmask_gen_slab_clear() {
+        let mut slab = BitmaskGenSlab::<u8, i32>::new();
+        let a = slab.insert(100);
+        let b = slab.insert(101);
+        slab.clear();
+        assert!(slab.is_empty());
+
+        let c = slab.insert(102);
+        let d = slab.insert(103);
+        assert!(slab.get(a).is_none());
+        assert!(slab.get(b).is_none());
+        assert_eq!(slab[c], 102);
+        assert_eq!(slab[d], 103);
+        assert_eq!(c.index(), a.index());
+    }
+
+    #[test]
+    fn test_bitmask_gen_slab_iter() {
+        let mut slab = BitmaskGenSlab::<u8, i32>::new();
+        let a = slab.insert_with_mask(0b00000010, 100);
+        let b = slab.insert_with_mask(0b00000001, 101);
+        let c = slab.insert_with_mask(0b00000011, 102);
+        slab.remove(a);
+
+        let x: Vec<_> = slab.iter().map(|(k, x)| (k, x.item)).collect();
+        assert_eq!(x, vec![(b, 101), (c, 102)]);
+
+        for (_, x) in slab.iter_mut() {
+            x.item *= 2;
+        }
+        assert_eq!(slab.iter().filter_mask(&0b00000010).unwrap().0, c);
+        assert_eq!(slab.count_matching(&0b00000001), 2);
+        assert_eq!(slab.iter_matching(&0b00000010).next().unwrap().1.item, 204);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::collections::btree_map::{self, BTreeMap};
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+
+/// BitmaskGroupMap buckets T by the exact value of its bitmask.<br>
+///
+/// Each distinct bitmask value owns a Vec of T, so looking up or draining every item in a
+/// given state does not require filtering the whole collection.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// const QUEUED: u8 = 0b00000001;
+/// const RUNNING: u8 = 0b00000010;
+///
+/// let mut m = BitmaskGroupMap::<u8, &str>::new();
+/// m.push_with_mask(QUEUED, "a");
+/// m.push_with_mask(RUNNING, "b");
+/// m.push_with_mask(QUEUED, "c");
+///
+/// assert_eq!(m.get(&QUEUED), &["a", "c"]);
+/// m.move_group(&QUEUED, RUNNING);
+/// assert_eq!(m.drain_group(&RUNNING), vec!["b", "a", "c"]);
+/// assert!(m.is_empty());
+/// ```
+pub struct BitmaskGroupMap<B, T>
+where
+    B: Bitflag,
+{
+    groups: BTreeMap<B, Vec<T>>,
+    len: usize,
+}
+
+impl<'a, B, T> BitmaskGroupMap<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    pub fn new() -> Self {
+        Self {
+            groups: BTreeMap::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the total number of items across all groups.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the map contains no items.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of non-empty groups.
+    #[inline]
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Removes all groups.
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.len = 0;
+    }
+
+    /// Pushes T into the group of the default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T into the group of bitmask.
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.groups.entry(bitmask).or_default().push(value);
+        self.len += 1;
+    }
+
+    /// Returns the items whose bitmask is exactly bitmask, in insertion order.
+    pub fn get(&self, bitmask: &B) -> &[T] {
+        self.groups
+            .get(bitmask)
+            .map(|x| x.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns the items whose bitmask is exactly bitmask mutably.
+    pub fn get_mut(&mut self, bitmask: &B) -> &mut [T] {
+        self.groups
+            .get_mut(bitmask)
+            .map(|x| x.as_mut_slice())
+            .unwrap_or(&mut [])
+    }
+
+    /// Removes and returns the group of bitmask.
+    pub fn drain_group(&mut self, bitmask: &B) -> Vec<T> {
+        let x = self.groups.remove(bitmask).unwrap_or_default();
+        self.len -= x.len();
+        x
+    }
+
+    /// Moves every item from the group of from to the end of the group of to.
+    pub fn move_group(&mut self, from: &B, to: B) {
+        if *from == to {
+            return;
+        }
+        if let Some(mut x) = self.groups.remove(from) {
+            self.groups.entry(to).or_default().append(&mut x);
+        }
+    }
+
+    /// Removes and returns T at index of the group of bitmask, or None if out of bounds.
+    pub fn remove(&mut self, bitmask: &B, index: usize) -> Option<T> {
+        let group = self.groups.get_mut(bitmask)?;
+        if index >= group.len() {
+            return None;
+        }
+        let x = group.remove(index);
+        if group.is_empty() {
+            self.groups.remove(bitmask);
+        }
+        self.len -= 1;
+        Some(x)
+    }
+
+    /// Returns an iterator over the bitmask values that have items, in ascending order.
+    #[inline]
+    pub fn masks(&self) -> btree_map::Keys<'_, B, Vec<T>> {
+        self.groups.keys()
+    }
+
+    /// Returns a BitmaskGroupMapIter over (bitmask, group) pairs, in ascending bitmask order.
+    #[inline]
+    pub fn iter_groups(&self) -> BitmaskGroupMapIter<'_, B, T> {
+        BitmaskGroupMapIter {
+            inner: self.groups.iter(),
+        }
+    }
+
+    /// Returns a BitmaskGroupMapIterMatching over the (bitmask, group) pairs whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskGroupMapIterMatching<'a, B, T> {
+        BitmaskGroupMapIterMatching {
+            inner: self.groups.iter(),
+            mask,
+        }
+    }
+
+    /// Returns the number of items whose bitmask matches mask.
+    pub fn count_matching(&'a self, mask: &'a B) -> usize {
+        self.iter_matching(mask).map(|(_, x)| x.len()).sum()
+    }
+
+    /// Moves the items into a BitmaskVec, grouped in ascending bitmask order.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.len);
+        for (bitmask, group) in self.groups {
+            for x in group {
+                v.push_with_mask(bitmask.clone(), x);
+            }
+        }
+        v
+    }
+}
+
+impl<'a, B, T> Default for BitmaskGroupMap<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskGroupMap<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let mut m = Self::new();
+        for x in value.drain(..) {
+            m.push_with_mask(x.bitmask, x.item);
+        }
+        m
+    }
+}
+
+impl<'a, B, T> FromIterator<BitmaskItem<B, T>> for BitmaskGroupMap<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    fn from_iter<I: IntoIterator<Item = BitmaskItem<B, T>>>(iter: I) -> Self {
+        let mut m = Self::new();
+        for x in iter {
+            m.push_with_mask(x.bitmask, x.item);
+        }
+        m
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (bitmask, group) pairs.
+pub struct BitmaskGroupMapIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: btree_map::Iter<'a, B, Vec<T>>,
+}
+
+impl<'a, B, T> Iterator for BitmaskGroupMapIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = (&'a B, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(b, x)| (b, x.as_slice()))
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (bitmask, group) pairs whose bitmask matches a mask.
+pub struct BitmaskGroupMapIterMatching<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: btree_map::Iter<'a, B, Vec<T>>,
+    mask: &'a B,
+}
+
+impl<'a, B, T> Iterator for BitmaskGroupMapIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (&'a B, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = self.mask;
+        self.inner
+            .find(|(b, _)| b.matches_mask(mask))
+            .map(|(b, x)| (b, x.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_group_map::BitmaskGroupMap;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_bitmask_group_map_push() {
+        let mut m = BitmaskGroupMap::<u8, i32>::new();
+        m.push_with_mask(0b00000100, 100);
+        m.push_with_mask(0b00000010, 101);
+        m.push_with_mask(0b00000100, 102);
+        m.push(103);
+
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.group_count(), 3);
+        assert_eq!(m.get(&0b00000100), &[100, 102]);
+        assert_eq!(m.get(&0), &[103]);
+        assert!(m.get(&0b10000000).is_empty());
+        // groups are kept in bitmask order
+        assert_eq!(
+            m.masks().copied().collect::<Vec<_>>(),
+            vec![0, 0b00000010, 0b00000100]
+        );
+    }
+
+    #[test]
+    fn test_bitmask_group_map_drain_remove() {
+        let mut m = BitmaskGroupMap::<u8, i32>::new();
+        m.push_with_mask(0b00000001, 100);
+        m.push_with_mask(0b00000010, 101);
+        m.push_with_mask(0b00000010, 102);
+
+        m.get_mut(&0b00000001)[0] = 7;
+        assert_eq!(m.remove(&0b00000001, 0), Some(7));
+        assert_eq!(m.remove(&0b00000001, 0), None);
+        assert_eq!(m.group_count(), 1);
+
+        assert_eq!(m.drain_group(&0b00000010), vec![101, 102]);
+        assert!(m.drain_group(&0b00000010).is_empty());
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn test_bitmask_group_map_move_group() {
+        let mut m = BitmaskGroupMap::<u8, i32>::new();
+        m.push_with_mask(0b00000001, 100);
+        m.push_with_mask(0b00000010, 101);
+        m.push_with_mask(0b00000001, 102);
+
+        m.move_group(&0b00000001, 0b00000010);
+        assert_eq!(m.get(&0b00000010), &[101, 100, 102]);
+        m.move_group(&0b00000010, 0b00000010);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.group_count(), 1);
+    }
+
+    #[test]
+    fn test_bitmask_group_map_iter_matching() {
+        let mut m = BitmaskGroupMap::<u8, i32>::new();
+        m.push_with_mask(0b00000011, 100);
+        m.push_with_mask(0b00000001, 101);
+        m.push_with_mask(0b00000011, 102);
+        m.push_with_mask(0b00000110, 103);
+
+        let x: Vec<_> = m.iter_matching(&0b00000010).map(|(b, _)| *b).collect();
+        assert_eq!(x, vec![0b00000011, 0b00000110]);
+        assert_eq!(m.count_matching(&0b00000010), 3);
+        assert_eq!(m.iter_groups().count(), 3);
+    }
+
+    #[test]
+    fn test_bitmask_group_map_conversion() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000001, 102);
+
+        let m = BitmaskGroupMap::from(v);
+        assert_eq!(m.get(&0b00000001), &[100, 102]);
+        let v = m.into_bitmask_vec();
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[1], 102);
+    }
+}
@@ -0,0 +1,437 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMut, BitmaskVecIterWithMask,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Index, IndexMut};
+use core::slice::Iter;
+
+/// BitmaskIndexedVec is a vec that pairs bitmasks with T and maintains a reverse index
+/// from each bit position to the (sorted) indices of the elements with that bit set.<br>
+///
+/// iter_matching() and count_matching() start from the rarest bit of the mask and only
+/// look at those candidates instead of scanning every element. This trades memory and
+/// slower mutation for faster queries.<br>
+/// Bitmasks can only be changed through set_mask() so the index stays in sync.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// const RUNNING: u8 = 0b00000010;
+/// const FAILED: u8 = 0b00000100;
+///
+/// let mut v = BitmaskIndexedVec::<u8, i32>::new();
+/// v.push_with_mask(0, 100);
+/// v.push_with_mask(RUNNING, 101);
+/// v.push_with_mask(RUNNING | FAILED, 102);
+///
+/// assert_eq!(v.count_matching(&RUNNING), 2);
+/// v.set_mask(1, FAILED);
+/// let x: Vec<_> = v.iter_matching(&FAILED).map(|x| x.item).collect();
+/// assert_eq!(x, vec![101, 102]);
+/// ```
+pub struct BitmaskIndexedVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Vec<BitmaskItem<B, T>>,
+    bits: Vec<Vec<usize>>,
+}
+
+impl<'a, B, T> BitmaskIndexedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Vec::with_capacity(capacity),
+            bits: vec![Vec::new(); B::BITS],
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Returns the sorted indices of the elements that have bit set.
+    #[inline]
+    pub fn indices_with_bit(&self, bit: usize) -> &[usize] {
+        self.bits[bit].as_slice()
+    }
+
+    /// Clears the vector and the index.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        for x in self.bits.iter_mut() {
+            x.clear();
+        }
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask, adding the new index to each set bit.
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        let index = self.inner.len();
+        for bit in bitmask.set_bits() {
+            self.bits[bit].push(index);
+        }
+        self.inner.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        let x = self.inner.pop()?;
+        for bit in x.bitmask.set_bits() {
+            self.bits[bit].pop();
+        }
+        Some(x)
+    }
+
+    /// Replaces the bitmask at index, updating only the bits that changed.
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        let old = self.inner[index].bitmask;
+        for bit in (old ^ bitmask).set_bits() {
+            let list = &mut self.bits[bit];
+            match list.binary_search(&index) {
+                Ok(pos) => {
+                    list.remove(pos);
+                }
+                Err(pos) => list.insert(pos, index),
+            }
+        }
+        self.inner[index].bitmask = bitmask;
+    }
+
+    /// Removes an element and bitmask from the vector and returns it, replacing it with the last element.
+    pub fn swap_with_mask_remove(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let last = self.inner.len() - 1;
+        if index == last {
+            return self.pop_with_mask().unwrap();
+        }
+        // index the removed slot with the last element's bits, then drop the last slot
+        let old = self.inner[index].bitmask;
+        self.set_mask(index, self.inner[last].bitmask);
+        self.inner.swap(index, last);
+        let mut x = self.pop_with_mask().unwrap();
+        x.bitmask = old;
+        x
+    }
+
+    /// Removes an element without bitmask from the vector and returns it, replacing it with the last element.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.swap_with_mask_remove(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left.
+    /// * this touches every index entry after index. Prefer swap_remove() when order doesn't matter.
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let x = self.inner.remove(index);
+        for list in self.bits.iter_mut() {
+            let pos = match list.binary_search(&index) {
+                Ok(pos) => {
+                    list.remove(pos);
+                    pos
+                }
+                Err(pos) => pos,
+            };
+            for i in list[pos..].iter_mut() {
+                *i -= 1;
+            }
+        }
+        x
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMut for mutable iteration over T. Bitmasks can only be changed with set_mask().
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecIterMut<'a, B, T> {
+        BitmaskVecIterMut::new(self.inner.iter_mut())
+    }
+
+    /// Returns a BitmaskIndexedVecIterMatching over the BitmaskItems whose bitmask matches mask,
+    /// visiting only the elements that have the mask's rarest bit set.
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskIndexedVecIterMatching<'a, B, T> {
+        BitmaskIndexedVecIterMatching {
+            items: &self.inner,
+            candidates: self.candidates(mask).map(|x| x.iter()),
+            pos: 0,
+            mask,
+        }
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    /// This is O(1) when mask has a single bit set.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        match self.candidates(mask) {
+            Some(x) if mask.count_bits() == 1 => x.len(),
+            Some(x) => x
+                .iter()
+                .filter(|&&i| self.inner[i].matches_mask(mask))
+                .count(),
+            None => self.inner.len(),
+        }
+    }
+
+    /// Moves the items into a BitmaskVec, dropping the index.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.inner.len());
+        for x in self.inner {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+
+    /// Returns the shortest index list among the bits set in mask, or None if mask is zero.
+    fn candidates(&self, mask: &B) -> Option<&[usize]> {
+        mask.set_bits()
+            .map(|bit| self.bits[bit].as_slice())
+            .min_by_key(|x| x.len())
+    }
+}
+
+impl<'a, B, T> Default for BitmaskIndexedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskIndexedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let mut v = Self::with_capacity(value.len());
+        for x in value.drain(..) {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskIndexedVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<B, T> IndexMut<usize> for BitmaskIndexedVec<B, T>
+where
+    B: Bitflag,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index].item
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem for items whose bitmask matches mask, using the bit index.
+pub struct BitmaskIndexedVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    items: &'a [BitmaskItem<B, T>],
+    candidates: Option<Iter<'a, usize>>,
+    pos: usize,
+    mask: &'a B,
+}
+
+impl<'a, B, T> Iterator for BitmaskIndexedVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let items = self.items;
+        let mask = self.mask;
+        match self.candidates.as_mut() {
+            Some(candidates) => candidates
+                .map(|&i| &items[i])
+                .find(|x| x.matches_mask(mask)),
+            // a zero mask matches everything
+            None => {
+                let x = items.get(self.pos);
+                self.pos += 1;
+                x
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_indexed_vec::BitmaskIndexedVec;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    fn matching(v: &BitmaskIndexedVec<u8, i32>, mask: u8) -> Vec<i32> {
+        v.iter_matching(&mask).map(|x| x.item).collect()
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_push() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push(101);
+        v.push_with_mask(0b00000011, 102);
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.indices_with_bit(1), &[0, 2]);
+        assert_eq!(v.indices_with_bit(0), &[2]);
+        assert!(v.indices_with_bit(7).is_empty());
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_iter_matching() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000001, 102);
+        v.push(103);
+
+        assert_eq!(matching(&v, 0b00000010), vec![100, 101]);
+        assert_eq!(matching(&v, 0b00000011), vec![100]);
+        assert_eq!(matching(&v, 0b10000000), Vec::<i32>::new());
+        assert_eq!(matching(&v, 0).len(), 4);
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_count_matching() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000110, 100);
+        v.push_with_mask(0b00000100, 101);
+        v.push(102);
+
+        assert_eq!(v.count_matching(&0b00000100), 2);
+        assert_eq!(v.count_matching(&0b00000110), 1);
+        assert_eq!(v.count_matching(&0), 3);
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_set_mask() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push(100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        v.set_mask(0, 0b00000011);
+        v.set_mask(2, 0b00000100);
+        assert_eq!(v.indices_with_bit(0), &[0]);
+        assert_eq!(v.indices_with_bit(1), &[0, 1]);
+        assert_eq!(v.indices_with_bit(2), &[2]);
+        assert_eq!(matching(&v, 0b00000011), vec![100]);
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_pop() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push(102);
+
+        assert_eq!(v.pop(), Some(102));
+        let x = v.pop_with_mask().unwrap();
+        assert_eq!((x.bitmask, x.item), (0b00000001, 101));
+        assert_eq!(v.indices_with_bit(0), &[0]);
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_swap_remove() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000001, 103);
+
+        // 103 moves into slot 0
+        assert_eq!(v.swap_remove(0), 100);
+        assert_eq!(v[0], 103);
+        assert_eq!(v.indices_with_bit(0), &[0]);
+        assert_eq!(v.indices_with_bit(1), &[1, 2]);
+
+        // removing the last element moves nothing
+        let x = v.swap_with_mask_remove(2);
+        assert_eq!((x.bitmask, x.item), (0b00000010, 102));
+        assert_eq!(v.indices_with_bit(1), &[1]);
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_remove() {
+        let mut v = BitmaskIndexedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        // later indices shift down
+        assert_eq!(v.remove(0), 100);
+        assert_eq!(v.indices_with_bit(0), &[0, 1]);
+        assert_eq!(v.indices_with_bit(1), &[1]);
+        assert_eq!(matching(&v, 0b00000010), vec![102]);
+    }
+
+    #[test]
+    fn test_bitmask_indexed_vec_from_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+
+        let v = BitmaskIndexedVec::from(v);
+        assert_eq!(v.indices_with_bit(0), &[0, 1]);
+        assert_eq!(v.into_bitmask_vec().len(), 2);
+    }
+}
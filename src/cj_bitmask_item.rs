@@ -1,7 +1,25 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_mask_parse::{parse_mask, MaskParseError};
 use cj_common::cj_binary::bitbuf::*;
+use core::cmp::Ordering;
+use core::fmt::{Debug, Display, Formatter};
+use core::mem::size_of;
+use core::ops::{BitAnd, Range};
+use core::str::FromStr;
 
 /// BitmaskItem pairs T with a bitmask
-#[derive(Debug, Clone)]
+/// BitmaskItems order by bitmask first, then by item. Use cmp_item_first() to order by item first.
+///
+/// The compiler orders the two fields to keep padding to a minimum, so BitmaskItem<u8, u8> takes
+/// 2 bytes. Padding is still added when the alignment of T is larger than the size of the bitmask,
+/// for example BitmaskItem<u8, u32> takes 8 bytes. SIZE and PADDING give the layout for a pair of
+/// types; BitmaskVecSoA stores bitmasks and T in separate buffers and has no padding at all.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// assert_eq!(BitmaskItem::<u8, u8>::SIZE, 2);
+/// assert_eq!(BitmaskItem::<u8, u32>::PADDING, 3);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct BitmaskItem<B, T>
 where
     B: Bitflag,
@@ -10,6 +28,17 @@ where
     pub item: T,
 }
 
+impl<B, T> BitmaskItem<B, T>
+where
+    B: Bitflag,
+{
+    /// Size in bytes of a BitmaskItem, and so of each element of a BitmaskVec.
+    pub const SIZE: usize = size_of::<Self>();
+
+    /// Bytes of each BitmaskItem taken by alignment padding.
+    pub const PADDING: usize = Self::SIZE - size_of::<B>() - size_of::<T>();
+}
+
 impl<'a, B, T> BitmaskItem<B, T>
 where
     B: Bitflag + CjMatchesMask<'a, B>,
@@ -24,13 +53,244 @@ where
     pub fn matches_mask(&self, mask: &'a B) -> bool {
         self.bitmask.matches_mask(mask)
     }
+
+    /// Returns true if any set flag in mask is set in bitmask<br>
+    ///   <i>(bitmask & mask) != 0</i>
+    #[inline]
+    pub fn matches_any(&self, mask: &B) -> bool
+    where
+        B: Copy + Default + PartialEq + BitAnd<Output = B>,
+    {
+        (self.bitmask & *mask) != B::default()
+    }
+
+    /// Returns true if bitmask is exactly mask<br>
+    ///   <i>bitmask == mask</i>
+    #[inline]
+    pub fn matches_exact(&self, mask: &B) -> bool
+    where
+        B: PartialEq,
+    {
+        self.bitmask == *mask
+    }
+
+    /// Compares by item first, then by bitmask. Pass to sort_by() for item first ordering.
+    #[inline]
+    pub fn cmp_item_first(&self, other: &Self) -> Ordering
+    where
+        B: Ord,
+        T: Ord,
+    {
+        self.item
+            .cmp(&other.item)
+            .then_with(|| self.bitmask.cmp(&other.bitmask))
+    }
+
+    /// Consumes the BitmaskItem, returning (bitmask, item)
+    #[inline]
+    pub fn into_tuple(self) -> (B, T) {
+        (self.bitmask, self.item)
+    }
+
+    /// Returns references to bitmask and item as a tuple
+    #[inline]
+    pub fn as_tuple(&self) -> (&B, &T) {
+        (&self.bitmask, &self.item)
+    }
+
+    /// Returns the value packed into the bits of range, with range.start as its lowest bit.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// const PRIORITY: core::ops::Range<usize> = 4..8;
+    ///
+    /// let mut x = BitmaskItem::new(0b00000001u8, 100);
+    /// x.set_field(PRIORITY, 5);
+    /// assert_eq!(x.bitmask, 0b01010001);
+    /// assert_eq!(x.get_field(PRIORITY), 5);
+    /// ```
+    /// # Panics
+    /// Panics if range is wider than 64 bits.
+    pub fn get_field(&self, range: Range<usize>) -> u64 {
+        assert!(range.len() <= 64, "bit field is wider than 64 bits");
+        range
+            .rev()
+            .fold(0, |acc, bit| (acc << 1) | self.bitmask.get_bit(bit) as u64)
+    }
+
+    /// Packs value into the bits of range, with range.start as its lowest bit. Other bits are unchanged.
+    /// # Panics
+    /// Panics if range is wider than 64 bits or value doesn't fit in it.
+    pub fn set_field(&mut self, range: Range<usize>, value: u64) {
+        let width = range.len();
+        assert!(width <= 64, "bit field is wider than 64 bits");
+        assert!(
+            width == 64 || value >> width == 0,
+            "value {value} doesn't fit in a {width} bit field"
+        );
+        for (i, bit) in range.enumerate() {
+            self.bitmask.set_bit(bit, (value >> i) & 1 == 1);
+        }
+    }
+}
+
+impl<B, T> From<(B, T)> for BitmaskItem<B, T>
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn from(value: (B, T)) -> Self {
+        Self {
+            bitmask: value.0,
+            item: value.1,
+        }
+    }
+}
+
+impl<B, T> From<BitmaskItem<B, T>> for (B, T)
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn from(value: BitmaskItem<B, T>) -> Self {
+        (value.bitmask, value.item)
+    }
+}
+
+/// Error returned when parsing a BitmaskItem from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BitmaskItemParseError<E> {
+    /// There is no ':' between the bitmask and the item.
+    MissingSeparator,
+    /// The bitmask failed to parse.
+    Mask(MaskParseError),
+    /// The item failed to parse.
+    Item(E),
+}
+
+impl<E: Display> Display for BitmaskItemParseError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BitmaskItemParseError::MissingSeparator => {
+                write!(f, "expected ':' between the bitmask and the item")
+            }
+            BitmaskItemParseError::Mask(e) => write!(f, "invalid bitmask: {e}"),
+            BitmaskItemParseError::Item(e) => write!(f, "invalid item: {e}"),
+        }
+    }
+}
+
+impl<E: Debug + Display> std::error::Error for BitmaskItemParseError<E> {}
+
+impl<B, T> FromStr for BitmaskItem<B, T>
+where
+    B: Bitflag + BitmaskBits,
+    T: FromStr,
+{
+    type Err = BitmaskItemParseError<T::Err>;
+
+    /// Parses "bitmask:item", where bitmask is read by parse_mask() and item is parsed with
+    /// T::from_str(). Everything after the first ':' is the item.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let x: BitmaskItem<u8, String> = "0b00000110:hello".parse().unwrap();
+    /// assert_eq!(x, BitmaskItem::new(0b00000110, "hello".to_string()));
+    ///
+    /// let x: BitmaskItem<u16, i32> = "0x1F:-5".parse().unwrap();
+    /// assert_eq!(x.bitmask, 0x1F);
+    ///
+    /// assert_eq!(
+    ///     "0b1:x".parse::<BitmaskItem<u8, i32>>(),
+    ///     Err(BitmaskItemParseError::Item("x".parse::<i32>().unwrap_err()))
+    /// );
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mask, item) = s
+            .split_once(':')
+            .ok_or(BitmaskItemParseError::MissingSeparator)?;
+        let bitmask = parse_mask(mask.trim()).map_err(BitmaskItemParseError::Mask)?;
+        let item = item.parse().map_err(BitmaskItemParseError::Item)?;
+        Ok(Self { bitmask, item })
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::cj_bitmask_item::BitmaskItem;
+    use crate::cj_bitmask_item::{BitmaskItem, BitmaskItemParseError};
+    use crate::cj_mask_parse::MaskParseError;
     use cj_common::prelude::CjMatchesMask;
 
+    #[test]
+    fn test_bitmask_item_from_str() {
+        let x: BitmaskItem<u8, i32> = "0b101:-7".parse().unwrap();
+        assert_eq!(x, BitmaskItem::new(0b101, -7));
+        let x: BitmaskItem<u32, String> = " 0xFF00 : a:b".parse().unwrap();
+        assert_eq!(x, BitmaskItem::new(0xFF00, " a:b".to_string()));
+        let x: BitmaskItem<u8, String> = "255:".parse().unwrap();
+        assert_eq!(x, BitmaskItem::new(255, String::new()));
+
+        type E = BitmaskItemParseError<core::num::ParseIntError>;
+        assert_eq!(
+            "0b101".parse::<BitmaskItem<u8, i32>>(),
+            Err(E::MissingSeparator)
+        );
+        assert_eq!(
+            "0x100:1".parse::<BitmaskItem<u8, i32>>(),
+            Err(E::Mask(MaskParseError::Overflow))
+        );
+        assert_eq!(
+            "0b_:1".parse::<BitmaskItem<u8, i32>>(),
+            Err(E::Mask(MaskParseError::Empty))
+        );
+        assert_eq!(
+            "0b102:1".parse::<BitmaskItem<u8, i32>>(),
+            Err(E::Mask(MaskParseError::InvalidDigit { pos: 4, ch: '2' }))
+        );
+        assert!(matches!(
+            "0b1:x".parse::<BitmaskItem<u8, i32>>(),
+            Err(E::Item(_))
+        ));
+        assert_eq!(
+            E::MissingSeparator.to_string(),
+            "expected ':' between the bitmask and the item"
+        );
+    }
+
+    #[test]
+    fn test_bitmask_item_layout() {
+        assert_eq!(BitmaskItem::<u8, u8>::SIZE, 2);
+        assert_eq!(BitmaskItem::<u8, u8>::PADDING, 0);
+        assert_eq!(BitmaskItem::<u8, u16>::SIZE, 4);
+        assert_eq!(BitmaskItem::<u8, u16>::PADDING, 1);
+        assert_eq!(BitmaskItem::<u16, u8>::SIZE, 4);
+        assert_eq!(BitmaskItem::<u8, [u8; 3]>::SIZE, 4);
+        assert_eq!(BitmaskItem::<u32, u64>::PADDING, 4);
+        assert_eq!(BitmaskItem::<u64, [u8; 8]>::PADDING, 0);
+    }
+
+    #[test]
+    fn test_bitmask_item_field() {
+        let mut x = BitmaskItem::new(0b1000_0001u8, 100);
+        assert_eq!(x.get_field(1..4), 0);
+        x.set_field(1..4, 0b101);
+        assert_eq!(x.bitmask, 0b1000_1011);
+        assert_eq!(x.get_field(1..4), 0b101);
+        assert_eq!(x.get_field(0..8), 0b1000_1011);
+        x.set_field(1..4, 0);
+        assert_eq!(x.bitmask, 0b1000_0001);
+
+        let mut x = BitmaskItem::new(0u128, 100);
+        x.set_field(64..128, u64::MAX);
+        assert_eq!(x.bitmask, (u64::MAX as u128) << 64);
+        assert_eq!(x.get_field(60..124), 0xFFFF_FFFF_FFFF_FFF0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_item_field_overflow() {
+        let mut x = BitmaskItem::new(0u8, 100);
+        x.set_field(4..6, 4);
+    }
+
     #[test]
     fn test_bitmask_item() {
         let x = BitmaskItem {
@@ -61,4 +321,96 @@ mod test {
 
         assert!(x.matches_mask(&0b00000010u8));
     }
+
+    #[test]
+    fn test_bitmask_item_eq() {
+        let x = BitmaskItem::new(2u8, 1000);
+
+        assert_eq!(x, BitmaskItem::new(2u8, 1000));
+        assert_ne!(x, BitmaskItem::new(3u8, 1000));
+        assert_ne!(x, BitmaskItem::new(2u8, 1001));
+    }
+
+    #[test]
+    fn test_bitmask_item_hash() {
+        use std::collections::HashSet;
+
+        let mut set = HashSet::new();
+        set.insert(BitmaskItem::new(2u8, 1000));
+        set.insert(BitmaskItem::new(2u8, 1000));
+        set.insert(BitmaskItem::new(3u8, 1000));
+
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_bitmask_item_ord() {
+        let mut x = vec![
+            BitmaskItem::new(2u8, 1),
+            BitmaskItem::new(1u8, 3),
+            BitmaskItem::new(2u8, 0),
+        ];
+
+        x.sort();
+        assert_eq!(
+            x,
+            vec![
+                BitmaskItem::new(1u8, 3),
+                BitmaskItem::new(2u8, 0),
+                BitmaskItem::new(2u8, 1)
+            ]
+        );
+
+        x.sort_by(BitmaskItem::cmp_item_first);
+        assert_eq!(x[0], BitmaskItem::new(2u8, 0));
+        assert_eq!(x[2], BitmaskItem::new(1u8, 3));
+    }
+
+    #[test]
+    fn test_bitmask_item_matches_any() {
+        let x = BitmaskItem::new(0b00000110u8, 1000);
+
+        assert!(x.matches_any(&0b00000010u8));
+        assert!(x.matches_any(&0b10000100u8));
+        assert!(!x.matches_any(&0b10000001u8));
+        assert!(!x.matches_any(&0u8));
+    }
+
+    #[test]
+    fn test_bitmask_item_matches_exact() {
+        let x = BitmaskItem::new(0b00000110u8, 1000);
+
+        assert!(x.matches_exact(&0b00000110u8));
+        assert!(!x.matches_exact(&0b00000010u8));
+        assert!(!x.matches_exact(&0b00000111u8));
+    }
+
+    #[test]
+    fn test_bitmask_item_from_tuple() {
+        let x: BitmaskItem<u8, i32> = (0b00000010, 1000).into();
+
+        assert_eq!(x, BitmaskItem::new(0b00000010u8, 1000));
+    }
+
+    #[test]
+    fn test_bitmask_item_into_tuple() {
+        let x = BitmaskItem::new(0b00000010u8, 1000);
+        assert_eq!(x.as_tuple(), (&0b00000010u8, &1000));
+
+        let (bitmask, item) = x.clone().into_tuple();
+        assert_eq!((bitmask, item), (0b00000010u8, 1000));
+
+        let t: (u8, i32) = x.into();
+        assert_eq!(t, (0b00000010u8, 1000));
+    }
+
+    #[test]
+    fn test_bitmask_item_default() {
+        let mut x = BitmaskItem::new(0b00000010u8, 1000);
+        assert_eq!(BitmaskItem::<u8, i32>::default(), BitmaskItem::new(0u8, 0));
+
+        let y = core::mem::take(&mut x);
+        assert_eq!(y, BitmaskItem::new(0b00000010u8, 1000));
+        assert_eq!(x, BitmaskItem::new(0u8, 0));
+    }
 }
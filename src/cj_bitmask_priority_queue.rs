@@ -0,0 +1,266 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use cj_common::cj_binary::bitbuf::*;
+use core::cmp::Ordering;
+
+/// Heap entry. Equal priorities pop in insertion order.
+struct Entry<B, T, P>
+where
+    B: Bitflag,
+{
+    priority: P,
+    seq: u64,
+    item: BitmaskItem<B, T>,
+}
+
+impl<B, T, P> PartialEq for Entry<B, T, P>
+where
+    B: Bitflag,
+    P: Ord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<B, T, P> Eq for Entry<B, T, P>
+where
+    B: Bitflag,
+    P: Ord,
+{
+}
+
+impl<B, T, P> PartialOrd for Entry<B, T, P>
+where
+    B: Bitflag,
+    P: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<B, T, P> Ord for Entry<B, T, P>
+where
+    B: Bitflag,
+    P: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// BitmaskPriorityQueue is a binary heap of BitmaskItems ordered by a priority computed from
+/// the bitmask.<br>
+///
+/// The priority function is supplied at construction, or use by_popcount() or
+/// by_priority_bits() for the common cases. pop() returns the highest priority BitmaskItem,
+/// and items with equal priority pop in the order they were pushed.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// // bit 7 marks urgent work, bit 6 high priority work
+/// let mut q = BitmaskPriorityQueue::<u8, &str, u8>::by_priority_bits(0b11000000);
+/// q.push_with_mask(0b00000001, "normal");
+/// q.push_with_mask(0b01000001, "high");
+/// q.push_with_mask(0b10000000, "urgent");
+/// q.push_with_mask(0b00000011, "normal 2");
+///
+/// assert_eq!(q.pop(), Some("urgent"));
+/// assert_eq!(q.pop(), Some("high"));
+/// assert_eq!(q.pop(), Some("normal"));
+/// assert_eq!(q.pop(), Some("normal 2"));
+/// ```
+pub struct BitmaskPriorityQueue<B, T, P = u32>
+where
+    B: Bitflag,
+{
+    heap: BinaryHeap<Entry<B, T, P>>,
+    priority: Box<dyn Fn(&B) -> P + Send + Sync>,
+    seq: u64,
+}
+
+impl<'a, B, T, P> BitmaskPriorityQueue<B, T, P>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    P: Ord,
+{
+    /// Constructs a new, empty queue that orders items by priority(bitmask), highest first.
+    /// priority must be Send + Sync so the queue can be shared across threads.
+    pub fn with_priority<F>(priority: F) -> Self
+    where
+        F: Fn(&B) -> P + Send + Sync + 'static,
+    {
+        Self {
+            heap: BinaryHeap::new(),
+            priority: Box::new(priority),
+            seq: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Returns true if the queue contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Clears the queue, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    /// Returns the priority the queue assigns to bitmask.
+    #[inline]
+    pub fn priority_of(&self, bitmask: &B) -> P {
+        (self.priority)(bitmask)
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask.
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        let priority = (self.priority)(&bitmask);
+        self.heap.push(Entry {
+            priority,
+            seq: self.seq,
+            item: BitmaskItem::new(bitmask, value),
+        });
+        self.seq += 1;
+    }
+
+    /// Pops the highest priority T without the bitmask.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops the highest priority BitmaskItem containing both T and bitmask.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        self.heap.pop().map(|x| x.item)
+    }
+
+    /// Returns the highest priority BitmaskItem without removing it.
+    #[inline]
+    pub fn peek_with_mask(&self) -> Option<&BitmaskItem<B, T>> {
+        self.heap.peek().map(|x| &x.item)
+    }
+
+    /// Returns an iterator over the BitmaskItems in arbitrary order.
+    #[inline]
+    pub fn iter_with_mask(&self) -> impl Iterator<Item = &BitmaskItem<B, T>> {
+        self.heap.iter().map(|x| &x.item)
+    }
+
+    /// Returns the number of items whose bitmask matches mask.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.heap
+            .iter()
+            .filter(|x| x.item.matches_mask(mask))
+            .count()
+    }
+
+    /// Moves the items into a BitmaskVec, highest priority first.
+    pub fn into_bitmask_vec(mut self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.heap.len());
+        while let Some(x) = self.heap.pop() {
+            v.push_with_mask(x.item.bitmask, x.item.item);
+        }
+        v
+    }
+}
+
+impl<'a, B, T> BitmaskPriorityQueue<B, T, u32>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    /// Constructs a new, empty queue where bitmasks with more set bits have higher priority.
+    pub fn by_popcount() -> Self {
+        Self::with_priority(|b: &B| b.count_bits())
+    }
+}
+
+impl<'a, B, T> BitmaskPriorityQueue<B, T, B>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits + Ord + Send + Sync + 'static,
+{
+    /// Constructs a new, empty queue ordered by the value of bitmask & bits, so higher priority
+    /// bits outrank lower ones and bits outside of bits are ignored.
+    pub fn by_priority_bits(bits: B) -> Self {
+        Self::with_priority(move |b: &B| *b & bits)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_priority_queue::BitmaskPriorityQueue;
+
+    #[test]
+    fn test_bitmask_priority_queue_with_priority() {
+        let mut q = BitmaskPriorityQueue::<u8, i32, i32>::with_priority(|b| -(*b as i32));
+        q.push_with_mask(0b00000100, 100);
+        q.push_with_mask(0b00000001, 101);
+        q.push(102);
+
+        assert_eq!(q.len(), 3);
+        assert_eq!(q.peek_with_mask().unwrap().item, 102);
+        assert_eq!(q.pop(), Some(102));
+        assert_eq!(q.pop(), Some(101));
+        assert_eq!(q.pop(), Some(100));
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn test_bitmask_priority_queue_send_sync() {
+        fn assert_send_sync<X: Send + Sync>(_: &X) {}
+        let q = BitmaskPriorityQueue::<u8, i32, u8>::by_priority_bits(0b11000000);
+        assert_send_sync(&q);
+        let n = std::thread::spawn(move || q.len()).join().unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[test]
+    fn test_bitmask_priority_queue_by_popcount() {
+        let mut q = BitmaskPriorityQueue::<u8, i32>::by_popcount();
+        q.push_with_mask(0b00000001, 100);
+        q.push_with_mask(0b00000111, 101);
+        q.push_with_mask(0b00000010, 102);
+        q.push_with_mask(0b00000011, 103);
+
+        assert_eq!(q.priority_of(&0b11110000), 4);
+        let x = q.pop_with_mask().unwrap();
+        assert_eq!((x.bitmask, x.item), (0b00000111, 101));
+        assert_eq!(q.pop(), Some(103));
+        assert_eq!(q.pop(), Some(100));
+        assert_eq!(q.pop(), Some(102));
+    }
+
+    #[test]
+    fn test_bitmask_priority_queue_by_priority_bits() {
+        let mut q = BitmaskPriorityQueue::<u16, i32, u16>::by_priority_bits(0b1100);
+        q.push_with_mask(0b0011, 100);
+        q.push_with_mask(0b0100, 101);
+        q.push_with_mask(0b1000, 102);
+        q.push_with_mask(0b0111, 103);
+
+        assert_eq!(q.count_matching(&0b0100), 2);
+        assert_eq!(q.iter_with_mask().count(), 4);
+        let v = q.into_bitmask_vec();
+        let x: Vec<_> = v.as_slice().iter().map(|x| x.item).collect();
+        assert_eq!(x, vec![102, 101, 103, 100]);
+    }
+}
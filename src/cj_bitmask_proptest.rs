@@ -0,0 +1,73 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use cj_common::cj_binary::bitbuf::*;
+use core::fmt::{Binary, Debug};
+use proptest::collection::{vec, SizeRange};
+use proptest::strategy::Strategy;
+
+/// Strategy generating a BitmaskItem from a bitmask strategy and a T strategy.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// use proptest::prelude::*;
+///
+/// proptest!(|(x in bitmask_item(any::<u8>(), 0..100i32))| {
+///     prop_assert!(x.item < 100);
+/// });
+/// ```
+pub fn bitmask_item<B, T>(
+    mask: impl Strategy<Value = B>,
+    item: impl Strategy<Value = T>,
+) -> impl Strategy<Value = BitmaskItem<B, T>>
+where
+    B: Bitflag + Debug,
+    T: Debug,
+{
+    (mask, item).prop_map(BitmaskItem::from)
+}
+
+/// Strategy generating a BitmaskVec whose length falls within size, with each bitmask and T
+/// drawn from the supplied strategies. Shrinking removes elements and shrinks bitmasks and T.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// use proptest::prelude::*;
+///
+/// proptest!(|(v in bitmask_vec(0..4u8, any::<i32>(), 0..20))| {
+///     prop_assert!(v.len() < 20);
+///     prop_assert_eq!(v.count_matching(&0b00000100), 0);
+/// });
+/// ```
+pub fn bitmask_vec<'a, B, T>(
+    mask: impl Strategy<Value = B>,
+    item: impl Strategy<Value = T>,
+    size: impl Into<SizeRange>,
+) -> impl Strategy<Value = BitmaskVec<B, T>>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Binary + Debug,
+    T: Debug,
+{
+    vec(bitmask_item(mask, item), size).prop_map(|items| {
+        let mut v = BitmaskVec::new();
+        *v.as_mut_vec() = items;
+        v
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_proptest::{bitmask_item, bitmask_vec};
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_bitmask_proptest_item(x in bitmask_item(0..4u8, 100..200i32)) {
+            prop_assert!(x.bitmask < 4);
+            prop_assert!((100..200).contains(&x.item));
+        }
+
+        #[test]
+        fn test_bitmask_proptest_vec(v in bitmask_vec(Just(0b00000010u8), any::<i32>(), 1..10)) {
+            prop_assert!(!v.is_empty() && v.len() < 10);
+            prop_assert_eq!(v.count_matching(&0b00000010), v.len());
+        }
+    }
+}
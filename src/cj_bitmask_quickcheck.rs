@@ -0,0 +1,104 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use quickcheck::{Arbitrary, Gen};
+
+/// Smaller bitmasks to try: zero first, then mask with each set bit cleared in turn.
+fn shrink_mask<B>(mask: B) -> Vec<B>
+where
+    B: BitmaskBits,
+{
+    let mut masks = Vec::new();
+    if mask.is_zero() {
+        return masks;
+    }
+    masks.push(B::default());
+    if mask.count_bits() > 1 {
+        masks.extend(mask.set_bits().map(|bit| mask & !B::bit(bit)));
+    }
+    masks
+}
+
+impl<B, T> Arbitrary for BitmaskItem<B, T>
+where
+    B: Bitflag + BitmaskBits + Arbitrary,
+    T: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        Self {
+            bitmask: B::arbitrary(g),
+            item: T::arbitrary(g),
+        }
+    }
+
+    /// Shrinks by clearing bits of the bitmask, then by shrinking T.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let bitmask = self.bitmask;
+        let item = self.item.clone();
+        let masks = shrink_mask(bitmask).into_iter().map(move |bitmask| Self {
+            bitmask,
+            item: item.clone(),
+        });
+        let items = self.item.shrink().map(move |item| Self { bitmask, item });
+        Box::new(masks.chain(items))
+    }
+}
+
+impl<B, T> Arbitrary for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'static, B> + Clone + Default + BitmaskBits + Arbitrary,
+    T: Arbitrary,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let mut v = Self::new();
+        *v.as_mut_vec() = Vec::<BitmaskItem<B, T>>::arbitrary(g);
+        v
+    }
+
+    /// Shrinks by dropping elements, then by shrinking each remaining BitmaskItem.
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(self.as_slice().to_vec().shrink().map(|items| {
+            let mut v = Self::new();
+            *v.as_mut_vec() = items;
+            v
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_item::BitmaskItem;
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use quickcheck::{quickcheck, Arbitrary};
+
+    #[test]
+    fn test_bitmask_quickcheck_item_shrink() {
+        let x = BitmaskItem::new(0b00000110u8, 0u32);
+        let masks: Vec<u8> = x.shrink().map(|x| x.bitmask).collect();
+
+        assert_eq!(masks, vec![0b00000000, 0b00000100, 0b00000010]);
+        assert_eq!(BitmaskItem::new(0u8, 0u32).shrink().count(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_quickcheck_vec_shrink() {
+        let mut v = BitmaskVec::<u8, u32>::new();
+        v.push_with_mask(0b00000001, 0);
+        v.push_with_mask(0b00000010, 0);
+
+        let shrunk: Vec<_> = v.shrink().collect();
+        assert!(shrunk.iter().any(|x| x.len() < v.len()));
+        assert!(shrunk.iter().any(|x| x.mask_union() == 0b00000001));
+    }
+
+    #[test]
+    fn test_bitmask_quickcheck_property() {
+        fn prop(v: BitmaskVec<u8, i32>) -> bool {
+            v.count_matching(&0) == v.len()
+        }
+        quickcheck(prop as fn(BitmaskVec<u8, i32>) -> bool);
+    }
+}
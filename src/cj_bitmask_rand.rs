@@ -0,0 +1,148 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Picks k random elements whose bitmask matches mask, each equally likely, in one pass and
+    /// without collecting every match first (reservoir sampling). Returns fewer than k elements if
+    /// fewer match. The returned elements are in no particular order.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// for i in 0..100 {
+    ///     v.push_with_mask((i % 2) as u8, i);
+    /// }
+    ///
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    /// let picked = v.sample_matching(&0b00000001, 5, &mut rng);
+    /// assert_eq!(picked.len(), 5);
+    /// assert!(picked.iter().all(|x| x.item % 2 == 1));
+    /// ```
+    pub fn sample_matching<R>(&self, mask: &'a B, k: usize, rng: &mut R) -> Vec<&BitmaskItem<B, T>>
+    where
+        R: Rng + ?Sized,
+    {
+        let mut picked = Vec::with_capacity(k.min(self.len()));
+        if k == 0 {
+            return picked;
+        }
+        let mut seen = 0usize;
+        for x in self.as_slice().iter() {
+            if !x.matches_mask(mask) {
+                continue;
+            }
+            if seen < k {
+                picked.push(x);
+            } else {
+                let j = rng.random_range(0..=seen);
+                if j < k {
+                    picked[j] = x;
+                }
+            }
+            seen += 1;
+        }
+        picked
+    }
+
+    /// Shuffles the vector in place. Each bitmask moves together with its T.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// use rand::SeedableRng;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// for i in 0..8 {
+    ///     v.push_with_mask(i as u8, i);
+    /// }
+    ///
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    /// v.shuffle(&mut rng);
+    /// assert!(v.iter_with_mask().all(|x| x.bitmask as i32 == x.item));
+    /// ```
+    #[inline]
+    pub fn shuffle<R>(&mut self, rng: &mut R)
+    where
+        R: Rng + ?Sized,
+    {
+        self.as_mut_vec().shuffle(rng);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_bitmask_rand_sample_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        for i in 0..50 {
+            v.push_with_mask(if i < 10 { 0b00000010 } else { 0 }, i);
+        }
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let x = v.sample_matching(&0b00000010, 3, &mut rng);
+        assert_eq!(x.len(), 3);
+        assert!(x.iter().all(|x| x.item < 10));
+
+        let mut x: Vec<i32> = v
+            .sample_matching(&0b00000010, 20, &mut rng)
+            .iter()
+            .map(|x| x.item)
+            .collect();
+        x.sort();
+        assert_eq!(x, (0..10).collect::<Vec<_>>());
+
+        assert!(v.sample_matching(&0b00000010, 0, &mut rng).is_empty());
+        assert!(v.sample_matching(&0b10000000, 3, &mut rng).is_empty());
+        // k larger than the vec does not reserve k slots
+        assert_eq!(
+            v.sample_matching(&0b00000010, usize::MAX, &mut rng).len(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_bitmask_rand_sample_matching_spread() {
+        let mut v = BitmaskVec::<u8, usize>::new();
+        for i in 0..10 {
+            v.push_with_mask(0b00000001, i);
+        }
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let mut hits = [0usize; 10];
+        for _ in 0..1000 {
+            for x in v.sample_matching(&0b00000001, 2, &mut rng) {
+                hits[x.item] += 1;
+            }
+        }
+        // every element should be picked roughly 200 times
+        assert!(hits.iter().all(|&h| h > 100 && h < 300));
+    }
+
+    #[test]
+    fn test_bitmask_rand_shuffle() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        for i in 0..20 {
+            v.push_with_mask(i as u8, i);
+        }
+        let mut rng = StdRng::seed_from_u64(3);
+
+        v.shuffle(&mut rng);
+        let x: Vec<i32> = v.iter().copied().collect();
+        assert_ne!(x, (0..20).collect::<Vec<_>>());
+        assert!(v.iter_with_mask().all(|x| x.bitmask as i32 == x.item));
+
+        v.sort_by_item();
+        let x: Vec<i32> = v.iter().copied().collect();
+        assert_eq!(x, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::cj_bitmask_vec::BitmaskVec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{BitAnd, BitOr, BitXor};
+use rayon::prelude::*;
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Send,
+    T: Send,
+{
+    /// Sorts the vector by bitmask in parallel. The sort is stable.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000100, 100);
+    /// v.push_with_mask(0b00000001, 101);
+    /// v.push_with_mask(0b00000010, 102);
+    ///
+    /// v.par_sort_by_mask();
+    /// assert_eq!(v[0], 101);
+    /// ```
+    #[inline]
+    pub fn par_sort_by_mask(&mut self)
+    where
+        B: Ord,
+    {
+        self.as_mut_vec()
+            .par_sort_by(|a, b| a.bitmask.cmp(&b.bitmask));
+    }
+
+    /// Sorts the vector by T in parallel. The sort is stable.
+    #[inline]
+    pub fn par_sort_by_item(&mut self)
+    where
+        T: Ord,
+    {
+        self.as_mut_vec().par_sort_by(|a, b| a.item.cmp(&b.item));
+    }
+
+    /// Sets the bits of mask on every element's bitmask in parallel <i>(bitmask | mask)</i>.
+    /// # Panics
+    /// Panics if mask has bits outside the schema set with set_schema().
+    pub fn par_or_mask_all(&mut self, mask: &B)
+    where
+        B: Copy + Sync + BitOr<Output = B>,
+    {
+        self.assert_schema(mask);
+        let mask = *mask;
+        self.as_mut_vec()
+            .par_iter_mut()
+            .for_each(|x| x.bitmask = x.bitmask | mask);
+    }
+
+    /// Keeps only the bits of mask on every element's bitmask in parallel <i>(bitmask & mask)</i>.
+    pub fn par_and_mask_all(&mut self, mask: &B)
+    where
+        B: Copy + Sync + BitAnd<Output = B>,
+    {
+        let mask = *mask;
+        self.as_mut_vec()
+            .par_iter_mut()
+            .for_each(|x| x.bitmask = x.bitmask & mask);
+    }
+
+    /// Toggles the bits of mask on every element's bitmask in parallel <i>(bitmask ^ mask)</i>.
+    /// # Panics
+    /// Panics if mask has bits outside the schema set with set_schema().
+    pub fn par_xor_mask_all(&mut self, mask: &B)
+    where
+        B: Copy + Sync + BitXor<Output = B>,
+    {
+        self.assert_schema(mask);
+        let mask = *mask;
+        self.as_mut_vec()
+            .par_iter_mut()
+            .for_each(|x| x.bitmask = x.bitmask ^ mask);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_bitmask_rayon_par_sort_by_mask() {
+        // many equal bitmasks, so stability shows
+        let mut v = BitmaskVec::<u16, i32>::new();
+        for i in 0..1000 {
+            v.push_with_mask((i % 7) as u16, i);
+        }
+        let mut v2 = v.clone();
+        v.par_sort_by_mask();
+        v2.sort_by_mask();
+
+        assert!(v.is_sorted_by_mask());
+        assert!(v.iter().eq(v2.iter()));
+    }
+
+    #[test]
+    fn test_bitmask_rayon_par_sort_by_item() {
+        let mut v = BitmaskVec::<u16, i32>::new();
+        for i in 0..1000 {
+            v.push_with_mask(i as u16, 1000 - i);
+        }
+        v.par_sort_by_item();
+
+        assert!(v.is_sorted_by_item());
+        // bitmasks move with their items
+        assert!(v
+            .iter_with_mask()
+            .all(|x| x.bitmask as i32 == 1000 - x.item));
+    }
+
+    #[test]
+    fn test_bitmask_rayon_par_mask_all() {
+        let mut v = BitmaskVec::<u16, i32>::new();
+        for i in 0..1000 {
+            v.push_with_mask((i % 4) as u16, i);
+        }
+
+        v.par_or_mask_all(&0b1000_0000_0000_0000);
+        assert_eq!(v.count_matching(&0b1000_0000_0000_0000), 1000);
+
+        v.par_xor_mask_all(&0b1000_0000_0000_0001);
+        assert_eq!(v.count_matching(&0b1000_0000_0000_0000), 0);
+        assert_eq!(v.count_matching(&0b0000_0000_0000_0001), 500);
+
+        v.par_and_mask_all(&0b0000_0000_0000_0010);
+        assert_eq!(v.count_matching(&0b0000_0000_0000_0010), 500);
+        assert!(v.as_slice().iter().all(|x| x.bitmask & !0b10 == 0));
+    }
+}
@@ -0,0 +1,451 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::iter::Enumerate;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Index, IndexMut};
+use core::slice::{Iter, IterMut};
+
+/// Key type of a BitmaskSlab.<br>
+///
+/// usize keys are plain slot indexes. BitmaskKey also carries the generation of its slot, see
+/// BitmaskGenSlab.
+pub trait SlabKey: Copy {
+    /// Builds the key for the slot at index, currently at generation.
+    fn from_slot(index: usize, generation: u32) -> Self;
+    /// Returns the slot index of the key.
+    fn slot(&self) -> usize;
+    /// Returns true if the key is valid for a slot currently at generation.
+    fn is_current(&self, generation: u32) -> bool;
+}
+
+impl SlabKey for usize {
+    #[inline]
+    fn from_slot(index: usize, _generation: u32) -> Self {
+        index
+    }
+
+    #[inline]
+    fn slot(&self) -> usize {
+        *self
+    }
+
+    #[inline]
+    fn is_current(&self, _generation: u32) -> bool {
+        true
+    }
+}
+
+enum Entry<B, T>
+where
+    B: Bitflag,
+{
+    Occupied(BitmaskItem<B, T>),
+    Vacant(usize),
+}
+
+struct Slot<B, T>
+where
+    B: Bitflag,
+{
+    generation: u32,
+    entry: Entry<B, T>,
+}
+
+/// BitmaskSlab is an arena that pairs bitmasks with T and hands out stable keys.<br>
+///
+/// Removing an entry does not move any other entry, so a key stays valid until its own entry
+/// is removed. Freed slots are reused by later inserts.<br>
+/// K is the key type, usize by default. BitmaskGenSlab is the same arena with BitmaskKey keys.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut slab = BitmaskSlab::<u8, i32>::new();
+/// let a = slab.insert_with_mask(0b00000001, 100);
+/// let b = slab.insert_with_mask(0b00000010, 101);
+/// let c = slab.insert_with_mask(0b00000011, 102);
+///
+/// assert_eq!(slab.remove(a), Some(100));
+/// assert_eq!(slab[c], 102);
+///
+/// let keys: Vec<_> = slab.iter_matching(&0b00000010).map(|(k, _)| k).collect();
+/// assert_eq!(keys, vec![b, c]);
+/// ```
+pub struct BitmaskSlab<B, T, K = usize>
+where
+    B: Bitflag,
+{
+    slots: Vec<Slot<B, T>>,
+    next_free: usize,
+    len: usize,
+    _key: PhantomData<K>,
+}
+
+impl<'a, B, T, K> BitmaskSlab<B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    K: SlabKey,
+{
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Constructs a new, empty slab with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            next_free: 0,
+            len: 0,
+            _key: PhantomData,
+        }
+    }
+
+    /// Returns the number of entries the slab can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Returns the number of occupied entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the slab contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all entries. Previously returned keys become invalid.
+    pub fn clear(&mut self) {
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if let Entry::Occupied(_) = slot.entry {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+            slot.entry = Entry::Vacant(i + 1);
+        }
+        self.next_free = 0;
+        self.len = 0;
+    }
+
+    /// Returns the key the next insert will use.
+    #[inline]
+    pub fn vacant_key(&self) -> K {
+        let generation = self.slots.get(self.next_free).map_or(0, |x| x.generation);
+        K::from_slot(self.next_free, generation)
+    }
+
+    /// Inserts T with a default bitmask of zero, returning its key.
+    #[inline]
+    pub fn insert(&mut self, value: T) -> K {
+        self.insert_with_mask(B::default(), value)
+    }
+
+    /// Inserts T and the supplied bitmask, returning its key.
+    pub fn insert_with_mask(&mut self, bitmask: B, value: T) -> K {
+        let key = self.vacant_key();
+        let index = self.next_free;
+        let entry = Entry::Occupied(BitmaskItem::new(bitmask, value));
+        if index == self.slots.len() {
+            self.slots.push(Slot {
+                generation: 0,
+                entry,
+            });
+            self.next_free = index + 1;
+        } else if let Entry::Vacant(next) = mem::replace(&mut self.slots[index].entry, entry) {
+            self.next_free = next;
+        }
+        self.len += 1;
+        key
+    }
+
+    /// Removes and returns T at key, or None if key is not occupied.
+    #[inline]
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        self.remove_with_mask(key).map(|x| x.item)
+    }
+
+    /// Removes and returns the BitmaskItem at key, or None if key is not occupied.
+    pub fn remove_with_mask(&mut self, key: K) -> Option<BitmaskItem<B, T>> {
+        let index = key.slot();
+        match self.slots.get_mut(index) {
+            Some(slot)
+                if key.is_current(slot.generation) && matches!(slot.entry, Entry::Occupied(_)) =>
+            {
+                let old = mem::replace(&mut slot.entry, Entry::Vacant(self.next_free));
+                slot.generation = slot.generation.wrapping_add(1);
+                self.next_free = index;
+                self.len -= 1;
+                match old {
+                    Entry::Occupied(x) => Some(x),
+                    Entry::Vacant(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true if key is occupied.
+    #[inline]
+    pub fn contains(&self, key: K) -> bool {
+        self.get_with_mask(key).is_some()
+    }
+
+    /// Returns T at key, or None if key is not occupied.
+    #[inline]
+    pub fn get(&self, key: K) -> Option<&T> {
+        self.get_with_mask(key).map(|x| &x.item)
+    }
+
+    /// Returns mutable T at key, or None if key is not occupied.
+    #[inline]
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        self.get_with_mask_mut(key).map(|x| &mut x.item)
+    }
+
+    /// Returns the BitmaskItem at key, or None if key is not occupied.
+    #[inline]
+    pub fn get_with_mask(&self, key: K) -> Option<&BitmaskItem<B, T>> {
+        match self.slots.get(key.slot()) {
+            Some(Slot {
+                generation,
+                entry: Entry::Occupied(x),
+            }) if key.is_current(*generation) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Returns the mutable BitmaskItem at key, or None if key is not occupied.
+    #[inline]
+    pub fn get_with_mask_mut(&mut self, key: K) -> Option<&mut BitmaskItem<B, T>> {
+        match self.slots.get_mut(key.slot()) {
+            Some(Slot {
+                generation,
+                entry: Entry::Occupied(x),
+            }) if key.is_current(*generation) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Returns a BitmaskSlabIter for iterating over (key, BitmaskItem) pairs.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskSlabIter<'a, B, T, K> {
+        BitmaskSlabIter {
+            inner: self.slots.iter().enumerate(),
+            _key: PhantomData,
+        }
+    }
+
+    /// Returns a BitmaskSlabIterMut for mutably iterating over (key, BitmaskItem) pairs.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskSlabIterMut<'a, B, T, K> {
+        BitmaskSlabIterMut {
+            inner: self.slots.iter_mut().enumerate(),
+            _key: PhantomData,
+        }
+    }
+
+    /// Returns a BitmaskSlabIterMatching for iterating over the (key, BitmaskItem) pairs whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskSlabIterMatching<'a, B, T, K> {
+        BitmaskSlabIterMatching {
+            inner: self.iter(),
+            mask,
+        }
+    }
+
+    /// Returns the number of entries whose bitmask matches mask.
+    pub fn count_matching(&'a self, mask: &'a B) -> usize {
+        self.iter_matching(mask).count()
+    }
+}
+
+impl<'a, B, T, K> Default for BitmaskSlab<B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    K: SlabKey,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T, K> Index<K> for BitmaskSlab<B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    K: SlabKey,
+{
+    type Output = T;
+
+    /// Returns T at key. Panics if key is not occupied.
+    fn index(&self, key: K) -> &Self::Output {
+        self.get(key).expect("invalid slab key")
+    }
+}
+
+impl<'a, B, T, K> IndexMut<K> for BitmaskSlab<B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    K: SlabKey,
+{
+    /// Returns mutable T at key. Panics if key is not occupied.
+    fn index_mut(&mut self, key: K) -> &mut Self::Output {
+        self.get_mut(key).expect("invalid slab key")
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (key, BitmaskItem) for every occupied entry.
+pub struct BitmaskSlabIter<'a, B, T, K = usize>
+where
+    B: Bitflag,
+{
+    inner: Enumerate<Iter<'a, Slot<B, T>>>,
+    _key: PhantomData<K>,
+}
+
+impl<'a, B, T, K> BitmaskSlabIter<'a, B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+    K: SlabKey,
+{
+    /// Returns the next (key, BitmaskItem) whose bitmask matches mask.
+    pub fn filter_mask(&mut self, mask: &'a B) -> Option<(K, &'a BitmaskItem<B, T>)> {
+        self.find(|(_, x)| x.matches_mask(mask))
+    }
+}
+
+impl<'a, B, T, K> Iterator for BitmaskSlabIter<'a, B, T, K>
+where
+    B: Bitflag,
+    K: SlabKey,
+{
+    type Item = (K, &'a BitmaskItem<B, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.find_map(|(index, slot)| match &slot.entry {
+            Entry::Occupied(x) => Some((K::from_slot(index, slot.generation), x)),
+            Entry::Vacant(_) => None,
+        })
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (key, mutable BitmaskItem) for every occupied entry.
+pub struct BitmaskSlabIterMut<'a, B, T, K = usize>
+where
+    B: Bitflag,
+{
+    inner: Enumerate<IterMut<'a, Slot<B, T>>>,
+    _key: PhantomData<K>,
+}
+
+impl<'a, B, T, K> Iterator for BitmaskSlabIterMut<'a, B, T, K>
+where
+    B: Bitflag,
+    K: SlabKey,
+{
+    type Item = (K, &'a mut BitmaskItem<B, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.find_map(|(index, slot)| match &mut slot.entry {
+            Entry::Occupied(x) => Some((K::from_slot(index, slot.generation), x)),
+            Entry::Vacant(_) => None,
+        })
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (key, BitmaskItem) for occupied entries whose bitmask matches a mask.
+pub struct BitmaskSlabIterMatching<'a, B, T, K = usize>
+where
+    B: Bitflag,
+{
+    inner: BitmaskSlabIter<'a, B, T, K>,
+    mask: &'a B,
+}
+
+impl<'a, B, T, K> Iterator for BitmaskSlabIterMatching<'a, B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+    K: SlabKey,
+{
+    type Item = (K, &'a BitmaskItem<B, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.filter_mask(self.mask)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_slab::BitmaskSlab;
+
+    #[test]
+    fn test_bitmask_slab_insert_remove() {
+        let mut slab = BitmaskSlab::<u8, i32>::new();
+        let a = slab.insert_with_mask(0b00000001, 100);
+        let b = slab.insert(101);
+        let c = slab.insert_with_mask(0b00000010, 102);
+        assert_eq!(slab.len(), 3);
+
+        assert_eq!(slab.remove(b), Some(101));
+        assert_eq!(slab.remove(b), None);
+        assert!(!slab.contains(b));
+        assert_eq!(slab[a], 100);
+        assert_eq!(slab[c], 102);
+        assert_eq!(slab.len(), 2);
+    }
+
+    #[test]
+    fn test_bitmask_slab_reuse() {
+        let mut slab = BitmaskSlab::<u8, i32>::new();
+        let a = slab.insert(100);
+        let b = slab.insert(101);
+        slab.remove(a);
+        slab.remove(b);
+
+        assert_eq!(slab.vacant_key(), b);
+        assert_eq!(slab.insert(102), b);
+        assert_eq!(slab.insert(103), a);
+        assert_eq!(slab.insert(104), 2);
+        assert_eq!(slab.len(), 3);
+    }
+
+    #[test]
+    fn test_bitmask_slab_get() {
+        let mut slab = BitmaskSlab::<u8, i32>::new();
+        let a = slab.insert_with_mask(0b00000001, 100);
+        *slab.get_mut(a).unwrap() += 1;
+        slab.get_with_mask_mut(a).unwrap().bitmask = 0b00000100;
+        assert_eq!(slab.get(a), Some(&101));
+        assert_eq!(slab.get_with_mask(a).unwrap().bitmask, 0b00000100);
+        assert!(slab.get(5).is_none());
+    }
+
+    #[test]
+    fn test_bitmask_slab_iter() {
+        let mut slab = BitmaskSlab::<u8, i32>::new();
+        let a = slab.insert_with_mask(0b00000010, 100);
+        let b = slab.insert_with_mask(0b00000001, 101);
+        let c = slab.insert_with_mask(0b00000011, 102);
+        slab.remove(a);
+
+        let x: Vec<_> = slab.iter().map(|(k, x)| (k, x.item)).collect();
+        assert_eq!(x, vec![(b, 101), (c, 102)]);
+
+        for (_, x) in slab.iter_mut() {
+            x.item *= 2;
+        }
+        let mut iter = slab.iter();
+        assert_eq!(iter.filter_mask(&0b00000010).unwrap().0, c);
+        assert_eq!(slab.count_matching(&0b00000001), 2);
+        assert_eq!(slab.iter_matching(&0b00000010).next().unwrap().1.item, 204);
+
+        slab.clear();
+        assert!(slab.is_empty());
+        assert_eq!(slab.iter().count(), 0);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Index, IndexMut};
+use slotmap::secondary::Iter;
+use slotmap::{Key, SecondaryMap, SlotMap};
+
+/// BitmaskSecondaryMap pairs bitmasks with T for the keys of a slotmap::SlotMap, stored in a
+/// slotmap::SecondaryMap.<br>
+///
+/// The primary SlotMap keeps owning the entities; BitmaskSecondaryMap attaches a bitmask (and
+/// optionally a component T) to its keys so they can be filtered with iter_matching() and
+/// keys_matching(). sync_with() drops the entries whose key was removed from the primary map.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// use slotmap::{DefaultKey, SlotMap};
+///
+/// let mut entities = SlotMap::new();
+/// let a = entities.insert("a");
+/// let b = entities.insert("b");
+///
+/// let mut flags = BitmaskSecondaryMap::<DefaultKey, u8, i32>::new();
+/// flags.insert_with_mask(a, 0b00000001, 100);
+/// flags.insert_with_mask(b, 0b00000011, 101);
+///
+/// assert_eq!(flags.keys_matching(&0b00000010), vec![b]);
+///
+/// entities.remove(b);
+/// flags.sync_with(&entities);
+/// assert_eq!(flags.count_matching(&0b00000001), 1);
+/// ```
+pub struct BitmaskSecondaryMap<K, B, T>
+where
+    K: Key,
+    B: Bitflag,
+{
+    inner: SecondaryMap<K, BitmaskItem<B, T>>,
+}
+
+impl<'a, K, B, T> BitmaskSecondaryMap<K, B, T>
+where
+    K: Key,
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            inner: SecondaryMap::new(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: SecondaryMap::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the map contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Returns true if the map holds an element for key.
+    #[inline]
+    pub fn contains_key(&self, key: K) -> bool {
+        self.inner.contains_key(key)
+    }
+
+    /// Inserts T with a default bitmask of zero for key, returning the T it replaced.
+    #[inline]
+    pub fn insert(&mut self, key: K, value: T) -> Option<T> {
+        self.insert_with_mask(key, B::default(), value)
+            .map(|x| x.item)
+    }
+
+    /// Inserts T and bitmask for key, returning the BitmaskItem it replaced.<br>
+    /// Does nothing and returns None if key was removed from its SlotMap and a newer key
+    /// already has an element here.
+    #[inline]
+    pub fn insert_with_mask(&mut self, key: K, bitmask: B, value: T) -> Option<BitmaskItem<B, T>> {
+        self.inner.insert(key, BitmaskItem::new(bitmask, value))
+    }
+
+    /// Removes and returns T for key.
+    #[inline]
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        self.remove_with_mask(key).map(|x| x.item)
+    }
+
+    /// Removes and returns the BitmaskItem for key.
+    #[inline]
+    pub fn remove_with_mask(&mut self, key: K) -> Option<BitmaskItem<B, T>> {
+        self.inner.remove(key)
+    }
+
+    /// Returns T for key.
+    #[inline]
+    pub fn get(&self, key: K) -> Option<&T> {
+        self.inner.get(key).map(|x| &x.item)
+    }
+
+    /// Returns mutable T for key.
+    #[inline]
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        self.inner.get_mut(key).map(|x| &mut x.item)
+    }
+
+    /// Returns the BitmaskItem for key.
+    #[inline]
+    pub fn get_with_mask(&self, key: K) -> Option<&BitmaskItem<B, T>> {
+        self.inner.get(key)
+    }
+
+    /// Returns the mutable BitmaskItem for key.
+    #[inline]
+    pub fn get_with_mask_mut(&mut self, key: K) -> Option<&mut BitmaskItem<B, T>> {
+        self.inner.get_mut(key)
+    }
+
+    /// Replaces the bitmask for key. Returns false if the map holds no element for key.
+    #[inline]
+    pub fn set_mask(&mut self, key: K, bitmask: B) -> bool {
+        match self.inner.get_mut(key) {
+            Some(x) => {
+                x.bitmask = bitmask;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the elements whose key is no longer in primary.
+    pub fn sync_with<V>(&mut self, primary: &SlotMap<K, V>) {
+        self.inner.retain(|key, _| primary.contains_key(key));
+    }
+
+    /// Returns a BitmaskSecondaryMapIterMatching for iterating over the keys and BitmaskItems
+    /// whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskSecondaryMapIterMatching<'a, K, B, T> {
+        BitmaskSecondaryMapIterMatching {
+            inner: self.inner.iter(),
+            mask,
+        }
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&'a self, mask: &'a B) -> usize {
+        self.iter_matching(mask).count()
+    }
+
+    /// Returns the keys of the elements whose bitmask matches mask.
+    pub fn keys_matching(&'a self, mask: &'a B) -> Vec<K> {
+        self.iter_matching(mask).map(|(k, _)| k).collect()
+    }
+
+    /// Returns the underlying SecondaryMap.
+    #[inline]
+    pub fn as_secondary_map(&self) -> &SecondaryMap<K, BitmaskItem<B, T>> {
+        &self.inner
+    }
+
+    /// Converts into the underlying SecondaryMap.
+    #[inline]
+    pub fn into_secondary_map(self) -> SecondaryMap<K, BitmaskItem<B, T>> {
+        self.inner
+    }
+}
+
+impl<'a, K, B, T> Default for BitmaskSecondaryMap<K, B, T>
+where
+    K: Key,
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, B, T> From<SecondaryMap<K, BitmaskItem<B, T>>> for BitmaskSecondaryMap<K, B, T>
+where
+    K: Key,
+    B: Bitflag,
+{
+    fn from(value: SecondaryMap<K, BitmaskItem<B, T>>) -> Self {
+        Self { inner: value }
+    }
+}
+
+impl<K, B, T> Index<K> for BitmaskSecondaryMap<K, B, T>
+where
+    K: Key,
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, key: K) -> &Self::Output {
+        &self.inner[key].item
+    }
+}
+
+impl<K, B, T> IndexMut<K> for BitmaskSecondaryMap<K, B, T>
+where
+    K: Key,
+    B: Bitflag,
+{
+    fn index_mut(&mut self, key: K) -> &mut Self::Output {
+        &mut self.inner[key].item
+    }
+}
+
+impl<'a, B, K> BitmaskVec<B, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    K: Key,
+{
+    /// Creates a vector pairing each key of a SecondaryMap with its bitmask, so BitmaskVec
+    /// filters can select keys into the primary SlotMap.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// use slotmap::{SecondaryMap, SlotMap};
+    ///
+    /// let mut entities = SlotMap::new();
+    /// let a = entities.insert("a");
+    /// let b = entities.insert("b");
+    /// let mut masks = SecondaryMap::new();
+    /// masks.insert(a, 0b00000001u8);
+    /// masks.insert(b, 0b00000011u8);
+    ///
+    /// let v = BitmaskVec::from_secondary_map(&masks);
+    /// let names: Vec<_> = v
+    ///     .iter_matching(&0b00000010)
+    ///     .map(|x| entities[x.item])
+    ///     .collect();
+    /// assert_eq!(names, vec!["b"]);
+    /// ```
+    pub fn from_secondary_map(map: &SecondaryMap<K, B>) -> Self {
+        let mut v = Self::with_capacity(map.len());
+        for (key, bitmask) in map.iter() {
+            v.push_with_mask(bitmask.clone(), key);
+        }
+        v
+    }
+
+    /// Creates a SecondaryMap holding the bitmask of each key. Later duplicates of a key
+    /// replace earlier ones.
+    pub fn to_secondary_map(&self) -> SecondaryMap<K, B> {
+        let mut map = SecondaryMap::with_capacity(self.len());
+        for x in self.as_slice() {
+            map.insert(x.item, x.bitmask.clone());
+        }
+        map
+    }
+}
+
+// =================================================================================================
+/// Iter that returns the key and BitmaskItem from a BitmaskSecondaryMap for items whose bitmask matches mask.
+pub struct BitmaskSecondaryMapIterMatching<'a, K, B, T>
+where
+    K: Key,
+    B: Bitflag,
+{
+    inner: Iter<'a, K, BitmaskItem<B, T>>,
+    mask: &'a B,
+}
+
+impl<'a, K, B, T> Iterator for BitmaskSecondaryMapIterMatching<'a, K, B, T>
+where
+    K: Key,
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (K, &'a BitmaskItem<B, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = self.mask;
+        self.inner.by_ref().find(|(_, x)| x.matches_mask(mask))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_slotmap::BitmaskSecondaryMap;
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use slotmap::{DefaultKey, SecondaryMap, SlotMap};
+
+    #[test]
+    fn test_bitmask_secondary_map() {
+        let mut entities = SlotMap::new();
+        let a = entities.insert(1);
+        let b = entities.insert(2);
+        let c = entities.insert(3);
+
+        let mut m = BitmaskSecondaryMap::<DefaultKey, u8, i32>::new();
+        m.insert_with_mask(a, 0b00000001, 100);
+        m.insert_with_mask(b, 0b00000011, 101);
+        assert_eq!(m.insert(c, 102), None);
+        assert!(m.set_mask(c, 0b00000010));
+
+        let mut keys = m.keys_matching(&0b00000010);
+        keys.sort();
+        assert_eq!(keys, vec![b, c]);
+        m[a] += 100;
+        assert_eq!(m.get(a), Some(&200));
+
+        entities.remove(b);
+        let d = entities.insert(4);
+        assert!(!m.contains_key(d));
+        m.sync_with(&entities);
+        assert_eq!(m.len(), 2);
+        assert!(m.get_with_mask(b).is_none());
+        assert!(!m.set_mask(b, 0b00000001));
+        assert_eq!(m.remove(c), Some(102));
+        assert_eq!(m.count_matching(&0b00000000), 1);
+    }
+
+    #[test]
+    fn test_bitmask_vec_secondary_map() {
+        let mut entities = SlotMap::new();
+        let a = entities.insert("a");
+        let b = entities.insert("b");
+
+        let mut v = BitmaskVec::<u8, DefaultKey>::new();
+        v.push_with_mask(0b00000001, a);
+        v.push_with_mask(0b00000110, b);
+
+        let map: SecondaryMap<DefaultKey, u8> = v.to_secondary_map();
+        assert_eq!(map[b], 0b00000110);
+
+        let v = BitmaskVec::from_secondary_map(&map);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.indices_matching(&0b00000100).len(), 1);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{BitmaskVec, BitmaskVecIter, BitmaskVecIterWithMask};
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Index, IndexMut};
+
+/// Number of elements covered by each summary mask of a BitmaskSummaryVec.
+pub const SUMMARY_BLOCK_LEN: usize = 512;
+
+/// BitmaskSummaryVec is a vec that pairs bitmasks with T and keeps, for every block of
+/// SUMMARY_BLOCK_LEN elements, the OR of the bitmasks in that block.<br>
+///
+/// A block whose summary doesn't match a mask can't contain a match, so any_match(),
+/// iter_matching() and count_matching() skip it without looking at its elements. Masks that are
+/// rare across a large vec are answered after reading only the summaries.
+///
+/// The summaries are conservative: removing elements or clearing bits with set_mask() leaves the
+/// old bits in place, which only costs extra scanning. rebuild_summary() recomputes them exactly.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// const ALERT: u8 = 0b10000000;
+///
+/// let mut v = BitmaskSummaryVec::<u8, usize>::new();
+/// for i in 0..10_000 {
+///     v.push_with_mask(0b00000001, i);
+/// }
+/// assert!(!v.may_match(&ALERT));
+/// assert!(!v.any_match(&ALERT));
+///
+/// v.set_mask(5_000, ALERT);
+/// assert!(v.any_match(&ALERT));
+/// assert_eq!(v.indices_matching(&ALERT), vec![5_000]);
+/// ```
+pub struct BitmaskSummaryVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Vec<BitmaskItem<B, T>>,
+    summary: Vec<B>,
+}
+
+impl<'a, B, T> BitmaskSummaryVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Vec::with_capacity(capacity),
+            summary: Vec::with_capacity(capacity.div_ceil(SUMMARY_BLOCK_LEN)),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Returns the summary masks, one per block of SUMMARY_BLOCK_LEN elements.
+    #[inline]
+    pub fn summary(&self) -> &[B] {
+        self.summary.as_slice()
+    }
+
+    /// Returns T at index, or None if index is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.inner.get(index).map(|x| &x.item)
+    }
+
+    /// Returns the BitmaskItem at index, or None if index is out of bounds.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<&BitmaskItem<B, T>> {
+        self.inner.get(index)
+    }
+
+    /// Clears the vector and its summaries.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.summary.clear();
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest.
+    /// The summary of the last remaining block keeps the bits of the dropped elements.
+    pub fn truncate(&mut self, len: usize) {
+        self.inner.truncate(len);
+        self.summary.truncate(len.div_ceil(SUMMARY_BLOCK_LEN));
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        let block = self.inner.len() / SUMMARY_BLOCK_LEN;
+        if block == self.summary.len() {
+            self.summary.push(bitmask);
+        } else {
+            self.summary[block] = self.summary[block] | bitmask;
+        }
+        self.inner.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        let x = self.inner.pop()?;
+        self.summary
+            .truncate(self.inner.len().div_ceil(SUMMARY_BLOCK_LEN));
+        Some(x)
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let x = self.inner.remove(index);
+        // the first element of every following block shifted into the block before it
+        let mut i = (index / SUMMARY_BLOCK_LEN + 1) * SUMMARY_BLOCK_LEN - 1;
+        while i < self.inner.len() {
+            let block = i / SUMMARY_BLOCK_LEN;
+            self.summary[block] = self.summary[block] | self.inner[i].bitmask;
+            i += SUMMARY_BLOCK_LEN;
+        }
+        self.summary
+            .truncate(self.inner.len().div_ceil(SUMMARY_BLOCK_LEN));
+        x
+    }
+
+    /// Replaces the bitmask at index and adds its bits to the summary of its block.
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        self.inner[index].bitmask = bitmask;
+        let block = index / SUMMARY_BLOCK_LEN;
+        self.summary[block] = self.summary[block] | bitmask;
+    }
+
+    /// Recomputes every summary from the current bitmasks, dropping bits left behind by
+    /// removals and set_mask().
+    pub fn rebuild_summary(&mut self) {
+        self.summary = self
+            .inner
+            .chunks(SUMMARY_BLOCK_LEN)
+            .map(|c| c.iter().fold(B::default(), |acc, x| acc | x.bitmask))
+            .collect();
+    }
+
+    /// Returns false if no element can match mask, judging by the summaries alone.
+    /// True means a match is possible, not certain.
+    #[inline]
+    pub fn may_match(&self, mask: &'a B) -> bool {
+        self.summary.iter().any(|x| x.matches_mask(mask))
+    }
+
+    /// Returns true if any element's bitmask matches mask, only scanning blocks whose summary matches.
+    #[inline]
+    pub fn any_match(&'a self, mask: &'a B) -> bool {
+        self.iter_matching(mask).next().is_some()
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskSummaryVecIterMatching for iterating over the BitmaskItems whose bitmask
+    /// matches mask, skipping blocks whose summary doesn't match.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskSummaryVecIterMatching<'a, B, T> {
+        BitmaskSummaryVecIterMatching {
+            items: self.inner.as_slice(),
+            summary: self.summary.as_slice(),
+            mask,
+            index: 0,
+        }
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&'a self, mask: &'a B) -> usize {
+        self.iter_matching(mask).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask matches mask.
+    pub fn indices_matching(&'a self, mask: &'a B) -> Vec<usize> {
+        let mut iter = self.iter_matching(mask);
+        let mut indices = Vec::new();
+        while let Some(i) = iter.next_index() {
+            indices.push(i);
+        }
+        indices
+    }
+
+    /// Moves the items into a BitmaskVec, dropping the summaries.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::new();
+        *v.as_mut_vec() = self.inner;
+        v
+    }
+}
+
+impl<'a, B, T> Default for BitmaskSummaryVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskSummaryVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + BitmaskBits,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let mut v = Self {
+            inner: core::mem::take(value.as_mut_vec()),
+            summary: Vec::new(),
+        };
+        v.rebuild_summary();
+        v
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskSummaryVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<B, T> IndexMut<usize> for BitmaskSummaryVec<B, T>
+where
+    B: Bitflag,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index].item
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask, for items whose bitmask matches
+/// mask, skipping the blocks whose summary doesn't match.
+pub struct BitmaskSummaryVecIterMatching<'a, B, T>
+where
+    B: Bitflag,
+{
+    items: &'a [BitmaskItem<B, T>],
+    summary: &'a [B],
+    mask: &'a B,
+    index: usize,
+}
+
+impl<'a, B, T> BitmaskSummaryVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn next_index(&mut self) -> Option<usize> {
+        while self.index < self.items.len() {
+            let i = self.index;
+            if i.is_multiple_of(SUMMARY_BLOCK_LEN)
+                && !self.summary[i / SUMMARY_BLOCK_LEN].matches_mask(self.mask)
+            {
+                self.index += SUMMARY_BLOCK_LEN;
+                continue;
+            }
+            self.index += 1;
+            if self.items[i].matches_mask(self.mask) {
+                return Some(i);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskSummaryVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.next_index()?;
+        Some(&self.items[i])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_summary_vec::{BitmaskSummaryVec, SUMMARY_BLOCK_LEN};
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_summary_vec_any_match() {
+        let mut v = BitmaskSummaryVec::<u8, usize>::new();
+        for i in 0..SUMMARY_BLOCK_LEN * 3 {
+            v.push_with_mask(0b00000001, i);
+        }
+        assert_eq!(v.summary(), &[0b00000001; 3]);
+        assert!(!v.may_match(&0b00000010));
+        assert!(!v.any_match(&0b00000010));
+        assert_eq!(v.count_matching(&0b00000001), SUMMARY_BLOCK_LEN * 3);
+
+        v.set_mask(SUMMARY_BLOCK_LEN + 7, 0b00000011);
+        v.push_with_mask(0b00000110, 5000);
+        assert_eq!(
+            v.summary(),
+            &[0b00000001, 0b00000011, 0b00000001, 0b00000110]
+        );
+        assert!(v.any_match(&0b00000010));
+        assert_eq!(
+            v.indices_matching(&0b00000010),
+            vec![SUMMARY_BLOCK_LEN + 7, SUMMARY_BLOCK_LEN * 3]
+        );
+        let x: Vec<_> = v.iter_matching(&0b00000011).map(|x| x.item).collect();
+        assert_eq!(x, vec![SUMMARY_BLOCK_LEN + 7]);
+    }
+
+    #[test]
+    fn test_summary_vec_conservative() {
+        let mut v = BitmaskSummaryVec::<u8, usize>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000001, 101);
+
+        // clearing a bit leaves it in the summary until rebuilt
+        v.set_mask(1, 0b10000000);
+        v.set_mask(1, 0b00000001);
+        assert!(v.may_match(&0b10000000));
+        assert!(!v.any_match(&0b10000000));
+
+        v.rebuild_summary();
+        assert!(!v.may_match(&0b10000000));
+    }
+
+    #[test]
+    fn test_summary_vec_remove() {
+        let mut v = BitmaskSummaryVec::<u8, usize>::new();
+        for i in 0..SUMMARY_BLOCK_LEN * 3 {
+            let bitmask = match i {
+                _ if i == SUMMARY_BLOCK_LEN => 0b00000100,
+                _ if i == SUMMARY_BLOCK_LEN * 2 => 0b00001000,
+                _ => 0,
+            };
+            v.push_with_mask(bitmask, i);
+        }
+
+        // every later element shifts back into the previous block
+        v.remove(3);
+        assert_eq!(v.len(), SUMMARY_BLOCK_LEN * 3 - 1);
+        assert_eq!(v.indices_matching(&0b00000100), vec![SUMMARY_BLOCK_LEN - 1]);
+        assert_eq!(
+            v.indices_matching(&0b00001000),
+            vec![SUMMARY_BLOCK_LEN * 2 - 1]
+        );
+
+        while v.len() > SUMMARY_BLOCK_LEN {
+            v.pop();
+        }
+        assert_eq!(v.summary().len(), 1);
+        v.truncate(0);
+        assert!(v.summary().is_empty());
+        assert!(!v.may_match(&0));
+    }
+
+    #[test]
+    fn test_summary_vec_conversion() {
+        let mut b = BitmaskVec::<u8, usize>::new();
+        for i in 0..SUMMARY_BLOCK_LEN + 1 {
+            b.push_with_mask((i == SUMMARY_BLOCK_LEN) as u8, i);
+        }
+        let mut v = BitmaskSummaryVec::from(b);
+        assert_eq!(v.summary(), &[0, 1]);
+        v[0] = 42;
+        assert_eq!(v.get(0), Some(&42));
+
+        let b = v.into_bitmask_vec();
+        assert_eq!(b.indices_matching(&1), vec![SUMMARY_BLOCK_LEN]);
+    }
+}
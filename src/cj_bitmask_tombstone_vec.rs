@@ -0,0 +1,468 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::Index;
+use core::slice::{Iter, IterMut};
+
+/// BitmaskTombstoneVec is a vec that pairs bitmasks with T and removes entries by marking them
+/// with a reserved tombstone bit.<br>
+///
+/// mark_removed() is O(1) and never shifts indices, so entries can be removed while indices into
+/// the vec are held. Every accessor and iterator skips marked entries. sweep() compacts them away
+/// once index stability is no longer needed.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskTombstoneVec::<u8, i32>::new(7);
+/// v.push_with_mask(0b00000001, 100);
+/// v.push_with_mask(0b00000011, 101);
+/// v.push_with_mask(0b00000010, 102);
+///
+/// v.mark_removed(0);
+/// assert_eq!(v[2], 102);
+/// assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![101, 102]);
+///
+/// assert_eq!(v.sweep(), 1);
+/// assert_eq!(v[1], 102);
+/// ```
+pub struct BitmaskTombstoneVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Vec<BitmaskItem<B, T>>,
+    tombstone: usize,
+    removed: usize,
+}
+
+impl<'a, B, T> BitmaskTombstoneVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Constructs a new, empty vec reserving bit tombstone to mark removed entries.
+    /// # Panics
+    /// Panics if tombstone does not fit in B.
+    pub fn new(tombstone: usize) -> Self
+    where
+        B: BitmaskBits,
+    {
+        Self::with_capacity(tombstone, 0)
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity, reserving bit tombstone to mark removed entries.
+    /// # Panics
+    /// Panics if tombstone does not fit in B.
+    pub fn with_capacity(tombstone: usize, capacity: usize) -> Self
+    where
+        B: BitmaskBits,
+    {
+        Self::assert_tombstone(tombstone);
+        Self {
+            inner: Vec::with_capacity(capacity),
+            tombstone,
+            removed: 0,
+        }
+    }
+
+    #[inline]
+    fn assert_tombstone(tombstone: usize)
+    where
+        B: BitmaskBits,
+    {
+        assert!(
+            tombstone < B::BITS,
+            "tombstone bit {tombstone} doesn't fit in the bitmask"
+        );
+    }
+
+    /// Returns the bit reserved to mark removed entries.
+    #[inline]
+    pub fn tombstone_bit(&self) -> usize {
+        self.tombstone
+    }
+
+    /// Returns the number of live entries.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len() - self.removed
+    }
+
+    /// Returns true if the vector contains no live entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of entries, live or marked removed. Indices range over 0..raw_len().
+    #[inline]
+    pub fn raw_len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns the number of entries marked removed and not yet swept.
+    #[inline]
+    pub fn removed_count(&self) -> usize {
+        self.removed
+    }
+
+    /// Clears the vector, removing all entries.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.removed = 0;
+    }
+
+    /// Appends T to the back with a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Appends T and bitmask to the back. The tombstone bit is cleared from bitmask.
+    #[inline]
+    pub fn push_with_mask(&mut self, mut bitmask: B, value: T) {
+        bitmask.set_bit(self.tombstone, false);
+        self.inner.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Marks the entry at index as removed. Returns false if it was already marked.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    pub fn mark_removed(&mut self, index: usize) -> bool {
+        let bitmask = &mut self.inner[index].bitmask;
+        if bitmask.get_bit(self.tombstone) {
+            return false;
+        }
+        bitmask.set_bit(self.tombstone, true);
+        self.removed += 1;
+        true
+    }
+
+    /// Returns true if the entry at index is marked removed.
+    #[inline]
+    pub fn is_removed(&self, index: usize) -> bool {
+        self.inner
+            .get(index)
+            .is_some_and(|x| x.bitmask.get_bit(self.tombstone))
+    }
+
+    /// Drops all entries marked removed, shifting the live entries down.
+    /// Returns the number of entries dropped.
+    pub fn sweep(&mut self) -> usize {
+        let tombstone = self.tombstone;
+        self.inner.retain(|x| !x.bitmask.get_bit(tombstone));
+        core::mem::replace(&mut self.removed, 0)
+    }
+
+    /// Returns T at index, or None if out of bounds or marked removed.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.get_with_mask(index).map(|x| &x.item)
+    }
+
+    /// Returns mutable T at index, or None if out of bounds or marked removed.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let tombstone = self.tombstone;
+        self.inner
+            .get_mut(index)
+            .filter(|x| !x.bitmask.get_bit(tombstone))
+            .map(|x| &mut x.item)
+    }
+
+    /// Returns the BitmaskItem at index, or None if out of bounds or marked removed.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<&BitmaskItem<B, T>> {
+        self.inner
+            .get(index)
+            .filter(|x| !x.bitmask.get_bit(self.tombstone))
+    }
+
+    /// Replaces the bitmask at index, keeping the tombstone bit clear.
+    /// Returns false if index is out of bounds or marked removed.
+    pub fn set_mask(&mut self, index: usize, mut bitmask: B) -> bool {
+        let tombstone = self.tombstone;
+        match self
+            .inner
+            .get_mut(index)
+            .filter(|x| !x.bitmask.get_bit(tombstone))
+        {
+            Some(x) => {
+                bitmask.set_bit(tombstone, false);
+                x.bitmask = bitmask;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns an iterator over T of the live entries.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskTombstoneVecIter<'a, B, T> {
+        BitmaskTombstoneVecIter {
+            inner: self.inner.iter(),
+            tombstone: self.tombstone,
+        }
+    }
+
+    /// Returns an iterator over mutable T of the live entries.
+    #[inline]
+    pub fn iter_mut(&mut self) -> BitmaskTombstoneVecIterMut<'_, B, T> {
+        BitmaskTombstoneVecIterMut {
+            inner: self.inner.iter_mut(),
+            tombstone: self.tombstone,
+        }
+    }
+
+    /// Returns an iterator over (index, BitmaskItem) of the live entries.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskTombstoneVecIterWithMask<'a, B, T> {
+        BitmaskTombstoneVecIterWithMask {
+            inner: self.inner.iter().enumerate(),
+            tombstone: self.tombstone,
+            mask: None,
+        }
+    }
+
+    /// Returns an iterator over (index, BitmaskItem) of the live entries whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskTombstoneVecIterWithMask<'a, B, T> {
+        BitmaskTombstoneVecIterWithMask {
+            inner: self.inner.iter().enumerate(),
+            tombstone: self.tombstone,
+            mask: Some(mask),
+        }
+    }
+
+    /// Returns the number of live entries whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&'a self, mask: &'a B) -> usize {
+        self.iter_matching(mask).count()
+    }
+
+    /// Returns the indices of the live entries whose bitmask matches mask.
+    /// Indices stay valid until the next sweep().
+    #[inline]
+    pub fn indices_matching(&'a self, mask: &'a B) -> Vec<usize> {
+        self.iter_matching(mask).map(|(i, _)| i).collect()
+    }
+
+    /// Sweeps the removed entries and moves the live ones into a BitmaskVec.
+    pub fn into_bitmask_vec(mut self) -> BitmaskVec<B, T> {
+        self.sweep();
+        let mut v = BitmaskVec::new();
+        *v.as_mut_vec() = self.inner;
+        v
+    }
+
+    /// Moves the items of a BitmaskVec into a new vec reserving bit tombstone.
+    /// Entries of value that already have the tombstone bit set are treated as removed.
+    /// # Panics
+    /// Panics if tombstone does not fit in B.
+    pub fn from_bitmask_vec(mut value: BitmaskVec<B, T>, tombstone: usize) -> Self
+    where
+        B: BitmaskBits,
+    {
+        Self::assert_tombstone(tombstone);
+        let inner = core::mem::take(value.as_mut_vec());
+        let removed = inner
+            .iter()
+            .filter(|x| x.bitmask.get_bit(tombstone))
+            .count();
+        Self {
+            inner,
+            tombstone,
+            removed,
+        }
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskTombstoneVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    /// Returns T at index.
+    /// # Panics
+    /// Panics if index is out of bounds or marked removed.
+    fn index(&self, index: usize) -> &Self::Output {
+        let x = &self.inner[index];
+        assert!(
+            !x.bitmask.get_bit(self.tombstone),
+            "index {index} is marked removed"
+        );
+        &x.item
+    }
+}
+
+// =================================================================================================
+/// Iter that returns T of the live entries of a BitmaskTombstoneVec.
+pub struct BitmaskTombstoneVecIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+    tombstone: usize,
+}
+
+impl<'a, B, T> Iterator for BitmaskTombstoneVecIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tombstone = self.tombstone;
+        self.inner
+            .by_ref()
+            .find(|x| !x.bitmask.get_bit(tombstone))
+            .map(|x| &x.item)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns mutable T of the live entries of a BitmaskTombstoneVec.
+pub struct BitmaskTombstoneVecIterMut<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: IterMut<'a, BitmaskItem<B, T>>,
+    tombstone: usize,
+}
+
+impl<'a, B, T> Iterator for BitmaskTombstoneVecIterMut<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tombstone = self.tombstone;
+        self.inner
+            .by_ref()
+            .find(|x| !x.bitmask.get_bit(tombstone))
+            .map(|x| &mut x.item)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (index, BitmaskItem) of the live entries of a BitmaskTombstoneVec,
+/// optionally limited to those whose bitmask matches a mask.
+pub struct BitmaskTombstoneVecIterWithMask<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: core::iter::Enumerate<Iter<'a, BitmaskItem<B, T>>>,
+    tombstone: usize,
+    mask: Option<&'a B>,
+}
+
+impl<'a, B, T> Iterator for BitmaskTombstoneVecIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (usize, &'a BitmaskItem<B, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tombstone = self.tombstone;
+        let mask = self.mask;
+        self.inner
+            .by_ref()
+            .find(|(_, x)| !x.bitmask.get_bit(tombstone) && mask.is_none_or(|m| x.matches_mask(m)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_tombstone_vec::BitmaskTombstoneVec;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_tombstone_vec_mark_removed() {
+        let mut v = BitmaskTombstoneVec::<u8, i32>::new(7);
+        v.push_with_mask(0b10000100, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        // the tombstone bit is hidden
+        assert_eq!(v.get_with_mask(0).unwrap().bitmask, 0b00000100);
+
+        assert!(v.mark_removed(1));
+        assert!(!v.mark_removed(1));
+        assert!(v.is_removed(1));
+        assert!(!v.is_removed(9));
+        assert_eq!(v.len(), 1);
+        assert_eq!(v.raw_len(), 2);
+        assert_eq!(v.removed_count(), 1);
+
+        assert_eq!(v.get(1), None);
+        assert_eq!(v.get_mut(1), None);
+        assert!(!v.set_mask(1, 0));
+        assert_eq!(v[0], 100);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tombstone_vec_index_removed() {
+        let mut v = BitmaskTombstoneVec::<u8, i32>::new(7);
+        v.push(100);
+        v.mark_removed(0);
+        let _ = v[0];
+    }
+
+    #[test]
+    fn test_tombstone_vec_iter_skips_removed() {
+        let mut v = BitmaskTombstoneVec::<u8, i32>::new(7);
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.mark_removed(0);
+
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![101, 102]);
+        for x in v.iter_mut() {
+            *x += 1;
+        }
+        let x: Vec<_> = v.iter_with_mask().map(|(i, x)| (i, x.item)).collect();
+        assert_eq!(x, vec![(1, 102), (2, 103)]);
+        assert_eq!(v.indices_matching(&0b00000010), vec![1, 2]);
+        assert_eq!(v.count_matching(&0b00000011), 1);
+    }
+
+    #[test]
+    fn test_tombstone_vec_sweep() {
+        let mut v = BitmaskTombstoneVec::<u8, i32>::new(7);
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+        v.mark_removed(0);
+        v.mark_removed(2);
+
+        assert_eq!(v.sweep(), 2);
+        assert_eq!(v.sweep(), 0);
+        assert_eq!(v.raw_len(), 1);
+        assert_eq!(v[0], 101);
+
+        let b = v.into_bitmask_vec();
+        assert_eq!(b.len(), 1);
+        assert_eq!(b.as_slice()[0].bitmask, 0b00000010);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tombstone_vec_tombstone_out_of_range() {
+        let _ = BitmaskTombstoneVec::<u8, i32>::new(8);
+    }
+
+    #[test]
+    fn test_tombstone_vec_from_bitmask_vec() {
+        let mut b = BitmaskVec::<u8, i32>::new();
+        b.push_with_mask(0b00000001, 100);
+        b.push_with_mask(0b10000000, 101);
+
+        let mut v = BitmaskTombstoneVec::from_bitmask_vec(b, 7);
+        assert_eq!(v.len(), 1);
+        assert!(v.is_removed(1));
+        assert!(v.set_mask(0, 0b10000010));
+        assert_eq!(v.get_with_mask(0).unwrap().bitmask, 0b00000010);
+    }
+}
@@ -0,0 +1,453 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMatching, BitmaskVecIterWithMask,
+};
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Index, IndexMut};
+use core::slice::Iter;
+
+/// BitmaskTrackedVec is a vec that pairs bitmasks with T and records which indices had their
+/// item or bitmask modified since the last clear_dirty().<br>
+///
+/// Every mutable access goes through methods that mark the touched index dirty, so
+/// incremental consumers only need to reprocess the entries visited by iter_dirty().
+/// Removing from the middle marks every shifted index dirty.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskTrackedVec::<u8, i32>::new();
+/// v.push_with_mask(0b00000000, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000011, 102);
+/// v.clear_dirty();
+///
+/// v[0] = 500;
+/// v.set_mask(2, 0b00000100);
+/// let dirty: Vec<_> = v.iter_dirty().map(|(i, _)| i).collect();
+/// assert_eq!(dirty, vec![0, 2]);
+///
+/// v.clear_dirty();
+/// assert_eq!(v.iter_dirty().count(), 0);
+/// ```
+pub struct BitmaskTrackedVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Vec<BitmaskItem<B, T>>,
+    flags: Vec<bool>,
+    dirty: Vec<usize>,
+}
+
+impl<'a, B, T> BitmaskTrackedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Vec::with_capacity(capacity),
+            flags: Vec::with_capacity(capacity),
+            dirty: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Returns true if index was modified since the last clear_dirty().
+    #[inline]
+    pub fn is_dirty(&self, index: usize) -> bool {
+        self.flags.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns the modified indices, in the order they were first modified.
+    #[inline]
+    pub fn dirty_indices(&self) -> &[usize] {
+        self.dirty.as_slice()
+    }
+
+    /// Returns a BitmaskTrackedVecIterDirty over the (index, BitmaskItem) pairs modified since the last clear_dirty().
+    #[inline]
+    pub fn iter_dirty(&'a self) -> BitmaskTrackedVecIterDirty<'a, B, T> {
+        BitmaskTrackedVecIterDirty {
+            items: &self.inner,
+            dirty: self.dirty.iter(),
+        }
+    }
+
+    /// Forgets all recorded modifications.
+    pub fn clear_dirty(&mut self) {
+        for i in self.dirty.drain(..) {
+            self.flags[i] = false;
+        }
+    }
+
+    /// Marks index as modified.
+    #[inline]
+    pub fn mark_dirty(&mut self, index: usize) {
+        if !self.flags[index] {
+            self.flags[index] = true;
+            self.dirty.push(index);
+        }
+    }
+
+    /// Clears the vector and the recorded modifications.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.flags.clear();
+        self.dirty.clear();
+    }
+
+    /// Pushes T and a default bitmask of zero. The new index is marked dirty.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask. The new index is marked dirty.
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.inner.push(BitmaskItem::new(bitmask, value));
+        self.flags.push(false);
+        self.mark_dirty(self.inner.len() - 1);
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        let x = self.inner.pop()?;
+        if self.flags.pop() == Some(true) {
+            let index = self.inner.len();
+            self.dirty.retain(|&i| i != index);
+        }
+        Some(x)
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    /// index and every shifted index are marked dirty.
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.inner.insert(index, BitmaskItem::new(bitmask, value));
+        self.flags.push(false);
+        for i in index..self.inner.len() {
+            self.mark_dirty(i);
+        }
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left.
+    /// Every shifted index is marked dirty.
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let x = self.inner.remove(index);
+        self.flags.pop();
+        let len = self.inner.len();
+        self.dirty.retain(|&i| i < len);
+        for i in index..len {
+            self.mark_dirty(i);
+        }
+        x
+    }
+
+    /// Removes an element without bitmask from the vector and returns it, replacing it with the last element.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.swap_with_mask_remove(index).item
+    }
+
+    /// Removes an element and bitmask from the vector and returns it, replacing it with the last element.
+    /// index is marked dirty if an element was moved into it.
+    pub fn swap_with_mask_remove(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let last = self.inner.len() - 1;
+        self.inner.swap(index, last);
+        let x = self.pop_with_mask().unwrap();
+        if index < last {
+            self.mark_dirty(index);
+        }
+        x
+    }
+
+    /// Returns T at index, or None if out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.inner.get(index).map(|x| &x.item)
+    }
+
+    /// Returns mutable T at index and marks it dirty, or None if out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.get_with_mask_mut(index).map(|x| &mut x.item)
+    }
+
+    /// Returns the BitmaskItem at index, or None if out of bounds.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<&BitmaskItem<B, T>> {
+        self.inner.get(index)
+    }
+
+    /// Returns the mutable BitmaskItem at index and marks it dirty, or None if out of bounds.
+    #[inline]
+    pub fn get_with_mask_mut(&mut self, index: usize) -> Option<&mut BitmaskItem<B, T>> {
+        if index < self.inner.len() {
+            self.mark_dirty(index);
+        }
+        self.inner.get_mut(index)
+    }
+
+    /// Replaces the bitmask at index and marks it dirty.
+    #[inline]
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        self.inner[index].bitmask = bitmask;
+        self.mark_dirty(index);
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecIterMatching<'a, B, T> {
+        BitmaskVecIterMatching::new(self.inner.iter(), mask)
+    }
+
+    /// Moves the items into a BitmaskVec, dropping the recorded modifications.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.inner.len());
+        for x in self.inner {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<'a, B, T> Default for BitmaskTrackedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskTrackedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Converts a BitmaskVec with no recorded modifications.
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let inner: Vec<_> = value.drain(..).collect();
+        let len = inner.len();
+        Self {
+            inner,
+            flags: alloc::vec![false; len],
+            dirty: Vec::new(),
+        }
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskTrackedVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<'a, B, T> IndexMut<usize> for BitmaskTrackedVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Returns mutable T at index and marks it dirty.
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.mark_dirty(index);
+        &mut self.inner[index].item
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (index, BitmaskItem) for entries modified since the last clear_dirty().
+pub struct BitmaskTrackedVecIterDirty<'a, B, T>
+where
+    B: Bitflag,
+{
+    items: &'a [BitmaskItem<B, T>],
+    dirty: Iter<'a, usize>,
+}
+
+impl<'a, B, T> Iterator for BitmaskTrackedVecIterDirty<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = (usize, &'a BitmaskItem<B, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = *self.dirty.next()?;
+        Some((i, &self.items[i]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_tracked_vec::BitmaskTrackedVec;
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    #[test]
+    fn test_bitmask_tracked_vec_push() {
+        let mut v = BitmaskTrackedVec::<u8, i32>::new();
+        v.push(100);
+        v.push_with_mask(0b00000001, 101);
+        assert_eq!(v.dirty_indices(), &[0, 1]);
+
+        v.clear_dirty();
+        assert!(!v.is_dirty(0));
+        assert!(v.dirty_indices().is_empty());
+    }
+
+    #[test]
+    fn test_bitmask_tracked_vec_mutations() {
+        let mut v = BitmaskTrackedVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.clear_dirty();
+
+        v[3] = 500;
+        *v.get_mut(1).unwrap() += 1;
+        v.get_with_mask_mut(3).unwrap().bitmask = 0;
+        v.set_mask(0, 0b10000000);
+
+        assert_eq!(v.dirty_indices(), &[3, 1, 0]);
+        let x: Vec<_> = v.iter_dirty().map(|(i, x)| (i, x.item)).collect();
+        assert_eq!(x, vec![(3, 500), (1, 102), (0, 100)]);
+        assert!(!v.is_dirty(2));
+        assert!(v.get_mut(10).is_none());
+    }
+
+    #[test]
+    fn test_bitmask_tracked_vec_pop() {
+        let mut v = BitmaskTrackedVec::<u8, i32>::new();
+        v.push(100);
+        v.push(101);
+        v.push(102);
+        v.clear_dirty();
+
+        v[2] = 1;
+        v[0] = 1;
+        assert_eq!(v.pop(), Some(1));
+        assert_eq!(v.dirty_indices(), &[0]);
+
+        v.push(7);
+        assert_eq!(v.dirty_indices(), &[0, 2]);
+    }
+
+    #[test]
+    fn test_bitmask_tracked_vec_remove() {
+        let mut v = BitmaskTrackedVec::<u8, i32>::new();
+        v.push(100);
+        v.push(101);
+        v.push(102);
+        v.push(103);
+        v.clear_dirty();
+
+        // the shifted elements are dirty
+        v[3] = 1;
+        assert_eq!(v.remove(1), 101);
+        let mut x = v.dirty_indices().to_vec();
+        x.sort();
+        assert_eq!(x, vec![1, 2]);
+        assert!(!v.is_dirty(0));
+    }
+
+    #[test]
+    fn test_bitmask_tracked_vec_insert() {
+        let mut v = BitmaskTrackedVec::<u8, i32>::new();
+        v.push(100);
+        v.push(101);
+        v.push(102);
+        v.clear_dirty();
+
+        v.insert_with_mask(1, 0b00000001, 99);
+        let mut x = v.dirty_indices().to_vec();
+        x.sort();
+        assert_eq!(x, vec![1, 2, 3]);
+        assert_eq!(v[1], 99);
+        assert!(!v.is_dirty(0));
+    }
+
+    #[test]
+    fn test_bitmask_tracked_vec_swap_remove() {
+        let mut v = BitmaskTrackedVec::<u8, i32>::new();
+        v.push(100);
+        v.push(101);
+        v.push(102);
+        v.clear_dirty();
+
+        assert_eq!(v.swap_remove(0), 100);
+        assert_eq!(v[0], 102);
+        assert_eq!(v.dirty_indices(), &[0]);
+
+        // removing the last element moves nothing
+        let x = v.swap_with_mask_remove(1);
+        assert_eq!(x.item, 101);
+        assert_eq!(v.dirty_indices(), &[0]);
+    }
+
+    #[test]
+    fn test_bitmask_tracked_vec_from_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+
+        let mut v = BitmaskTrackedVec::from(v);
+        assert_eq!(v.iter_dirty().count(), 0);
+        v[1] = 5;
+        assert_eq!(v.iter_matching(&0b00000010).next().unwrap().item, 5);
+        assert_eq!(v.into_bitmask_vec().len(), 2);
+    }
+}
@@ -0,0 +1,302 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::mem;
+
+/// Records how to reverse a single change made through a BitmaskVecTransaction.
+enum Undo<B, T>
+where
+    B: Bitflag,
+{
+    Push,
+    Pop(BitmaskItem<B, T>),
+    Insert(usize),
+    Remove(usize, BitmaskItem<B, T>),
+    SwapRemove(usize, BitmaskItem<B, T>),
+    SetMask(usize, B),
+    Replace(usize, T),
+}
+
+/// BitmaskVecTransaction is an all-or-nothing set of changes to a BitmaskVec.<br>
+///
+/// Created by BitmaskVec::begin(). Changes made through the transaction are visible through it
+/// right away and are kept by commit(). rollback(), or dropping the transaction without
+/// committing, restores the vec to the state it had when begin() was called.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(0b00000001, 100);
+///
+/// let mut tx = v.begin();
+/// tx.push_with_mask(0b00000010, 101);
+/// tx.set_mask(0, 0b10000000);
+/// tx.rollback();
+/// assert_eq!(v.len(), 1);
+/// assert_eq!(v.count_matching(&0b00000001), 1);
+///
+/// let mut tx = v.begin();
+/// tx.push_with_mask(0b00000010, 101);
+/// tx.commit();
+/// assert_eq!(v[1], 101);
+/// ```
+pub struct BitmaskVecTransaction<'v, B, T>
+where
+    B: Bitflag,
+{
+    vec: &'v mut BitmaskVec<B, T>,
+    undo: Vec<Undo<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Starts a transaction. Changes made through the returned BitmaskVecTransaction are undone
+    /// unless it is committed.
+    #[inline]
+    pub fn begin(&mut self) -> BitmaskVecTransaction<'_, B, T> {
+        BitmaskVecTransaction {
+            vec: self,
+            undo: Vec::new(),
+        }
+    }
+}
+
+impl<'a, 'v, B, T> BitmaskVecTransaction<'v, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector, including uncommitted changes.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.vec.as_slice()
+    }
+
+    /// Returns the vec, including uncommitted changes.
+    #[inline]
+    pub(crate) fn vec(&self) -> &BitmaskVec<B, T> {
+        self.vec
+    }
+
+    /// Returns the number of changes made so far.
+    #[inline]
+    pub fn change_count(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema of the vec.
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.vec.push_with_mask(bitmask, value);
+        self.undo.push(Undo::Push);
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    /// T must be Clone so the item can be restored on rollback.
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>>
+    where
+        T: Clone,
+    {
+        let x = self.vec.pop_with_mask()?;
+        self.undo.push(Undo::Pop(x.clone()));
+        Some(x)
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema of the vec.
+    #[inline]
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.vec.insert_with_mask(index, bitmask, value);
+        self.undo.push(Undo::Insert(index));
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left.
+    /// T must be Clone so the item can be restored on rollback.
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T>
+    where
+        T: Clone,
+    {
+        let x = self.vec.remove_with_mask(index);
+        self.undo.push(Undo::Remove(index, x.clone()));
+        x
+    }
+
+    /// Removes an element and bitmask from the vector and returns it, replacing it with the last element.
+    /// T must be Clone so the item can be restored on rollback.
+    pub fn swap_with_mask_remove(&mut self, index: usize) -> BitmaskItem<B, T>
+    where
+        T: Clone,
+    {
+        let x = self.vec.swap_with_mask_remove(index);
+        self.undo.push(Undo::SwapRemove(index, x.clone()));
+        x
+    }
+
+    /// Removes the element at index, discarding it, and shifts all elements after it to the left.
+    #[inline]
+    pub fn delete(&mut self, index: usize) {
+        let x = self.vec.remove_with_mask(index);
+        self.undo.push(Undo::Remove(index, x));
+    }
+
+    /// Replaces the bitmask at index, returning the previous bitmask.
+    /// # Panics
+    /// Panics if BitmaskVec::set_mask() would panic for the new bitmask.
+    pub fn set_mask(&mut self, index: usize, bitmask: B) -> B {
+        if let Err(e) = self.vec.check_set_mask(index, &bitmask) {
+            panic!("{e}");
+        }
+        let old = mem::replace(&mut self.vec.as_mut_vec()[index].bitmask, bitmask);
+        self.undo.push(Undo::SetMask(index, old.clone()));
+        old
+    }
+
+    /// Replaces T at index, returning the previous T.
+    /// T must be Clone so the item can be restored on rollback.
+    pub fn replace(&mut self, index: usize, value: T) -> T
+    where
+        T: Clone,
+    {
+        let old = mem::replace(&mut self.vec.as_mut_vec()[index].item, value);
+        self.undo.push(Undo::Replace(index, old.clone()));
+        old
+    }
+
+    /// Keeps every change made through the transaction.
+    #[inline]
+    pub fn commit(mut self) {
+        self.undo.clear();
+    }
+
+    /// Undoes every change made through the transaction.
+    #[inline]
+    pub fn rollback(self) {}
+}
+
+impl<B, T> Drop for BitmaskVecTransaction<'_, B, T>
+where
+    B: Bitflag,
+{
+    /// Undoes changes in reverse order unless the transaction was committed.
+    fn drop(&mut self) {
+        let inner = self.vec.as_mut_vec();
+        while let Some(x) = self.undo.pop() {
+            match x {
+                Undo::Push => {
+                    inner.pop();
+                }
+                Undo::Pop(x) => inner.push(x),
+                Undo::Insert(index) => {
+                    inner.remove(index);
+                }
+                Undo::Remove(index, x) => inner.insert(index, x),
+                Undo::SwapRemove(index, x) => {
+                    inner.push(x);
+                    let last = inner.len() - 1;
+                    inner.swap(index, last);
+                }
+                Undo::SetMask(index, bitmask) => inner[index].bitmask = bitmask,
+                Undo::Replace(index, item) => inner[index].item = item,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+
+    fn pairs(v: &BitmaskVec<u8, i32>) -> Vec<(u8, i32)> {
+        v.as_slice().iter().map(|x| (x.bitmask, x.item)).collect()
+    }
+
+    #[test]
+    fn test_bitmask_transaction_commit() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        let mut tx = v.begin();
+        tx.push_with_mask(0b00000001, 105);
+        tx.remove_with_mask(0);
+        tx.set_mask(0, 0b10000000);
+        assert_eq!(tx.len(), 2);
+        assert_eq!(tx.change_count(), 3);
+        tx.commit();
+
+        assert_eq!(pairs(&v), vec![(0b10000000, 101), (0b00000001, 105)]);
+    }
+
+    #[test]
+    fn test_bitmask_transaction_rollback() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+        let before = pairs(&v);
+
+        let mut tx = v.begin();
+        tx.push(105);
+        tx.insert_with_mask(1, 0b00000001, 99);
+        assert_eq!(tx.remove_with_mask(3).item, 102);
+        assert_eq!(tx.swap_with_mask_remove(0).item, 100);
+        tx.set_mask(2, 0b11111111);
+        assert_eq!(tx.replace(1, 7), 99);
+        tx.pop_with_mask();
+        tx.delete(0);
+        tx.rollback();
+
+        assert_eq!(pairs(&v), before);
+    }
+
+    #[test]
+    fn test_bitmask_transaction_drop() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000011, 101);
+        let before = pairs(&v);
+        {
+            let mut tx = v.begin();
+            tx.swap_with_mask_remove(0);
+            tx.push_with_mask(0b00000001, 1);
+            tx.set_mask(0, 0b00000001);
+        }
+        assert_eq!(pairs(&v), before);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_transaction_schema() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.set_schema(0b00001111);
+        let mut tx = v.begin();
+        tx.push_with_mask(0b10000000, 100);
+    }
+}
@@ -1,8 +1,69 @@
+use crate::cj_bitmask_bits::BitmaskBits;
 use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_error::Error;
+use alloc::boxed::Box;
+use alloc::collections::btree_map::{BTreeMap, Entry};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::{Drain, Vec};
 use cj_common::cj_binary::bitbuf::*;
-use std::ops::{AddAssign, Index, IndexMut, RangeBounds};
-use std::slice::{Iter, IterMut};
-use std::vec::Drain;
+use core::cmp::Ordering;
+use core::fmt::{Binary, Debug, Display, Formatter, Write};
+use core::hash::{Hash, Hasher};
+use core::iter::StepBy;
+use core::mem::{self, size_of, ManuallyDrop, MaybeUninit};
+use core::ops::{
+    Add, AddAssign, BitAnd, BitOr, BitXor, Deref, DerefMut, Index, IndexMut, Not, Range,
+    RangeBounds,
+};
+use core::slice::{Iter, IterMut};
+use std::collections::HashMap;
+
+/// The elements before the selected index, the element at it, and the elements after it.
+pub type SelectNth<'s, B, T> = (
+    &'s mut [BitmaskItem<B, T>],
+    &'s mut BitmaskItem<B, T>,
+    &'s mut [BitmaskItem<B, T>],
+);
+
+/// Which entry to_hashmap() and to_hashmap_with_mask() keep when several entries share a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keep the entry that comes first in the vec.
+    KeepFirst,
+    /// Keep the entry that comes last in the vec.
+    KeepLast,
+}
+
+/// Error returned by try_push_with_mask(). Holds the value that was not pushed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushError<T> {
+    /// The bitmask has bits outside the schema set with set_schema().
+    OutsideSchema(T),
+    /// The vec could not allocate room for another element.
+    AllocFailed(T),
+}
+
+impl<T> PushError<T> {
+    /// Returns the value that was not pushed.
+    #[inline]
+    pub fn into_value(self) -> T {
+        match self {
+            PushError::OutsideSchema(x) | PushError::AllocFailed(x) => x,
+        }
+    }
+}
+
+impl<T> Display for PushError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PushError::OutsideSchema(_) => write!(f, "bitmask has bits outside the schema"),
+            PushError::AllocFailed(_) => write!(f, "memory allocation failed"),
+        }
+    }
+}
+
+impl<T: Debug> std::error::Error for PushError<T> {}
 
 /// BitmaskVec is a vec that pairs bitmasks with T. Bitmasks u8 through u128 are supported.<br>
 ///
@@ -36,6 +97,21 @@ where
     B: Bitflag,
 {
     inner: Vec<BitmaskItem<B, T>>,
+    bit_names: Vec<(usize, &'static str)>,
+    schema: Option<MaskSchema<B>>,
+}
+
+/// Bits a BitmaskVec accepts in new bitmasks, with the check instantiated where B's bit
+/// operations are known.
+#[derive(Clone)]
+struct MaskSchema<B> {
+    allowed: B,
+    check: fn(&B, &B) -> bool,
+}
+
+#[inline]
+fn within_schema<B: BitmaskBits>(allowed: &B, bitmask: &B) -> bool {
+    (*bitmask & !*allowed).is_zero()
 }
 
 impl<'a, B, T> BitmaskVec<B, T>
@@ -45,6 +121,8 @@ where
     pub fn new() -> Self {
         Self {
             inner: Vec::<BitmaskItem<B, T>>::new(),
+            bit_names: Vec::new(),
+            schema: None,
         }
     }
 
@@ -52,6 +130,8 @@ where
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             inner: Vec::<BitmaskItem<B, T>>::with_capacity(capacity),
+            bit_names: Vec::new(),
+            schema: None,
         }
     }
 
@@ -64,6 +144,7 @@ where
     /// Moves all the elements of other into self, leaving other empty.
     #[inline]
     pub fn append(&mut self, other: &mut Self) {
+        self.assert_schema_items(&other.inner);
         self.inner.append(&mut other.inner)
     }
 
@@ -103,8 +184,11 @@ where
     }
 
     /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema set with set_schema().
     #[inline]
     pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.assert_schema(&bitmask);
         self.inner.insert(index, BitmaskItem::new(bitmask, value));
     }
 
@@ -155,6 +239,9 @@ where
     where
         T: Clone,
     {
+        if new_len > self.inner.len() {
+            self.assert_schema(&bitmask);
+        }
         self.inner.resize(new_len, BitmaskItem::new(bitmask, value));
     }
 
@@ -164,16 +251,28 @@ where
     where
         T: Clone,
     {
+        if new_len > self.inner.len() {
+            self.assert_schema(&value.bitmask);
+        }
         self.inner.resize(new_len, value);
     }
 
     /// Resizes the Vec in-place so that len is equal to new_len.
     #[inline]
-    pub fn resize_with<F>(&mut self, new_len: usize, f: F)
+    pub fn resize_with<F>(&mut self, new_len: usize, mut f: F)
     where
         F: FnMut() -> BitmaskItem<B, T>,
     {
-        self.inner.resize_with(new_len, f);
+        if new_len <= self.inner.len() {
+            self.inner.truncate(new_len);
+            return;
+        }
+        self.inner.reserve(new_len - self.inner.len());
+        while self.inner.len() < new_len {
+            let x = f();
+            self.assert_schema(&x.bitmask);
+            self.inner.push(x);
+        }
     }
 
     /// Clones and appends all elements in a slice to the Vec.
@@ -182,6 +281,7 @@ where
     where
         T: Clone,
     {
+        self.assert_schema_items(other);
         self.inner.extend_from_slice(other);
     }
 
@@ -232,9 +332,97 @@ where
     /// v.push_with_mask(0b00000010, 101);
     /// v.push_with_mask(0b00000011, 102);
     /// ```
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema set with set_schema().
     #[inline]
     pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.assert_schema(&bitmask);
+        self.inner.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pushes T and bitmask, returning the value back instead of panicking if bitmask has bits
+    /// outside the schema or the vec cannot allocate room for it.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.set_schema(0b00001111);
+    /// assert_eq!(v.try_push_with_mask(0b00000011, 100), Ok(()));
+    /// assert_eq!(
+    ///     v.try_push_with_mask(0b00010000, 101),
+    ///     Err(PushError::OutsideSchema(101))
+    /// );
+    /// assert_eq!(v.len(), 1);
+    /// ```
+    pub fn try_push_with_mask(&mut self, bitmask: B, value: T) -> Result<(), PushError<T>> {
+        if !self.allows_mask(&bitmask) {
+            return Err(PushError::OutsideSchema(value));
+        }
+        if self.inner.try_reserve(1).is_err() {
+            return Err(PushError::AllocFailed(value));
+        }
         self.inner.push(BitmaskItem::new(bitmask, value));
+        Ok(())
+    }
+
+    /// Inserts an element and bitmask at position index, returning an Error instead of panicking
+    /// if index is greater than len(), bitmask has bits outside the schema or the vec cannot
+    /// allocate room for it.
+    pub fn try_insert_with_mask(
+        &mut self,
+        index: usize,
+        bitmask: B,
+        value: T,
+    ) -> Result<(), Error> {
+        self.check_index(index, self.inner.len() + 1)?;
+        if !self.allows_mask(&bitmask) {
+            return Err(Error::OutsideSchema);
+        }
+        self.inner.try_reserve(1).map_err(|_| Error::AllocFailed)?;
+        self.inner.insert(index, BitmaskItem::new(bitmask, value));
+        Ok(())
+    }
+
+    /// Removes and returns T at index, shifting all elements after it to the left, or returns
+    /// an Error if index is out of bounds.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// assert_eq!(
+    ///     v.try_remove(1),
+    ///     Err(Error::IndexOutOfBounds { index: 1, len: 1 })
+    /// );
+    /// assert_eq!(v.try_remove(0), Ok(100));
+    /// ```
+    pub fn try_remove(&mut self, index: usize) -> Result<T, Error> {
+        self.check_index(index, self.inner.len())?;
+        Ok(self.inner.remove(index).item)
+    }
+
+    /// Removes and returns T at index, replacing it with the last element, or returns an Error
+    /// if index is out of bounds.
+    pub fn try_swap_remove(&mut self, index: usize) -> Result<T, Error> {
+        self.check_index(index, self.inner.len())?;
+        Ok(self.inner.swap_remove(index).item)
+    }
+
+    /// Shortens the vector to len elements, or returns an Error if len is greater than len().
+    pub fn try_truncate(&mut self, len: usize) -> Result<(), Error> {
+        self.check_index(len, self.inner.len() + 1)?;
+        self.inner.truncate(len);
+        Ok(())
+    }
+
+    #[inline]
+    fn check_index(&self, index: usize, end: usize) -> Result<(), Error> {
+        if index < end {
+            Ok(())
+        } else {
+            Err(Error::IndexOutOfBounds {
+                index,
+                len: self.inner.len(),
+            })
+        }
     }
 
     /// Pops T from the Vec without the bitmask.  If both T and bitmask are wanted,
@@ -392,789 +580,4461 @@ where
     pub fn iter_with_mask_mut(&'a mut self) -> BitmaskVecIterWithMaskMut<'a, B, T> {
         BitmaskVecIterWithMaskMut::new(self.inner.iter_mut())
     }
-}
 
-impl<'a, B, T> Default for BitmaskVec<B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    fn default() -> Self {
-        Self::new()
+    /// Returns a BitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    /// ```
+    /// # use cj_bitmask_vec::{cj_bitmask_vec::*, cj_bitmask_item::*};
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000000, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000011, 102);
+    /// v.push_with_mask(0b00000100, 103);
+    ///
+    /// let total: i32 = v.iter_matching(&0b00000010).map(|x| x.item).sum();
+    /// assert_eq!(total, 203);
+    /// ```
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecIterMatching<'a, B, T> {
+        BitmaskVecIterMatching::new(self.inner.iter(), mask)
     }
-}
-
-impl<'a, B, T> Index<usize> for BitmaskVec<B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    type Output = T;
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.inner[index].item
+    /// Returns the number of elements whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        count_chunks(&self.inner, mask)
     }
-}
 
-impl<'a, B, T> IndexMut<usize> for BitmaskVec<B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.inner[index].item
+    /// Returns the indices of the elements whose bitmask matches mask.
+    pub fn indices_matching(&self, mask: &'a B) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for (n, chunk) in self.inner.chunks(SCAN_CHUNK_LEN).enumerate() {
+            let mut hits = scan_chunk(chunk, mask);
+            while hits != 0 {
+                indices.push(n * SCAN_CHUNK_LEN + hits.trailing_zeros() as usize);
+                hits &= hits - 1;
+            }
+        }
+        indices
     }
-}
 
-impl<'a, B, T> AddAssign<(B, T)> for BitmaskVec<B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    #[inline(always)]
-    fn add_assign(&mut self, rhs: (B, T)) {
-        self.push_with_mask(rhs.0, rhs.1);
+    /// Returns the index of the first element after index after whose bitmask matches mask,
+    /// wrapping around to the start of the vec, so the element at after is checked last.
+    /// Lets a round-robin scheduler resume from the element it picked last time. If after is
+    /// past the end, the scan starts at index 0.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000000, 101);
+    /// v.push_with_mask(0b00000001, 102);
+    ///
+    /// let mut picked = Vec::new();
+    /// let mut last = usize::MAX;
+    /// for _ in 0..3 {
+    ///     last = v.next_matching_index(&0b00000001, last).unwrap();
+    ///     picked.push(last);
+    /// }
+    /// assert_eq!(picked, vec![0, 2, 0]);
+    /// assert_eq!(v.next_matching_index(&0b00000010, 0), None);
+    /// ```
+    pub fn next_matching_index(&self, mask: &'a B, after: usize) -> Option<usize> {
+        let split = after.saturating_add(1).min(self.inner.len());
+        let (head, tail) = self.inner.split_at(split);
+        tail.iter()
+            .position(|x| x.bitmask.matches_mask(mask))
+            .map(|i| split + i)
+            .or_else(|| head.iter().position(|x| x.bitmask.matches_mask(mask)))
     }
-}
 
-impl<'a, B, T> AddAssign<T> for BitmaskVec<B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    #[inline(always)]
-    fn add_assign(&mut self, rhs: T) {
-        self.push(rhs);
+    /// Sets the bits of mask on every element's bitmask <i>(bitmask | mask)</i>.
+    /// # Panics
+    /// Panics if mask has bits outside the schema set with set_schema().
+    pub fn or_mask_all(&mut self, mask: &B)
+    where
+        B: Copy + BitOr<Output = B>,
+    {
+        self.assert_schema(mask);
+        for x in self.inner.iter_mut() {
+            x.bitmask = x.bitmask | *mask;
+        }
     }
-}
 
-impl<'a, B, T> AddAssign for BitmaskVec<B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    #[inline(always)]
-    fn add_assign(&mut self, rhs: Self) {
-        self.inner.extend(rhs.inner);
+    /// Keeps only the bits of mask on every element's bitmask <i>(bitmask & mask)</i>.
+    pub fn and_mask_all(&mut self, mask: &B)
+    where
+        B: Copy + BitAnd<Output = B>,
+    {
+        for x in self.inner.iter_mut() {
+            x.bitmask = x.bitmask & *mask;
+        }
     }
-}
-
-// =================================================================================================
-/// Iter that returns T (excludes bitmask)
-pub struct BitmaskVecIter<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    inner: Iter<'a, BitmaskItem<B, T>>,
-}
 
-impl<'a, B, T> BitmaskVecIter<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    pub fn new(i: Iter<'a, BitmaskItem<B, T>>) -> Self {
-        Self { inner: i }
+    /// Toggles the bits of mask on every element's bitmask <i>(bitmask ^ mask)</i>.
+    /// # Panics
+    /// Panics if mask has bits outside the schema set with set_schema().
+    pub fn xor_mask_all(&mut self, mask: &B)
+    where
+        B: Copy + BitXor<Output = B>,
+    {
+        self.assert_schema(mask);
+        for x in self.inner.iter_mut() {
+            x.bitmask = x.bitmask ^ *mask;
+        }
     }
 
+    /// Sorts the vector by bitmask. The sort is stable.
     #[inline]
-    fn next_inner(&mut self) -> Option<&'a T> {
-        if let Some(item) = self.inner.next() {
-            return Some(&item.item);
-        }
-        None
+    pub fn sort_by_mask(&mut self)
+    where
+        B: Ord,
+    {
+        self.inner.sort_by(|a, b| a.bitmask.cmp(&b.bitmask));
     }
-}
 
-impl<'a, B, T> Iterator for BitmaskVecIter<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    type Item = &'a T;
+    /// Sorts the vector by T. The sort is stable.
+    #[inline]
+    pub fn sort_by_item(&mut self)
+    where
+        T: Ord,
+    {
+        self.inner.sort_by(|a, b| a.item.cmp(&b.item));
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next_inner()
+    /// Returns true if the elements are sorted by bitmask, as sort_by_mask() leaves them.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000010, 100);
+    /// v.push_with_mask(0b00000001, 101);
+    /// assert!(!v.is_sorted_by_mask());
+    ///
+    /// v.sort_by_mask();
+    /// assert!(v.is_sorted_by_mask());
+    /// ```
+    #[inline]
+    pub fn is_sorted_by_mask(&self) -> bool
+    where
+        B: Ord,
+    {
+        self.inner.is_sorted_by(|a, b| a.bitmask <= b.bitmask)
     }
-}
 
-// =================================================================================================
-/// Iter that returns BitmaskItem, containing both T and bitmask.
-pub struct BitmaskVecIterWithMask<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    inner: Iter<'a, BitmaskItem<B, T>>,
-}
+    /// Returns true if the elements are sorted by T, as sort_by_item() leaves them.
+    #[inline]
+    pub fn is_sorted_by_item(&self) -> bool
+    where
+        T: Ord,
+    {
+        self.inner.is_sorted_by(|a, b| a.item <= b.item)
+    }
 
-impl<'a, B, T> BitmaskVecIterWithMask<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    pub fn new(i: Iter<'a, BitmaskItem<B, T>>) -> Self {
-        Self { inner: i }
+    /// Returns how many elements have each bit set, indexed by bit position
+    /// (the result has one entry per bit of B), computed in one pass over the bitmasks.
+    /// ```
+    /// # use cj_bitmask_vec::{cj_bitmask_vec::*, cj_bitmask_item::*};
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000011, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b10000000, 102);
+    ///
+    /// let h = v.bit_histogram();
+    /// assert_eq!(h.len(), 8);
+    /// assert_eq!(&h[..3], &[1, 2, 0]);
+    /// assert_eq!(h[7], 1);
+    /// ```
+    pub fn bit_histogram(&self) -> Vec<usize>
+    where
+        B: BitmaskBits,
+    {
+        let mut counts = vec![0; B::BITS];
+        for x in self.inner.iter() {
+            for bit in x.bitmask.set_bits() {
+                counts[bit] += 1;
+            }
+        }
+        counts
     }
-    #[inline]
-    fn next_inner(&mut self) -> Option<&'a BitmaskItem<B, T>> {
-        if let Some(item) = self.inner.next() {
-            return Some(item);
+
+    /// Returns how many elements have exactly n bits set, indexed by n
+    /// (the result has one entry per bit of B plus one for zero).
+    pub fn popcount_histogram(&self) -> Vec<usize>
+    where
+        B: BitmaskBits,
+    {
+        let mut counts = vec![0; B::BITS + 1];
+        for x in self.inner.iter() {
+            counts[x.bitmask.count_bits() as usize] += 1;
         }
-        None
+        counts
     }
 
-    pub fn filter_mask(&mut self, mask: &'a B) -> Option<&'a BitmaskItem<B, T>> {
-        self.inner.by_ref().find(|&item| item.matches_mask(mask))
+    /// Returns the total number of set bits across all bitmasks.
+    pub fn total_set_bits(&self) -> usize
+    where
+        B: BitmaskBits,
+    {
+        self.inner
+            .iter()
+            .map(|x| x.bitmask.count_bits() as usize)
+            .sum()
     }
-}
 
-impl<'a, B, T> Iterator for BitmaskVecIterWithMask<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    type Item = &'a BitmaskItem<B, T>;
+    /// Registers a human readable name for bit, used by describe() and describe_mask().
+    /// Naming a bit again replaces its previous name.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.name_bit(0, "QUEUED");
+    /// v.name_bit(1, "RUNNING");
+    /// v.push_with_mask(0b00000011, 100);
+    /// v.push_with_mask(0b10000000, 101);
+    ///
+    /// assert_eq!(v.describe(0).unwrap(), "QUEUED|RUNNING");
+    /// assert_eq!(v.describe(1).unwrap(), "bit7");
+    /// assert_eq!(v.describe_mask(&0), "0");
+    /// ```
+    /// # Panics
+    /// Panics if bit does not fit in B.
+    pub fn name_bit(&mut self, bit: usize, name: &'static str)
+    where
+        B: BitmaskBits,
+    {
+        assert!(bit < B::BITS, "bit {bit} doesn't fit in the bitmask");
+        match self.bit_names.binary_search_by_key(&bit, |x| x.0) {
+            Ok(i) => self.bit_names[i].1 = name,
+            Err(i) => self.bit_names.insert(i, (bit, name)),
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next_inner()
+    /// Returns the name registered for bit, if any.
+    pub fn bit_name(&self, bit: usize) -> Option<&'static str> {
+        self.bit_names
+            .binary_search_by_key(&bit, |x| x.0)
+            .ok()
+            .map(|i| self.bit_names[i].1)
     }
-}
 
-// =================================================================================================
-/// Iter that returns mutable T (excludes bitmask)
-pub struct BitmaskVecIterMut<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    inner: IterMut<'a, BitmaskItem<B, T>>,
-}
+    /// Renders mask as the names of its set bits joined by '|', lowest bit first.
+    /// Bits without a registered name render as bitN, and a zero mask renders as 0.
+    pub fn describe_mask(&self, mask: &B) -> String
+    where
+        B: BitmaskBits,
+    {
+        let mut s = String::new();
+        for bit in mask.set_bits() {
+            if !s.is_empty() {
+                s.push('|');
+            }
+            match self.bit_name(bit) {
+                Some(name) => s.push_str(name),
+                None => {
+                    let _ = write!(s, "bit{bit}");
+                }
+            }
+        }
+        if s.is_empty() {
+            s.push('0');
+        }
+        s
+    }
 
-impl<'a, B, T> BitmaskVecIterMut<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    pub fn new(i: IterMut<'a, BitmaskItem<B, T>>) -> Self {
-        Self { inner: i }
+    /// Renders the bitmask at index with describe_mask(), or returns None if out of bounds.
+    pub fn describe(&self, index: usize) -> Option<String>
+    where
+        B: BitmaskBits,
+    {
+        self.inner
+            .get(index)
+            .map(|x| self.describe_mask(&x.bitmask))
     }
 
-    #[inline]
-    fn next_inner_mut(&mut self) -> Option<&'a mut T> {
-        if let Some(item) = self.inner.next() {
-            return Some(&mut item.item);
+    /// Appends every T from iter, each paired with a clone of mask.
+    /// Reserves once using the iterator's lower size bound.
+    /// # Panics
+    /// Panics if mask has bits outside the schema set with set_schema().
+    pub fn extend_with_mask<I>(&mut self, mask: B, iter: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.assert_schema(&mask);
+        let iter = iter.into_iter();
+        self.inner.reserve(iter.size_hint().0);
+        for x in iter {
+            self.inner.push(BitmaskItem::new(mask.clone(), x));
         }
-        None
     }
-}
 
-impl<'a, B, T> Iterator for BitmaskVecIterMut<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B>,
-{
-    type Item = &'a mut T;
+    /// Creates a vec of n clones of value, all paired with mask. The masked counterpart of vec![value; n].
+    /// Like every constructor, it returns a vec without a schema.
+    pub fn from_elem_with_mask(mask: B, value: T, n: usize) -> Self
+    where
+        T: Clone,
+    {
+        let mut v = Self::new();
+        v.inner = vec![BitmaskItem::new(mask, value); n];
+        v
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next_inner_mut()
+    /// Returns a new vec holding n copies of the contents, bitmasks included.
+    /// # Panics
+    /// Panics if the capacity would overflow.
+    pub fn repeat(&self, n: usize) -> Self
+    where
+        T: Clone,
+    {
+        let mut v =
+            Self::with_capacity(self.inner.len().checked_mul(n).expect("capacity overflow"));
+        for _ in 0..n {
+            v.inner.extend_from_slice(&self.inner);
+        }
+        v.bit_names.clone_from(&self.bit_names);
+        v.schema.clone_from(&self.schema);
+        v
     }
-}
 
-// =================================================================================================
-/// Iter that returns mutable BitmaskItem, containing both T and bitmask.
-pub struct BitmaskVecIterWithMaskMut<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    inner: IterMut<'a, BitmaskItem<B, T>>,
-}
+    /// Joins parts into a single vec, in order. The total length is computed up front so the
+    /// result is allocated once. Bit names and the schema are taken from the first part.
+    /// # Panics
+    /// Panics if a later part has a bitmask outside the schema of the first.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut a = BitmaskVec::<u8, i32>::new();
+    /// a.push_with_mask(0b00000001, 1);
+    /// let mut b = BitmaskVec::<u8, i32>::new();
+    /// b.push_with_mask(0b00000010, 2);
+    ///
+    /// let v = BitmaskVec::concat(vec![a.clone(), b.clone()]);
+    /// assert_eq!(v, vec![1, 2]);
+    /// assert_eq!(a + b, v);
+    /// ```
+    pub fn concat<I>(parts: I) -> Self
+    where
+        I: IntoIterator<Item = Self>,
+    {
+        let parts: Vec<Self> = parts.into_iter().collect();
+        let len: usize = parts.iter().map(|x| x.inner.len()).sum();
+        let mut parts = parts.into_iter();
+        let mut v = match parts.next() {
+            Some(first) => first,
+            None => return Self::new(),
+        };
+        v.inner.reserve_exact(len - v.inner.len());
+        for mut x in parts {
+            v.append(&mut x);
+        }
+        v
+    }
 
-impl<'a, B, T> BitmaskVecIterWithMaskMut<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    pub fn new(i: IterMut<'a, BitmaskItem<B, T>>) -> Self {
-        Self { inner: i }
+    /// Inserts T and bitmask after every element whose bitmask is less than or equal to bitmask,
+    /// keeping a vec sorted by sort_by_mask() sorted. Returns the insertion index.
+    #[inline]
+    pub fn insert_sorted_by_mask(&mut self, bitmask: B, value: T) -> usize
+    where
+        B: Ord,
+    {
+        self.insert_sorted_by(bitmask, value, |a, b| a.bitmask.cmp(&b.bitmask))
+    }
+
+    /// Inserts T and bitmask after every element that compares less than or equal to it, keeping
+    /// a vec sorted by compare sorted. Binary searches for the insertion point. Returns the insertion index.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema set with set_schema().
+    pub fn insert_sorted_by<F>(&mut self, bitmask: B, value: T, mut compare: F) -> usize
+    where
+        F: FnMut(&BitmaskItem<B, T>, &BitmaskItem<B, T>) -> Ordering,
+    {
+        self.assert_schema(&bitmask);
+        let x = BitmaskItem::new(bitmask, value);
+        let index = self
+            .inner
+            .partition_point(|y| compare(y, &x) != Ordering::Greater);
+        self.inner.insert(index, x);
+        index
     }
+
+    /// Replaces T at index, keeping its bitmask, and returns the previous T.
     #[inline]
-    fn next_inner_mut(&mut self) -> Option<&'a mut BitmaskItem<B, T>> {
-        if let Some(item) = self.inner.next() {
-            return Some(item);
+    pub fn replace(&mut self, index: usize, value: T) -> T {
+        mem::replace(&mut self.inner[index].item, value)
+    }
+
+    /// Replaces the bitmask and T at index, returning the previous BitmaskItem.
+    /// # Panics
+    /// Panics if set_mask() would panic for index and bitmask.
+    #[inline]
+    pub fn replace_with_mask(&mut self, index: usize, bitmask: B, value: T) -> BitmaskItem<B, T> {
+        self.assert_set_mask(index, &bitmask);
+        mem::replace(&mut self.inner[index], BitmaskItem::new(bitmask, value))
+    }
+
+    /// Moves T out of index, leaving T::default() with a zero bitmask behind.
+    /// Unlike remove(), no elements are shifted.
+    #[inline]
+    pub fn take(&mut self, index: usize) -> T
+    where
+        T: Default,
+    {
+        self.take_with_mask(index).item
+    }
+
+    /// Moves the BitmaskItem out of index, leaving T::default() with a zero bitmask behind.
+    /// Unlike remove_with_mask(), no elements are shifted.
+    /// # Panics
+    /// Panics if set_mask() would panic for index and a zero bitmask.
+    #[inline]
+    pub fn take_with_mask(&mut self, index: usize) -> BitmaskItem<B, T>
+    where
+        T: Default,
+    {
+        self.replace_with_mask(index, B::default(), T::default())
+    }
+
+    /// Overwrites every T with a clone of value, leaving the bitmasks untouched.
+    pub fn fill(&mut self, value: T)
+    where
+        T: Clone,
+    {
+        for x in self.inner.iter_mut() {
+            x.item = value.clone();
         }
-        None
     }
 
-    pub fn filter_mask(&mut self, mask: &'a B) -> Option<&'a mut BitmaskItem<B, T>> {
-        self.inner.by_ref().find(|item| item.matches_mask(mask))
+    /// Overwrites every T with the result of calling f, leaving the bitmasks untouched.
+    pub fn fill_with<F>(&mut self, mut f: F)
+    where
+        F: FnMut() -> T,
+    {
+        for x in self.inner.iter_mut() {
+            x.item = f();
+        }
     }
-}
 
-impl<'a, B, T> Iterator for BitmaskVecIterWithMaskMut<'a, B, T>
-where
-    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-{
-    type Item = &'a mut BitmaskItem<B, T>;
+    /// Overwrites T with a clone of value for every item whose bitmask matches mask.
+    pub fn fill_matching(&mut self, mask: &'a B, value: T)
+    where
+        T: Clone,
+    {
+        self.fill_with_matching(mask, || value.clone());
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next_inner_mut()
+    /// Overwrites T with the result of calling f for every item whose bitmask matches mask.
+    pub fn fill_with_matching<F>(&mut self, mask: &'a B, mut f: F)
+    where
+        F: FnMut() -> T,
+    {
+        for x in self.inner.iter_mut() {
+            if x.matches_mask(mask) {
+                x.item = f();
+            }
+        }
     }
-}
 
-// pub struct BitmaskVecIterFilter<'a, B, T>
-// where
-//     B: Bitflag + CjMatchesMask<'a, B> + Default + Clone,
-//     Self: Sized,
-// {
-//     inner: BitmaskVecIterWithMask<'a, B, T>,
-//     the_mask: B,
-// }
-//
-// impl<'a, B, T> BitmaskVecIterFilter<'a, B, T>
-// where
-//     B: Bitflag + CjMatchesMask<'a, B> + Default + Clone,
-// {
-//     pub fn new(mut i: BitmaskVecIterWithMask<'a, B, T>) -> Self {
-//         Self {
-//             inner: i,
-//             the_mask: B::default(),
-//         }
-//     }
-//
-//     pub fn filter_X(
-//         mut self,
-//         mask: &'a B,
-//     ) -> Filter<Iter<'a, BitmaskItem<B, T>>, impl FnMut(&'a &'a BitmaskItem<B, T>) -> bool> {
-//         self.the_mask = mask.clone();
-//
-//         self.inner.inner.filter(|f| f.matches_mask(mask))
-//     }
-//
-//     #[inline]
-//     fn next_inner(&mut self) -> Option<&'a BitmaskItem<B, T>> {
-//         if let Some(item) = self.inner.next() {
-//             return Some(&item);
-//         }
-//         None
-//     }
-// }
-//
-// impl<'a, B, T> Iterator for BitmaskVecIterFilter<'a, B, T>
-// where
-//     B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
-// {
-//     type Item = &'a BitmaskItem<B, T>;
-//
-//     fn next(&mut self) -> Option<Self::Item> {
-//         self.next_inner()
-//     }
-// }
+    /// Sets every bitmask to a clone of mask, leaving T untouched.
+    /// # Panics
+    /// Panics if mask has bits outside the schema set with set_schema().
+    pub fn fill_masks(&mut self, mask: B) {
+        self.assert_schema(&mask);
+        for x in self.inner.iter_mut() {
+            x.bitmask = mask.clone();
+        }
+    }
+
+    /// Sets every bitmask to zero, leaving T untouched.
+    #[inline]
+    pub fn clear_masks(&mut self) {
+        self.fill_masks(B::default());
+    }
+
+    /// Flips every bit of every element's bitmask <i>(!bitmask)</i>.
+    /// # Panics
+    /// Panics if an inverted bitmask has bits outside the schema set with set_schema(), in
+    /// which case the vec is left unchanged.
+    pub fn invert_masks(&mut self)
+    where
+        B: Copy + Not<Output = B>,
+    {
+        self.assert_new_masks(|x| Some(!x.bitmask));
+        for x in self.inner.iter_mut() {
+            x.bitmask = !x.bitmask;
+        }
+    }
+
+    /// Flips every bit of the bitmask of each element whose bitmask matches mask.
+    /// # Panics
+    /// Panics if an inverted bitmask has bits outside the schema set with set_schema(), in
+    /// which case the vec is left unchanged.
+    pub fn invert_masks_matching(&mut self, mask: &'a B)
+    where
+        B: Copy + Not<Output = B>,
+    {
+        self.assert_new_masks(|x| x.matches_mask(mask).then(|| !x.bitmask));
+        for x in self.inner.iter_mut() {
+            if x.matches_mask(mask) {
+                x.bitmask = !x.bitmask;
+            }
+        }
+    }
+
+    /// Returns the OR of every bitmask: the bits set on at least one element. Zero if empty.
+    pub fn mask_union(&self) -> B
+    where
+        B: Copy + BitOr<Output = B>,
+    {
+        self.inner
+            .iter()
+            .fold(B::default(), |acc, x| acc | x.bitmask)
+    }
+
+    /// Returns the AND of every bitmask: the bits set on every element. Zero if empty.
+    pub fn mask_intersection(&self) -> B
+    where
+        B: Copy + BitAnd<Output = B>,
+    {
+        let mut iter = self.inner.iter();
+        match iter.next() {
+            Some(first) => iter.fold(first.bitmask, |acc, x| acc & x.bitmask),
+            None => B::default(),
+        }
+    }
+
+    /// Returns mutable references to several elements at once, or None if any index is out of bounds
+    /// or repeated.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000000, 101);
+    ///
+    /// if let Some([a, b]) = v.get_disjoint_mut([0, 1]) {
+    ///     // move the flag from one entry to the other
+    ///     a.bitmask &= !0b00000001;
+    ///     b.bitmask |= 0b00000001;
+    /// }
+    /// assert_eq!(v.indices_matching(&0b00000001), vec![1]);
+    /// ```
+    #[inline]
+    pub fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Option<[&mut BitmaskItem<B, T>; N]> {
+        self.inner.get_disjoint_mut(indices).ok()
+    }
+
+    /// Returns an iterator over the elements whose bitmask matches mask, delivered in Vecs of
+    /// up to batch_size references. Only the last batch may be shorter. Panics if batch_size is 0.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// for i in 0..5 {
+    ///     v.push_with_mask(0b00000001, 100 + i);
+    ///     v.push_with_mask(0b00000000, 200 + i);
+    /// }
+    ///
+    /// let sizes: Vec<usize> = v.chunks_matching(&0b00000001, 2).map(|b| b.len()).collect();
+    /// assert_eq!(sizes, vec![2, 2, 1]);
+    /// ```
+    #[inline]
+    pub fn chunks_matching(
+        &'a self,
+        mask: &'a B,
+        batch_size: usize,
+    ) -> BitmaskVecChunksMatching<'a, B, T> {
+        assert!(batch_size != 0, "batch_size must be non-zero");
+        BitmaskVecChunksMatching::new(self.iter_matching(mask), batch_size)
+    }
+
+    /// Returns page page_index (zero based) of the elements whose bitmask matches mask, holding
+    /// up to page_size items, along with the total number of matching elements. Both are
+    /// gathered in a single pass. Panics if page_size is 0.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// for i in 0..10 {
+    ///     v.push_with_mask((i % 2) as u8, i);
+    /// }
+    ///
+    /// let (page, total) = v.iter_matching_page(&0b00000001, 1, 2);
+    /// assert_eq!(total, 5);
+    /// assert_eq!(page.iter().map(|x| x.item).collect::<Vec<_>>(), vec![5, 7]);
+    /// ```
+    pub fn iter_matching_page(
+        &'a self,
+        mask: &'a B,
+        page_index: usize,
+        page_size: usize,
+    ) -> (Vec<&'a BitmaskItem<B, T>>, usize) {
+        assert!(page_size != 0, "page_size must be non-zero");
+        let start = page_index.saturating_mul(page_size);
+        let end = start.saturating_add(page_size);
+        let mut page = Vec::with_capacity(page_size.min(self.inner.len()));
+        let mut total = 0;
+        for x in self.inner.iter() {
+            if x.matches_mask(mask) {
+                if total >= start && total < end {
+                    page.push(x);
+                }
+                total += 1;
+            }
+        }
+        (page, total)
+    }
+
+    /// Decomposes the vector into its raw parts: (pointer, length, capacity).<br>
+    /// The caller becomes responsible for the memory, which can be released by rebuilding the
+    /// vector with from_raw_parts(). Bit names and the schema are dropped, so the rebuilt vector
+    /// has neither and its bitmasks are no longer checked.
+    pub fn into_raw_parts(self) -> (*mut BitmaskItem<B, T>, usize, usize) {
+        let mut inner = ManuallyDrop::new(self.inner);
+        (inner.as_mut_ptr(), inner.len(), inner.capacity())
+    }
+
+    /// Creates a vector from the raw parts returned by into_raw_parts().
+    ///
+    /// # Safety
+    /// The same requirements as Vec::from_raw_parts apply: ptr, length and capacity must come from
+    /// a Vec of BitmaskItem (for example via into_raw_parts()), and ownership of the memory passes
+    /// to the returned vector.
+    pub unsafe fn from_raw_parts(
+        ptr: *mut BitmaskItem<B, T>,
+        length: usize,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            inner: unsafe { Vec::from_raw_parts(ptr, length, capacity) },
+            bit_names: Vec::new(),
+            schema: None,
+        }
+    }
+
+    /// Consumes and leaks the vector, returning a mutable slice of its contents that lives for
+    /// as long as B and T allow, up to 'static. Useful for lookup tables built once at startup.
+    #[inline]
+    pub fn leak<'b>(self) -> &'b mut [BitmaskItem<B, T>] {
+        self.inner.leak()
+    }
+
+    /// Returns the spare capacity of the vector as a slice of MaybeUninit BitmaskItems.<br>
+    /// Write BitmaskItems into it and then call set_len() to make them part of the vector.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::with_capacity(3);
+    /// let spare = v.spare_capacity_mut();
+    /// for (i, x) in spare.iter_mut().take(3).enumerate() {
+    ///     x.write(BitmaskItem::new(1 << i, 100 + i as i32));
+    /// }
+    /// unsafe { v.set_len(3) };
+    /// assert_eq!(v[2], 102);
+    /// ```
+    #[inline]
+    pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<BitmaskItem<B, T>>] {
+        self.inner.spare_capacity_mut()
+    }
+
+    /// Forces the length of the vector to new_len.
+    ///
+    /// # Safety
+    /// The same requirements as Vec::set_len apply: new_len must not exceed capacity(), and the
+    /// elements from the old length up to new_len must be initialized, typically through
+    /// spare_capacity_mut().
+    #[inline]
+    pub unsafe fn set_len(&mut self, new_len: usize) {
+        unsafe { self.inner.set_len(new_len) }
+    }
+
+    /// Returns T at index without bounds checking.
+    ///
+    /// # Safety
+    /// index must be less than len(). Calling this with an out of bounds index is undefined
+    /// behavior even if the returned reference is not used.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    ///
+    /// let mut total = 0;
+    /// for i in v.indices_matching(&0b00000010) {
+    ///     // indices_matching() only returns indices in bounds
+    ///     total += unsafe { *v.get_unchecked(i) };
+    /// }
+    /// assert_eq!(total, 101);
+    /// ```
+    #[inline]
+    pub unsafe fn get_unchecked(&self, index: usize) -> &T {
+        unsafe { &self.inner.get_unchecked(index).item }
+    }
+
+    /// Returns a mutable reference to T at index without bounds checking.
+    ///
+    /// # Safety
+    /// index must be less than len(). Calling this with an out of bounds index is undefined
+    /// behavior even if the returned reference is not used.
+    #[inline]
+    pub unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut T {
+        unsafe { &mut self.inner.get_unchecked_mut(index).item }
+    }
+
+    /// Returns the BitmaskItem at index, containing both T and bitmask, without bounds checking.
+    ///
+    /// # Safety
+    /// index must be less than len(). Calling this with an out of bounds index is undefined
+    /// behavior even if the returned reference is not used.
+    #[inline]
+    pub unsafe fn get_with_mask_unchecked(&self, index: usize) -> &BitmaskItem<B, T> {
+        unsafe { self.inner.get_unchecked(index) }
+    }
+
+    /// Returns the bit registered under name, if any.
+    pub fn bit_for(&self, name: &str) -> Option<usize> {
+        self.bit_names.iter().find(|x| x.1 == name).map(|x| x.0)
+    }
+
+    /// Builds a bitmask with the bits registered under names set.
+    /// Returns None if any name isn't registered.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.name_bit(0, "READ");
+    /// v.name_bit(1, "WRITE");
+    /// v.push_with_mask(0b00000011, 100);
+    /// v.push_with_mask(0b00000001, 101);
+    ///
+    /// let mask = v.mask_for(&["READ", "WRITE"]).unwrap();
+    /// assert_eq!(mask, 0b00000011);
+    /// assert_eq!(v.count_matching(&mask), 1);
+    /// assert!(v.mask_for(&["EXECUTE"]).is_none());
+    /// ```
+    pub fn mask_for(&self, names: &[&str]) -> Option<B> {
+        let mut mask = B::default();
+        for name in names {
+            mask.set_bit(self.bit_for(name)?, true);
+        }
+        Some(mask)
+    }
+
+    /// Reorders the vector so the element at index is where a sort by compare would put it,
+    /// with no element before it greater and no element after it less. Runs in O(n) on average.
+    /// Returns the elements before index, the element at index, and the elements after it.
+    /// Panics if index is out of bounds.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 50);
+    /// v.push_with_mask(0b00000001, 10);
+    /// v.push_with_mask(0b00000001, 40);
+    /// v.push_with_mask(0b00000001, 20);
+    ///
+    /// // the two smallest items, in no particular order
+    /// let (smallest, _, _) = v.select_nth_unstable_by(2, |a, b| a.item.cmp(&b.item));
+    /// let mut k: Vec<i32> = smallest.iter().map(|x| x.item).collect();
+    /// k.sort();
+    /// assert_eq!(k, vec![10, 20]);
+    /// ```
+    #[inline]
+    pub fn select_nth_unstable_by<F>(&mut self, index: usize, compare: F) -> SelectNth<'_, B, T>
+    where
+        F: FnMut(&BitmaskItem<B, T>, &BitmaskItem<B, T>) -> Ordering,
+    {
+        self.inner.select_nth_unstable_by(index, compare)
+    }
+
+    /// select_nth_unstable_by() ordering by bitmask.
+    #[inline]
+    pub fn select_nth_unstable_by_mask(&mut self, index: usize) -> SelectNth<'_, B, T>
+    where
+        B: Ord,
+    {
+        self.select_nth_unstable_by(index, |a, b| a.bitmask.cmp(&b.bitmask))
+    }
+
+    /// select_nth_unstable_by() ordering by T.
+    #[inline]
+    pub fn select_nth_unstable_by_item(&mut self, index: usize) -> SelectNth<'_, B, T>
+    where
+        T: Ord,
+    {
+        self.select_nth_unstable_by(index, |a, b| a.item.cmp(&b.item))
+    }
+
+    /// Returns the BitmaskItem with the smallest T among the elements whose bitmask matches mask,
+    /// or None if nothing matches. If several are equally small, the first is returned.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 30);
+    /// v.push_with_mask(0b00000000, 5);
+    /// v.push_with_mask(0b00000011, 10);
+    ///
+    /// assert_eq!(v.min_matching(&0b00000001).unwrap().item, 10);
+    /// assert_eq!(v.max_matching(&0b00000001).unwrap().item, 30);
+    /// assert!(v.min_matching(&0b10000000).is_none());
+    /// ```
+    pub fn min_matching(&self, mask: &'a B) -> Option<&BitmaskItem<B, T>>
+    where
+        T: Ord,
+    {
+        self.inner
+            .iter()
+            .filter(|x| x.matches_mask(mask))
+            .min_by(|a, b| a.item.cmp(&b.item))
+    }
+
+    /// Returns the BitmaskItem with the largest T among the elements whose bitmask matches mask,
+    /// or None if nothing matches. If several are equally large, the last is returned.
+    pub fn max_matching(&self, mask: &'a B) -> Option<&BitmaskItem<B, T>>
+    where
+        T: Ord,
+    {
+        self.inner
+            .iter()
+            .filter(|x| x.matches_mask(mask))
+            .max_by(|a, b| a.item.cmp(&b.item))
+    }
+
+    /// Returns the BitmaskItem whose T gives the smallest key among the elements whose bitmask
+    /// matches mask, or None if nothing matches. If several are equally small, the first is returned.
+    pub fn min_matching_by_key<K, F>(&self, mask: &'a B, mut f: F) -> Option<&BitmaskItem<B, T>>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.inner
+            .iter()
+            .filter(|x| x.matches_mask(mask))
+            .min_by_key(|x| f(&x.item))
+    }
+
+    /// Returns the BitmaskItem whose T gives the largest key among the elements whose bitmask
+    /// matches mask, or None if nothing matches. If several are equally large, the last is returned.
+    pub fn max_matching_by_key<K, F>(&self, mask: &'a B, mut f: F) -> Option<&BitmaskItem<B, T>>
+    where
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.inner
+            .iter()
+            .filter(|x| x.matches_mask(mask))
+            .max_by_key(|x| f(&x.item))
+    }
+
+    /// Folds every T whose bitmask matches mask into an accumulator, starting from init.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 3);
+    /// v.push_with_mask(0b00000000, 100);
+    /// v.push_with_mask(0b00000011, 4);
+    ///
+    /// assert_eq!(v.fold_matching(&0b00000001, 1, |acc, x| acc * x), 12);
+    /// assert_eq!(v.sum_matching(&0b00000001), 7);
+    /// ```
+    pub fn fold_matching<A, F>(&self, mask: &'a B, init: A, mut f: F) -> A
+    where
+        F: FnMut(A, &T) -> A,
+    {
+        let mut acc = init;
+        for x in self.inner.iter() {
+            if x.matches_mask(mask) {
+                acc = f(acc, &x.item);
+            }
+        }
+        acc
+    }
+
+    /// Returns the sum of every T whose bitmask matches mask, or T::default() if nothing matches.
+    pub fn sum_matching(&self, mask: &'a B) -> T
+    where
+        T: Copy + Default + Add<Output = T>,
+    {
+        let mut sum = T::default();
+        for x in self.inner.iter() {
+            if x.matches_mask(mask) {
+                sum = sum + x.item;
+            }
+        }
+        sum
+    }
+
+    /// Returns how many elements carry each distinct bitmask value, computed in one pass.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000001, 102);
+    ///
+    /// let counts = v.mask_value_counts();
+    /// assert_eq!(counts[&0b00000001], 2);
+    /// assert_eq!(counts[&0b00000010], 1);
+    /// ```
+    pub fn mask_value_counts(&self) -> HashMap<B, usize>
+    where
+        B: Eq + Hash,
+    {
+        self.stats_by_mask(|count: &mut usize, _| *count += 1)
+    }
+
+    /// Computes an aggregate per distinct bitmask value in one pass.
+    /// Each aggregate starts at A::default() and f folds every T with that bitmask into it.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000001, 102);
+    ///
+    /// let totals = v.stats_by_mask(|total: &mut i32, x| *total += x);
+    /// assert_eq!(totals[&0b00000001], 202);
+    /// ```
+    pub fn stats_by_mask<A, F>(&self, mut f: F) -> HashMap<B, A>
+    where
+        B: Eq + Hash,
+        A: Default,
+        F: FnMut(&mut A, &T),
+    {
+        let mut stats = HashMap::new();
+        for x in self.inner.iter() {
+            f(stats.entry(x.bitmask.clone()).or_default(), &x.item);
+        }
+        stats
+    }
+
+    /// Combines the elements of self and other pairwise with f into a new vec. The result is as
+    /// long as the shorter of the two.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut base = BitmaskVec::<u8, i32>::new();
+    /// base.push_with_mask(0b00000001, 100);
+    /// base.push_with_mask(0b00000010, 101);
+    /// let mut computed = BitmaskVec::<u8, i32>::new();
+    /// computed.push_with_mask(0b00000100, 1);
+    /// computed.push_with_mask(0b00000100, 2);
+    ///
+    /// let v = base.zip_with(&computed, |a, b| {
+    ///     BitmaskItem::new(a.bitmask | b.bitmask, a.item + b.item)
+    /// });
+    /// assert_eq!(v[1], 103);
+    /// assert_eq!(v.count_matching(&0b00000100), 2);
+    /// ```
+    pub fn zip_with<U, V, F>(&self, other: &BitmaskVec<B, U>, mut f: F) -> BitmaskVec<B, V>
+    where
+        F: FnMut(&BitmaskItem<B, T>, &BitmaskItem<B, U>) -> BitmaskItem<B, V>,
+    {
+        let mut v = BitmaskVec::new();
+        *v.as_mut_vec() = self
+            .inner
+            .iter()
+            .zip(other.as_slice().iter())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        v
+    }
+
+    /// Combines the elements of self and other pairwise into a new vec, ORing their bitmasks and
+    /// pairing their items. The result is as long as the shorter of the two.
+    pub fn zip<U>(&self, other: &BitmaskVec<B, U>) -> BitmaskVec<B, (T, U)>
+    where
+        B: Copy + BitOr<Output = B>,
+        T: Clone,
+        U: Clone,
+    {
+        self.zip_with(other, |a, b| {
+            BitmaskItem::new(a.bitmask | b.bitmask, (a.item.clone(), b.item.clone()))
+        })
+    }
+
+    /// Returns a BitmaskVecJoinMatching for iterating over the T of self and U of other at the
+    /// positions where the bitmasks of both match mask. The two vecs are expected to be index
+    /// aligned, such as two component columns of the same entities; iteration stops at the end
+    /// of the shorter one.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut positions = BitmaskVec::<u8, i32>::new();
+    /// positions.push_with_mask(0b00000001, 10);
+    /// positions.push_with_mask(0b00000001, 20);
+    /// positions.push_with_mask(0b00000000, 30);
+    /// let mut velocities = BitmaskVec::<u8, i32>::new();
+    /// velocities.push_with_mask(0b00000001, 1);
+    /// velocities.push_with_mask(0b00000000, 2);
+    /// velocities.push_with_mask(0b00000001, 3);
+    ///
+    /// let moved: Vec<_> = positions
+    ///     .join_matching(&velocities, &0b00000001)
+    ///     .map(|(p, v)| p + v)
+    ///     .collect();
+    /// assert_eq!(moved, vec![11]);
+    /// ```
+    #[inline]
+    pub fn join_matching<U>(
+        &'a self,
+        other: &'a BitmaskVec<B, U>,
+        mask: &'a B,
+    ) -> BitmaskVecJoinMatching<'a, B, T, U> {
+        BitmaskVecJoinMatching {
+            left: self.inner.iter(),
+            right: other.as_slice().iter(),
+            mask,
+        }
+    }
+
+    /// Returns a BitmaskVecJoinMatchingMut for iterating over mutable T of self and U of other
+    /// at the positions where the bitmasks of both match mask. Iteration stops at the end of the
+    /// shorter vec.
+    #[inline]
+    pub fn join_matching_mut<U>(
+        &'a mut self,
+        other: &'a mut BitmaskVec<B, U>,
+        mask: &'a B,
+    ) -> BitmaskVecJoinMatchingMut<'a, B, T, U> {
+        BitmaskVecJoinMatchingMut {
+            left: self.inner.iter_mut(),
+            right: other.as_mut_vec().iter_mut(),
+            mask,
+        }
+    }
+
+    /// Merges other into self by key. An entry of other whose key matches an existing entry has
+    /// its bitmask ORed into that entry, keeping the existing item. Other entries are appended.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut tags = BitmaskVec::<u8, &str>::new();
+    /// tags.push_with_mask(0b00000001, "a");
+    /// tags.push_with_mask(0b00000001, "b");
+    ///
+    /// let mut batch = BitmaskVec::<u8, &str>::new();
+    /// batch.push_with_mask(0b00000010, "b");
+    /// batch.push_with_mask(0b00000010, "c");
+    ///
+    /// tags.merge_by_key(batch, |x| *x);
+    /// assert_eq!(tags.len(), 3);
+    /// assert_eq!(tags.indices_matching(&0b00000011), vec![1]);
+    /// ```
+    pub fn merge_by_key<K, F>(&mut self, other: BitmaskVec<B, T>, key_fn: F)
+    where
+        B: Copy + BitOr<Output = B>,
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.merge_by_key_inner(other, false, key_fn);
+    }
+
+    /// Like merge_by_key(), but an entry of other whose key matches an existing entry also
+    /// replaces that entry's item.
+    pub fn merge_by_key_replace<K, F>(&mut self, other: BitmaskVec<B, T>, key_fn: F)
+    where
+        B: Copy + BitOr<Output = B>,
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.merge_by_key_inner(other, true, key_fn);
+    }
+
+    fn merge_by_key_inner<K, F>(
+        &mut self,
+        mut other: BitmaskVec<B, T>,
+        replace: bool,
+        mut key_fn: F,
+    ) where
+        B: Copy + BitOr<Output = B>,
+        K: Ord,
+        F: FnMut(&T) -> K,
+    {
+        self.assert_schema_items(&other.inner);
+        let mut keys = BTreeMap::new();
+        for (i, x) in self.inner.iter().enumerate() {
+            keys.entry(key_fn(&x.item)).or_insert(i);
+        }
+        for x in mem::take(other.as_mut_vec()) {
+            match keys.entry(key_fn(&x.item)) {
+                Entry::Occupied(e) => {
+                    let existing = &mut self.inner[*e.get()];
+                    existing.bitmask = existing.bitmask | x.bitmask;
+                    if replace {
+                        existing.item = x.item;
+                    }
+                }
+                Entry::Vacant(e) => {
+                    e.insert(self.inner.len());
+                    self.inner.push(x);
+                }
+            }
+        }
+    }
+
+    /// Returns the entries of self whose key is also present in other, in the order of self.
+    /// The bitmask of each is combine(mask in self, mask of the first entry of other with the same key),
+    /// e.g. `|a, b| a & b` for the flags set in both or `|a, b| a | b` for the flags set in either.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut before = BitmaskVec::<u8, &str>::new();
+    /// before.push_with_mask(0b00000011, "a");
+    /// before.push_with_mask(0b00000001, "b");
+    ///
+    /// let mut after = BitmaskVec::<u8, &str>::new();
+    /// after.push_with_mask(0b00000001, "c");
+    /// after.push_with_mask(0b00000110, "a");
+    ///
+    /// let both = before.intersect_by_key(&after, |x| *x, |a, b| a & b);
+    /// assert_eq!(both.len(), 1);
+    /// assert_eq!(both[0], "a");
+    /// assert_eq!(both.indices_matching(&0b00000010), vec![0]);
+    /// ```
+    pub fn intersect_by_key<K, F, C>(
+        &self,
+        other: &BitmaskVec<B, T>,
+        mut key_fn: F,
+        mut combine: C,
+    ) -> BitmaskVec<B, T>
+    where
+        B: Copy,
+        T: Clone,
+        K: Ord,
+        F: FnMut(&T) -> K,
+        C: FnMut(B, B) -> B,
+    {
+        let mut keys = BTreeMap::new();
+        for x in other.as_slice().iter() {
+            keys.entry(key_fn(&x.item)).or_insert(x.bitmask);
+        }
+        let mut v = BitmaskVec::new();
+        for x in self.inner.iter() {
+            if let Some(bitmask) = keys.get(&key_fn(&x.item)) {
+                v.push_with_mask(combine(x.bitmask, *bitmask), x.item.clone());
+            }
+        }
+        v
+    }
+
+    /// Consumes the vec and routes each entry into a BitmaskVec keyed by its exact bitmask,
+    /// preserving order within each bucket.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000001, 102);
+    ///
+    /// let buckets = v.split_by_mask_value();
+    /// assert_eq!(buckets.len(), 2);
+    /// assert_eq!(buckets[&0b00000001].len(), 2);
+    /// assert_eq!(buckets[&0b00000001][1], 102);
+    /// ```
+    pub fn split_by_mask_value(mut self) -> HashMap<B, BitmaskVec<B, T>>
+    where
+        B: Eq + Hash,
+    {
+        let mut buckets: HashMap<B, BitmaskVec<B, T>> = HashMap::new();
+        for x in mem::take(&mut self.inner) {
+            buckets
+                .entry(x.bitmask.clone())
+                .or_default()
+                .as_mut_vec()
+                .push(x);
+        }
+        buckets
+    }
+
+    /// Removes the elements whose bitmask matches mask and feeds them into target in order,
+    /// without collecting them into an intermediate Vec. Returns the number of elements moved.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// use std::collections::VecDeque;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000011, 102);
+    ///
+    /// let mut done = VecDeque::new();
+    /// assert_eq!(v.drain_matching_into(&0b00000001, &mut done), 2);
+    /// assert_eq!(v.len(), 1);
+    /// assert_eq!(done[1].item, 102);
+    /// ```
+    pub fn drain_matching_into<E>(&mut self, mask: &'a B, target: &mut E) -> usize
+    where
+        E: Extend<BitmaskItem<B, T>>,
+    {
+        let mut count = 0;
+        target.extend(
+            self.inner
+                .extract_if(.., |x| x.matches_mask(mask))
+                .inspect(|_| count += 1),
+        );
+        count
+    }
+
+    /// Collects references to the BitmaskItems whose bitmask matches mask into any collection.
+    /// The matches are counted first so the collection can be sized up front.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000011, 102);
+    ///
+    /// let x: Vec<&BitmaskItem<u8, i32>> = v.collect_matching(&0b00000001);
+    /// assert_eq!(x.len(), 2);
+    ///
+    /// let x: BTreeMap<i32, u8> = v
+    ///     .collect_matching::<Vec<_>>(&0b00000010)
+    ///     .into_iter()
+    ///     .map(|x| (x.item, x.bitmask))
+    ///     .collect();
+    /// assert_eq!(x[&102], 0b00000011);
+    /// ```
+    pub fn collect_matching<'s, C>(&'s self, mask: &'a B) -> C
+    where
+        C: FromIterator<&'s BitmaskItem<B, T>>,
+    {
+        let count = self.count_matching(mask);
+        ExactCount::new(self.inner.iter().filter(|x| x.matches_mask(mask)), count).collect()
+    }
+
+    /// Collects clones of the BitmaskItems whose bitmask matches mask into any collection.
+    pub fn collect_matching_cloned<C>(&self, mask: &'a B) -> C
+    where
+        T: Clone,
+        C: FromIterator<BitmaskItem<B, T>>,
+    {
+        let count = self.count_matching(mask);
+        ExactCount::new(
+            self.inner.iter().filter(|x| x.matches_mask(mask)).cloned(),
+            count,
+        )
+        .collect()
+    }
+
+    /// Consumes the vec and collects the BitmaskItems whose bitmask matches mask into any
+    /// collection. The other items are dropped.
+    pub fn into_matching<C>(mut self, mask: &'a B) -> C
+    where
+        C: FromIterator<BitmaskItem<B, T>>,
+    {
+        let count = self.count_matching(mask);
+        ExactCount::new(
+            mem::take(&mut self.inner)
+                .into_iter()
+                .filter(|x| x.matches_mask(mask)),
+            count,
+        )
+        .collect()
+    }
+
+    /// Builds a HashMap of clones of the BitmaskItems keyed by key_fn(T).
+    /// duplicates chooses which entry is kept when several share a key.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, (u32, &str)>::new();
+    /// v.push_with_mask(0b00000001, (7, "first"));
+    /// v.push_with_mask(0b00000010, (9, "other"));
+    /// v.push_with_mask(0b00000011, (7, "second"));
+    ///
+    /// let by_id = v.to_hashmap(|x| x.0, DuplicateKeys::KeepFirst);
+    /// assert_eq!(by_id[&7].item.1, "first");
+    /// let by_id = v.to_hashmap(|x| x.0, DuplicateKeys::KeepLast);
+    /// assert_eq!(by_id[&7].bitmask, 0b00000011);
+    /// ```
+    pub fn to_hashmap<K, F>(
+        &self,
+        mut key_fn: F,
+        duplicates: DuplicateKeys,
+    ) -> HashMap<K, BitmaskItem<B, T>>
+    where
+        T: Clone,
+        K: Eq + Hash,
+        F: FnMut(&T) -> K,
+    {
+        self.to_hashmap_with_mask(|x| key_fn(&x.item), duplicates)
+    }
+
+    /// Builds a HashMap of clones of the BitmaskItems keyed by key_fn(BitmaskItem), so the key can
+    /// depend on the bitmask as well.
+    /// duplicates chooses which entry is kept when several share a key.
+    pub fn to_hashmap_with_mask<K, F>(
+        &self,
+        mut key_fn: F,
+        duplicates: DuplicateKeys,
+    ) -> HashMap<K, BitmaskItem<B, T>>
+    where
+        T: Clone,
+        K: Eq + Hash,
+        F: FnMut(&BitmaskItem<B, T>) -> K,
+    {
+        let mut map = HashMap::with_capacity(self.inner.len());
+        for x in self.inner.iter() {
+            match duplicates {
+                DuplicateKeys::KeepFirst => {
+                    map.entry(key_fn(x)).or_insert_with(|| x.clone());
+                }
+                DuplicateKeys::KeepLast => {
+                    map.insert(key_fn(x), x.clone());
+                }
+            }
+        }
+        map
+    }
+
+    /// Removes consecutive elements whose T maps to the same key, keeping the first of each run.
+    #[inline]
+    pub fn dedup_by_key<K, F>(&mut self, key_fn: F)
+    where
+        K: PartialEq,
+        F: FnMut(&T) -> K,
+    {
+        self.dedup_by_key_with(key_fn, |_, _| {});
+    }
+
+    /// Removes consecutive elements whose T maps to the same key. For every removed element,
+    /// resolve(kept, removed) is called first so it can merge the two, e.g. OR the bitmasks, or
+    /// swap them to keep the later one.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, (u32, &str)>::new();
+    /// v.push_with_mask(0b00000001, (1, "a"));
+    /// v.push_with_mask(0b00000010, (1, "b"));
+    /// v.push_with_mask(0b00000100, (2, "c"));
+    ///
+    /// v.dedup_by_key_with(|x| x.0, |kept, removed| kept.bitmask |= removed.bitmask);
+    /// assert_eq!(v.len(), 2);
+    /// assert_eq!(v[0], (1, "a"));
+    /// assert_eq!(v.indices_matching(&0b00000011), vec![0]);
+    /// ```
+    pub fn dedup_by_key_with<K, F, R>(&mut self, mut key_fn: F, mut resolve: R)
+    where
+        K: PartialEq,
+        F: FnMut(&T) -> K,
+        R: FnMut(&mut BitmaskItem<B, T>, &mut BitmaskItem<B, T>),
+    {
+        self.inner.dedup_by(|removed, kept| {
+            if key_fn(&removed.item) == key_fn(&kept.item) {
+                resolve(kept, removed);
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Returns a BitmaskVecIterMatchingField for iterating over the BitmaskItems whose bit field
+    /// range holds value. See BitmaskItem::get_field().
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// const PRIORITY: core::ops::Range<usize> = 4..8;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b0011_0001, 100);
+    /// v.push_with_mask(0b0001_0001, 101);
+    /// v.push_with_mask(0b0011_0000, 102);
+    ///
+    /// let x: Vec<_> = v.iter_matching_field(PRIORITY, 3).map(|x| x.item).collect();
+    /// assert_eq!(x, vec![100, 102]);
+    /// ```
+    /// # Panics
+    /// Panics if range is wider than 64 bits.
+    #[inline]
+    pub fn iter_matching_field(
+        &self,
+        range: Range<usize>,
+        value: u64,
+    ) -> BitmaskVecIterMatchingField<'_, B, T> {
+        assert!(range.len() <= 64, "bit field is wider than 64 bits");
+        BitmaskVecIterMatchingField {
+            inner: self.inner.iter(),
+            range,
+            value,
+        }
+    }
+
+    /// Returns the generation counter stored in the top bits bits of the bitmask at index.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// const GEN_BITS: usize = 2;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.bump_generation(1, GEN_BITS);
+    /// v.bump_generation_all(GEN_BITS);
+    ///
+    /// assert_eq!(v.generation(0, GEN_BITS), 1);
+    /// assert_eq!(v.generation(1, GEN_BITS), 2);
+    /// assert_eq!(v.indices_with_generation(GEN_BITS, 2), vec![1]);
+    /// // the low bits are untouched
+    /// assert_eq!(v.count_matching(&0b00000010), 1);
+    /// ```
+    /// # Panics
+    /// Panics if index is out of bounds, or bits is wider than B or 64.
+    #[inline]
+    pub fn generation(&self, index: usize, bits: usize) -> u64
+    where
+        B: BitmaskBits,
+    {
+        self.inner[index].get_field(Self::generation_range(bits))
+    }
+
+    /// Sets the generation counter stored in the top bits bits of the bitmask at index.
+    /// # Panics
+    /// Panics if index is out of bounds, bits is wider than B or 64, generation doesn't fit in
+    /// bits, or set_mask() would panic for the new bitmask.
+    #[inline]
+    pub fn set_generation(&mut self, index: usize, bits: usize, generation: u64)
+    where
+        B: BitmaskBits,
+    {
+        let mut x = BitmaskItem::new(self.inner[index].bitmask, ());
+        x.set_field(Self::generation_range(bits), generation);
+        self.assert_set_mask(index, &x.bitmask);
+        self.inner[index].bitmask = x.bitmask;
+    }
+
+    /// Increments the generation counter of the element at index, wrapping to 0 after the
+    /// largest value bits bits can hold.
+    /// # Panics
+    /// Panics if index is out of bounds, bits is wider than B or 64, or set_mask() would panic
+    /// for the new bitmask.
+    #[inline]
+    pub fn bump_generation(&mut self, index: usize, bits: usize)
+    where
+        B: BitmaskBits,
+    {
+        let generation = self.generation(index, bits);
+        self.set_generation(index, bits, Self::next_generation(generation, bits));
+    }
+
+    /// Increments the generation counter of every element, wrapping to 0 after the largest
+    /// value bits bits can hold.
+    /// # Panics
+    /// Panics if bits is wider than B or 64, or a new bitmask has bits outside the schema set
+    /// with set_schema(), in which case the vec is left unchanged.
+    pub fn bump_generation_all(&mut self, bits: usize)
+    where
+        B: BitmaskBits,
+    {
+        let range = Self::generation_range(bits);
+        let bumped = |x: &BitmaskItem<B, T>| {
+            let mut y = BitmaskItem::new(x.bitmask, ());
+            y.set_field(
+                range.clone(),
+                Self::next_generation(x.get_field(range.clone()), bits),
+            );
+            y.bitmask
+        };
+        self.assert_new_masks(|x| Some(bumped(x)));
+        for i in 0..self.inner.len() {
+            self.inner[i].bitmask = bumped(&self.inner[i]);
+        }
+    }
+
+    /// Returns a BitmaskVecIterMatchingField for iterating over the BitmaskItems whose generation
+    /// counter, stored in the top bits bits, is generation.
+    #[inline]
+    pub fn iter_with_generation(
+        &self,
+        bits: usize,
+        generation: u64,
+    ) -> BitmaskVecIterMatchingField<'_, B, T>
+    where
+        B: BitmaskBits,
+    {
+        self.iter_matching_field(Self::generation_range(bits), generation)
+    }
+
+    /// Returns the indices of the elements whose generation counter, stored in the top bits bits, is generation.
+    pub fn indices_with_generation(&self, bits: usize, generation: u64) -> Vec<usize>
+    where
+        B: BitmaskBits,
+    {
+        let range = Self::generation_range(bits);
+        self.inner
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.get_field(range.clone()) == generation)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[inline]
+    fn generation_range(bits: usize) -> Range<usize>
+    where
+        B: BitmaskBits,
+    {
+        assert!(
+            bits <= B::BITS && bits <= 64,
+            "generation of {bits} bits doesn't fit in the bitmask"
+        );
+        B::BITS - bits..B::BITS
+    }
+
+    #[inline]
+    fn next_generation(generation: u64, bits: usize) -> u64 {
+        match bits {
+            64 => generation.wrapping_add(1),
+            _ => (generation + 1) & ((1 << bits) - 1),
+        }
+    }
+
+    /// Restricts the bits new bitmasks may have to those set in allowed.<br>
+    ///
+    /// These methods panic on a bitmask with other bits set, leaving the vec unchanged:
+    /// push_with_mask(), insert_with_mask(), insert_sorted_by_mask(), insert_sorted_by(),
+    /// replace_with_mask(), set_mask(), fill_masks(), or_mask_all(), xor_mask_all(),
+    /// invert_masks(), invert_masks_matching(), extend_with_mask(), extend_from_slice(),
+    /// append(), concat(), merge_by_key(), merge_by_key_replace(), set_generation(),
+    /// bump_generation(), bump_generation_all(), the growing resize methods, `+` and `+=`,
+    /// par_or_mask_all() and par_xor_mask_all() (rayon feature), and the mask writers of
+    /// begin(), cursor_mut() and apply_deferred(). try_push_with_mask(), try_insert_with_mask()
+    /// and try_set_mask() return Error::OutsideSchema instead, and apply() returns
+    /// PatchError::Rejected.<br>
+    ///
+    /// Bitmasks changed through mutable references, such as as_mut_slice(), iter_with_mask_mut()
+    /// or the slice methods reached through DerefMut, and elements already in the vec are not
+    /// checked; find_schema_violation() looks for those.
+    /// ```should_panic
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.set_schema(0b01111111); // bit 7 is reserved
+    /// v.push_with_mask(0b00000011, 100);
+    /// v.push_with_mask(0b10000000, 101); // panics
+    /// ```
+    pub fn set_schema(&mut self, allowed: B)
+    where
+        B: BitmaskBits,
+    {
+        self.schema = Some(MaskSchema {
+            allowed,
+            check: within_schema::<B>,
+        });
+    }
+
+    /// Removes the schema, allowing every bit again.
+    #[inline]
+    pub fn clear_schema(&mut self) {
+        self.schema = None;
+    }
+
+    /// Returns the bits allowed by the schema, or None if no schema is set.
+    #[inline]
+    pub fn schema(&self) -> Option<&B> {
+        self.schema.as_ref().map(|x| &x.allowed)
+    }
+
+    /// Returns true if bitmask only has bits allowed by the schema, or no schema is set.
+    #[inline]
+    pub fn allows_mask(&self, bitmask: &B) -> bool {
+        self.schema
+            .as_ref()
+            .is_none_or(|x| (x.check)(&x.allowed, bitmask))
+    }
+
+    /// Returns the index of the first element whose bitmask has bits outside the schema.
+    pub fn find_schema_violation(&self) -> Option<usize> {
+        self.inner
+            .iter()
+            .position(|x| !self.allows_mask(&x.bitmask))
+    }
+
+    /// Replaces the bitmask at index.
+    /// # Panics
+    /// Panics if index is out of bounds or bitmask has bits outside the schema set with set_schema().
+    #[inline]
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        self.assert_set_mask(index, &bitmask);
+        self.inner[index].bitmask = bitmask;
+    }
+
+    /// Replaces the bitmask at index, returning an Error instead of panicking if index is out
+    /// of bounds or bitmask has bits outside the schema.
+    pub fn try_set_mask(&mut self, index: usize, bitmask: B) -> Result<(), Error> {
+        self.check_set_mask(index, &bitmask)?;
+        self.inner[index].bitmask = bitmask;
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn assert_schema(&self, bitmask: &B) {
+        assert!(
+            self.allows_mask(bitmask),
+            "bitmask has bits outside the schema"
+        );
+    }
+
+    /// Checks every bitmask of items before any of them is added.
+    fn assert_schema_items(&self, items: &[BitmaskItem<B, T>]) {
+        if self.schema.is_some() {
+            for x in items {
+                self.assert_schema(&x.bitmask);
+            }
+        }
+    }
+
+    /// Returns the Error try_set_mask() would return for replacing the bitmask at index.
+    pub(crate) fn check_set_mask(&self, index: usize, bitmask: &B) -> Result<(), Error> {
+        self.check_index(index, self.inner.len())?;
+        if !self.allows_mask(bitmask) {
+            return Err(Error::OutsideSchema);
+        }
+        Ok(())
+    }
+
+    /// Panics with the Error try_set_mask() would return for replacing the bitmask at index.
+    #[inline]
+    pub(crate) fn assert_set_mask(&self, index: usize, bitmask: &B) {
+        if let Err(e) = self.check_set_mask(index, bitmask) {
+            panic!("{e}");
+        }
+    }
+
+    /// Checks the bitmask new returns for each element against the schema, before any element
+    /// is changed. Elements for which new returns None are skipped.
+    fn assert_new_masks<F>(&self, new: F)
+    where
+        F: Fn(&BitmaskItem<B, T>) -> Option<B>,
+    {
+        if self.schema.is_some() {
+            for x in self.inner.iter() {
+                if let Some(bitmask) = new(x) {
+                    self.assert_schema(&bitmask);
+                }
+            }
+        }
+    }
+
+    /// Moves every element whose bitmask matches from_mask to to_mask: the bits of from_mask
+    /// are cleared and the bits of to_mask are set <i>((bitmask & !from_mask) | to_mask)</i>,
+    /// leaving other bits unchanged. Returns the number of elements changed.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// const QUEUED: u8 = 0b00000001;
+    /// const RUNNING: u8 = 0b00000010;
+    /// const COMPLETED: u8 = 0b00000100;
+    /// const URGENT: u8 = 0b10000000;
+    ///
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(QUEUED, 100);
+    /// v.push_with_mask(QUEUED | URGENT, 101);
+    /// v.push_with_mask(COMPLETED, 102);
+    ///
+    /// assert_eq!(v.transition(&QUEUED, &RUNNING), 2);
+    /// assert_eq!(v.transition(&RUNNING, &COMPLETED), 2);
+    /// assert_eq!(v.count_matching(&COMPLETED), 3);
+    /// assert_eq!(v.as_slice()[1].bitmask, COMPLETED | URGENT);
+    /// ```
+    /// # Panics
+    /// Panics if to_mask has bits outside the schema set with set_schema().
+    pub fn transition(&mut self, from_mask: &'a B, to_mask: &B) -> usize
+    where
+        B: Copy + BitAnd<Output = B> + BitOr<Output = B> + Not<Output = B>,
+    {
+        self.assert_schema(to_mask);
+        let mut count = 0;
+        for x in self.inner.iter_mut() {
+            if x.bitmask.matches_mask(from_mask) {
+                x.bitmask = (x.bitmask & !*from_mask) | *to_mask;
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+// methods matching against a mask they build themselves, which lives shorter than 'a
+impl<B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + for<'m> CjMatchesMask<'m, B> + Clone + Default,
+{
+    /// Returns an iterator over the BitmaskItems whose bitmask has the bit registered under name set.
+    /// Returns None if name isn't registered.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.name_bit(1, "RUNNING");
+    /// v.push_with_mask(0b00000010, 100);
+    /// v.push_with_mask(0b00000001, 101);
+    /// v.push_with_mask(0b00000011, 102);
+    ///
+    /// let running: Vec<i32> = v.iter_matching_named("RUNNING").unwrap().map(|x| x.item).collect();
+    /// assert_eq!(running, vec![100, 102]);
+    /// ```
+    pub fn iter_matching_named(
+        &self,
+        name: &str,
+    ) -> Option<impl Iterator<Item = &BitmaskItem<B, T>>> {
+        let mask = self.mask_for(&[name])?;
+        Some(
+            self.inner
+                .iter()
+                .filter(move |x| x.bitmask.matches_mask(&mask)),
+        )
+    }
+}
+
+impl<'a, B, T> Default for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B, T> BitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    /// Returns the backing Vec, for containers built on top of BitmaskVec.
+    #[inline]
+    pub(crate) fn as_mut_vec(&mut self) -> &mut Vec<BitmaskItem<B, T>> {
+        &mut self.inner
+    }
+}
+
+impl<B, T> Deref for BitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    type Target = [BitmaskItem<B, T>];
+
+    /// Exposes the slice methods of the underlying BitmaskItems.
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<B, T> DerefMut for BitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<B, T> AsRef<[BitmaskItem<B, T>]> for BitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn as_ref(&self) -> &[BitmaskItem<B, T>] {
+        &self.inner
+    }
+}
+
+impl<B, T> AsMut<[BitmaskItem<B, T>]> for BitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn as_mut(&mut self) -> &mut [BitmaskItem<B, T>] {
+        &mut self.inner
+    }
+}
+
+impl<'a, B, T> Index<usize> for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<'a, B, T> IndexMut<usize> for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index].item
+    }
+}
+
+impl<'a, B, T> AddAssign<(B, T)> for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: (B, T)) {
+        self.push_with_mask(rhs.0, rhs.1);
+    }
+}
+
+impl<'a, B, T> AddAssign<T> for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: T) {
+        self.push(rhs);
+    }
+}
+
+impl<'a, B, T> Add for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    type Output = Self;
+
+    /// Appends the items of rhs to self.
+    #[inline]
+    fn add(mut self, mut rhs: Self) -> Self {
+        self.append(&mut rhs);
+        self
+    }
+}
+
+impl<'a, B, T> AddAssign for BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: Self) {
+        self.assert_schema_items(&rhs.inner);
+        self.inner.extend(rhs.inner);
+    }
+}
+
+impl<B, T> Clone for BitmaskVec<B, T>
+where
+    B: Bitflag + Clone,
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            bit_names: self.bit_names.clone(),
+            schema: self.schema.clone(),
+        }
+    }
+
+    /// Overwrites self with a copy of source, reusing the existing allocations.
+    fn clone_from(&mut self, source: &Self) {
+        self.inner.clone_from(&source.inner);
+        self.bit_names.clone_from(&source.bit_names);
+        self.schema.clone_from(&source.schema);
+    }
+}
+
+impl<B, T> PartialEq for BitmaskVec<B, T>
+where
+    B: Bitflag + PartialEq,
+    T: PartialEq,
+{
+    /// Two vectors are equal when they hold equal bitmasks and T in the same order.
+    /// Registered bit names and the schema are not compared.
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<B, T> Eq for BitmaskVec<B, T>
+where
+    B: Bitflag + Eq,
+    T: Eq,
+{
+}
+
+impl<B, T> PartialEq<[T]> for BitmaskVec<B, T>
+where
+    B: Bitflag,
+    T: PartialEq,
+{
+    /// Compares T positionally, ignoring bitmasks.
+    fn eq(&self, other: &[T]) -> bool {
+        self.inner.len() == other.len() && self.inner.iter().zip(other).all(|(a, b)| a.item == *b)
+    }
+}
+
+impl<B, T> PartialEq<&[T]> for BitmaskVec<B, T>
+where
+    B: Bitflag,
+    T: PartialEq,
+{
+    /// Compares T positionally, ignoring bitmasks.
+    #[inline]
+    fn eq(&self, other: &&[T]) -> bool {
+        *self == **other
+    }
+}
+
+impl<B, T> PartialEq<Vec<T>> for BitmaskVec<B, T>
+where
+    B: Bitflag,
+    T: PartialEq,
+{
+    /// Compares T positionally, ignoring bitmasks.
+    #[inline]
+    fn eq(&self, other: &Vec<T>) -> bool {
+        *self == *other.as_slice()
+    }
+}
+
+impl<B, T> PartialEq<BitmaskVec<B, T>> for [T]
+where
+    B: Bitflag,
+    T: PartialEq,
+{
+    /// Compares T positionally, ignoring bitmasks.
+    #[inline]
+    fn eq(&self, other: &BitmaskVec<B, T>) -> bool {
+        *other == *self
+    }
+}
+
+impl<B, T> PartialEq<BitmaskVec<B, T>> for &[T]
+where
+    B: Bitflag,
+    T: PartialEq,
+{
+    /// Compares T positionally, ignoring bitmasks.
+    #[inline]
+    fn eq(&self, other: &BitmaskVec<B, T>) -> bool {
+        *other == **self
+    }
+}
+
+impl<B, T> PartialEq<BitmaskVec<B, T>> for Vec<T>
+where
+    B: Bitflag,
+    T: PartialEq,
+{
+    /// Compares T positionally, ignoring bitmasks.
+    #[inline]
+    fn eq(&self, other: &BitmaskVec<B, T>) -> bool {
+        *other == *self.as_slice()
+    }
+}
+
+impl<B, T> PartialOrd for BitmaskVec<B, T>
+where
+    B: Bitflag + PartialOrd,
+    T: PartialOrd,
+{
+    /// Compares lexicographically, element by element, using the ordering of BitmaskItem.
+    /// Registered bit names and the schema are not compared.
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.inner.partial_cmp(&other.inner)
+    }
+}
+
+impl<B, T> Ord for BitmaskVec<B, T>
+where
+    B: Bitflag + Ord,
+    T: Ord,
+{
+    /// Compares lexicographically, element by element, using the ordering of BitmaskItem.
+    /// Registered bit names and the schema are not compared.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner.cmp(&other.inner)
+    }
+}
+
+impl<B, T> Hash for BitmaskVec<B, T>
+where
+    B: Bitflag + Hash,
+    T: Hash,
+{
+    /// Hashes the bitmasks and T in order, consistent with PartialEq. Registered bit names and
+    /// the schema are not hashed.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state);
+    }
+}
+
+impl<B, T> Debug for BitmaskVec<B, T>
+where
+    B: Bitflag + Binary,
+    T: Debug,
+{
+    /// Prints index, binary bitmask and item for each element.<br>
+    /// The alternate form `{:#?}` prints one element per line as a table.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000010, 101);
+    /// v.push_with_mask(0b00000011, 102);
+    ///
+    /// assert_eq!(format!("{:?}", v), "[0: 0b00000010 => 101, 1: 0b00000011 => 102]");
+    /// assert_eq!(
+    ///     format!("{:#?}", v),
+    ///     "index  mask      item\n0      00000010  101\n1      00000011  102\n"
+    /// );
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let bits = size_of::<B>() * 8;
+        if f.alternate() {
+            writeln!(f, "{:<6} {:<bits$}  item", "index", "mask")?;
+            for (i, x) in self.inner.iter().enumerate() {
+                writeln!(f, "{:<6} {:0bits$b}  {:?}", i, x.bitmask, x.item)?;
+            }
+            return Ok(());
+        }
+        f.write_str("[")?;
+        for (i, x) in self.inner.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(
+                f,
+                "{}: {:#0w$b} => {:?}",
+                i,
+                x.bitmask,
+                x.item,
+                w = bits + 2
+            )?;
+        }
+        f.write_str("]")
+    }
+}
+
+// =================================================================================================
+/// Number of bitmasks compared per pass of a filtered scan.
+const SCAN_CHUNK_LEN: usize = 64;
+
+/// Returns a bitmap with bit i set if items\[i\] matches mask. Every bitmask in the chunk is
+/// compared and the results are or'ed in without branching, so the loop can be unrolled.
+#[inline]
+fn scan_chunk<'a, B, T>(items: &[BitmaskItem<B, T>], mask: &'a B) -> u64
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    debug_assert!(items.len() <= SCAN_CHUNK_LEN);
+    let mut hits = 0u64;
+    for (i, x) in items.iter().enumerate() {
+        hits |= (x.bitmask.matches_mask(mask) as u64) << i;
+    }
+    hits
+}
+
+/// Bitmap of the matching positions at the front of a filtered scan. Bit 0 is the front of the
+/// remaining items and covered is the number of positions the bitmap holds results for.
+#[derive(Default)]
+struct ScanBitmap {
+    hits: u64,
+    covered: usize,
+}
+
+impl ScanBitmap {
+    /// Returns the offset in rest of the first item matching mask, scanning SCAN_CHUNK_LEN
+    /// bitmasks at a time as needed. The bitmap is moved forward to that item, so the caller
+    /// must advance past offset items to keep rest in step with it.
+    #[inline]
+    fn find<'a, B, T>(&mut self, rest: &[BitmaskItem<B, T>], mask: &'a B) -> Option<usize>
+    where
+        B: Bitflag + CjMatchesMask<'a, B>,
+    {
+        let mut offset = 0;
+        while self.hits == 0 {
+            offset += self.covered;
+            let chunk = &rest[offset..];
+            if chunk.is_empty() {
+                self.covered = 0;
+                return None;
+            }
+            self.covered = chunk.len().min(SCAN_CHUNK_LEN);
+            self.hits = scan_chunk(&chunk[..self.covered], mask);
+        }
+        let i = self.hits.trailing_zeros() as usize;
+        self.hits >>= i;
+        self.covered -= i;
+        Some(offset + i)
+    }
+
+    /// Moves the front of the bitmap forward by n positions.
+    #[inline]
+    fn advance(&mut self, n: usize) {
+        if n < self.covered {
+            self.hits >>= n;
+            self.covered -= n;
+        } else {
+            self.reset();
+        }
+    }
+
+    /// Discards the results, for when the mask changes.
+    #[inline]
+    fn reset(&mut self) {
+        self.hits = 0;
+        self.covered = 0;
+    }
+}
+
+/// Returns the number of items matching mask.
+#[inline]
+fn count_chunks<'a, B, T>(items: &[BitmaskItem<B, T>], mask: &'a B) -> usize
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    items
+        .chunks(SCAN_CHUNK_LEN)
+        .map(|x| scan_chunk(x, mask).count_ones() as usize)
+        .sum()
+}
+
+// =================================================================================================
+/// Iter that returns T (excludes bitmask)
+pub struct BitmaskVecIter<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecIter<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>) -> Self {
+        Self { inner: i }
+    }
+
+    #[inline]
+    fn next_inner(&mut self) -> Option<&'a T> {
+        if let Some(item) = self.inner.next() {
+            return Some(&item.item);
+        }
+        None
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIter<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_inner()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask.
+pub struct BitmaskVecIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+    scan: ScanBitmap,
+    scan_mask: Option<&'a B>,
+}
+
+impl<'a, B, T> BitmaskVecIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>) -> Self {
+        Self {
+            inner: i,
+            scan: ScanBitmap::default(),
+            scan_mask: None,
+        }
+    }
+    #[inline]
+    fn next_inner(&mut self) -> Option<&'a BitmaskItem<B, T>> {
+        self.scan.advance(1);
+        self.inner.next()
+    }
+
+    /// Returns the next item whose bitmask matches mask, skipping the ones before it.<br>
+    /// Bitmasks are compared ahead in chunks, and the results are kept for the next call with
+    /// the same mask reference.
+    pub fn filter_mask(&mut self, mask: &'a B) -> Option<&'a BitmaskItem<B, T>> {
+        let i = self.seek_mask(mask)?;
+        self.scan.advance(1);
+        self.inner.nth(i)
+    }
+
+    /// Returns the item the next filter_mask(mask) call would return, without consuming it.
+    /// Non-matching items before it are skipped.
+    pub fn peek_mask(&mut self, mask: &'a B) -> Option<&'a BitmaskItem<B, T>> {
+        let i = self.seek_mask(mask)?;
+        let rest = &self.inner.as_slice()[i..];
+        self.inner = rest.iter();
+        rest.first()
+    }
+
+    #[inline]
+    fn seek_mask(&mut self, mask: &'a B) -> Option<usize> {
+        if !self.scan_mask.is_some_and(|x| core::ptr::eq(x, mask)) {
+            self.scan.reset();
+            self.scan_mask = Some(mask);
+        }
+        self.scan.find(self.inner.as_slice(), mask)
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_inner()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns mutable T (excludes bitmask)
+pub struct BitmaskVecIterMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: IterMut<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecIterMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: IterMut<'a, BitmaskItem<B, T>>) -> Self {
+        Self { inner: i }
+    }
+
+    #[inline]
+    fn next_inner_mut(&mut self) -> Option<&'a mut T> {
+        if let Some(item) = self.inner.next() {
+            return Some(&mut item.item);
+        }
+        None
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIterMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_inner_mut()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns mutable BitmaskItem, containing both T and bitmask.
+pub struct BitmaskVecIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    inner: IterMut<'a, BitmaskItem<B, T>>,
+    scan: ScanBitmap,
+    scan_mask: Option<&'a B>,
+}
+
+impl<'a, B, T> BitmaskVecIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new(i: IterMut<'a, BitmaskItem<B, T>>) -> Self {
+        Self {
+            inner: i,
+            scan: ScanBitmap::default(),
+            scan_mask: None,
+        }
+    }
+    #[inline]
+    fn next_inner_mut(&mut self) -> Option<&'a mut BitmaskItem<B, T>> {
+        self.scan.advance(1);
+        self.inner.next()
+    }
+
+    /// Returns the next item whose bitmask matches mask, skipping the ones before it.<br>
+    /// Bitmasks are compared ahead in chunks, and the results are kept for the next call with
+    /// the same mask reference.
+    pub fn filter_mask(&mut self, mask: &'a B) -> Option<&'a mut BitmaskItem<B, T>> {
+        if !self.scan_mask.is_some_and(|x| core::ptr::eq(x, mask)) {
+            self.scan.reset();
+            self.scan_mask = Some(mask);
+        }
+        let i = self.scan.find(self.inner.as_slice(), mask)?;
+        self.scan.advance(1);
+        self.inner.nth(i)
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    type Item = &'a mut BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_inner_mut()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask, for items whose bitmask matches mask.
+pub struct BitmaskVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+    mask: &'a B,
+    scan: ScanBitmap,
+}
+
+impl<'a, B, T> BitmaskVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>, mask: &'a B) -> Self {
+        Self {
+            inner: i,
+            mask,
+            scan: ScanBitmap::default(),
+        }
+    }
+
+    /// Returns an iterator over every step-th matching item, starting with the first match.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// for i in 0..10 {
+    ///     v.push_with_mask((i % 2) as u8, i);
+    /// }
+    /// let x: Vec<_> = v.iter_matching(&1).step_by_matching(2).map(|x| x.item).collect();
+    /// assert_eq!(x, vec![1, 5, 9]);
+    /// ```
+    /// # Panics
+    /// Panics if step is 0.
+    #[inline]
+    pub fn step_by_matching(self, step: usize) -> StepBy<Self> {
+        self.step_by(step)
+    }
+
+    /// Returns the next matching item without consuming it. Non-matching items before it are skipped.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.push_with_mask(0b00000000, 100);
+    /// v.push_with_mask(0b00000001, 101);
+    ///
+    /// let mut iter = v.iter_matching(&0b00000001);
+    /// assert_eq!(iter.peek_matching().map(|x| x.item), Some(101));
+    /// assert_eq!(iter.next().map(|x| x.item), Some(101));
+    /// assert!(iter.peek_matching().is_none());
+    /// ```
+    pub fn peek_matching(&mut self) -> Option<&'a BitmaskItem<B, T>> {
+        let i = self.scan.find(self.inner.as_slice(), self.mask)?;
+        let rest = &self.inner.as_slice()[i..];
+        self.inner = rest.iter();
+        rest.first()
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.scan.find(self.inner.as_slice(), self.mask)?;
+        self.scan.advance(1);
+        self.inner.nth(i)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.inner.len()))
+    }
+
+    fn count(self) -> usize {
+        let rest = self.inner.as_slice();
+        let covered = self.scan.covered.min(rest.len());
+        self.scan.hits.count_ones() as usize + count_chunks(&rest[covered..], self.mask)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask, for items whose bit field equals a value.
+pub struct BitmaskVecIterMatchingField<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+    range: Range<usize>,
+    value: u64,
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIterMatchingField<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let range = &self.range;
+        let value = self.value;
+        self.inner
+            .by_ref()
+            .find(|x| x.get_field(range.clone()) == value)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns T and U from two index aligned vecs at the positions where both bitmasks match mask.
+pub struct BitmaskVecJoinMatching<'a, B, T, U>
+where
+    B: Bitflag,
+{
+    left: Iter<'a, BitmaskItem<B, T>>,
+    right: Iter<'a, BitmaskItem<B, U>>,
+    mask: &'a B,
+}
+
+impl<'a, B, T, U> Iterator for BitmaskVecJoinMatching<'a, B, T, U>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (&'a T, &'a U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let a = self.left.next()?;
+            let b = self.right.next()?;
+            if a.matches_mask(self.mask) && b.matches_mask(self.mask) {
+                return Some((&a.item, &b.item));
+            }
+        }
+    }
+}
+
+// =================================================================================================
+/// Iter that returns mutable T and U from two index aligned vecs at the positions where both bitmasks match mask.
+pub struct BitmaskVecJoinMatchingMut<'a, B, T, U>
+where
+    B: Bitflag,
+{
+    left: IterMut<'a, BitmaskItem<B, T>>,
+    right: IterMut<'a, BitmaskItem<B, U>>,
+    mask: &'a B,
+}
+
+impl<'a, B, T, U> Iterator for BitmaskVecJoinMatchingMut<'a, B, T, U>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (&'a mut T, &'a mut U);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let a = self.left.next()?;
+            let b = self.right.next()?;
+            if a.matches_mask(self.mask) && b.matches_mask(self.mask) {
+                return Some((&mut a.item, &mut b.item));
+            }
+        }
+    }
+}
+
+// =================================================================================================
+/// Iter that returns Vecs of up to batch_size BitmaskItems whose bitmask matches mask.
+pub struct BitmaskVecChunksMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: BitmaskVecIterMatching<'a, B, T>,
+    batch_size: usize,
+}
+
+impl<'a, B, T> BitmaskVecChunksMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: BitmaskVecIterMatching<'a, B, T>, batch_size: usize) -> Self {
+        Self {
+            inner: i,
+            batch_size,
+        }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecChunksMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = Vec<&'a BitmaskItem<B, T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch: Vec<_> = self.inner.by_ref().take(self.batch_size).collect();
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
+// =================================================================================================
+/// Iter adapter reporting a known number of remaining items, so collections can be sized up front.
+struct ExactCount<I> {
+    inner: I,
+    remaining: usize,
+}
+
+impl<I> ExactCount<I> {
+    #[inline]
+    fn new(inner: I, remaining: usize) -> Self {
+        Self { inner, remaining }
+    }
+}
+
+impl<I> Iterator for ExactCount<I>
+where
+    I: Iterator,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.inner.next()?;
+        self.remaining = self.remaining.saturating_sub(1);
+        Some(x)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+// pub struct BitmaskVecIterFilter<'a, B, T>
+// where
+//     B: Bitflag + CjMatchesMask<'a, B> + Default + Clone,
+//     Self: Sized,
+// {
+//     inner: BitmaskVecIterWithMask<'a, B, T>,
+//     the_mask: B,
+// }
+//
+// impl<'a, B, T> BitmaskVecIterFilter<'a, B, T>
+// where
+//     B: Bitflag + CjMatchesMask<'a, B> + Default + Clone,
+// {
+//     pub fn new(mut i: BitmaskVecIterWithMask<'a, B, T>) -> Self {
+//         Self {
+//             inner: i,
+//             the_mask: B::default(),
+//         }
+//     }
+//
+//     pub fn filter_X(
+//         mut self,
+//         mask: &'a B,
+//     ) -> Filter<Iter<'a, BitmaskItem<B, T>>, impl FnMut(&'a &'a BitmaskItem<B, T>) -> bool> {
+//         self.the_mask = mask.clone();
+//
+//         self.inner.inner.filter(|f| f.matches_mask(mask))
+//     }
+//
+//     #[inline]
+//     fn next_inner(&mut self) -> Option<&'a BitmaskItem<B, T>> {
+//         if let Some(item) = self.inner.next() {
+//             return Some(&item);
+//         }
+//         None
+//     }
+// }
+//
+// impl<'a, B, T> Iterator for BitmaskVecIterFilter<'a, B, T>
+// where
+//     B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+// {
+//     type Item = &'a BitmaskItem<B, T>;
+//
+//     fn next(&mut self) -> Option<Self::Item> {
+//         self.next_inner()
+//     }
+// }
+
+// pub trait BitmaskVecFilter<'a, B, T, F>
+// where
+//     B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Sized + 'a,
+//     T: Sized + 'a,
+//     F : FnMut(&'a &'a BitmaskItem<B, T>) -> bool,
+// {
+//     fn filter(
+//         &'a mut self,
+//         mask: B,
+//     ) -> Filter<Iter<'a, BitmaskItem<B, T>>, F>;
+//
+//
+// }
+//
+// impl <'a, B, T, F>BitmaskVecFilter<'a, B, T, F> for BitmaskVecIterWithMask<'a, B, T>
+// where
+// B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Sized + 'a,
+// T: Sized + 'a,
+// F : FnMut(&'a &'a BitmaskItem<B, T>) -> bool,
+// {
+//     fn filter(&'a mut self, mask: B) -> Filter<Iter<'a, BitmaskItem<B, T>>, F> {
+//         self.filter_x(mask)
+//     }
+// }
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::{BitmaskVec, PushError};
+    use crate::cj_error::Error;
+    use crate::prelude::BitmaskItem;
+    use cj_common::prelude::Bitflag;
+
+    #[test]
+    fn test_bitmask_vec() {
+        let _ = BitmaskVec::<u8, i32>::new();
+    }
+
+    #[test]
+    fn test_bitmask_vec_push() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push_with_mask(2, 0);
+
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn test_bitmask_vec_pop() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push_with_mask(2, 999);
+
+        let x = v.pop();
+
+        assert_eq!(x, Some(999));
+    }
+
+    #[test]
+    fn test_bitmask_vec_pop_with_mask() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push_with_mask(2, 999);
+
+        let x = v.pop_with_mask();
+        assert!(x.is_some());
+        let x = x.unwrap();
+        assert_eq!(x.bitmask, 0b00000010);
+        assert_eq!(x.item, 999);
+    }
+
+    #[test]
+    fn test_bitmask_vec_index() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push_with_mask(2, 999);
+
+        let x = v[1];
+        assert_eq!(x, 400);
+    }
+
+    #[test]
+    fn test_bitmask_vec_index_mut() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push_with_mask(2, 999);
+
+        v[1] = 800;
+        let x = v[1];
+        assert_eq!(x, 800);
+    }
+
+    #[test]
+    fn test_bitmask_vec_iter() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000110, 103);
+        v.push_with_mask(0b00000001, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        assert_eq!(v.iter().count(), 7);
+    }
+
+    #[test]
+    fn test_bitmask_vec_iter_2() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000110, 103);
+        v.push_with_mask(0b00000001, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut total = 0;
+        for x in v.iter() {
+            total += x;
+        }
+        assert_eq!(total, 721);
+    }
+
+    #[test]
+    fn test_bitmask_vec_iter_with_mask() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000110, 103);
+        v.push_with_mask(0b00000001, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        assert_eq!(v.iter_with_mask().count(), 7);
+    }
+
+    #[test]
+    fn test_bitmask_vec_with_mask_match() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000110, 103);
+        v.push_with_mask(0b00000001, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut count = 0;
+        let mut match_count = 0;
+        for x in v.iter_with_mask() {
+            count += 1;
+            if x.matches_mask(&0b00000010) {
+                match_count += 1;
+            }
+        }
+
+        assert_eq!(count, 7,);
+        assert_eq!(match_count, 3);
+    }
+
+    #[test]
+    fn test_bitmask_vec_with_mask_filter() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut count = 0;
+        let mut z = v.iter_with_mask();
+        while let Some(pair) = z.filter_mask(&0b00000010) {
+            assert!([101, 102, 104].contains(&pair.item));
+            count += 1;
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_bitmask_vec_iter_mut() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut total = 0;
+        let x = v.iter_mut();
+        for z in x {
+            total += *z;
+            *z *= 2;
+        }
+
+        let mut total_2 = 0;
+        let x = v.iter();
+        for z in x {
+            total_2 += *z;
+        }
+
+        assert_eq!(total_2, total * 2)
+    }
+
+    #[test]
+    fn test_bitmask_vec_iter_masked_mut() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut total = 0;
+        let x = v.iter_with_mask_mut();
+        for z in x {
+            total += z.item;
+            z.item *= 2;
+
+            z.bitmask.set_bit(7, true);
+        }
+
+        let mut total_2 = 0;
+        let x = v.iter_with_mask();
+        for z in x {
+            total_2 += z.item;
+            assert!(z.matches_mask(&0b10000000));
+        }
+
+        assert_eq!(total_2, total * 2)
+    }
+
+    #[test]
+    fn test_bitmask_vec_with_capacity() {
+        let v = BitmaskVec::<u8, i32>::with_capacity(10);
+
+        assert_eq!(10, v.capacity())
+    }
+
+    #[test]
+    fn test_bitmask_vec_append() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut v2 = BitmaskVec::<u8, i32>::new();
+        v2.push_with_mask(0b00000000, 100);
+        v2.push_with_mask(0b00000010, 101);
+        v2.push_with_mask(0b00000010, 102);
+        v2.push_with_mask(0b00000100, 103);
+        v2.push_with_mask(0b00000011, 104);
+        v2.push_with_mask(0b00000001, 105);
+        v2.push_with_mask(0b00000000, 106);
+
+        v.append(&mut v2);
+
+        assert_eq!(v.len(), 14);
+        assert_eq!(v2.len(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_as_slice() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        assert_eq!(v.as_slice().len(), 7);
+    }
+
+    #[test]
+    fn test_bitmask_vec_as_mut_slice() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+        {
+            let s = v.as_mut_slice();
+            s[1].item = 500;
+            s[1].bitmask.set_bit(7, true);
+        }
+        assert_eq!(v[1], 500);
+        // hmmm.  TO.DO. maybe i should change index/indexmut to return BitmaskItem instead of just T...
+        assert_eq!(v.iter_with_mask().nth(1).unwrap().bitmask, 0b10000010);
+    }
+
+    #[test]
+    fn test_bitmask_vec_clear() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+        v.clear();
+        assert_eq!(v.len(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_drain() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let x: Vec<_> = v.drain(1..).collect();
+        assert_eq!(v.len(), 1);
+        assert_eq!(x.len(), 6);
+    }
+
+    #[test]
+    fn test_bitmask_vec_insert() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        v.insert(2, 500);
+        v.insert_with_mask(3, 0b11000000, 600);
+        assert_eq!(v.len(), 9);
+    }
+
+    #[test]
+    fn test_bitmask_vec_reserve() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        v.reserve(10);
+        assert!(v.capacity() >= 17);
+    }
+
+    #[test]
+    fn test_bitmask_vec_resize() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        v.resize(16, 799);
+        assert_eq!(v.len(), 16);
+    }
+
+    #[test]
+    fn test_bitmask_vec_resize_with_mask() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        v.resize_with_mask(16, 3, 799);
+        assert_eq!(v.len(), 16);
+    }
+
+    #[test]
+    fn test_bitmask_vec_resize_with_bitmask_item() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        v.resize_with_bitmask_item(16, BitmaskItem::new(3, 799));
+        assert_eq!(v.len(), 16);
+    }
+
+    #[test]
+    fn test_bitmask_vec_resize_with() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let b = 0b00111111;
+        let mut i = 400;
+
+        v.resize_with(16, || {
+            i += 1;
+            BitmaskItem::new(b, i)
+        });
+        assert_eq!(v.len(), 16);
+    }
+
+    #[test]
+    fn test_bitmask_vec_extend_from_slice() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let mut v2 = BitmaskVec::<u8, i32>::new();
+        v2.extend_from_slice(v.as_slice());
+
+        assert_eq!(v2.len(), 7);
+    }
+
+    #[test]
+    fn test_bitmask_vec_into_boxed_slice() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let x = v.into_boxed_slice();
+
+        assert_eq!(x.len(), 7);
+    }
+
+    #[test]
+    fn test_bitmask_vec_add_assign() {
+        let mut v1 = BitmaskVec::<u8, i32>::new();
+        v1 += (0b00000000, 100);
+        v1 += (0b00000010, 101);
+        v1 += (0b00000010, 102);
+        v1 += (0b00000100, 103);
+        v1 += (0b00000011, 104);
+        v1 += (0b00000001, 105);
+        v1 += (0b00000000, 106);
+
+        assert_eq!(v1[2], 102);
+    }
+
+    #[test]
+    fn test_bitmask_vec_add_assign_2() {
+        let mut v1 = BitmaskVec::<u8, i32>::new();
+        v1 += 100;
+        v1 += 101;
+        v1 += 102;
+        v1 += 103;
+        v1 += 104;
+        v1 += 105;
+        v1 += 106;
+
+        assert_eq!(v1[2], 102);
+    }
+
+    #[test]
+    fn test_bitmask_vec_add_assign_3() {
+        let mut v1 = BitmaskVec::<u8, i32>::new();
+        v1 += (0b00000000, 100);
+        v1 += (0b00000010, 101);
+        v1 += (0b00000010, 102);
+        v1 += (0b00000100, 103);
+        v1 += (0b00000011, 104);
+        v1 += (0b00000001, 105);
+        v1 += (0b00000000, 106);
+
+        let mut v2 = BitmaskVec::<u8, i32>::new();
+        v2 += (0b00000000, 100);
+        v2 += (0b00000010, 101);
+        v2 += (0b00000010, 102);
+        v2 += (0b00000100, 103);
+        v2 += (0b00000011, 104);
+        v2 += (0b00000001, 105);
+        v2 += (0b00000000, 106);
+
+        v1 += v2;
+
+        assert_eq!(v1[9], 102);
+    }
+
+    #[test]
+    fn test_bitmask_vec_iter_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+        v.push_with_mask(0b00000001, 105);
+        v.push_with_mask(0b00000000, 106);
+
+        let x: Vec<_> = v.iter_matching(&0b00000010).map(|x| x.item).collect();
+        assert_eq!(x, vec![101, 102, 104]);
+        assert_eq!(v.iter_matching(&0b00000000).count(), 7);
+    }
+
+    #[test]
+    fn test_bitmask_vec_count_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        assert_eq!(v.count_matching(&0b00000010), 3);
+        assert_eq!(v.count_matching(&0b00000011), 1);
+        assert_eq!(v.count_matching(&0b10000000), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_indices_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        assert_eq!(v.indices_matching(&0b00000010), vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_or_mask_all() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+
+        v.or_mask_all(&0b10000010);
+        assert_eq!(v.count_matching(&0b10000010), 3);
+        assert_eq!(v.iter_with_mask().nth(2).unwrap().bitmask, 0b10000110);
+    }
+
+    #[test]
+    fn test_bitmask_vec_and_mask_all() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+
+        v.and_mask_all(&0b00000001);
+        let x: Vec<_> = v.iter_with_mask().map(|x| x.bitmask).collect();
+        assert_eq!(x, vec![0b00000001, 0, 0]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_xor_mask_all() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000000, 101);
+
+        v.xor_mask_all(&0b00000001);
+        let x: Vec<_> = v.iter_with_mask().map(|x| x.bitmask).collect();
+        assert_eq!(x, vec![0b00000010, 0b00000001]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_sort_by_mask() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000100, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000100, 102);
+        v.push_with_mask(0b00000010, 103);
+
+        v.sort_by_mask();
+        let x: Vec<_> = v.iter().copied().collect();
+        assert_eq!(x, vec![101, 103, 100, 102]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_sort_by_item() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000010, 102);
+
+        v.sort_by_item();
+        let x: Vec<_> = v.iter_with_mask().map(|x| x.bitmask).collect();
+        assert_eq!(x, vec![0b00000001, 0b00000010, 0b00000100]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_is_sorted() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        assert!(v.is_sorted_by_mask());
+        assert!(v.is_sorted_by_item());
+
+        v.push_with_mask(0b00000100, 101);
+        v.push_with_mask(0b00000001, 102);
+        v.push_with_mask(0b00000001, 103);
+        assert!(!v.is_sorted_by_mask());
+        assert!(v.is_sorted_by_item());
+        // the slice method, through Deref
+        assert!(v.is_sorted_by(|a, b| a.item <= b.item));
+        assert!(!v.is_sorted_by(|a, b| a.item >= b.item));
+
+        v.sort_by_mask();
+        assert!(v.is_sorted_by_mask());
+        assert!(!v.is_sorted_by_item());
+    }
+
+    #[test]
+    fn test_bitmask_vec_bit_histogram() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        assert_eq!(v.bit_histogram(), vec![1, 3, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(BitmaskVec::<u32, i32>::new().bit_histogram().len(), 32);
+    }
+
+    #[test]
+    fn test_bitmask_vec_popcount_histogram() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b11111111, 103);
+
+        assert_eq!(v.popcount_histogram(), vec![1, 1, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(v.total_set_bits(), 11);
+    }
+
+    #[test]
+    fn test_bitmask_vec_name_bit() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.name_bit(1, "RUNNING");
+        v.name_bit(0, "QUEUED");
+        assert_eq!(v.bit_name(1), Some("RUNNING"));
+        assert_eq!(v.bit_name(2), None);
+
+        v.name_bit(1, "ACTIVE");
+        assert_eq!(v.bit_name(1), Some("ACTIVE"));
+    }
+
+    #[test]
+    fn test_bitmask_vec_describe() {
+        let mut v = BitmaskVec::<u16, i32>::new();
+        v.name_bit(0, "QUEUED");
+        v.name_bit(1, "RUNNING");
+        v.push_with_mask(0b0000000000000000, 100);
+        v.push_with_mask(0b0000000000000010, 101);
+        v.push_with_mask(0b1000000000000011, 102);
+
+        assert_eq!(v.describe(0).unwrap(), "0");
+        assert_eq!(v.describe(1).unwrap(), "RUNNING");
+        assert_eq!(v.describe(2).unwrap(), "QUEUED|RUNNING|bit15");
+        assert!(v.describe(3).is_none());
+        assert_eq!(v.describe_mask(&0b0000000000000101), "QUEUED|bit2");
+    }
+
+    #[test]
+    fn test_bitmask_vec_debug() {
+        let mut v = BitmaskVec::<u16, &str>::new();
+        assert_eq!(format!("{:?}", v), "[]");
+
+        v.push_with_mask(0b0000000000000001, "a");
+        v.push("b");
+        assert_eq!(
+            format!("{:?}", v),
+            "[0: 0b0000000000000001 => \"a\", 1: 0b0000000000000000 => \"b\"]"
+        );
+        assert_eq!(
+            format!("{:#?}", v),
+            "index  mask              item\n0      0000000000000001  \"a\"\n1      0000000000000000  \"b\"\n"
+        );
+    }
+
+    #[test]
+    fn test_bitmask_vec_eq() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        let mut v2 = BitmaskVec::<u8, i32>::new();
+        v2.name_bit(1, "RUNNING");
+        v2.push_with_mask(0b00000010, 101);
+        v2.push_with_mask(0b00000011, 102);
+        assert_eq!(v, v2);
+
+        v2.as_mut_slice()[1].bitmask = 0b00000001;
+        assert_ne!(v, v2);
+        v2.as_mut_slice()[1].bitmask = 0b00000011;
+        v2[0] = 100;
+        assert_ne!(v, v2);
+        v2.pop();
+        assert_ne!(v, v2);
+    }
 
-// pub trait BitmaskVecFilter<'a, B, T, F>
-// where
-//     B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Sized + 'a,
-//     T: Sized + 'a,
-//     F : FnMut(&'a &'a BitmaskItem<B, T>) -> bool,
-// {
-//     fn filter(
-//         &'a mut self,
-//         mask: B,
-//     ) -> Filter<Iter<'a, BitmaskItem<B, T>>, F>;
-//
-//
-// }
-//
-// impl <'a, B, T, F>BitmaskVecFilter<'a, B, T, F> for BitmaskVecIterWithMask<'a, B, T>
-// where
-// B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Sized + 'a,
-// T: Sized + 'a,
-// F : FnMut(&'a &'a BitmaskItem<B, T>) -> bool,
-// {
-//     fn filter(&'a mut self, mask: B) -> Filter<Iter<'a, BitmaskItem<B, T>>, F> {
-//         self.filter_x(mask)
-//     }
-// }
+    #[test]
+    fn test_bitmask_vec_hash() {
+        use std::collections::HashSet;
 
-#[cfg(test)]
-mod test {
-    use crate::cj_bitmask_vec::BitmaskVec;
-    use crate::prelude::BitmaskItem;
-    use cj_common::prelude::Bitflag;
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+        let mut v2 = BitmaskVec::<u8, i32>::new();
+        v2.push_with_mask(0b00000010, 101);
+        v2.push_with_mask(0b00000011, 102);
+        let mut v3 = BitmaskVec::<u8, i32>::new();
+        v3.push_with_mask(0b00000011, 101);
+        v3.push_with_mask(0b00000011, 102);
+
+        let mut set = HashSet::new();
+        assert!(set.insert(v));
+        assert!(!set.insert(v2));
+        assert!(set.insert(v3));
+    }
 
     #[test]
-    fn test_bitmask_vec() {
-        let _ = BitmaskVec::<u8, i32>::new();
+    fn test_bitmask_vec_ord() {
+        use std::collections::BTreeMap;
+
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push_with_mask(0b00000001, 200);
+        let mut b = BitmaskVec::<u8, i32>::new();
+        b.push_with_mask(0b00000010, 100);
+        let mut c = BitmaskVec::<u8, i32>::new();
+        c.push_with_mask(0b00000001, 200);
+        c.push_with_mask(0b00000000, 0);
+
+        assert!(a < b);
+        assert!(a < c);
+        assert!(c < b);
+
+        let mut m = BTreeMap::new();
+        m.insert(b, "b");
+        m.insert(c, "c");
+        m.insert(a, "a");
+        assert_eq!(m.values().copied().collect::<Vec<_>>(), vec!["a", "c", "b"]);
     }
 
     #[test]
-    fn test_bitmask_vec_push() {
+    fn test_bitmask_vec_clone() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0, 100);
-        v.push_with_mask(1, 400);
-        v.push_with_mask(2, 0);
+        v.name_bit(1, "RUNNING");
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        let mut v2 = v.clone();
+        assert_eq!(v, v2);
+        assert_eq!(v2.describe(0).unwrap(), "RUNNING");
+        v2[0] = 5;
+        assert_eq!(v[0], 101);
+
+        let mut v3 = BitmaskVec::<u8, i32>::with_capacity(10);
+        v3.push(1);
+        v3.clone_from(&v);
+        assert_eq!(v3, v);
+        assert!(v3.capacity() >= 10);
+    }
 
-        assert_eq!(v.len(), 3);
+    #[test]
+    fn test_bitmask_vec_eq_items() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 1);
+        v.push_with_mask(0b00000011, 2);
+        v.push(3);
+
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(vec![1, 2, 3], v);
+        assert_eq!(v, [1, 2, 3][..]);
+        assert_eq!(v, &[1, 2, 3][..]);
+        assert_eq!(&[1, 2, 3][..], v);
+        assert_ne!(v, vec![1, 2]);
+        assert_ne!(v, vec![1, 2, 4]);
+        assert_ne!(vec![1, 2, 3, 4], v);
     }
 
     #[test]
-    fn test_bitmask_vec_pop() {
+    fn test_bitmask_vec_deref() {
+        fn total(x: &[BitmaskItem<u8, i32>]) -> i32 {
+            x.iter().map(|x| x.item).sum()
+        }
+
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0, 100);
-        v.push_with_mask(1, 400);
-        v.push_with_mask(2, 999);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000100, 103);
 
-        let x = v.pop();
+        assert_eq!(v.first().unwrap().item, 101);
+        assert_eq!(v.windows(2).count(), 2);
+        assert_eq!(v.iter_with_mask().position(|x| x.item == 103), Some(2));
+        assert_eq!(total(&v), 306);
+        assert_eq!(total(v.as_ref()), 306);
+
+        v.as_mut()[0].item = 1;
+        v.last_mut().unwrap().bitmask = 0;
+        v.reverse();
+        assert_eq!(v[0], 103);
+        assert_eq!(v[2], 1);
+        assert_eq!(v.count_matching(&0b00000100), 0);
+    }
 
-        assert_eq!(x, Some(999));
+    #[test]
+    fn test_bitmask_vec_extend_with_mask() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        v.extend_with_mask(0b00000010, vec![101, 102, 103]);
+        v.extend_with_mask(0b00000001, 104..106);
+
+        assert_eq!(v, vec![100, 101, 102, 103, 104, 105]);
+        assert_eq!(v.indices_matching(&0b00000010), vec![1, 2, 3]);
+        assert_eq!(v.indices_matching(&0b00000001), vec![4, 5]);
     }
 
     #[test]
-    fn test_bitmask_vec_pop_with_mask() {
+    fn test_bitmask_vec_from_elem_with_mask() {
+        let v = BitmaskVec::<u8, i32>::from_elem_with_mask(0b00000010, 7, 3);
+        assert_eq!(v, vec![7, 7, 7]);
+        assert_eq!(v.count_matching(&0b00000010), 3);
+
+        let v = BitmaskVec::<u8, i32>::from_elem_with_mask(0b00000010, 7, 0);
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn test_bitmask_vec_repeat() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0, 100);
-        v.push_with_mask(1, 400);
-        v.push_with_mask(2, 999);
+        v.push_with_mask(0b00000010, 1);
+        v.push_with_mask(0b00000001, 2);
 
-        let x = v.pop_with_mask();
-        assert!(x.is_some());
-        let x = x.unwrap();
-        assert_eq!(x.bitmask, 0b00000010);
-        assert_eq!(x.item, 999);
+        let r = v.repeat(3);
+        assert_eq!(r, vec![1, 2, 1, 2, 1, 2]);
+        assert_eq!(r.indices_matching(&0b00000001), vec![1, 3, 5]);
+        assert!(v.repeat(0).is_empty());
     }
 
     #[test]
-    fn test_bitmask_vec_index() {
+    #[should_panic(expected = "capacity overflow")]
+    fn test_bitmask_vec_repeat_overflow() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0, 100);
-        v.push_with_mask(1, 400);
-        v.push_with_mask(2, 999);
+        v.push(1);
+        v.push(2);
+        let _ = v.repeat(usize::MAX);
+    }
 
-        let x = v[1];
-        assert_eq!(x, 400);
+    #[test]
+    fn test_bitmask_vec_concat() {
+        let mut parts = Vec::new();
+        for i in 0..4 {
+            let mut v = BitmaskVec::<u8, i32>::new();
+            v.push_with_mask(1 << i, i);
+            v.push_with_mask(0, i * 10);
+            parts.push(v);
+        }
+        let v = BitmaskVec::concat(parts);
+        assert_eq!(v, vec![0, 0, 1, 10, 2, 20, 3, 30]);
+        assert_eq!(v.indices_matching(&0b00000100), vec![4]);
+        assert!(BitmaskVec::<u8, i32>::concat(Vec::new()).is_empty());
     }
 
     #[test]
-    fn test_bitmask_vec_index_mut() {
+    fn test_bitmask_vec_add() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push_with_mask(0b00000001, 1);
+        let mut b = BitmaskVec::<u8, i32>::new();
+        b.push_with_mask(0b00000010, 2);
+        b.push(3);
+
+        let v = a + b;
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(v.indices_matching(&0b00000010), vec![1]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_insert_sorted_by_mask() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0, 100);
-        v.push_with_mask(1, 400);
-        v.push_with_mask(2, 999);
+        assert_eq!(v.insert_sorted_by_mask(0b00000100, 100), 0);
+        assert_eq!(v.insert_sorted_by_mask(0b00000001, 101), 0);
+        assert_eq!(v.insert_sorted_by_mask(0b00000010, 102), 1);
+        assert_eq!(v.insert_sorted_by_mask(0b00000010, 103), 2);
+        assert_eq!(v.insert_sorted_by_mask(0b10000000, 104), 4);
 
-        v[1] = 800;
-        let x = v[1];
-        assert_eq!(x, 800);
+        assert_eq!(v, vec![101, 102, 103, 100, 104]);
     }
 
     #[test]
-    fn test_bitmask_vec_iter() {
+    fn test_bitmask_vec_insert_sorted_by() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        for x in [5, 1, 3, 9, 3] {
+            v.insert_sorted_by(0, x, |a, b| b.item.cmp(&a.item));
+        }
+        assert_eq!(v, vec![9, 5, 3, 3, 1]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_replace() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000110, 103);
-        v.push_with_mask(0b00000001, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000011, 102);
 
-        assert_eq!(v.iter().count(), 7);
+        assert_eq!(v.replace(0, 5), 101);
+        assert_eq!(v.indices_matching(&0b00000010), vec![0, 1]);
+
+        let x = v.replace_with_mask(1, 0b10000000, 6);
+        assert_eq!(x, BitmaskItem::new(0b00000011, 102));
+        assert_eq!(v, vec![5, 6]);
+        assert_eq!(v.indices_matching(&0b10000000), vec![1]);
     }
 
     #[test]
-    fn test_bitmask_vec_iter_2() {
+    fn test_bitmask_vec_take() {
+        let mut v = BitmaskVec::<u8, String>::new();
+        v.push_with_mask(0b00000010, "a".to_string());
+        v.push_with_mask(0b00000011, "b".to_string());
+
+        assert_eq!(v.take(0), "a");
+        assert_eq!(v[0], "");
+        assert_eq!(v.count_matching(&0b00000010), 1);
+
+        let x = v.take_with_mask(1);
+        assert_eq!((x.bitmask, x.item.as_str()), (0b00000011, "b"));
+        assert_eq!(v.count_matching(&0b00000001), 0);
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn test_bitmask_vec_fill() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000110, 103);
-        v.push_with_mask(0b00000001, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000100, 103);
 
-        let mut total = 0;
-        for x in v.iter() {
-            total += x;
-        }
-        assert_eq!(total, 721);
+        v.fill(0);
+        assert_eq!(v, vec![0, 0, 0]);
+        assert_eq!(v.indices_matching(&0b00000010), vec![0, 1]);
+
+        let mut n = 0;
+        v.fill_with(|| {
+            n += 1;
+            n
+        });
+        assert_eq!(v, vec![1, 2, 3]);
     }
 
     #[test]
-    fn test_bitmask_vec_iter_with_mask() {
+    fn test_bitmask_vec_fill_matching() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000110, 103);
-        v.push_with_mask(0b00000001, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000100, 103);
 
-        assert_eq!(v.iter_with_mask().count(), 7);
+        v.fill_matching(&0b00000010, 0);
+        assert_eq!(v, vec![0, 0, 103]);
+
+        let mut n = 10;
+        v.fill_with_matching(&0b00000001, || {
+            n += 1;
+            n
+        });
+        assert_eq!(v, vec![0, 11, 103]);
     }
 
     #[test]
-    fn test_bitmask_vec_with_mask_match() {
+    fn test_bitmask_vec_fill_masks() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000110, 103);
-        v.push_with_mask(0b00000001, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000011, 102);
+        v.push(103);
 
-        let mut count = 0;
-        let mut match_count = 0;
-        for x in v.iter_with_mask() {
-            count += 1;
-            if x.matches_mask(&0b00000010) {
-                match_count += 1;
-            }
-        }
+        v.fill_masks(0b10000000);
+        assert_eq!(v.count_matching(&0b10000000), 3);
+        assert_eq!(v.count_matching(&0b00000010), 0);
 
-        assert_eq!(count, 7,);
-        assert_eq!(match_count, 3);
+        v.clear_masks();
+        assert_eq!(v.count_matching(&0b10000000), 0);
+        assert_eq!(v, vec![101, 102, 103]);
     }
 
     #[test]
-    fn test_bitmask_vec_with_mask_filter() {
+    fn test_bitmask_vec_invert_masks() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b11111111, 103);
 
-        let mut count = 0;
-        let mut z = v.iter_with_mask();
-        while let Some(pair) = z.filter_mask(&0b00000010) {
-            assert!([101, 102, 104].contains(&pair.item));
-            count += 1;
-        }
-        assert_eq!(count, 3);
+        v.invert_masks();
+        let x: Vec<_> = v.as_slice().iter().map(|x| x.bitmask).collect();
+        assert_eq!(x, vec![0b11111101, 0b11111100, 0b00000000]);
+
+        v.invert_masks_matching(&0b11111100);
+        let x: Vec<_> = v.as_slice().iter().map(|x| x.bitmask).collect();
+        assert_eq!(x, vec![0b00000010, 0b00000011, 0b00000000]);
     }
 
     #[test]
-    fn test_bitmask_vec_iter_mut() {
+    fn test_bitmask_vec_mask_union_intersection() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
+        assert_eq!(v.mask_union(), 0);
+        assert_eq!(v.mask_intersection(), 0);
+
+        v.push_with_mask(0b00000110, 101);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b10000010, 103);
+
+        assert_eq!(v.mask_union(), 0b10000111);
+        assert_eq!(v.mask_intersection(), 0b00000010);
+    }
+
+    #[test]
+    fn test_bitmask_vec_get_disjoint_mut() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
         v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+
+        let [a, c] = v.get_disjoint_mut([0, 2]).unwrap();
+        core::mem::swap(&mut a.bitmask, &mut c.bitmask);
+        c.item += 100;
+
+        assert_eq!(v.indices_matching(&0b00000100), vec![0]);
+        assert_eq!(v[2], 202);
+        assert!(v.get_disjoint_mut([1, 1]).is_none());
+        assert!(v.get_disjoint_mut([0, 3]).is_none());
+    }
+
+    #[test]
+    fn test_bitmask_vec_windows() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
         v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
 
-        let mut total = 0;
-        let x = v.iter_mut();
-        for z in x {
-            total += *z;
-            *z *= 2;
-        }
+        let x: Vec<bool> = v
+            .windows(2)
+            .map(|w| w[0].bitmask & w[1].bitmask != 0)
+            .collect();
+        assert_eq!(x, vec![true, true]);
+        assert_eq!(v.windows(4).count(), 0);
+    }
 
-        let mut total_2 = 0;
-        let x = v.iter();
-        for z in x {
-            total_2 += *z;
+    #[test]
+    fn test_bitmask_vec_chunks() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+
+        let x: Vec<usize> = v.chunks(2).map(|c| c.len()).collect();
+        assert_eq!(x, vec![2, 1]);
+
+        for (i, c) in v.chunks_mut(2).enumerate() {
+            for x in c {
+                x.item += i as i32 * 10;
+            }
         }
+        assert_eq!(v[1], 101);
+        assert_eq!(v[2], 112);
+    }
 
-        assert_eq!(total_2, total * 2)
+    #[test]
+    fn test_bitmask_vec_chunks_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000000, 101);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000110, 103);
+
+        let x: Vec<Vec<i32>> = v
+            .chunks_matching(&0b00000010, 2)
+            .map(|b| b.iter().map(|x| x.item).collect())
+            .collect();
+        assert_eq!(x, vec![vec![100, 102], vec![103]]);
+        assert_eq!(v.chunks_matching(&0b10000000, 2).count(), 0);
     }
 
     #[test]
-    fn test_bitmask_vec_iter_masked_mut() {
+    fn test_bitmask_vec_iter_matching_page() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000000, 101);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000110, 103);
+        v.push_with_mask(0b00000010, 104);
+
+        let (page, total) = v.iter_matching_page(&0b00000010, 0, 3);
+        assert_eq!(total, 4);
+        assert_eq!(
+            page.iter().map(|x| x.item).collect::<Vec<_>>(),
+            vec![100, 102, 103]
+        );
+
+        let (page, total) = v.iter_matching_page(&0b00000010, 1, 3);
+        assert_eq!(total, 4);
+        assert_eq!(page.iter().map(|x| x.item).collect::<Vec<_>>(), vec![104]);
+
+        let (page, total) = v.iter_matching_page(&0b00000010, 2, 3);
+        assert_eq!(total, 4);
+        assert!(page.is_empty());
+    }
+
+    #[test]
+    fn test_bitmask_vec_raw_parts() {
+        let mut v = BitmaskVec::<u8, i32>::with_capacity(4);
+        v.push_with_mask(0b00000001, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
 
-        let mut total = 0;
-        let x = v.iter_with_mask_mut();
-        for z in x {
-            total += z.item;
-            z.item *= 2;
+        let (ptr, len, cap) = v.into_raw_parts();
+        assert_eq!(len, 2);
+        assert!(cap >= 4);
 
-            z.bitmask.set_bit(7, true);
-        }
+        let v = unsafe { BitmaskVec::<u8, i32>::from_raw_parts(ptr, len, cap) };
+        assert_eq!(v[1], 101);
+        assert_eq!(v.count_matching(&0b00000010), 1);
+    }
 
-        let mut total_2 = 0;
-        let x = v.iter_with_mask();
-        for z in x {
-            total_2 += z.item;
-            assert!(z.matches_mask(&0b10000000));
+    #[test]
+    fn test_bitmask_vec_leak() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        let table: &'static mut [BitmaskItem<u8, i32>] = v.leak();
+        table[0].item = 200;
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0], BitmaskItem::new(0b00000001, 200));
+    }
+
+    #[test]
+    fn test_bitmask_vec_spare_capacity_mut() {
+        let mut v = BitmaskVec::<u8, i32>::with_capacity(4);
+        v.push_with_mask(0b00000001, 100);
+
+        let spare = v.spare_capacity_mut();
+        assert!(spare.len() >= 3);
+        spare[0].write(BitmaskItem::new(0b00000010, 101));
+        spare[1].write(BitmaskItem::new(0b00000011, 102));
+        unsafe { v.set_len(3) };
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[2], 102);
+        assert_eq!(v.count_matching(&0b00000010), 2);
+    }
+
+    #[test]
+    fn test_bitmask_vec_get_unchecked() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        unsafe {
+            assert_eq!(*v.get_unchecked(1), 101);
+            assert_eq!(v.get_with_mask_unchecked(0).bitmask, 0b00000001);
+            *v.get_unchecked_mut(0) += 100;
         }
+        assert_eq!(v[0], 200);
+    }
+
+    #[test]
+    fn test_bitmask_vec_named_queries() {
+        let mut v = BitmaskVec::<u16, i32>::new();
+        v.name_bit(0, "READ");
+        v.name_bit(1, "WRITE");
+        v.name_bit(9, "ADMIN");
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b1000000010, 102);
+
+        assert_eq!(v.bit_for("ADMIN"), Some(9));
+        assert_eq!(v.bit_for("NOPE"), None);
+        assert_eq!(v.mask_for(&["READ", "ADMIN"]), Some(0b1000000001));
+        assert_eq!(v.mask_for(&[]), Some(0));
+        assert_eq!(v.mask_for(&["READ", "NOPE"]), None);
+
+        let x: Vec<i32> = v
+            .iter_matching_named("WRITE")
+            .unwrap()
+            .map(|x| x.item)
+            .collect();
+        assert_eq!(x, vec![100, 102]);
+        assert!(v.iter_matching_named("NOPE").is_none());
+    }
+
+    #[test]
+    fn test_bitmask_vec_select_nth_unstable() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00001000, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000100, 102);
+        v.push_with_mask(0b00000010, 103);
+
+        let (before, nth, after) = v.select_nth_unstable_by_mask(1);
+        assert_eq!(nth.bitmask, 0b00000010);
+        assert!(before.iter().all(|x| x.bitmask < 0b00000010));
+        assert!(after.iter().all(|x| x.bitmask > 0b00000010));
+
+        let (_, nth, _) = v.select_nth_unstable_by_item(3);
+        assert_eq!(nth.item, 103);
+
+        let (_, nth, _) = v.select_nth_unstable_by(0, |a, b| b.item.cmp(&a.item));
+        assert_eq!(nth.item, 103);
+        assert_eq!(v.len(), 4);
+    }
 
-        assert_eq!(total_2, total * 2)
+    #[test]
+    fn test_bitmask_vec_min_max_matching() {
+        let mut v = BitmaskVec::<u8, (i32, &str)>::new();
+        v.push_with_mask(0b00000010, (3, "c"));
+        v.push_with_mask(0b00000000, (0, "z"));
+        v.push_with_mask(0b00000011, (1, "d"));
+        v.push_with_mask(0b00000110, (1, "a"));
+
+        assert_eq!(v.min_matching(&0b00000010).unwrap().item, (1, "a"));
+        assert_eq!(v.max_matching(&0b00000010).unwrap().item, (3, "c"));
+        assert_eq!(
+            v.min_matching_by_key(&0b00000010, |x| x.0).unwrap().item,
+            (1, "d")
+        );
+        assert_eq!(
+            v.max_matching_by_key(&0b00000010, |x| x.1).unwrap().item,
+            (1, "d")
+        );
+        assert!(v.max_matching(&0b10000000).is_none());
+        assert!(v.min_matching_by_key(&0b10000000, |x| x.0).is_none());
     }
 
     #[test]
-    fn test_bitmask_vec_with_capacity() {
-        let v = BitmaskVec::<u8, i32>::with_capacity(10);
+    fn test_bitmask_vec_fold_sum_matching() {
+        let mut v = BitmaskVec::<u8, f64>::new();
+        v.push_with_mask(0b00000010, 1.5);
+        v.push_with_mask(0b00000000, 100.0);
+        v.push_with_mask(0b00000011, 2.5);
+
+        assert_eq!(v.sum_matching(&0b00000010), 4.0);
+        assert_eq!(v.sum_matching(&0b10000000), 0.0);
+        assert_eq!(
+            v.fold_matching(&0b00000010, 0.0f64, |acc, x| acc.max(*x)),
+            2.5
+        );
+        assert_eq!(v.fold_matching(&0b00000000, 0, |acc, _| acc + 1), 3);
+    }
 
-        assert_eq!(10, v.capacity())
+    #[test]
+    fn test_bitmask_vec_mask_value_counts() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000001, 102);
+        v.push(103);
+
+        let counts = v.mask_value_counts();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&0b00000001], 2);
+        assert_eq!(counts[&0b00000011], 1);
+        assert_eq!(counts[&0], 1);
+
+        let max = v.stats_by_mask(|m: &mut i32, x| *m = (*m).max(*x));
+        assert_eq!(max[&0b00000001], 102);
+        assert!(BitmaskVec::<u8, i32>::new().mask_value_counts().is_empty());
     }
 
     #[test]
-    fn test_bitmask_vec_append() {
+    fn test_bitmask_vec_step_by_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        for i in 0..10 {
+            v.push_with_mask(if i % 3 == 0 { 0b00000001 } else { 0 }, i);
+        }
+        let x: Vec<_> = v
+            .iter_matching(&0b00000001)
+            .step_by_matching(2)
+            .map(|x| x.item)
+            .collect();
+        assert_eq!(x, vec![0, 6]);
+        assert_eq!(v.iter_matching(&0b00000001).step_by_matching(1).count(), 4);
+        assert_eq!(v.iter_matching(&0b00000010).step_by_matching(3).count(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_peek_matching() {
         let mut v = BitmaskVec::<u8, i32>::new();
         v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000001, 101);
         v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000011, 103);
+
+        let mut iter = v.iter_matching(&0b00000001);
+        assert_eq!(iter.peek_matching().map(|x| x.item), Some(101));
+        assert_eq!(iter.peek_matching().map(|x| x.item), Some(101));
+        assert_eq!(iter.next().map(|x| x.item), Some(101));
+        assert_eq!(iter.peek_matching().map(|x| x.item), Some(103));
+        assert_eq!(iter.next().map(|x| x.item), Some(103));
+        assert!(iter.peek_matching().is_none());
+
+        let mut iter = v.iter_with_mask();
+        assert_eq!(iter.peek_mask(&0b00000010).map(|x| x.item), Some(102));
+        assert_eq!(iter.next().map(|x| x.item), Some(102));
+        assert_eq!(iter.filter_mask(&0b00000010).map(|x| x.item), Some(103));
+        assert!(iter.peek_mask(&0b00000010).is_none());
+    }
 
-        let mut v2 = BitmaskVec::<u8, i32>::new();
-        v2.push_with_mask(0b00000000, 100);
-        v2.push_with_mask(0b00000010, 101);
-        v2.push_with_mask(0b00000010, 102);
-        v2.push_with_mask(0b00000100, 103);
-        v2.push_with_mask(0b00000011, 104);
-        v2.push_with_mask(0b00000001, 105);
-        v2.push_with_mask(0b00000000, 106);
+    #[test]
+    fn test_bitmask_vec_zip_with() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        a.push_with_mask(0b00000001, 100);
+        a.push_with_mask(0b00000010, 101);
+        a.push_with_mask(0b00000011, 102);
+        let mut b = BitmaskVec::<u8, &str>::new();
+        b.push_with_mask(0b10000000, "x");
+        b.push_with_mask(0b00000000, "y");
+
+        let v = a.zip_with(&b, |a, b| {
+            BitmaskItem::new(a.bitmask & b.bitmask, b.item.len())
+        });
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.count_matching(&0b00000001), 0);
+
+        let v = a.zip(&b);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0], (100, "x"));
+        assert_eq!(v.indices_matching(&0b10000001), vec![0]);
+        assert_eq!(v.indices_matching(&0b00000010), vec![1]);
+    }
 
-        v.append(&mut v2);
+    #[test]
+    fn test_bitmask_vec_merge_by_key() {
+        let mut v = BitmaskVec::<u8, (i32, &str)>::new();
+        v.push_with_mask(0b00000001, (1, "a"));
+        v.push_with_mask(0b00000001, (2, "b"));
+
+        let mut other = BitmaskVec::<u8, (i32, &str)>::new();
+        other.push_with_mask(0b00000010, (2, "B"));
+        other.push_with_mask(0b00000100, (3, "c"));
+        other.push_with_mask(0b00001000, (3, "C"));
+
+        let mut merged = v.clone();
+        merged.merge_by_key(other.clone(), |x| x.0);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[1], (2, "b"));
+        assert_eq!(merged.indices_matching(&0b00000011), vec![1]);
+        assert_eq!(merged[2], (3, "c"));
+        assert_eq!(merged.indices_matching(&0b00001100), vec![2]);
+
+        v.merge_by_key_replace(other, |x| x.0);
+        assert_eq!(v[1], (2, "B"));
+        assert_eq!(v[2], (3, "C"));
+        assert_eq!(v.indices_matching(&0b00000011), vec![1]);
+    }
 
-        assert_eq!(v.len(), 14);
-        assert_eq!(v2.len(), 0);
+    #[test]
+    fn test_bitmask_vec_intersect_by_key() {
+        let mut a = BitmaskVec::<u8, (i32, &str)>::new();
+        a.push_with_mask(0b00000011, (1, "a"));
+        a.push_with_mask(0b00000001, (2, "b"));
+        a.push_with_mask(0b00000100, (3, "c"));
+
+        let mut b = BitmaskVec::<u8, (i32, &str)>::new();
+        b.push_with_mask(0b00000010, (3, "C"));
+        b.push_with_mask(0b00000110, (1, "A"));
+        b.push_with_mask(0b10000000, (1, "A2"));
+
+        let x = a.intersect_by_key(&b, |x| x.0, |a, b| a & b);
+        assert_eq!(x.len(), 2);
+        assert_eq!(x[0], (1, "a"));
+        assert_eq!(x[1], (3, "c"));
+        assert_eq!(x.indices_matching(&0b00000010), vec![0]);
+        assert_eq!(x.count_matching(&0b00000100), 0);
+
+        let x = a.intersect_by_key(&b, |x| x.0, |a, b| a | b);
+        assert_eq!(x.indices_matching(&0b00000111), vec![0]);
+        assert_eq!(x.indices_matching(&0b00000110), vec![0, 1]);
+        assert!(a
+            .intersect_by_key(&BitmaskVec::new(), |x| x.0, |a, b| a | b)
+            .is_empty());
     }
 
     #[test]
-    fn test_bitmask_vec_as_slice() {
+    fn test_bitmask_vec_split_by_mask_value() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000001, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000001, 102);
+        v.push(103);
+        v.push_with_mask(0b00000001, 104);
 
-        assert_eq!(v.as_slice().len(), 7);
+        let buckets = v.split_by_mask_value();
+        assert_eq!(buckets.len(), 3);
+        let x: Vec<_> = buckets[&0b00000001]
+            .as_slice()
+            .iter()
+            .map(|x| x.item)
+            .collect();
+        assert_eq!(x, vec![100, 102, 104]);
+        assert_eq!(buckets[&0].len(), 1);
+        assert_eq!(buckets[&0b00000010].count_matching(&0b00000010), 1);
+        assert_eq!(buckets[&0b00000010][0], 101);
+        assert!(BitmaskVec::<u8, i32>::new()
+            .split_by_mask_value()
+            .is_empty());
     }
 
     #[test]
-    fn test_bitmask_vec_as_mut_slice() {
+    fn test_bitmask_vec_drain_matching_into() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000001, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
-        {
-            let s = v.as_mut_slice();
-            s[1].item = 500;
-            s[1].bitmask.set_bit(7, true);
-        }
-        assert_eq!(v[1], 500);
-        // hmmm.  TO.DO. maybe i should change index/indexmut to return BitmaskItem instead of just T...
-        assert_eq!(v.iter_with_mask().nth(1).unwrap().bitmask, 0b10000010);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000000, 103);
+
+        let mut target = vec![BitmaskItem::new(0b10000000u8, 50)];
+        assert_eq!(v.drain_matching_into(&0b00000010, &mut target), 2);
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0], 100);
+        assert_eq!(v[1], 103);
+        assert_eq!(target.len(), 3);
+        assert_eq!(target[1].item, 101);
+        assert_eq!(target[2].item, 102);
+
+        let mut items = std::collections::VecDeque::new();
+        assert_eq!(v.drain_matching_into(&0b10000000, &mut items), 0);
+        assert_eq!(v.drain_matching_into(&0, &mut items), 2);
+        assert!(v.is_empty());
+        assert_eq!(items.len(), 2);
     }
 
     #[test]
-    fn test_bitmask_vec_clear() {
+    fn test_bitmask_vec_collect_matching() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000001, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
-        v.clear();
-        assert_eq!(v.len(), 0);
+        v.push_with_mask(0b00000011, 102);
+        v.push_with_mask(0b00000001, 100);
+
+        let x: Vec<&BitmaskItem<u8, i32>> = v.collect_matching(&0b00000001);
+        assert_eq!(x.len(), 3);
+        assert_eq!(x[1].item, 102);
+
+        let x: std::collections::HashSet<BitmaskItem<u8, i32>> =
+            v.collect_matching_cloned(&0b00000001);
+        assert_eq!(x.len(), 2);
+        assert!(x.contains(&BitmaskItem::new(0b00000011, 102)));
+
+        let x: Vec<BitmaskItem<u8, i32>> = v.into_matching(&0b00000010);
+        assert_eq!(x.len(), 2);
+        assert_eq!(x[0].item, 101);
     }
 
     #[test]
-    fn test_bitmask_vec_drain() {
-        let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
-
-        let x: Vec<_> = v.drain(1..).collect();
-        assert_eq!(v.len(), 1);
-        assert_eq!(x.len(), 6);
+    fn test_bitmask_vec_to_hashmap() {
+        use crate::cj_bitmask_vec::DuplicateKeys;
+
+        let mut v = BitmaskVec::<u8, (u32, &str)>::new();
+        v.push_with_mask(0b00000001, (1, "a"));
+        v.push_with_mask(0b00000010, (2, "b"));
+        v.push_with_mask(0b00000100, (1, "c"));
+
+        let m = v.to_hashmap(|x| x.0, DuplicateKeys::KeepFirst);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m[&1], BitmaskItem::new(0b00000001, (1, "a")));
+        let m = v.to_hashmap(|x| x.0, DuplicateKeys::KeepLast);
+        assert_eq!(m[&1], BitmaskItem::new(0b00000100, (1, "c")));
+        assert_eq!(m[&2].item.1, "b");
+
+        let m = v.to_hashmap_with_mask(|x| (x.bitmask, x.item.0), DuplicateKeys::KeepFirst);
+        assert_eq!(m.len(), 3);
+        assert_eq!(m[&(0b00000100, 1)].item.1, "c");
     }
 
     #[test]
-    fn test_bitmask_vec_insert() {
-        let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+    fn test_bitmask_vec_dedup_by_key() {
+        let mut v = BitmaskVec::<u8, (u32, &str)>::new();
+        v.push_with_mask(0b00000001, (1, "a"));
+        v.push_with_mask(0b00000010, (1, "b"));
+        v.push_with_mask(0b00000100, (2, "c"));
+        v.push_with_mask(0b00001000, (1, "d"));
+        v.push_with_mask(0b00010000, (1, "e"));
+
+        let mut x = v.clone();
+        x.dedup_by_key(|x| x.0);
+        assert_eq!(x.len(), 3);
+        assert_eq!(x[2], (1, "d"));
+        assert_eq!(x.count_matching(&0b00000010), 0);
+
+        v.dedup_by_key_with(
+            |x| x.0,
+            |kept, removed| {
+                removed.bitmask |= kept.bitmask;
+                std::mem::swap(kept, removed);
+            },
+        );
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0], (1, "b"));
+        assert_eq!(v[2], (1, "e"));
+        assert_eq!(v.indices_matching(&0b00000011), vec![0]);
+        assert_eq!(v.indices_matching(&0b00011000), vec![2]);
+    }
 
-        v.insert(2, 500);
-        v.insert_with_mask(3, 0b11000000, 600);
-        assert_eq!(v.len(), 9);
+    #[test]
+    fn test_bitmask_vec_iter_matching_field() {
+        let mut v = BitmaskVec::<u16, i32>::new();
+        v.push_with_mask(0b0000_0010_0000_0001, 100);
+        v.push_with_mask(0b0000_0011_0000_0001, 101);
+        v.push_with_mask(0b0000_0010_0000_0000, 102);
+        v.push_with_mask(0, 103);
+
+        let x: Vec<_> = v.iter_matching_field(8..12, 2).map(|x| x.item).collect();
+        assert_eq!(x, vec![100, 102]);
+        assert_eq!(v.iter_matching_field(8..12, 0).count(), 1);
+        assert_eq!(v.iter_matching_field(8..12, 9).count(), 0);
+        assert_eq!(v.iter_matching_field(0..0, 0).count(), 4);
     }
 
     #[test]
-    fn test_bitmask_vec_reserve() {
+    fn test_bitmask_vec_generation() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000111, 102);
+
+        assert_eq!(v.generation(0, 3), 0);
+        v.set_generation(1, 3, 7);
+        assert_eq!(v.as_slice()[1].bitmask, 0b11100011);
+        v.bump_generation_all(3);
+        assert_eq!(v.generation(0, 3), 1);
+        assert_eq!(v.generation(1, 3), 0);
+        assert_eq!(v.as_slice()[1].bitmask, 0b00000011);
+
+        v.bump_generation(2, 3);
+        assert_eq!(v.indices_with_generation(3, 1), vec![0]);
+        assert_eq!(v.indices_with_generation(3, 2), vec![2]);
+        let x: Vec<_> = v.iter_with_generation(3, 0).map(|x| x.item).collect();
+        assert_eq!(x, vec![101]);
+        assert_eq!(v.count_matching(&0b00000111), 1);
+
+        let mut v = BitmaskVec::<u64, i32>::new();
+        v.push(100);
+        v.set_generation(0, 64, u64::MAX);
+        v.bump_generation_all(64);
+        assert_eq!(v.generation(0, 64), 0);
+    }
 
-        v.reserve(10);
-        assert!(v.capacity() >= 17);
+    #[test]
+    #[should_panic]
+    fn test_bitmask_vec_generation_too_wide() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        v.generation(0, 9);
     }
 
     #[test]
-    fn test_bitmask_vec_resize() {
+    fn test_bitmask_vec_schema() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b10000000, 100);
+        assert!(v.allows_mask(&0b11111111));
+        assert!(v.schema().is_none());
+
+        v.set_schema(0b00001111);
+        assert_eq!(v.schema(), Some(&0b00001111));
+        assert_eq!(v.find_schema_violation(), Some(0));
+        v.set_mask(0, 0b00000101);
+        assert_eq!(v.find_schema_violation(), None);
+        v.push_with_mask(0b00001111, 101);
+        v.insert_with_mask(0, 0b00000001, 99);
+        assert!(!v.allows_mask(&0b00010000));
+
+        let c = v.clone();
+        assert_eq!(c.schema(), Some(&0b00001111));
+        v.clear_schema();
+        v.push_with_mask(0b11110000, 102);
+        assert_eq!(v.len(), 4);
+    }
 
-        v.resize(16, 799);
-        assert_eq!(v.len(), 16);
+    #[test]
+    #[should_panic]
+    fn test_bitmask_vec_schema_push() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.set_schema(0b01111111);
+        v.push_with_mask(0b10000000, 100);
     }
 
     #[test]
-    fn test_bitmask_vec_resize_with_mask() {
+    #[should_panic]
+    fn test_bitmask_vec_schema_set_mask() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push(100);
+        v.set_schema(0b01111111);
+        v.set_mask(0, 0b11000000);
+    }
 
-        v.resize_with_mask(16, 3, 799);
-        assert_eq!(v.len(), 16);
+    #[test]
+    fn test_bitmask_vec_try_push_with_mask() {
+        let mut v = BitmaskVec::<u8, String>::new();
+        assert!(v.try_push_with_mask(0b10000000, "a".to_string()).is_ok());
+        v.set_schema(0b01111111);
+        let e = v
+            .try_push_with_mask(0b10000001, "b".to_string())
+            .unwrap_err();
+        assert_eq!(e, PushError::OutsideSchema("b".to_string()));
+        assert_eq!(e.into_value(), "b");
+        assert!(v.try_push_with_mask(0b00000001, "c".to_string()).is_ok());
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[1], "c");
     }
 
     #[test]
-    fn test_bitmask_vec_resize_with_bitmask_item() {
+    fn test_bitmask_vec_try_remove_insert_truncate() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000001, 100);
         v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+        v.push_with_mask(0b00000100, 102);
+
+        assert_eq!(
+            v.try_swap_remove(3),
+            Err(Error::IndexOutOfBounds { index: 3, len: 3 })
+        );
+        assert_eq!(v.try_swap_remove(0), Ok(100));
+        assert_eq!(v.as_slice()[0].bitmask, 0b00000100);
+
+        assert_eq!(
+            v.try_insert_with_mask(3, 0b00001000, 103),
+            Err(Error::IndexOutOfBounds { index: 3, len: 2 })
+        );
+        assert_eq!(v.try_insert_with_mask(2, 0b00001000, 103), Ok(()));
+        v.set_schema(0b00001111);
+        assert_eq!(
+            v.try_insert_with_mask(0, 0b00010000, 104),
+            Err(Error::OutsideSchema)
+        );
+        assert_eq!(v.try_remove(1), Ok(101));
+
+        assert_eq!(
+            v.try_truncate(3),
+            Err(Error::IndexOutOfBounds { index: 3, len: 2 })
+        );
+        assert_eq!(v.try_truncate(1), Ok(()));
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0], 102);
+    }
 
-        v.resize_with_bitmask_item(16, BitmaskItem::new(3, 799));
-        assert_eq!(v.len(), 16);
+    #[test]
+    fn test_bitmask_vec_chunked_scan() {
+        let mask = |i: usize| ((i * 7) % 5) as u8;
+        let mut v = BitmaskVec::<u8, usize>::new();
+        for i in 0..300 {
+            v.push_with_mask(mask(i), i);
+        }
+        let expected: Vec<usize> = (0..300).filter(|&i| mask(i) & 1 == 1).collect();
+
+        assert_eq!(v.indices_matching(&1), expected);
+        assert_eq!(v.count_matching(&1), expected.len());
+        let x: Vec<usize> = v.iter_matching(&1).map(|x| x.item).collect();
+        assert_eq!(x, expected);
+
+        let mut iter = v.iter_matching(&1);
+        iter.next();
+        assert_eq!(iter.peek_matching().map(|x| x.item), Some(expected[1]));
+        assert_eq!(iter.count(), expected.len() - 1);
+
+        // mixing filter_mask with next() and another mask keeps the scan in step
+        let mut iter = v.iter_with_mask();
+        let mut seen = Vec::new();
+        while let Some(x) = iter.filter_mask(&1) {
+            seen.push(x.item);
+            if x.item % 3 == 0 {
+                iter.next();
+            }
+            if x.item % 4 == 0 {
+                seen.extend(iter.peek_mask(&2).map(|y| y.item));
+                iter.next();
+            }
+        }
+        let mut naive = Vec::new();
+        let mut pos = 0;
+        while let Some(i) = (pos..300).find(|&i| mask(i) & 1 == 1) {
+            naive.push(i);
+            pos = i + 1;
+            if i % 3 == 0 {
+                pos += 1;
+            }
+            if i % 4 == 0 {
+                match (pos..300).find(|&j| mask(j) & 2 == 2) {
+                    Some(j) => {
+                        naive.push(j);
+                        pos = j + 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+        assert_eq!(seen, naive);
+
+        for x in v.iter_with_mask_mut() {
+            x.bitmask = 0;
+        }
+        let mut iter = v.iter_with_mask_mut();
+        iter.next().unwrap().bitmask = 1;
+        assert!(iter.filter_mask(&1).is_none());
     }
 
     #[test]
-    fn test_bitmask_vec_resize_with() {
-        let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
-        v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
+    fn test_bitmask_vec_join_matching() {
+        let mut a = BitmaskVec::<u8, i32>::new();
+        let mut b = BitmaskVec::<u8, &str>::new();
+        for i in 0..6 {
+            a.push_with_mask((i % 2) as u8, i);
+            b.push_with_mask((i % 3 != 0) as u8, "x");
+        }
+        a.push_with_mask(0b00000001, 100);
 
-        let b = 0b00111111;
-        let mut i = 400;
+        let x: Vec<_> = a.join_matching(&b, &1).map(|(t, _)| *t).collect();
+        assert_eq!(x, vec![1, 5]);
+        assert_eq!(a.join_matching(&b, &0).count(), 6);
 
-        v.resize_with(16, || {
-            i += 1;
-            BitmaskItem::new(b, i)
-        });
-        assert_eq!(v.len(), 16);
+        for (t, u) in a.join_matching_mut(&mut b, &1) {
+            *t += 10;
+            *u = "y";
+        }
+        assert_eq!(a[1], 11);
+        assert_eq!(a[3], 3);
+        assert_eq!(a[6], 100);
+        assert_eq!(b[5], "y");
+        assert_eq!(b[4], "x");
     }
 
     #[test]
-    fn test_bitmask_vec_extend_from_slice() {
+    fn test_bitmask_vec_transition() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
         v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
-
-        let mut v2 = BitmaskVec::<u8, i32>::new();
-        v2.extend_from_slice(v.as_slice());
+        v.push_with_mask(0b10000001, 103);
+
+        assert_eq!(v.transition(&0b00000001, &0b00000100), 3);
+        assert_eq!(
+            v.as_slice().iter().map(|x| x.bitmask).collect::<Vec<_>>(),
+            vec![0b00000100, 0b00000110, 0b00000010, 0b10000100]
+        );
+        assert_eq!(v.transition(&0b00000001, &0b00001000), 0);
+        assert_eq!(v.transition(&0b00000110, &0b00000000), 1);
+        assert_eq!(v[1], 101);
+        assert_eq!(v.as_slice()[1].bitmask, 0);
+    }
 
-        assert_eq!(v2.len(), 7);
+    #[test]
+    #[should_panic]
+    fn test_bitmask_vec_transition_schema() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.set_schema(0b00001111);
+        v.push_with_mask(0b00000001, 100);
+        v.transition(&0b00000001, &0b00010000);
     }
 
     #[test]
-    fn test_bitmask_vec_into_boxed_slice() {
+    fn test_bitmask_vec_next_matching_index() {
         let mut v = BitmaskVec::<u8, i32>::new();
-        v.push_with_mask(0b00000000, 100);
-        v.push_with_mask(0b00000010, 101);
+        assert_eq!(v.next_matching_index(&0b00000000, 0), None);
+        v.push_with_mask(0b00000011, 100);
+        v.push_with_mask(0b00000001, 101);
         v.push_with_mask(0b00000010, 102);
-        v.push_with_mask(0b00000100, 103);
-        v.push_with_mask(0b00000011, 104);
-        v.push_with_mask(0b00000001, 105);
-        v.push_with_mask(0b00000000, 106);
-
-        let x = v.into_boxed_slice();
+        v.push_with_mask(0b00000011, 103);
+
+        assert_eq!(v.next_matching_index(&0b00000010, 0), Some(2));
+        assert_eq!(v.next_matching_index(&0b00000010, 2), Some(3));
+        assert_eq!(v.next_matching_index(&0b00000010, 3), Some(0));
+        assert_eq!(v.next_matching_index(&0b00000001, 100), Some(0));
+        assert_eq!(v.next_matching_index(&0b00000001, usize::MAX), Some(0));
+        // the element at after is checked last
+        assert_eq!(v.next_matching_index(&0b00000011, 0), Some(3));
+        assert_eq!(v.next_matching_index(&0b00000011, 3), Some(0));
+        assert_eq!(v.next_matching_index(&0b00000100, 1), None);
+    }
 
-        assert_eq!(x.len(), 7);
+    #[test]
+    fn test_bitmask_vec_schema_writers() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.set_schema(0b00001111);
+        v.extend_with_mask(0b00000001, [100, 101]);
+        v.or_mask_all(&0b00000100);
+        v.resize_with_mask(3, 0b00000010, 102);
+        v.fill_masks(0b00001000);
+        v.set_generation(0, 2, 0);
+
+        let mut other = BitmaskVec::<u8, i32>::new();
+        other.push_with_mask(0b00000010, 103);
+        v.append(&mut other);
+        assert_eq!(v.len(), 4);
+        assert_eq!(v.find_schema_violation(), None);
     }
 
     #[test]
-    fn test_bitmask_vec_add_assign() {
-        let mut v1 = BitmaskVec::<u8, i32>::new();
-        v1 += (0b00000000, 100);
-        v1 += (0b00000010, 101);
-        v1 += (0b00000010, 102);
-        v1 += (0b00000100, 103);
-        v1 += (0b00000011, 104);
-        v1 += (0b00000001, 105);
-        v1 += (0b00000000, 106);
+    #[should_panic]
+    fn test_bitmask_vec_schema_or_mask_all() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+        v.set_schema(0b00001111);
+        v.or_mask_all(&0b00010000);
+    }
 
-        assert_eq!(v1[2], 102);
+    #[test]
+    #[should_panic]
+    fn test_bitmask_vec_schema_append() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.set_schema(0b00001111);
+        let mut other = BitmaskVec::<u8, i32>::new();
+        other.push_with_mask(0b10000000, 100);
+        v.append(&mut other);
     }
 
     #[test]
-    fn test_bitmask_vec_add_assign_2() {
-        let mut v1 = BitmaskVec::<u8, i32>::new();
-        v1 += 100;
-        v1 += 101;
-        v1 += 102;
-        v1 += 103;
-        v1 += 104;
-        v1 += 105;
-        v1 += 106;
+    fn test_bitmask_vec_schema_invert_masks() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.set_schema(0b00001111);
 
-        assert_eq!(v1[2], 102);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| v.invert_masks()));
+        assert!(r.is_err());
+        // nothing was inverted
+        assert_eq!(v.as_slice()[0].bitmask, 0b00000001);
+        assert_eq!(v.as_slice()[1].bitmask, 0b00000010);
     }
 
     #[test]
-    fn test_bitmask_vec_add_assign_3() {
-        let mut v1 = BitmaskVec::<u8, i32>::new();
-        v1 += (0b00000000, 100);
-        v1 += (0b00000010, 101);
-        v1 += (0b00000010, 102);
-        v1 += (0b00000100, 103);
-        v1 += (0b00000011, 104);
-        v1 += (0b00000001, 105);
-        v1 += (0b00000000, 106);
+    fn test_bitmask_vec_schema_mutable_access() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.set_schema(0b00001111);
 
-        let mut v2 = BitmaskVec::<u8, i32>::new();
-        v2 += (0b00000000, 100);
-        v2 += (0b00000010, 101);
-        v2 += (0b00000010, 102);
-        v2 += (0b00000100, 103);
-        v2 += (0b00000011, 104);
-        v2 += (0b00000001, 105);
-        v2 += (0b00000000, 106);
+        // slice methods that can't add bits stay available
+        v.reverse();
+        v.swap(0, 1);
+        v.sort_by_mask();
+        assert_eq!(v, vec![100, 101]);
 
-        v1 += v2;
+        // bitmasks written through mutable references are not checked
+        v.as_mut_slice()[1].bitmask = 0b10000000;
+        assert_eq!(v.find_schema_violation(), Some(1));
+    }
 
-        assert_eq!(v1[9], 102);
+    #[test]
+    #[should_panic]
+    fn test_bitmask_vec_name_bit_out_of_range() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.name_bit(8, "NOPE");
     }
 }
@@ -0,0 +1,595 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::collections::vec_deque::{Iter, IterMut, VecDeque};
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{AddAssign, Index, IndexMut};
+
+/// BitmaskVecDeque is a double-ended queue that pairs bitmasks with T.<br>
+///
+/// It is backed by a VecDeque, so pushing and popping at either end is O(1), and offers the
+/// same filtered iteration API as BitmaskVec.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVecDeque::<u8, i32>::new();
+/// v.push_back_with_mask(0b00000010, 101);
+/// v.push_back_with_mask(0b00000011, 102);
+/// v.push_front_with_mask(0b00000000, 100);
+///
+/// let x = v.pop_front_with_mask().unwrap();
+/// assert_eq!(x.item, 100);
+///
+/// let mut count = 0;
+/// let mut iter = v.iter_with_mask();
+/// while let Some(pair) = iter.filter_mask(&0b00000010) {
+///     assert!([101, 102].contains(&pair.item));
+///     count += 1;
+/// }
+/// assert_eq!(count, 2);
+/// ```
+pub struct BitmaskVecDeque<B, T>
+where
+    B: Bitflag,
+{
+    inner: VecDeque<BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecDeque<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            inner: VecDeque::new(),
+        }
+    }
+
+    /// Constructs a new, empty deque with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of elements the deque can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Clears the deque, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the deque contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Reserves capacity for at least additional more elements.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    /// Shortens the deque, keeping the first len elements and dropping the rest.
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.inner.truncate(len);
+    }
+
+    /// Pushes T and a default bitmask of zero to the back of the deque.
+    #[inline]
+    pub fn push_back(&mut self, value: T) {
+        self.push_back_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask to the back of the deque.
+    #[inline]
+    pub fn push_back_with_mask(&mut self, bitmask: B, value: T) {
+        self.inner.push_back(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pushes T and a default bitmask of zero to the front of the deque.
+    #[inline]
+    pub fn push_front(&mut self, value: T) {
+        self.push_front_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask to the front of the deque.
+    #[inline]
+    pub fn push_front_with_mask(&mut self, bitmask: B, value: T) {
+        self.inner.push_front(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pops T from the back of the deque without the bitmask.
+    #[inline]
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.inner.pop_back().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the back of the deque.
+    #[inline]
+    pub fn pop_back_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        self.inner.pop_back()
+    }
+
+    /// Pops T from the front of the deque without the bitmask.
+    #[inline]
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.inner.pop_front().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the front of the deque.
+    #[inline]
+    pub fn pop_front_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        self.inner.pop_front()
+    }
+
+    /// Returns the first BitmaskItem, or None if the deque is empty.
+    #[inline]
+    pub fn front_with_mask(&self) -> Option<&BitmaskItem<B, T>> {
+        self.inner.front()
+    }
+
+    /// Returns the last BitmaskItem, or None if the deque is empty.
+    #[inline]
+    pub fn back_with_mask(&self) -> Option<&BitmaskItem<B, T>> {
+        self.inner.back()
+    }
+
+    /// Returns T at index, or None if out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.inner.get(index).map(|x| &x.item)
+    }
+
+    /// Returns the BitmaskItem at index, or None if out of bounds.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<&BitmaskItem<B, T>> {
+        self.inner.get(index)
+    }
+
+    /// Inserts an element with default bitmask at position index, shifting the shorter side of the deque.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index, shifting the shorter side of the deque.
+    #[inline]
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.inner.insert(index, BitmaskItem::new(bitmask, value));
+    }
+
+    /// Removes and returns T at index, or None if out of bounds.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        self.inner.remove(index).map(|x| x.item)
+    }
+
+    /// Removes and returns the BitmaskItem at index, or None if out of bounds.
+    #[inline]
+    pub fn remove_with_mask(&mut self, index: usize) -> Option<BitmaskItem<B, T>> {
+        self.inner.remove(index)
+    }
+
+    /// Returns a BitmaskVecDequeIter for iterating over T.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecDequeIter<'a, B, T> {
+        BitmaskVecDequeIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecDequeIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecDequeIterWithMask<'a, B, T> {
+        BitmaskVecDequeIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecDequeIterMut for mutably iterating over T.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecDequeIterMut<'a, B, T> {
+        BitmaskVecDequeIterMut::new(self.inner.iter_mut())
+    }
+
+    /// Returns a BitmaskVecDequeIterWithMaskMut for mutably iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask_mut(&'a mut self) -> BitmaskVecDequeIterWithMaskMut<'a, B, T> {
+        BitmaskVecDequeIterWithMaskMut::new(self.inner.iter_mut())
+    }
+
+    /// Returns a BitmaskVecDequeIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecDequeIterMatching<'a, B, T> {
+        BitmaskVecDequeIterMatching::new(self.inner.iter(), mask)
+    }
+
+    /// Returns the number of items whose bitmask matches mask.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.inner.iter().filter(|x| x.matches_mask(mask)).count()
+    }
+
+    /// Moves the items into a BitmaskVec, front to back.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.inner.len());
+        for x in self.inner {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<'a, B, T> Default for BitmaskVecDeque<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskVecDeque<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        Self {
+            inner: value.drain(..).collect(),
+        }
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskVecDeque<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<B, T> IndexMut<usize> for BitmaskVecDeque<B, T>
+where
+    B: Bitflag,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index].item
+    }
+}
+
+impl<'a, B, T> AddAssign<(B, T)> for BitmaskVecDeque<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Pushes (bitmask, T) to the back of the deque.
+    fn add_assign(&mut self, rhs: (B, T)) {
+        self.push_back_with_mask(rhs.0, rhs.1);
+    }
+}
+
+impl<'a, B, T> AddAssign<T> for BitmaskVecDeque<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Pushes T with a default bitmask to the back of the deque.
+    fn add_assign(&mut self, rhs: T) {
+        self.push_back(rhs);
+    }
+}
+
+// =================================================================================================
+/// Iter that returns T (excludes bitmask)
+pub struct BitmaskVecDequeIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecDequeIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>) -> Self {
+        Self { inner: i }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecDequeIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|x| &x.item)
+    }
+}
+
+impl<'a, B, T> DoubleEndedIterator for BitmaskVecDequeIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|x| &x.item)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask.
+pub struct BitmaskVecDequeIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecDequeIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>) -> Self {
+        Self { inner: i }
+    }
+
+    pub fn filter_mask(&mut self, mask: &'a B) -> Option<&'a BitmaskItem<B, T>> {
+        self.inner.by_ref().find(|&item| item.matches_mask(mask))
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecDequeIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, B, T> DoubleEndedIterator for BitmaskVecDequeIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns mutable T (excludes bitmask)
+pub struct BitmaskVecDequeIterMut<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: IterMut<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecDequeIterMut<'a, B, T>
+where
+    B: Bitflag,
+{
+    pub fn new(i: IterMut<'a, BitmaskItem<B, T>>) -> Self {
+        Self { inner: i }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecDequeIterMut<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|x| &mut x.item)
+    }
+}
+
+impl<'a, B, T> DoubleEndedIterator for BitmaskVecDequeIterMut<'a, B, T>
+where
+    B: Bitflag,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|x| &mut x.item)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns mutable BitmaskItem, containing both T and bitmask.
+pub struct BitmaskVecDequeIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: IterMut<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> BitmaskVecDequeIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: IterMut<'a, BitmaskItem<B, T>>) -> Self {
+        Self { inner: i }
+    }
+
+    pub fn filter_mask(&mut self, mask: &'a B) -> Option<&'a mut BitmaskItem<B, T>> {
+        self.inner
+            .by_ref()
+            .find(|item| item.bitmask.matches_mask(mask))
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecDequeIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a mut BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, B, T> DoubleEndedIterator for BitmaskVecDequeIterWithMaskMut<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItems whose bitmask matches a mask.
+pub struct BitmaskVecDequeIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+    mask: &'a B,
+}
+
+impl<'a, B, T> BitmaskVecDequeIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>, mask: &'a B) -> Self {
+        Self { inner: i, mask }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecDequeIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = self.mask;
+        self.inner.find(|x| x.matches_mask(mask))
+    }
+}
+
+impl<'a, B, T> DoubleEndedIterator for BitmaskVecDequeIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let mask = self.mask;
+        self.inner.rfind(|x| x.matches_mask(mask))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_bitmask_vec_deque::BitmaskVecDeque;
+
+    #[test]
+    fn test_bitmask_vec_deque_push_pop() {
+        let mut v = BitmaskVecDeque::<u8, i32>::new();
+        v.push_back_with_mask(0b00000010, 101);
+        v.push_front_with_mask(0b00000000, 100);
+        v += (0b00000011, 102);
+        v += 103;
+        assert_eq!(v.len(), 4);
+        assert_eq!(v.front_with_mask().unwrap().item, 100);
+        assert_eq!(v.back_with_mask().unwrap().item, 103);
+
+        assert_eq!(v.pop_front(), Some(100));
+        let x = v.pop_back_with_mask().unwrap();
+        assert_eq!((x.bitmask, x.item), (0, 103));
+        let x = v.pop_front_with_mask().unwrap();
+        assert_eq!((x.bitmask, x.item), (0b00000010, 101));
+        assert_eq!(v.pop_back(), Some(102));
+        assert!(v.is_empty());
+        assert_eq!(v.pop_front(), None);
+    }
+
+    #[test]
+    fn test_bitmask_vec_deque_index() {
+        let mut v = BitmaskVecDeque::<u8, i32>::new();
+        v.push_back(100);
+        v.push_back_with_mask(0b00000010, 101);
+
+        assert_eq!(v[0], 100);
+        v[1] = 5;
+        assert_eq!(v.get(1), Some(&5));
+        assert_eq!(v.get_with_mask(1).unwrap().bitmask, 0b00000010);
+        assert!(v.get(2).is_none());
+    }
+
+    #[test]
+    fn test_bitmask_vec_deque_insert_remove() {
+        let mut v = BitmaskVecDeque::<u8, i32>::new();
+        v.push_back(100);
+        v.push_back(101);
+
+        v.insert_with_mask(1, 0b00000001, 99);
+        assert_eq!(v[1], 99);
+        assert_eq!(v.remove(1), Some(99));
+        assert_eq!(v.remove_with_mask(0).unwrap().item, 100);
+        assert!(v.remove(10).is_none());
+        assert_eq!(v[0], 101);
+    }
+
+    #[test]
+    fn test_bitmask_vec_deque_iter() {
+        let mut v = BitmaskVecDeque::<u8, i32>::new();
+        v.push_back(1);
+        v.push_back(2);
+        v.push_back(3);
+        assert_eq!(v.iter().sum::<i32>(), 6);
+        assert_eq!(v.iter().next_back(), Some(&3));
+
+        for x in v.iter_mut() {
+            *x *= 2;
+        }
+        for x in v.iter_with_mask_mut() {
+            x.bitmask |= 0b10000000;
+        }
+        assert_eq!(v.iter().sum::<i32>(), 12);
+        assert_eq!(v.count_matching(&0b10000000), 3);
+    }
+
+    #[test]
+    fn test_bitmask_vec_deque_iter_matching() {
+        let mut v = BitmaskVecDeque::<u8, i32>::new();
+        v.push_back_with_mask(0b00000000, 100);
+        v.push_back_with_mask(0b00000010, 101);
+        v.push_back_with_mask(0b00000011, 102);
+        v.push_back_with_mask(0b00000100, 103);
+
+        let x: Vec<_> = v.iter_matching(&0b00000010).map(|x| x.item).collect();
+        assert_eq!(x, vec![101, 102]);
+        assert_eq!(v.iter_matching(&0b00000010).next_back().unwrap().item, 102);
+        assert_eq!(v.count_matching(&0b00000100), 1);
+
+        let mut iter = v.iter_with_mask();
+        assert_eq!(iter.filter_mask(&0b00000001).unwrap().item, 102);
+        assert!(iter.filter_mask(&0b00000001).is_none());
+    }
+
+    #[test]
+    fn test_bitmask_vec_deque_conversion() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+
+        let mut d = BitmaskVecDeque::from(v);
+        d.push_front(99);
+        let v = d.into_bitmask_vec();
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0], 99);
+        assert_eq!(v[2], 101);
+    }
+}
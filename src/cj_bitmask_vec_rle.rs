@@ -0,0 +1,660 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{AddAssign, Index, IndexMut};
+use core::slice::{Iter, IterMut};
+
+/// A run of consecutive elements sharing one bitmask. end is the exclusive end index of the run.
+#[derive(Debug, Clone, PartialEq)]
+struct Run<B> {
+    mask: B,
+    end: usize,
+}
+
+/// BitmaskVecRle pairs bitmasks with T like BitmaskVec, but run-length encodes the bitmasks:
+/// consecutive elements with identical bitmasks share a single stored bitmask.<br>
+///
+/// This cuts memory when long runs of identical bitmasks are common, such as batch loaded data,
+/// and lets mask scans test each run once instead of each element. Inserts, removes and
+/// set_mask() split and merge runs as needed, so the encoding stays minimal.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVecRle::<u8, i32>::new();
+/// for i in 0..100 {
+///     v.push_with_mask(0b00000001, i);
+/// }
+/// for i in 100..200 {
+///     v.push_with_mask(0b00000010, i);
+/// }
+/// assert_eq!(v.run_count(), 2);
+/// assert_eq!(v.count_matching(&0b00000010), 100);
+///
+/// v.set_mask(50, 0b00000011);
+/// assert_eq!(v.run_count(), 4);
+/// assert_eq!(v.count_matching(&0b00000010), 101);
+/// ```
+pub struct BitmaskVecRle<B, T>
+where
+    B: Bitflag,
+{
+    runs: Vec<Run<B>>,
+    items: Vec<T>,
+}
+
+impl<'a, B, T> BitmaskVecRle<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + PartialEq,
+{
+    pub fn new() -> Self {
+        Self {
+            runs: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity for T.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            runs: Vec::new(),
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the number of stored bitmask runs.
+    #[inline]
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Returns the T buffer.
+    #[inline]
+    pub fn items(&self) -> &[T] {
+        self.items.as_slice()
+    }
+
+    /// Returns the T buffer mutably.
+    #[inline]
+    pub fn items_mut(&mut self) -> &mut [T] {
+        self.items.as_mut_slice()
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.runs.clear();
+        self.items.clear();
+    }
+
+    /// Returns the index of the run holding the element at index.
+    #[inline]
+    fn run_of(&self, index: usize) -> usize {
+        self.runs.partition_point(|x| x.end <= index)
+    }
+
+    /// Returns the start index of run r.
+    #[inline]
+    fn run_start(&self, r: usize) -> usize {
+        if r == 0 {
+            0
+        } else {
+            self.runs[r - 1].end
+        }
+    }
+
+    /// Merges run r into run r - 1 if both have the same bitmask.
+    fn merge_with_prev(&mut self, r: usize) {
+        if r > 0 && r < self.runs.len() && self.runs[r - 1].mask == self.runs[r].mask {
+            self.runs[r - 1].end = self.runs[r].end;
+            self.runs.remove(r);
+        }
+    }
+
+    /// Returns the bitmask of the element at index, or None if out of bounds.
+    #[inline]
+    pub fn mask(&self, index: usize) -> Option<&B> {
+        if index < self.items.len() {
+            Some(&self.runs[self.run_of(index)].mask)
+        } else {
+            None
+        }
+    }
+
+    /// Returns T at index, or None if out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    /// Returns the bitmask and T at index, or None if out of bounds.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<(&B, &T)> {
+        Some((self.mask(index)?, self.items.get(index)?))
+    }
+
+    /// Returns T at index mutably, or None if out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.items.get_mut(index)
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask, extending the last run if the bitmask matches it.
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.items.push(value);
+        let end = self.items.len();
+        match self.runs.last_mut() {
+            Some(last) if last.mask == bitmask => last.end = end,
+            _ => self.runs.push(Run { mask: bitmask, end }),
+        }
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        let item = self.items.pop()?;
+        let last = self.runs.len() - 1;
+        let bitmask = self.runs[last].mask.clone();
+        self.runs[last].end -= 1;
+        if self.runs[last].end == self.run_start(last) {
+            self.runs.pop();
+        }
+        Some(BitmaskItem::new(bitmask, item))
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    /// Splits the run at index if the bitmask differs from both neighbours.
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        if index == self.items.len() {
+            self.push_with_mask(bitmask, value);
+            return;
+        }
+        self.items.insert(index, value);
+
+        let r = self.run_of(index);
+        let start = self.run_start(r);
+        let mut grow = r;
+        if self.runs[r].mask != bitmask {
+            if index == start && r > 0 && self.runs[r - 1].mask == bitmask {
+                grow = r - 1;
+            } else {
+                let old = self.runs[r].mask.clone();
+                let mut pieces = Vec::with_capacity(2);
+                if index > start {
+                    pieces.push(Run {
+                        mask: old,
+                        end: index,
+                    });
+                }
+                pieces.push(Run {
+                    mask: bitmask,
+                    end: index,
+                });
+                let n = pieces.len();
+                self.runs.splice(r..r, pieces);
+                grow = r + n - 1;
+            }
+        }
+        for x in self.runs[grow..].iter_mut() {
+            x.end += 1;
+        }
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left.
+    /// Runs left empty are dropped and their neighbours merged if they share a bitmask.
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let item = self.items.remove(index);
+        let r = self.run_of(index);
+        let bitmask = self.runs[r].mask.clone();
+        for x in self.runs[r..].iter_mut() {
+            x.end -= 1;
+        }
+        if self.runs[r].end == self.run_start(r) {
+            self.runs.remove(r);
+            self.merge_with_prev(r);
+        }
+        BitmaskItem::new(bitmask, item)
+    }
+
+    /// Replaces the bitmask of the element at index, splitting its run as needed.
+    /// Panics if index is out of bounds.
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        assert!(index < self.items.len(), "index out of bounds");
+        let r = self.run_of(index);
+        if self.runs[r].mask == bitmask {
+            return;
+        }
+        let start = self.run_start(r);
+        let end = self.runs[r].end;
+        let old = self.runs[r].mask.clone();
+
+        let mut pieces = Vec::with_capacity(3);
+        if index > start {
+            pieces.push(Run {
+                mask: old.clone(),
+                end: index,
+            });
+        }
+        pieces.push(Run {
+            mask: bitmask,
+            end: index + 1,
+        });
+        if index + 1 < end {
+            pieces.push(Run { mask: old, end });
+        }
+        let n = pieces.len();
+        self.runs.splice(r..r + 1, pieces);
+        self.merge_with_prev(r + n);
+        self.merge_with_prev(r);
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.items.len() {
+            return;
+        }
+        self.items.truncate(len);
+        if len == 0 {
+            self.runs.clear();
+            return;
+        }
+        let r = self.run_of(len - 1);
+        self.runs.truncate(r + 1);
+        self.runs[r].end = len;
+    }
+
+    /// Returns an iter over T.
+    /// * this iter excludes bitmask. Use iter_with_mask() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter(&'a self) -> Iter<'a, T> {
+        self.items.iter()
+    }
+
+    /// Returns an iter for mutable iteration over T. Bitmasks are changed through set_mask().
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> IterMut<'a, T> {
+        self.items.iter_mut()
+    }
+
+    /// Returns an iter over (bitmask, T) pairs.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecRleIterWithMask<'a, B, T> {
+        BitmaskVecRleIterWithMask::new(&self.runs, &self.items)
+    }
+
+    /// Returns a BitmaskVecRleIterMatching for iterating over the (bitmask, T) pairs whose bitmask matches mask.
+    /// Each run's bitmask is tested once.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecRleIterMatching<'a, B, T> {
+        BitmaskVecRleIterMatching::new(&self.runs, &self.items, mask)
+    }
+
+    /// Returns the number of elements whose bitmask matches mask. Each run's bitmask is tested once.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        let mut start = 0;
+        let mut count = 0;
+        for x in self.runs.iter() {
+            if x.mask.matches_mask(mask) {
+                count += x.end - start;
+            }
+            start = x.end;
+        }
+        count
+    }
+
+    /// Returns the indices of the elements whose bitmask matches mask.
+    pub fn indices_matching(&self, mask: &'a B) -> Vec<usize> {
+        let mut start = 0;
+        let mut indices = Vec::new();
+        for x in self.runs.iter() {
+            if x.mask.matches_mask(mask) {
+                indices.extend(start..x.end);
+            }
+            start = x.end;
+        }
+        indices
+    }
+
+    /// Moves all items into a BitmaskVec.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.items.len());
+        let mut runs = self.runs.into_iter();
+        let mut run = runs.next();
+        for (i, item) in self.items.into_iter().enumerate() {
+            while let Some(x) = &run {
+                if i < x.end {
+                    break;
+                }
+                run = runs.next();
+            }
+            if let Some(x) = &run {
+                v.push_with_mask(x.mask.clone(), item);
+            }
+        }
+        v
+    }
+}
+
+impl<'a, B, T> Default for BitmaskVecRle<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + PartialEq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskVecRle<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + PartialEq,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let mut v = Self::with_capacity(value.len());
+        for x in value.drain(..) {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskVecRle<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.items[index]
+    }
+}
+
+impl<B, T> IndexMut<usize> for BitmaskVecRle<B, T>
+where
+    B: Bitflag,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.items[index]
+    }
+}
+
+impl<'a, B, T> AddAssign<(B, T)> for BitmaskVecRle<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + PartialEq,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: (B, T)) {
+        self.push_with_mask(rhs.0, rhs.1);
+    }
+}
+
+impl<'a, B, T> AddAssign<T> for BitmaskVecRle<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + PartialEq,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: T) {
+        self.push(rhs);
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (bitmask, T) pairs.
+pub struct BitmaskVecRleIterWithMask<'a, B, T>
+where
+    B: Bitflag,
+{
+    runs: &'a [Run<B>],
+    items: &'a [T],
+    run: usize,
+    pos: usize,
+}
+
+impl<'a, B, T> BitmaskVecRleIterWithMask<'a, B, T>
+where
+    B: Bitflag,
+{
+    fn new(runs: &'a [Run<B>], items: &'a [T]) -> Self {
+        Self {
+            runs,
+            items,
+            run: 0,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecRleIterWithMask<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = (&'a B, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.items.get(self.pos)?;
+        while self.runs[self.run].end <= self.pos {
+            self.run += 1;
+        }
+        self.pos += 1;
+        Some((&self.runs[self.run].mask, item))
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (bitmask, T) pairs whose bitmask matches mask. Non matching runs are skipped whole.
+pub struct BitmaskVecRleIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    runs: &'a [Run<B>],
+    items: &'a [T],
+    mask: &'a B,
+    run: usize,
+    pos: usize,
+}
+
+impl<'a, B, T> BitmaskVecRleIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn new(runs: &'a [Run<B>], items: &'a [T], mask: &'a B) -> Self {
+        Self {
+            runs,
+            items,
+            mask,
+            run: 0,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecRleIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (&'a B, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let runs = self.runs;
+        loop {
+            let x = runs.get(self.run)?;
+            if self.pos < x.end && x.mask.matches_mask(self.mask) {
+                let item = &self.items[self.pos];
+                self.pos += 1;
+                return Some((&x.mask, item));
+            }
+            self.pos = x.end;
+            self.run += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_bitmask_vec_rle::BitmaskVecRle;
+
+    fn masks(v: &BitmaskVecRle<u8, i32>) -> Vec<u8> {
+        v.iter_with_mask().map(|(m, _)| *m).collect()
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_push_pop() {
+        let mut v = BitmaskVecRle::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000001, 101);
+        v += (0b00000010, 102);
+        v += 103;
+
+        assert_eq!(v.len(), 4);
+        assert_eq!(v.run_count(), 3);
+        assert_eq!(v[1], 101);
+        assert_eq!(v.mask(2), Some(&0b00000010));
+        assert_eq!(v.mask(4), None);
+
+        assert_eq!(v.pop(), Some(103));
+        let x = v.pop_with_mask().unwrap();
+        assert_eq!((x.bitmask, x.item), (0b00000010, 102));
+        assert_eq!(v.run_count(), 1);
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_insert() {
+        let mut v = BitmaskVecRle::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000010, 102);
+
+        // same mask as the run: no split
+        v.insert_with_mask(1, 0b00000001, 200);
+        assert_eq!(v.run_count(), 2);
+        // at a run boundary, joining the previous run
+        v.insert_with_mask(3, 0b00000001, 201);
+        assert_eq!(v.run_count(), 2);
+        // in the middle of a run with a new mask: split
+        v.insert_with_mask(2, 0b00000100, 202);
+        assert_eq!(v.run_count(), 4);
+
+        assert_eq!(v.items(), &[100, 200, 202, 101, 201, 102]);
+        assert_eq!(
+            masks(&v),
+            vec![0b00000001, 0b00000001, 0b00000100, 0b00000001, 0b00000001, 0b00000010]
+        );
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_remove() {
+        let mut v = BitmaskVecRle::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000001, 102);
+
+        let x = v.remove_with_mask(1);
+        assert_eq!((x.bitmask, x.item), (0b00000010, 101));
+        // the emptied run is dropped and its neighbours merged
+        assert_eq!(v.run_count(), 1);
+        assert_eq!(v.remove(0), 100);
+        assert_eq!(masks(&v), vec![0b00000001]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_set_mask() {
+        let mut v = BitmaskVecRle::<u8, i32>::new();
+        for i in 0..5 {
+            v.push_with_mask(0b00000001, i);
+        }
+
+        v.set_mask(2, 0b00000010);
+        assert_eq!(v.run_count(), 3);
+        v.set_mask(2, 0b00000001);
+        assert_eq!(v.run_count(), 1);
+        v.set_mask(4, 0b00000010);
+        v.set_mask(3, 0b00000010);
+        assert_eq!(v.run_count(), 2);
+        assert_eq!(
+            masks(&v),
+            vec![0b00000001, 0b00000001, 0b00000001, 0b00000010, 0b00000010]
+        );
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_truncate() {
+        let mut v = BitmaskVecRle::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+
+        v.truncate(2);
+        assert_eq!(v.run_count(), 2);
+        assert_eq!(masks(&v), vec![0b00000001, 0b00000010]);
+        v.truncate(0);
+        assert!(v.is_empty());
+        assert_eq!(v.run_count(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_matching() {
+        let mut v = BitmaskVecRle::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        assert_eq!(v.count_matching(&0b00000010), 3);
+        assert_eq!(v.indices_matching(&0b00000010), vec![1, 2, 4]);
+        let x: Vec<_> = v.iter_matching(&0b00000010).map(|(_, x)| *x).collect();
+        assert_eq!(x, vec![101, 102, 104]);
+        assert_eq!(v.iter_matching(&0b01000000).count(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_rle_from_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000010, 102);
+
+        let rle = BitmaskVecRle::from(v);
+        assert_eq!(rle.run_count(), 2);
+
+        let mut v = rle.into_bitmask_vec();
+        assert_eq!(v.len(), 3);
+        assert_eq!(v.iter_with_mask().nth(2).unwrap().bitmask, 0b00000010);
+        assert_eq!(v.iter_with_mask().nth(1).unwrap().bitmask, 0b00000001);
+    }
+}
@@ -0,0 +1,500 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::iter::Zip;
+use core::ops::{AddAssign, Index, IndexMut};
+use core::slice::{Iter, IterMut};
+
+/// BitmaskVecSoA pairs bitmasks with T like BitmaskVec, but stores the bitmasks and T in two
+/// separate contiguous buffers (structure of arrays).<br>
+///
+/// Mask only scans such as count_matching(), indices_matching() and iter_matching() only touch
+/// the bitmask buffer, which keeps filter heavy workloads cache friendly when T is large.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVecSoA::<u8, i32>::new();
+/// v.push_with_mask(0b00000000, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000011, 102);
+/// v.push_with_mask(0b00000100, 103);
+/// v.push_with_mask(0b00000110, 104);
+/// v.push(105);  // <- bitmask will default to zero
+///
+/// assert_eq!(v.count_matching(&0b00000010), 3);
+/// assert_eq!(v.indices_matching(&0b00000010), vec![1, 2, 4]);
+/// for (bitmask, item) in v.iter_matching(&0b00000010) {
+///     assert!([101, 102, 104].contains(item));
+///     assert_eq!(bitmask & 0b00000010, 0b00000010);
+/// }
+/// ```
+pub struct BitmaskVecSoA<B, T>
+where
+    B: Bitflag,
+{
+    masks: Vec<B>,
+    items: Vec<T>,
+}
+
+impl<'a, B, T> BitmaskVecSoA<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            masks: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            masks: Vec::with_capacity(capacity),
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of elements the vector can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.masks.capacity().min(self.items.capacity())
+    }
+
+    /// Returns the bitmask buffer.
+    #[inline]
+    pub fn masks(&self) -> &[B] {
+        self.masks.as_slice()
+    }
+
+    /// Returns the bitmask buffer mutably.
+    #[inline]
+    pub fn masks_mut(&mut self) -> &mut [B] {
+        self.masks.as_mut_slice()
+    }
+
+    /// Returns the T buffer.
+    #[inline]
+    pub fn items(&self) -> &[T] {
+        self.items.as_slice()
+    }
+
+    /// Returns the T buffer mutably.
+    #[inline]
+    pub fn items_mut(&mut self) -> &mut [T] {
+        self.items.as_mut_slice()
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.masks.clear();
+        self.items.clear();
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.items.insert(index, value);
+        self.masks.insert(index, bitmask);
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let item = self.items.remove(index);
+        BitmaskItem::new(self.masks.remove(index), item)
+    }
+
+    /// Reserves capacity for at least additional more elements.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.masks.reserve(additional);
+        self.items.reserve(additional);
+    }
+
+    /// Removes an element without bitmask from the vector and returns it.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        self.swap_with_mask_remove(index).item
+    }
+
+    /// Removes an element and bitmask from the vector and returns it.
+    #[inline]
+    pub fn swap_with_mask_remove(&mut self, index: usize) -> BitmaskItem<B, T> {
+        let item = self.items.swap_remove(index);
+        BitmaskItem::new(self.masks.swap_remove(index), item)
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.masks.truncate(len);
+        self.items.truncate(len);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.masks.push(bitmask);
+        self.items.push(value);
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        let item = self.items.pop()?;
+        self.masks
+            .pop()
+            .map(|bitmask| BitmaskItem::new(bitmask, item))
+    }
+
+    /// Returns an iter over T.
+    /// * this iter excludes bitmask. Use iter_with_mask() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter(&'a mut self) -> Iter<'a, T> {
+        self.items.iter()
+    }
+
+    /// Returns a BitmaskVecSoAIterWithMask for iterating over (bitmask, T) pairs.
+    #[inline]
+    pub fn iter_with_mask(&'a mut self) -> BitmaskVecSoAIterWithMask<'a, B, T> {
+        BitmaskVecSoAIterWithMask::new(self.masks.iter().zip(self.items.iter()))
+    }
+
+    /// Returns an iter for mutable iteration over T.
+    /// * this iter excludes bitmask. Use iter_with_mask_mut() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> IterMut<'a, T> {
+        self.items.iter_mut()
+    }
+
+    /// Returns an iter for mutable iteration over (bitmask, T) pairs.
+    #[inline]
+    pub fn iter_with_mask_mut(&'a mut self) -> Zip<IterMut<'a, B>, IterMut<'a, T>> {
+        self.masks.iter_mut().zip(self.items.iter_mut())
+    }
+
+    /// Returns a BitmaskVecSoAIterMatching for iterating over the (bitmask, T) pairs whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecSoAIterMatching<'a, B, T> {
+        BitmaskVecSoAIterMatching::new(&self.masks, &self.items, mask)
+    }
+
+    /// Returns the number of elements whose bitmask matches mask. Only the bitmask buffer is scanned.
+    #[inline]
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.masks.iter().filter(|x| x.matches_mask(mask)).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask matches mask. Only the bitmask buffer is scanned.
+    pub fn indices_matching(&self, mask: &'a B) -> Vec<usize> {
+        self.masks
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.matches_mask(mask))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Moves all items into a BitmaskVec.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.items.len());
+        for (bitmask, item) in self.masks.into_iter().zip(self.items) {
+            v.push_with_mask(bitmask, item);
+        }
+        v
+    }
+}
+
+impl<'a, B, T> Default for BitmaskVecSoA<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for BitmaskVecSoA<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let mut v = Self::with_capacity(value.len());
+        for x in value.drain(..) {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<B, T> Index<usize> for BitmaskVecSoA<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.items[index]
+    }
+}
+
+impl<B, T> IndexMut<usize> for BitmaskVecSoA<B, T>
+where
+    B: Bitflag,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.items[index]
+    }
+}
+
+impl<'a, B, T> AddAssign<(B, T)> for BitmaskVecSoA<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: (B, T)) {
+        self.push_with_mask(rhs.0, rhs.1);
+    }
+}
+
+impl<'a, B, T> AddAssign<T> for BitmaskVecSoA<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: T) {
+        self.push(rhs);
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (bitmask, T) pairs.
+pub struct BitmaskVecSoAIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    inner: Zip<Iter<'a, B>, Iter<'a, T>>,
+}
+
+impl<'a, B, T> BitmaskVecSoAIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(i: Zip<Iter<'a, B>, Iter<'a, T>>) -> Self {
+        Self { inner: i }
+    }
+
+    pub fn filter_mask(&mut self, mask: &'a B) -> Option<(&'a B, &'a T)> {
+        self.inner
+            .by_ref()
+            .find(|(bitmask, _)| bitmask.matches_mask(mask))
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecSoAIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (&'a B, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+impl<'a, B, T> DoubleEndedIterator for BitmaskVecSoAIterWithMask<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (bitmask, T) pairs whose bitmask matches mask. Only matching T are touched.
+pub struct BitmaskVecSoAIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    masks: &'a [B],
+    items: &'a [T],
+    mask: &'a B,
+    pos: usize,
+}
+
+impl<'a, B, T> BitmaskVecSoAIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    pub fn new(masks: &'a [B], items: &'a [T], mask: &'a B) -> Self {
+        Self {
+            masks,
+            items,
+            mask,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecSoAIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (&'a B, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = self.mask;
+        let offset = self.masks[self.pos..]
+            .iter()
+            .position(|x| x.matches_mask(mask))?;
+        let index = self.pos + offset;
+        self.pos = index + 1;
+        Some((&self.masks[index], &self.items[index]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_bitmask_vec_soa::BitmaskVecSoA;
+
+    #[test]
+    fn test_bitmask_vec_soa_push() {
+        let mut v = BitmaskVecSoA::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v += (2, 0);
+        v += 5;
+
+        assert_eq!(v.len(), 4);
+        assert_eq!(v[1], 400);
+        assert_eq!(v.masks(), &[0, 1, 2, 0]);
+        assert_eq!(v.items(), &[100, 400, 0, 5]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_soa_pop_with_mask() {
+        let mut v = BitmaskVecSoA::<u8, i32>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(2, 999);
+
+        let x = v.pop_with_mask().unwrap();
+        assert_eq!(x.bitmask, 0b00000010);
+        assert_eq!(x.item, 999);
+        assert_eq!(v.pop(), Some(100));
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn test_bitmask_vec_soa_insert_remove() {
+        let mut v = BitmaskVecSoA::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.insert(1, 500);
+        v.insert_with_mask(0, 0b11000000, 600);
+
+        assert_eq!(v.items(), &[600, 100, 500, 101]);
+        assert_eq!(v.remove(2), 500);
+        let x = v.remove_with_mask(0);
+        assert_eq!(x.bitmask, 0b11000000);
+        assert_eq!(x.item, 600);
+        assert_eq!(v.swap_remove(0), 100);
+        assert_eq!(v.masks(), &[0b00000010]);
+    }
+
+    #[test]
+    fn test_bitmask_vec_soa_iter_with_mask() {
+        let mut v = BitmaskVecSoA::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000100, 102);
+        v.push_with_mask(0b00000010, 103);
+
+        let mut iter = v.iter_with_mask();
+        assert_eq!(iter.filter_mask(&0b00000010), Some((&0b00000011, &101)));
+        assert_eq!(iter.next_back(), Some((&0b00000010, &103)));
+        assert_eq!(iter.filter_mask(&0b00000010), None);
+    }
+
+    #[test]
+    fn test_bitmask_vec_soa_iter_with_mask_mut() {
+        let mut v = BitmaskVecSoA::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        for (bitmask, item) in v.iter_with_mask_mut() {
+            *bitmask |= 0b10000000;
+            *item *= 2;
+        }
+        assert_eq!(v.masks(), &[0b10000000, 0b10000010]);
+        assert_eq!(v.iter().sum::<i32>(), 402);
+    }
+
+    #[test]
+    fn test_bitmask_vec_soa_matching() {
+        let mut v = BitmaskVecSoA::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        assert_eq!(v.count_matching(&0b00000010), 3);
+        assert_eq!(v.indices_matching(&0b00000010), vec![1, 2, 4]);
+        let x: Vec<_> = v.iter_matching(&0b00000010).map(|(_, x)| *x).collect();
+        assert_eq!(x, vec![101, 102, 104]);
+        assert_eq!(v.iter_matching(&0b01000000).count(), 0);
+    }
+
+    #[test]
+    fn test_bitmask_vec_soa_from_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        let soa = BitmaskVecSoA::from(v);
+        assert_eq!(soa.masks(), &[0b00000001, 0b00000010]);
+
+        let mut v = soa.into_bitmask_vec();
+        assert_eq!(v.iter_with_mask().nth(1).unwrap().bitmask, 0b00000010);
+    }
+}
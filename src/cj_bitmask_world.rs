@@ -0,0 +1,446 @@
+use alloc::boxed::Box;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::any::{Any, TypeId};
+use core::iter::Enumerate;
+use core::slice::{Iter, IterMut};
+
+/// BitmaskWorld owns a single mask column plus any number of item columns, one per component
+/// type, all indexed by row.<br>
+///
+/// Rows are added with spawn() and components are attached with insert(). Columns are registered
+/// by type the first time they are used, and a row may leave any column empty. iter_matching()
+/// returns the requested columns as a tuple for every row whose bitmask matches mask and that
+/// has all of them, which makes BitmaskWorld a small archetype-free entity component store.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// struct Position(i32);
+/// struct Velocity(i32);
+/// const ACTIVE: u8 = 0b00000001;
+///
+/// let mut w = BitmaskWorld::<u8>::new();
+/// let a = w.spawn(ACTIVE);
+/// w.insert(a, Position(10));
+/// w.insert(a, Velocity(1));
+/// let b = w.spawn(ACTIVE);
+/// w.insert(b, Position(20));
+/// let c = w.spawn(0);
+/// w.insert(c, Position(30));
+/// w.insert(c, Velocity(3));
+///
+/// let moved: Vec<i32> = w
+///     .iter_matching::<(Position, Velocity)>(&ACTIVE)
+///     .map(|(_, (p, v))| p.0 + v.0)
+///     .collect();
+/// assert_eq!(moved, vec![11]);
+/// ```
+pub struct BitmaskWorld<B>
+where
+    B: Bitflag,
+{
+    masks: Vec<B>,
+    columns: BTreeMap<TypeId, Box<dyn AnyColumn>>,
+}
+
+impl<'a, B> BitmaskWorld<B>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            masks: Vec::new(),
+            columns: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of rows.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.masks.len()
+    }
+
+    /// Returns true if the world contains no rows.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// Registers a column for C, with every existing row left empty. Returns false if the
+    /// column was already registered.
+    pub fn register<C: 'static>(&mut self) -> bool {
+        if self.has_column::<C>() {
+            return false;
+        }
+        let mut column: Vec<Option<C>> = Vec::with_capacity(self.masks.len());
+        column.resize_with(self.masks.len(), || None);
+        self.columns.insert(TypeId::of::<C>(), Box::new(column));
+        true
+    }
+
+    /// Returns true if a column for C is registered.
+    #[inline]
+    pub fn has_column<C: 'static>(&self) -> bool {
+        self.columns.contains_key(&TypeId::of::<C>())
+    }
+
+    /// Appends a row with bitmask and no components, returning its index.
+    pub fn spawn(&mut self, bitmask: B) -> usize {
+        self.masks.push(bitmask);
+        for column in self.columns.values_mut() {
+            column.push_none();
+        }
+        self.masks.len() - 1
+    }
+
+    /// Removes the row at index and returns its bitmask, dropping its components. The last row
+    /// is moved into its place.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    pub fn despawn(&mut self, index: usize) -> B {
+        let bitmask = self.masks.swap_remove(index);
+        for column in self.columns.values_mut() {
+            column.swap_remove(index);
+        }
+        bitmask
+    }
+
+    /// Returns the bitmask of the row at index.
+    #[inline]
+    pub fn mask(&self, index: usize) -> Option<&B> {
+        self.masks.get(index)
+    }
+
+    /// Replaces the bitmask of the row at index.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    #[inline]
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        self.masks[index] = bitmask;
+    }
+
+    /// Attaches value to the row at index, registering the column for C if needed. Returns the
+    /// component it replaced.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    pub fn insert<C: 'static>(&mut self, index: usize, value: C) -> Option<C> {
+        assert!(
+            index < self.masks.len(),
+            "index {index} is out of bounds for length {}",
+            self.masks.len()
+        );
+        self.register::<C>();
+        self.column_mut::<C>().and_then(|x| x[index].replace(value))
+    }
+
+    /// Detaches and returns the C of the row at index.
+    pub fn remove<C: 'static>(&mut self, index: usize) -> Option<C> {
+        self.column_mut::<C>()?.get_mut(index)?.take()
+    }
+
+    /// Returns the C of the row at index.
+    #[inline]
+    pub fn get<C: 'static>(&self, index: usize) -> Option<&C> {
+        self.column::<C>()?.get(index)?.as_ref()
+    }
+
+    /// Returns the mutable C of the row at index.
+    #[inline]
+    pub fn get_mut<C: 'static>(&mut self, index: usize) -> Option<&mut C> {
+        self.column_mut::<C>()?.get_mut(index)?.as_mut()
+    }
+
+    /// Returns a BitmaskWorldIterMatching for iterating over the rows whose bitmask matches mask
+    /// and that have every component in Q, yielding the row index and the components as a tuple.
+    #[inline]
+    pub fn iter_matching<Q>(&'a self, mask: &'a B) -> BitmaskWorldIterMatching<'a, B, Q>
+    where
+        Q: BitmaskQuery<'a>,
+    {
+        BitmaskWorldIterMatching {
+            masks: self.masks.iter().enumerate(),
+            columns: Q::columns(self),
+            mask,
+        }
+    }
+
+    /// Returns a BitmaskWorldIterMatchingMut for iterating over the rows whose bitmask matches
+    /// mask and that have a C, yielding the row index and mutable C.
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut w = BitmaskWorld::<u8>::new();
+    /// for i in 0..4 {
+    ///     let row = w.spawn((i % 2) as u8);
+    ///     w.insert(row, i);
+    /// }
+    ///
+    /// for (_, x) in w.iter_matching_mut::<i32>(&0b00000001) {
+    ///     *x *= 10;
+    /// }
+    /// assert_eq!(w.get::<i32>(3), Some(&30));
+    /// assert_eq!(w.get::<i32>(2), Some(&2));
+    /// ```
+    #[inline]
+    pub fn iter_matching_mut<C: 'static>(
+        &'a mut self,
+        mask: &'a B,
+    ) -> BitmaskWorldIterMatchingMut<'a, B, C> {
+        let column = match self.columns.get_mut(&TypeId::of::<C>()) {
+            Some(x) => downcast_mut::<C>(x.as_mut()).iter_mut(),
+            None => [].iter_mut(),
+        };
+        BitmaskWorldIterMatchingMut {
+            masks: self.masks.iter().enumerate(),
+            column,
+            mask,
+        }
+    }
+
+    /// Returns the number of rows whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.masks.iter().filter(|x| x.matches_mask(mask)).count()
+    }
+
+    /// Returns the indices of the rows whose bitmask matches mask.
+    pub fn indices_matching(&self, mask: &'a B) -> Vec<usize> {
+        self.masks
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.matches_mask(mask))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl<B> BitmaskWorld<B>
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn column<C: 'static>(&self) -> Option<&[Option<C>]> {
+        self.columns
+            .get(&TypeId::of::<C>())
+            .map(|x| downcast_ref::<C>(x.as_ref()).as_slice())
+    }
+
+    #[inline]
+    fn column_mut<C: 'static>(&mut self) -> Option<&mut Vec<Option<C>>> {
+        self.columns
+            .get_mut(&TypeId::of::<C>())
+            .map(|x| downcast_mut::<C>(x.as_mut()))
+    }
+}
+
+impl<'a, B> Default for BitmaskWorld<B>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type erased item column of a BitmaskWorld.
+trait AnyColumn {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn push_none(&mut self);
+    fn swap_remove(&mut self, index: usize);
+}
+
+impl<C: 'static> AnyColumn for Vec<Option<C>> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn push_none(&mut self) {
+        self.push(None);
+    }
+
+    fn swap_remove(&mut self, index: usize) {
+        Vec::swap_remove(self, index);
+    }
+}
+
+// columns are keyed by the TypeId of C, so the downcasts only fail on a bug in BitmaskWorld
+#[inline]
+fn downcast_ref<C: 'static>(column: &dyn AnyColumn) -> &Vec<Option<C>> {
+    column
+        .as_any()
+        .downcast_ref()
+        .expect("column type does not match its TypeId")
+}
+
+#[inline]
+fn downcast_mut<C: 'static>(column: &mut dyn AnyColumn) -> &mut Vec<Option<C>> {
+    column
+        .as_any_mut()
+        .downcast_mut()
+        .expect("column type does not match its TypeId")
+}
+
+/// Tuple of component types read together by BitmaskWorld::iter_matching(), from (A,) up to
+/// (A, B, C, D, E, F).
+pub trait BitmaskQuery<'w> {
+    /// Tuple of references yielded for each row.
+    type Item;
+    /// Tuple of the columns being read.
+    type Columns;
+
+    /// Returns the columns, or None if any of them is not registered.
+    fn columns<M: Bitflag>(world: &'w BitmaskWorld<M>) -> Option<Self::Columns>;
+
+    /// Returns the components of the row at index, or None if any of them is missing.
+    fn fetch(columns: &Self::Columns, index: usize) -> Option<Self::Item>;
+}
+
+macro_rules! impl_bitmask_query {
+    ($($c:ident $v:ident),+) => {
+        impl<'w, $($c: 'static),+> BitmaskQuery<'w> for ($($c,)+) {
+            type Item = ($(&'w $c,)+);
+            type Columns = ($(&'w [Option<$c>],)+);
+
+            fn columns<M: Bitflag>(world: &'w BitmaskWorld<M>) -> Option<Self::Columns> {
+                Some(($(world.column::<$c>()?,)+))
+            }
+
+            #[inline]
+            fn fetch(columns: &Self::Columns, index: usize) -> Option<Self::Item> {
+                let ($($v,)+) = *columns;
+                Some(($($v[index].as_ref()?,)+))
+            }
+        }
+    };
+}
+
+impl_bitmask_query!(A a);
+impl_bitmask_query!(A a, B b);
+impl_bitmask_query!(A a, B b, C c);
+impl_bitmask_query!(A a, B b, C c, D d);
+impl_bitmask_query!(A a, B b, C c, D d, E e);
+impl_bitmask_query!(A a, B b, C c, D d, E e, F f);
+
+// =================================================================================================
+/// Iter that returns the row index and the components in Q from a BitmaskWorld for rows whose bitmask matches mask.
+pub struct BitmaskWorldIterMatching<'a, B, Q>
+where
+    B: Bitflag,
+    Q: BitmaskQuery<'a>,
+{
+    masks: Enumerate<Iter<'a, B>>,
+    columns: Option<Q::Columns>,
+    mask: &'a B,
+}
+
+impl<'a, B, Q> Iterator for BitmaskWorldIterMatching<'a, B, Q>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+    Q: BitmaskQuery<'a>,
+{
+    type Item = (usize, Q::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let columns = self.columns.as_ref()?;
+        let mask = self.mask;
+        self.masks
+            .by_ref()
+            .filter(|(_, x)| x.matches_mask(mask))
+            .find_map(|(i, _)| Q::fetch(columns, i).map(|x| (i, x)))
+    }
+}
+
+// =================================================================================================
+/// Iter that returns the row index and mutable C from a BitmaskWorld for rows whose bitmask matches mask.
+pub struct BitmaskWorldIterMatchingMut<'a, B, C>
+where
+    B: Bitflag,
+{
+    masks: Enumerate<Iter<'a, B>>,
+    column: IterMut<'a, Option<C>>,
+    mask: &'a B,
+}
+
+impl<'a, B, C> Iterator for BitmaskWorldIterMatchingMut<'a, B, C>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = (usize, &'a mut C);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let x = self.column.next()?;
+            let (i, bitmask) = self.masks.next()?;
+            if let Some(x) = x {
+                if bitmask.matches_mask(self.mask) {
+                    return Some((i, x));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_world::BitmaskWorld;
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn test_bitmask_world_query() {
+        let mut w = BitmaskWorld::<u8>::new();
+        let a = w.spawn(0b00000001);
+        let b = w.spawn(0b00000011);
+        let c = w.spawn(0b00000010);
+        w.insert(a, Name("a"));
+        w.insert(b, Name("b"));
+        w.insert(c, Name("c"));
+        w.insert(b, 20u32);
+        w.insert(c, 30u32);
+
+        let x: Vec<_> = w
+            .iter_matching::<(Name, u32)>(&0b00000010)
+            .map(|(i, (n, v))| (i, n.0, *v))
+            .collect();
+        assert_eq!(x, vec![(b, "b", 20), (c, "c", 30)]);
+        assert_eq!(w.iter_matching::<(Name,)>(&0b00000001).count(), 2);
+        assert_eq!(w.iter_matching::<(i64,)>(&0).count(), 0);
+        assert_eq!(w.insert(a, Name("A")), Some(Name("a")));
+        assert_eq!(w.remove::<u32>(b), Some(20));
+        assert_eq!(w.iter_matching::<(Name, u32)>(&0).count(), 1);
+        assert_eq!(w.indices_matching(&0b00000010), vec![b, c]);
+    }
+
+    #[test]
+    fn test_bitmask_world_register_despawn() {
+        let mut w = BitmaskWorld::<u8>::default();
+        w.spawn(0b00000001);
+        w.spawn(0b00000010);
+        assert!(w.register::<i32>());
+        assert!(!w.register::<i32>());
+        assert_eq!(w.get::<i32>(1), None);
+
+        w.insert(1, 101);
+        *w.get_mut::<i32>(1).unwrap() += 1;
+        assert_eq!(w.despawn(0), 0b00000001);
+        assert_eq!(w.len(), 1);
+        assert_eq!(w.mask(0), Some(&0b00000010));
+        assert_eq!(w.get::<i32>(0), Some(&102));
+
+        w.set_mask(0, 0b00000100);
+        assert_eq!(w.count_matching(&0b00000100), 1);
+        assert_eq!(w.iter_matching_mut::<u64>(&0).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_world_insert_out_of_bounds() {
+        let mut w = BitmaskWorld::<u8>::new();
+        w.insert(0, 100);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use cj_common::cj_binary::bitbuf::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+type Shard<B, T> = Mutex<Vec<BitmaskItem<B, T>>>;
+
+/// ConcurrentBitmaskVec is an append only, thread safe vec that pairs bitmasks with T.<br>
+///
+/// Items are spread over several independently locked shards. Each thread pushes to its own
+/// shard, picked by thread id, so producers pushing from many threads rarely wait on each other.
+/// Use snapshot() or into_bitmask_vec() to iterate the collected items.<br>
+/// Items pushed by the same thread keep their relative order; items from different
+/// threads are grouped by shard.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let v = ConcurrentBitmaskVec::<u8, i32>::new();
+/// std::thread::scope(|s| {
+///     for t in 0..4 {
+///         let v = &v;
+///         s.spawn(move || {
+///             for i in 0..100 {
+///                 v.push_with_mask((i % 2) as u8, t * 100 + i);
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(v.len(), 400);
+/// assert_eq!(v.count_matching(&0b00000001), 200);
+///
+/// let mut v = v.into_bitmask_vec();
+/// assert_eq!(v.iter_with_mask().count(), 400);
+/// ```
+pub struct ConcurrentBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    shards: Box<[Shard<B, T>]>,
+    len: AtomicUsize,
+}
+
+impl<'a, B, T> ConcurrentBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Creates an empty vec with one shard per available CPU.
+    pub fn new() -> Self {
+        let shards = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        Self::with_shards(shards)
+    }
+
+    /// Creates an empty vec with the given number of shards (at least one).
+    pub fn with_shards(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Mutex::new(Vec::new())).collect(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of shards.
+    #[inline]
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of elements pushed so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Returns true if no elements have been pushed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&self, value: T) {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask. Can be called from many threads at once.
+    pub fn push_with_mask(&self, bitmask: B, value: T) {
+        let mut shard = self.acquire_shard();
+        shard.push(BitmaskItem::new(bitmask, value));
+        self.len.fetch_add(1, Ordering::Release);
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.shards
+            .iter()
+            .map(|s| lock(s).iter().filter(|x| x.matches_mask(mask)).count())
+            .sum()
+    }
+
+    /// Copies the current contents into a BitmaskVec for iteration.
+    /// Pushes that happen while the snapshot is taken may or may not be included.
+    pub fn snapshot(&self) -> BitmaskVec<B, T>
+    where
+        T: Clone,
+    {
+        let mut v = BitmaskVec::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            v.extend_from_slice(&lock(shard));
+        }
+        v
+    }
+
+    /// Consumes the vec, moving all items into a BitmaskVec.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.len());
+        for shard in self.shards.into_vec() {
+            let items = shard.into_inner().unwrap_or_else(|e| e.into_inner());
+            for x in items {
+                v.push_with_mask(x.bitmask, x.item);
+            }
+        }
+        v
+    }
+
+    /// Locks the calling thread's shard. A thread always uses the same shard, which keeps its
+    /// pushes in order.
+    fn acquire_shard(&self) -> MutexGuard<'_, Vec<BitmaskItem<B, T>>> {
+        let mut hasher = DefaultHasher::new();
+        std::thread::current().id().hash(&mut hasher);
+        lock(&self.shards[hasher.finish() as usize % self.shards.len()])
+    }
+}
+
+impl<'a, B, T> Default for ConcurrentBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[inline]
+fn lock<X>(m: &Mutex<X>) -> MutexGuard<'_, X> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_concurrent_bitmask_vec::ConcurrentBitmaskVec;
+
+    #[test]
+    fn test_concurrent_bitmask_vec_push() {
+        let v = ConcurrentBitmaskVec::<u8, i32>::with_shards(2);
+        v.push_with_mask(0b00000001, 100);
+        v.push(101);
+
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.shard_count(), 2);
+        assert_eq!(v.count_matching(&0b00000001), 1);
+    }
+
+    #[test]
+    fn test_concurrent_bitmask_vec_threads() {
+        let v = ConcurrentBitmaskVec::<u8, i32>::with_shards(4);
+        std::thread::scope(|s| {
+            for t in 0..8 {
+                let v = &v;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        v.push_with_mask((i % 4) as u8, t * 1000 + i);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(v.len(), 8000);
+        assert_eq!(v.count_matching(&0b00000011), 2000);
+        let mut items: Vec<_> = v.into_bitmask_vec().iter().copied().collect();
+        items.sort();
+        assert_eq!(items, (0..8000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_concurrent_bitmask_vec_thread_order() {
+        // more threads than shards, so threads share shards
+        let v = ConcurrentBitmaskVec::<u8, (u8, i32)>::with_shards(3);
+        std::thread::scope(|s| {
+            for t in 0..6 {
+                let v = &v;
+                s.spawn(move || {
+                    for i in 0..2000 {
+                        v.push_with_mask(t, (t, i));
+                    }
+                });
+            }
+        });
+
+        let v = v.into_bitmask_vec();
+        for t in 0..6 {
+            let seq: Vec<i32> = v
+                .as_slice()
+                .iter()
+                .filter(|x| x.item.0 == t)
+                .map(|x| x.item.1)
+                .collect();
+            assert_eq!(seq, (0..2000).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_concurrent_bitmask_vec_snapshot() {
+        let v = ConcurrentBitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000000, 101);
+
+        let mut snap = v.snapshot();
+        v.push(102);
+
+        assert_eq!(snap.len(), 2);
+        assert_eq!(v.len(), 3);
+        assert_eq!(snap.iter_matching(&0b00000010).count(), 1);
+        assert_eq!(snap.iter().count(), 2);
+    }
+}
@@ -0,0 +1,426 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMatching, BitmaskVecIterMut, BitmaskVecIterWithMask,
+    BitmaskVecIterWithMaskMut,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{AddAssign, Index, IndexMut};
+
+/// CowBitmaskVec is a copy-on-write vec that pairs bitmasks with T.<br>
+///
+/// Cloning is O(1): clones share the same items until one of them is mutated,
+/// at which point that copy clones the items into its own buffer.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = CowBitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(0b00000000, 100);
+/// v.push_with_mask(0b00000010, 101);
+///
+/// let snapshot = v.clone();
+/// assert!(v.ptr_eq(&snapshot));
+///
+/// v.push_with_mask(0b00000011, 102);
+/// assert!(!v.ptr_eq(&snapshot));
+/// assert_eq!(v.len(), 3);
+/// assert_eq!(snapshot.len(), 2);
+/// ```
+pub struct CowBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Arc<Vec<BitmaskItem<B, T>>>,
+}
+
+impl<'a, B, T> CowBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Vec::with_capacity(capacity)),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns true if the items are currently shared with another clone.
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.inner) > 1
+    }
+
+    /// Returns true if both vecs share the same underlying items.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Extracts a mutable slice containing the entire vector, copying the items first if they are shared.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [BitmaskItem<B, T>]
+    where
+        T: Clone,
+    {
+        self.make_mut().as_mut_slice()
+    }
+
+    /// Clears the vector, removing all values. Shared items are left untouched for the other clones.
+    #[inline]
+    pub fn clear(&mut self) {
+        match Arc::get_mut(&mut self.inner) {
+            Some(inner) => inner.clear(),
+            None => self.inner = Arc::new(Vec::new()),
+        }
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T)
+    where
+        T: Clone,
+    {
+        self.push_with_mask(B::default(), value);
+    }
+
+    /// Pushes T and the supplied bitmask
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T)
+    where
+        T: Clone,
+    {
+        self.make_mut().push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.pop_with_mask().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>>
+    where
+        T: Clone,
+    {
+        self.make_mut().pop()
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T)
+    where
+        T: Clone,
+    {
+        self.insert_with_mask(index, B::default(), value);
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T)
+    where
+        T: Clone,
+    {
+        self.make_mut()
+            .insert(index, BitmaskItem::new(bitmask, value));
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T
+    where
+        T: Clone,
+    {
+        self.remove_with_mask(index).item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T>
+    where
+        T: Clone,
+    {
+        self.make_mut().remove(index)
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest
+    #[inline]
+    pub fn truncate(&mut self, len: usize)
+    where
+        T: Clone,
+    {
+        if len < self.len() {
+            self.make_mut().truncate(len);
+        }
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    /// * this iter excludes bitmask. Use iter_with_mask() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecIterMatching<'a, B, T> {
+        BitmaskVecIterMatching::new(self.inner.iter(), mask)
+    }
+
+    /// Returns a BitmaskVecIterMut for mutable iteration over T, copying the items first if they are shared.
+    /// * this iter excludes bitmask. Use iter_with_mask_mut() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecIterMut<'a, B, T>
+    where
+        T: Clone,
+    {
+        BitmaskVecIterMut::new(self.make_mut().iter_mut())
+    }
+
+    /// Returns a BitmaskVecIterWithMaskMut for mutable iteration over T and bitmask, copying the items first if they are shared.
+    #[inline]
+    pub fn iter_with_mask_mut(&'a mut self) -> BitmaskVecIterWithMaskMut<'a, B, T>
+    where
+        T: Clone,
+    {
+        BitmaskVecIterWithMaskMut::new(self.make_mut().iter_mut())
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.inner.iter().filter(|x| x.matches_mask(mask)).count()
+    }
+
+    /// Moves the items into a BitmaskVec, cloning them only if they are still shared.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T>
+    where
+        T: Clone,
+    {
+        let items = Arc::try_unwrap(self.inner).unwrap_or_else(|x| (*x).clone());
+        let mut v = BitmaskVec::with_capacity(items.len());
+        for x in items {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+
+    #[inline]
+    fn make_mut(&mut self) -> &mut Vec<BitmaskItem<B, T>>
+    where
+        T: Clone,
+    {
+        Arc::make_mut(&mut self.inner)
+    }
+}
+
+impl<B, T> Clone for CowBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    /// Returns a new handle sharing the same items. O(1).
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<'a, B, T> Default for CowBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for CowBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        Self {
+            inner: Arc::new(value.drain(..).collect()),
+        }
+    }
+}
+
+impl<B, T> Index<usize> for CowBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<B, T> IndexMut<usize> for CowBitmaskVec<B, T>
+where
+    B: Bitflag + Clone,
+    T: Clone,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut Arc::make_mut(&mut self.inner)[index].item
+    }
+}
+
+impl<'a, B, T> AddAssign<(B, T)> for CowBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    T: Clone,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: (B, T)) {
+        self.push_with_mask(rhs.0, rhs.1);
+    }
+}
+
+impl<'a, B, T> AddAssign<T> for CowBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    T: Clone,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: T) {
+        self.push(rhs);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_cow_bitmask_vec::CowBitmaskVec;
+
+    #[test]
+    fn test_cow_bitmask_vec_clone_shares() {
+        let mut v = CowBitmaskVec::<u8, i32>::new();
+        v += 100;
+        assert!(!v.is_shared());
+
+        let v2 = v.clone();
+        assert!(v.ptr_eq(&v2));
+        assert!(v.is_shared());
+    }
+
+    #[test]
+    fn test_cow_bitmask_vec_push_copies() {
+        let mut v = CowBitmaskVec::<u8, i32>::new();
+        v += (0b00000001, 100);
+        let v2 = v.clone();
+        v.push_with_mask(0b10000000, 101);
+
+        assert!(!v.ptr_eq(&v2));
+        assert!(!v.is_shared());
+        assert_eq!(v.len(), 2);
+        assert_eq!(v2.len(), 1);
+    }
+
+    #[test]
+    fn test_cow_bitmask_vec_index_mut_copies() {
+        let mut v = CowBitmaskVec::<u8, i32>::new();
+        v += 100;
+        v += 101;
+        let v2 = v.clone();
+        v[1] = 500;
+
+        assert_eq!(v[1], 500);
+        assert_eq!(v2[1], 101);
+    }
+
+    #[test]
+    fn test_cow_bitmask_vec_iter_with_mask_mut() {
+        let mut v = CowBitmaskVec::<u8, i32>::new();
+        v += (0b00000000, 100);
+        v += (0b00000010, 101);
+        let v2 = v.clone();
+        for x in v.iter_with_mask_mut() {
+            x.bitmask |= 0b10000000;
+        }
+
+        assert_eq!(v.count_matching(&0b10000000), 2);
+        assert_eq!(v2.count_matching(&0b10000000), 0);
+    }
+
+    #[test]
+    fn test_cow_bitmask_vec_remove() {
+        let mut v = CowBitmaskVec::<u8, i32>::new();
+        v += (0b00000000, 100);
+        v += (0b00000010, 101);
+        v += (0b00000011, 102);
+        v += (0b00000100, 103);
+        let v2 = v.clone();
+
+        assert_eq!(v.remove(0), 100);
+        let x = v.remove_with_mask(0);
+        assert_eq!((x.bitmask, x.item), (0b00000010, 101));
+        assert_eq!(v.pop(), Some(103));
+        v.clear();
+
+        assert!(v.is_empty());
+        assert_eq!(v2.len(), 4);
+    }
+
+    #[test]
+    fn test_cow_bitmask_vec_iter_matching() {
+        let mut v = CowBitmaskVec::<u8, i32>::new();
+        v += (0b00000000, 100);
+        v += (0b00000010, 101);
+        v += (0b00000011, 102);
+
+        let x: Vec<_> = v.iter_matching(&0b00000010).map(|x| x.item).collect();
+        assert_eq!(x, vec![101, 102]);
+        assert_eq!(v.iter().sum::<i32>(), 303);
+    }
+
+    #[test]
+    fn test_cow_bitmask_vec_conversions() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        let cow = CowBitmaskVec::from(v);
+        let shared = cow.clone();
+        let mut v = cow.into_bitmask_vec();
+        assert_eq!(v.len(), 2);
+        assert_eq!(v.iter_with_mask().nth(1).unwrap().bitmask, 0b00000010);
+        assert_eq!(shared.len(), 2);
+    }
+}
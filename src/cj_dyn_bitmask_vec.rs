@@ -0,0 +1,455 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+/// Number of u64 words needed to hold width bits.
+#[inline]
+fn words_for(width: usize) -> usize {
+    width.div_ceil(64)
+}
+
+/// DynMask is a bitmask whose width is chosen at runtime.<br>
+///
+/// Used with DynBitmaskVec when the number of flags isn't known at compile time.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut mask = DynMask::new(300);
+/// mask.set_bit(299, true);
+/// assert!(mask.has_bit(299));
+/// assert_eq!(mask, DynMask::from_bits(300, &[299]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DynMask {
+    width: usize,
+    words: Vec<u64>,
+}
+
+impl DynMask {
+    /// Creates a mask of width bits with no bits set.
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            words: vec![0; words_for(width)],
+        }
+    }
+
+    /// Creates a mask of width bits with the given bits set. Panics if a bit is out of range.
+    pub fn from_bits(width: usize, bits: &[usize]) -> Self {
+        let mut x = Self::new(width);
+        for &bit in bits {
+            x.set_bit(bit, true);
+        }
+        x
+    }
+
+    /// Returns the number of bits in the mask.
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the words of the mask, lowest word first.
+    #[inline]
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Sets or clears a bit. Panics if bit is out of range.
+    #[inline]
+    pub fn set_bit(&mut self, bit: usize, value: bool) {
+        assert!(
+            bit < self.width,
+            "bit {bit} out of range for width {}",
+            self.width
+        );
+        set_word_bit(&mut self.words, bit, value);
+    }
+
+    /// Returns true if the given bit is set. Bits out of range are never set.
+    #[inline]
+    pub fn has_bit(&self, bit: usize) -> bool {
+        bit < self.width && word_bit(&self.words, bit)
+    }
+
+    /// Returns the number of set bits
+    #[inline]
+    pub fn count_bits(&self) -> u32 {
+        self.words.iter().map(|x| x.count_ones()).sum()
+    }
+
+    /// Returns true if all set flags in mask are set in self. Panics if the widths differ.
+    #[inline]
+    pub fn matches_mask(&self, mask: &DynMask) -> bool {
+        assert_width(self.width, mask.width);
+        words_match(&self.words, &mask.words)
+    }
+}
+
+#[inline]
+fn word_bit(words: &[u64], bit: usize) -> bool {
+    (words[bit / 64] >> (bit % 64)) & 1 == 1
+}
+
+#[inline]
+fn set_word_bit(words: &mut [u64], bit: usize, value: bool) {
+    if value {
+        words[bit / 64] |= 1 << (bit % 64);
+    } else {
+        words[bit / 64] &= !(1 << (bit % 64));
+    }
+}
+
+#[inline]
+fn words_match(words: &[u64], mask: &[u64]) -> bool {
+    words.iter().zip(mask).all(|(a, m)| a & m == *m)
+}
+
+#[inline]
+fn assert_width(width: usize, other: usize) {
+    assert!(
+        width == other,
+        "mask width {other} does not match width {width}"
+    );
+}
+
+/// DynBitmaskVec pairs T with bitmasks whose width is chosen at runtime.<br>
+///
+/// Bitmasks are kept in a single bit matrix of width bits per element, so a plugin system can
+/// size its flags after loading plugins. Every DynMask passed in must have the vec's width.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let width = 200; // e.g. the number of registered plugins
+/// let mut v = DynBitmaskVec::<i32>::new(width);
+/// v.push_with_mask(&DynMask::from_bits(width, &[1, 150]), 100);
+/// v.push_with_mask(&DynMask::from_bits(width, &[150]), 101);
+/// v.push(102);
+///
+/// let mask = DynMask::from_bits(width, &[150]);
+/// assert_eq!(v.count_matching(&mask), 2);
+/// assert_eq!(v.indices_matching(&mask), vec![0, 1]);
+/// ```
+pub struct DynBitmaskVec<T> {
+    width: usize,
+    stride: usize,
+    masks: Vec<u64>,
+    items: Vec<T>,
+}
+
+impl<T> DynBitmaskVec<T> {
+    /// Constructs a new, empty vec whose bitmasks are width bits wide.
+    pub fn new(width: usize) -> Self {
+        Self::with_capacity(width, 0)
+    }
+
+    /// Constructs a new, empty vec whose bitmasks are width bits wide, with at least the specified capacity.
+    pub fn with_capacity(width: usize, capacity: usize) -> Self {
+        let stride = words_for(width);
+        Self {
+            width,
+            stride,
+            masks: Vec::with_capacity(capacity * stride),
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of bits in each bitmask.
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.masks.clear();
+        self.items.clear();
+    }
+
+    /// Returns the T buffer.
+    #[inline]
+    pub fn items(&self) -> &[T] {
+        self.items.as_slice()
+    }
+
+    /// Returns the T buffer mutably.
+    #[inline]
+    pub fn items_mut(&mut self) -> &mut [T] {
+        self.items.as_mut_slice()
+    }
+
+    #[inline]
+    fn words(&self, index: usize) -> &[u64] {
+        &self.masks[index * self.stride..(index + 1) * self.stride]
+    }
+
+    #[inline]
+    fn words_mut(&mut self, index: usize) -> &mut [u64] {
+        &mut self.masks[index * self.stride..(index + 1) * self.stride]
+    }
+
+    /// Pushes T and a bitmask with no bits set.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.masks.resize(self.masks.len() + self.stride, 0);
+        self.items.push(value);
+    }
+
+    /// Pushes T and the supplied bitmask. Panics if the mask width differs from the vec's width.
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: &DynMask, value: T) {
+        assert_width(self.width, bitmask.width);
+        self.masks.extend_from_slice(&bitmask.words);
+        self.items.push(value);
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.pop_with_mask().map(|x| x.1)
+    }
+
+    /// Pops T along with its bitmask from the vec.
+    pub fn pop_with_mask(&mut self) -> Option<(DynMask, T)> {
+        let item = self.items.pop()?;
+        let words = self.masks.split_off(self.items.len() * self.stride);
+        Some((
+            DynMask {
+                width: self.width,
+                words,
+            },
+            item,
+        ))
+    }
+
+    /// Removes and returns T and its bitmask at position index, shifting all elements after it to the left.
+    pub fn remove_with_mask(&mut self, index: usize) -> (DynMask, T) {
+        let item = self.items.remove(index);
+        let words = self
+            .masks
+            .drain(index * self.stride..(index + 1) * self.stride)
+            .collect();
+        (
+            DynMask {
+                width: self.width,
+                words,
+            },
+            item,
+        )
+    }
+
+    /// Removes and returns T at position index, shifting all elements after it to the left.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.remove_with_mask(index).1
+    }
+
+    /// Returns T at index, or None if out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    /// Returns a copy of the bitmask at index, or None if out of bounds.
+    pub fn mask(&self, index: usize) -> Option<DynMask> {
+        if index < self.items.len() {
+            Some(DynMask {
+                width: self.width,
+                words: self.words(index).to_vec(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the bitmask at index. Panics if index is out of bounds or the mask width differs.
+    #[inline]
+    pub fn set_mask(&mut self, index: usize, bitmask: &DynMask) {
+        assert_width(self.width, bitmask.width);
+        self.words_mut(index).copy_from_slice(&bitmask.words);
+    }
+
+    /// Returns true if bit is set in the bitmask at index. Panics if index is out of bounds.
+    #[inline]
+    pub fn has_bit(&self, index: usize, bit: usize) -> bool {
+        assert!(index < self.items.len(), "index out of bounds");
+        bit < self.width && word_bit(self.words(index), bit)
+    }
+
+    /// Sets or clears bit in the bitmask at index. Panics if index or bit is out of range.
+    #[inline]
+    pub fn set_bit(&mut self, index: usize, bit: usize, value: bool) {
+        assert!(
+            bit < self.width,
+            "bit {bit} out of range for width {}",
+            self.width
+        );
+        set_word_bit(self.words_mut(index), bit, value);
+    }
+
+    /// Returns an iter over T.
+    #[inline]
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Returns an iter for mutable iteration over T.
+    #[inline]
+    pub fn iter_mut(&mut self) -> core::slice::IterMut<'_, T> {
+        self.items.iter_mut()
+    }
+
+    /// Returns a DynBitmaskVecIterMatching for iterating over the (index, T) pairs whose bitmask matches mask.
+    /// Panics if the mask width differs from the vec's width.
+    #[inline]
+    pub fn iter_matching<'a>(&'a self, mask: &'a DynMask) -> DynBitmaskVecIterMatching<'a, T> {
+        assert_width(self.width, mask.width);
+        DynBitmaskVecIterMatching::new(self, mask)
+    }
+
+    /// Returns the number of elements whose bitmask matches mask. Panics if the mask width differs.
+    pub fn count_matching(&self, mask: &DynMask) -> usize {
+        self.iter_matching(mask).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask matches mask. Panics if the mask width differs.
+    pub fn indices_matching(&self, mask: &DynMask) -> Vec<usize> {
+        self.iter_matching(mask).map(|(i, _)| i).collect()
+    }
+}
+
+impl<T> Index<usize> for DynBitmaskVec<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.items[index]
+    }
+}
+
+impl<T> IndexMut<usize> for DynBitmaskVec<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.items[index]
+    }
+}
+
+// =================================================================================================
+/// Iter that returns (index, T) pairs whose bitmask matches mask.
+pub struct DynBitmaskVecIterMatching<'a, T> {
+    inner: &'a DynBitmaskVec<T>,
+    mask: &'a DynMask,
+    pos: usize,
+}
+
+impl<'a, T> DynBitmaskVecIterMatching<'a, T> {
+    pub fn new(v: &'a DynBitmaskVec<T>, mask: &'a DynMask) -> Self {
+        Self {
+            inner: v,
+            mask,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, T> Iterator for DynBitmaskVecIterMatching<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.inner.len() {
+            let index = self.pos;
+            self.pos += 1;
+            if words_match(self.inner.words(index), &self.mask.words) {
+                return Some((index, &self.inner.items[index]));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_dyn_bitmask_vec::{DynBitmaskVec, DynMask};
+
+    #[test]
+    fn test_dyn_mask() {
+        let mut x = DynMask::new(70);
+        x.set_bit(69, true);
+        x.set_bit(3, true);
+
+        assert_eq!(x.width(), 70);
+        assert_eq!(x.words(), &[0b1000, 0b100000]);
+        assert_eq!(x.count_bits(), 2);
+        assert!(x.has_bit(69));
+        assert!(!x.has_bit(200));
+        assert!(x.matches_mask(&DynMask::from_bits(70, &[69])));
+        assert!(!x.matches_mask(&DynMask::from_bits(70, &[68])));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_dyn_mask_width_mismatch() {
+        let mut v = DynBitmaskVec::<i32>::new(70);
+        v.push_with_mask(&DynMask::new(64), 100);
+    }
+
+    #[test]
+    fn test_dyn_bitmask_vec_push_pop() {
+        let mut v = DynBitmaskVec::<i32>::new(100);
+        v.push_with_mask(&DynMask::from_bits(100, &[0, 99]), 100);
+        v.push(101);
+
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0], 100);
+        assert!(v.has_bit(0, 99));
+        assert!(!v.has_bit(1, 99));
+        assert_eq!(v.pop(), Some(101));
+
+        let (mask, item) = v.pop_with_mask().unwrap();
+        assert_eq!(item, 100);
+        assert_eq!(mask, DynMask::from_bits(100, &[0, 99]));
+        assert!(v.pop_with_mask().is_none());
+    }
+
+    #[test]
+    fn test_dyn_bitmask_vec_mutate() {
+        let mut v = DynBitmaskVec::<i32>::new(130);
+        v.push(100);
+        v.push(101);
+        v.push(102);
+
+        v.set_bit(1, 129, true);
+        v.set_mask(2, &DynMask::from_bits(130, &[5]));
+        assert_eq!(v.mask(1), Some(DynMask::from_bits(130, &[129])));
+        assert_eq!(v.mask(3), None);
+
+        let (mask, item) = v.remove_with_mask(1);
+        assert_eq!((mask, item), (DynMask::from_bits(130, &[129]), 101));
+        assert_eq!(v.items(), &[100, 102]);
+        assert_eq!(v.mask(1), Some(DynMask::from_bits(130, &[5])));
+    }
+
+    #[test]
+    fn test_dyn_bitmask_vec_matching() {
+        let mut v = DynBitmaskVec::<i32>::new(65);
+        v.push_with_mask(&DynMask::from_bits(65, &[1]), 100);
+        v.push_with_mask(&DynMask::from_bits(65, &[1, 64]), 101);
+        v.push_with_mask(&DynMask::from_bits(65, &[64]), 102);
+
+        let mask = DynMask::from_bits(65, &[64]);
+        assert_eq!(v.count_matching(&mask), 2);
+        assert_eq!(v.indices_matching(&mask), vec![1, 2]);
+        let x: Vec<_> = v.iter_matching(&mask).map(|(_, x)| *x).collect();
+        assert_eq!(x, vec![101, 102]);
+        assert_eq!(v.count_matching(&DynMask::new(65)), 3);
+    }
+}
@@ -0,0 +1,35 @@
+use core::fmt::{Display, Formatter};
+
+/// Error returned by the fallible BitmaskVec methods, such as try_remove() and
+/// try_insert_with_mask(). The vec is left unchanged when one is returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The index is past the end of the vec.
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The bitmask has bits outside the schema set with set_schema().
+    OutsideSchema,
+    /// The vec could not allocate room for another element.
+    AllocFailed,
+    /// The StateMachine of a BitmaskStateVec does not allow the move of the element at index.
+    IllegalTransition { index: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for length {len}")
+            }
+            Error::OutsideSchema => write!(f, "bitmask has bits outside the schema"),
+            Error::AllocFailed => write!(f, "memory allocation failed"),
+            Error::IllegalTransition { index } => {
+                write!(
+                    f,
+                    "state machine does not allow the move of element {index}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
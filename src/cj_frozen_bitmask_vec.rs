@@ -0,0 +1,243 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMatching, BitmaskVecIterWithMask,
+};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::Index;
+
+/// FrozenBitmaskVec is an immutable snapshot of a BitmaskVec.<br>
+///
+/// Items are held in an `Arc<[BitmaskItem<B, T>]>`, so clones are cheap and can be
+/// shared across threads for read mostly workloads.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(0b00000000, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000011, 102);
+///
+/// let frozen = v.freeze();
+/// let shared = frozen.clone();
+/// let handle = std::thread::spawn(move || shared.count_matching(&0b00000010));
+/// assert_eq!(handle.join().unwrap(), 2);
+/// assert_eq!(frozen[2], 102);
+/// ```
+pub struct FrozenBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: Arc<[BitmaskItem<B, T>]>,
+}
+
+impl<'a, B, T> FrozenBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        &self.inner
+    }
+
+    /// Returns T at index, or None if out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.inner.get(index).map(|x| &x.item)
+    }
+
+    /// Returns the BitmaskItem at index, or None if out of bounds.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<&BitmaskItem<B, T>> {
+        self.inner.get(index)
+    }
+
+    /// Returns true if both handles share the same underlying items.
+    #[inline]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    /// * this iter excludes bitmask. Use iter_with_mask() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecIterMatching<'a, B, T> {
+        BitmaskVecIterMatching::new(self.inner.iter(), mask)
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.inner.iter().filter(|x| x.matches_mask(mask)).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask matches mask.
+    pub fn indices_matching(&self, mask: &'a B) -> Vec<usize> {
+        self.inner
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.matches_mask(mask))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Copies the items into a new, mutable BitmaskVec.
+    pub fn to_bitmask_vec(&self) -> BitmaskVec<B, T>
+    where
+        T: Clone,
+    {
+        let mut v = BitmaskVec::with_capacity(self.len());
+        v.extend_from_slice(&self.inner);
+        v
+    }
+}
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    /// Converts the vector into an immutable FrozenBitmaskVec that can be cheaply cloned and shared.
+    #[inline]
+    pub fn freeze(self) -> FrozenBitmaskVec<B, T> {
+        FrozenBitmaskVec {
+            inner: Arc::from(self.into_boxed_slice()),
+        }
+    }
+}
+
+impl<B, T> Clone for FrozenBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<B, T> Index<usize> for FrozenBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_frozen_bitmask_vec::FrozenBitmaskVec;
+
+    #[test]
+    fn test_frozen_bitmask_vec_freeze() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000100, 101);
+
+        let v: FrozenBitmaskVec<u8, i32> = v.freeze();
+        assert_eq!(v.len(), 2);
+        assert_eq!(v[0], 100);
+        assert_eq!(v.get(1), Some(&101));
+        assert_eq!(v.get(2), None);
+        assert_eq!(v.get_with_mask(1).unwrap().bitmask, 0b00000100);
+    }
+
+    #[test]
+    fn test_frozen_bitmask_vec_clone_shares() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+
+        let v = v.freeze();
+        let v2 = v.clone();
+        assert!(v.ptr_eq(&v2));
+    }
+
+    #[test]
+    fn test_frozen_bitmask_vec_iter() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        let v = v.freeze();
+        assert_eq!(v.iter().sum::<i32>(), 303);
+        assert_eq!(v.iter_with_mask().count(), 3);
+
+        let mut z = v.iter_with_mask();
+        assert_eq!(z.filter_mask(&0b00000010).unwrap().item, 101);
+        assert_eq!(z.filter_mask(&0b00000010).unwrap().item, 102);
+        assert!(z.filter_mask(&0b00000010).is_none());
+    }
+
+    #[test]
+    fn test_frozen_bitmask_vec_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000100, 101);
+        v.push_with_mask(0b00000011, 102);
+
+        let v = v.freeze();
+        assert_eq!(v.count_matching(&0b00000010), 2);
+        assert_eq!(v.indices_matching(&0b00000010), vec![0, 2]);
+        assert_eq!(v.iter_matching(&0b00000100).next().unwrap().item, 101);
+    }
+
+    #[test]
+    fn test_frozen_bitmask_vec_threads() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000010, 100);
+        v.push_with_mask(0b00000001, 101);
+
+        let v = v.freeze();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let v = v.clone();
+                std::thread::spawn(move || v.count_matching(&0b00000010))
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_frozen_bitmask_vec_to_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push(100);
+
+        let v = v.freeze();
+        let mut v2 = v.to_bitmask_vec();
+        v2.push(101);
+        assert_eq!(v2.len(), 2);
+        assert_eq!(v.len(), 1);
+    }
+}
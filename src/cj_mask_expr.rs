@@ -0,0 +1,814 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_counted_vec::BitmaskCountedVec;
+use crate::cj_bitmask_indexed_vec::BitmaskIndexedVec;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_slab::{BitmaskSlab, SlabKey};
+use crate::cj_bitmask_summary_vec::BitmaskSummaryVec;
+use crate::cj_bitmask_tombstone_vec::BitmaskTombstoneVec;
+use crate::cj_bitmask_tracked_vec::BitmaskTrackedVec;
+use crate::cj_bitmask_vec::BitmaskVec;
+use crate::cj_bitmask_vec_deque::BitmaskVecDeque;
+use crate::cj_bitmask_vec_rle::BitmaskVecRle;
+use crate::cj_bitmask_vec_soa::BitmaskVecSoA;
+use crate::cj_cow_bitmask_vec::CowBitmaskVec;
+use crate::cj_frozen_bitmask_vec::FrozenBitmaskVec;
+use crate::cj_persistent_bitmask_vec::PersistentBitmaskVec;
+use crate::cj_sorted_bitmask_vec::SortedBitmaskVec;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::fmt::{Display, Formatter};
+use core::ops::{BitAnd, BitOr, Not};
+use core::slice::Iter;
+
+/// MaskExpr is a compound boolean filter over bitmasks.<br>
+///
+/// Build one from All / Any leaves combined with `&`, `|` and `!`, then evaluate it per element
+/// with matches() or the *_matching_expr() methods.<br>
+/// BitmaskVec, BitmaskCountedVec, BitmaskIndexedVec, BitmaskSummaryVec, BitmaskTrackedVec,
+/// CowBitmaskVec, FrozenBitmaskVec, SortedBitmaskVec, BitmaskVecDeque, BitmaskVecRle,
+/// BitmaskVecSoA, BitmaskSlab, BitmaskGenSlab, BitmaskTombstoneVec and PersistentBitmaskVec have
+/// them. The other containers (DynBitmaskVec, BitmaskGroupMap, BitmaskSecondaryMap, BitmaskWorld,
+/// ConcurrentBitmaskVec and BitmaskPriorityQueue) don't; filter those with matches() or
+/// BitmaskItem::matches_expr().
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// use MaskExpr::*;
+/// const RUNNING: u8 = 0b00000001;
+/// const FAILED: u8 = 0b00000010;
+/// const CANCELLED: u8 = 0b00000100;
+///
+/// let mut v = BitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(RUNNING, 100);
+/// v.push_with_mask(RUNNING | FAILED, 101);
+/// v.push_with_mask(RUNNING | CANCELLED, 102);
+/// v.push_with_mask(0, 103);
+///
+/// let healthy = All(RUNNING) & !Any(FAILED | CANCELLED);
+/// assert_eq!(v.indices_matching_expr(&healthy), vec![0]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MaskExpr<B> {
+    /// Every bit of the mask is set <i>(bitmask & mask) == mask</i>
+    All(B),
+    /// At least one bit of the mask is set <i>(bitmask & mask) != 0</i>
+    Any(B),
+    /// The bitmask is exactly the mask <i>bitmask == mask</i>
+    Exact(B),
+    /// The inner expression doesn't match
+    Not(Box<MaskExpr<B>>),
+    /// Both expressions match
+    And(Box<MaskExpr<B>>, Box<MaskExpr<B>>),
+    /// Either expression matches
+    Or(Box<MaskExpr<B>>, Box<MaskExpr<B>>),
+}
+
+impl<B> MaskExpr<B>
+where
+    B: Copy + Default + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns true if bitmask satisfies the expression.
+    pub fn matches(&self, bitmask: &B) -> bool {
+        match self {
+            MaskExpr::All(mask) => (*bitmask & *mask) == *mask,
+            MaskExpr::Any(mask) => (*bitmask & *mask) != B::default(),
+            MaskExpr::Exact(mask) => *bitmask == *mask,
+            MaskExpr::Not(x) => !x.matches(bitmask),
+            MaskExpr::And(a, b) => a.matches(bitmask) && b.matches(bitmask),
+            MaskExpr::Or(a, b) => a.matches(bitmask) || b.matches(bitmask),
+        }
+    }
+}
+
+impl<B> BitAnd for MaskExpr<B> {
+    type Output = Self;
+
+    #[inline]
+    fn bitand(self, rhs: Self) -> Self::Output {
+        MaskExpr::And(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl<B> BitOr for MaskExpr<B> {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        MaskExpr::Or(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl<B> Not for MaskExpr<B> {
+    type Output = Self;
+
+    #[inline]
+    fn not(self) -> Self::Output {
+        MaskExpr::Not(Box::new(self))
+    }
+}
+
+/// Error returned when a mask expression string can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskExprError {
+    /// A character that doesn't fit the grammar was found at byte offset pos.
+    UnexpectedChar { pos: usize, ch: char },
+    /// The input ended where a flag name, '(' or ')' was expected.
+    UnexpectedEnd,
+    /// The flag name starting at byte offset pos isn't registered.
+    UnknownFlag { pos: usize, name: String },
+    /// The operator or `(` at byte offset pos nests deeper than MAX_EXPR_DEPTH.
+    TooDeep { pos: usize },
+}
+
+impl Display for MaskExprError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MaskExprError::UnexpectedChar { pos, ch } => {
+                write!(f, "unexpected '{ch}' at position {pos}")
+            }
+            MaskExprError::UnexpectedEnd => f.write_str("unexpected end of expression"),
+            MaskExprError::UnknownFlag { pos, name } => {
+                write!(f, "unknown flag '{name}' at position {pos}")
+            }
+            MaskExprError::TooDeep { pos } => {
+                write!(
+                    f,
+                    "expression nested deeper than {MAX_EXPR_DEPTH} at position {pos}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MaskExprError {}
+
+/// The deepest nesting of operators and parentheses parse_with() accepts, so parsing and
+/// evaluating untrusted input can't overflow the stack. Each `&` or `|` of a chain nests one
+/// level deeper, as the chain builds a left-nested tree.
+pub const MAX_EXPR_DEPTH: usize = 128;
+
+/// Recursive descent parser for mask expressions. `!` binds tightest, then `&`, then `|`.
+struct Parser<'s, F> {
+    src: &'s str,
+    pos: usize,
+    depth: usize,
+    lookup: F,
+}
+
+impl<'s, B, F> Parser<'s, F>
+where
+    F: FnMut(&str) -> Option<B>,
+{
+    fn skip_ws(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ws();
+        self.src[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), MaskExprError> {
+        match self.peek() {
+            Some(c) if c == ch => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(MaskExprError::UnexpectedChar {
+                pos: self.pos,
+                ch: c,
+            }),
+            None => Err(MaskExprError::UnexpectedEnd),
+        }
+    }
+
+    /// Steps over the operator or `(` at pos into one more level of nesting.
+    fn enter(&mut self) -> Result<(), MaskExprError> {
+        if self.depth == MAX_EXPR_DEPTH {
+            return Err(MaskExprError::TooDeep { pos: self.pos });
+        }
+        self.depth += 1;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<MaskExpr<B>, MaskExprError> {
+        let mut expr = self.parse_and()?;
+        let mut ops = 0;
+        while self.peek() == Some('|') {
+            self.enter()?;
+            ops += 1;
+            expr = expr | self.parse_and()?;
+        }
+        self.depth -= ops;
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<MaskExpr<B>, MaskExprError> {
+        let mut expr = self.parse_unary()?;
+        let mut ops = 0;
+        while self.peek() == Some('&') {
+            self.enter()?;
+            ops += 1;
+            expr = expr & self.parse_unary()?;
+        }
+        self.depth -= ops;
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<MaskExpr<B>, MaskExprError> {
+        match self.peek() {
+            Some('!') => {
+                self.enter()?;
+                let expr = !self.parse_unary()?;
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Some('(') => {
+                self.enter()?;
+                let expr = self.parse_or()?;
+                self.expect(')')?;
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                let start = self.pos;
+                let len = self.src[start..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(self.src.len() - start);
+                self.pos += len;
+                let name = &self.src[start..self.pos];
+                match (self.lookup)(name) {
+                    Some(mask) => Ok(MaskExpr::All(mask)),
+                    None => Err(MaskExprError::UnknownFlag {
+                        pos: start,
+                        name: String::from(name),
+                    }),
+                }
+            }
+            Some(c) => Err(MaskExprError::UnexpectedChar {
+                pos: self.pos,
+                ch: c,
+            }),
+            None => Err(MaskExprError::UnexpectedEnd),
+        }
+    }
+}
+
+impl<B> MaskExpr<B> {
+    /// Parses an expression such as `"QUEUED & !(FAILED | CANCELLED)"`.<br>
+    /// Flag names are resolved to masks by lookup and become All(mask) leaves. `!` binds
+    /// tightest, then `&`, then `|`, and parentheses group. Nesting deeper than MAX_EXPR_DEPTH,
+    /// counting each operator of a chain, returns MaskExprError::TooDeep.
+    pub fn parse_with<F>(src: &str, lookup: F) -> Result<Self, MaskExprError>
+    where
+        F: FnMut(&str) -> Option<B>,
+    {
+        let mut parser = Parser {
+            src,
+            pos: 0,
+            depth: 0,
+            lookup,
+        };
+        let expr = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(ch) => Err(MaskExprError::UnexpectedChar {
+                pos: parser.pos,
+                ch,
+            }),
+        }
+    }
+}
+
+impl<B, T> BitmaskItem<B, T>
+where
+    B: Bitflag + Copy + Default + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns true if bitmask satisfies expr.
+    #[inline]
+    pub fn matches_expr(&self, expr: &MaskExpr<B>) -> bool {
+        expr.matches(&self.bitmask)
+    }
+}
+
+macro_rules! impl_slice_matching_expr {
+    ($t:ident<B, T $(, $k:ident: $kb:path)?> $(, $bb:path)?) => {
+        impl<'a, B, T $(, $k)?> $t<B, T $(, $k)?>
+        where
+            B: Bitflag
+                + CjMatchesMask<'a, B>
+                + Clone
+                + Default
+                + Copy
+                + PartialEq
+                + BitAnd<Output = B>
+                $(+ $bb)?,
+            $($k: $kb,)?
+        {
+            /// Returns a BitmaskVecIterMatchingExpr for iterating over the BitmaskItems whose bitmask satisfies expr.
+            #[inline]
+            pub fn iter_matching_expr<'e>(
+                &'e self,
+                expr: &'e MaskExpr<B>,
+            ) -> BitmaskVecIterMatchingExpr<'e, B, T> {
+                BitmaskVecIterMatchingExpr::new(self.as_slice().iter(), expr)
+            }
+
+            /// Returns the number of elements whose bitmask satisfies expr.
+            #[inline]
+            pub fn count_matching_expr(&self, expr: &MaskExpr<B>) -> usize {
+                self.iter_matching_expr(expr).count()
+            }
+
+            /// Returns the indices of the elements whose bitmask satisfies expr.
+            pub fn indices_matching_expr(&self, expr: &MaskExpr<B>) -> Vec<usize> {
+                self.as_slice()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, x)| expr.matches(&x.bitmask))
+                    .map(|(i, _)| i)
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_slice_matching_expr!(BitmaskVec<B, T>);
+impl_slice_matching_expr!(BitmaskCountedVec<B, T>, BitmaskBits);
+impl_slice_matching_expr!(BitmaskIndexedVec<B, T>, BitmaskBits);
+impl_slice_matching_expr!(BitmaskSummaryVec<B, T>, BitmaskBits);
+impl_slice_matching_expr!(BitmaskTrackedVec<B, T>);
+impl_slice_matching_expr!(CowBitmaskVec<B, T>);
+impl_slice_matching_expr!(FrozenBitmaskVec<B, T>);
+impl_slice_matching_expr!(SortedBitmaskVec<B, T, K: Ord>);
+
+impl<'a, B, T> BitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+{
+    /// Parses a mask expression, resolving flag names through the names registered with name_bit().
+    /// ```
+    /// # use cj_bitmask_vec::prelude::*;
+    /// let mut v = BitmaskVec::<u8, i32>::new();
+    /// v.name_bit(0, "QUEUED");
+    /// v.name_bit(1, "FAILED");
+    /// v.name_bit(2, "CANCELLED");
+    /// v.push_with_mask(0b00000001, 100);
+    /// v.push_with_mask(0b00000011, 101);
+    /// v.push_with_mask(0b00000101, 102);
+    ///
+    /// let expr = v.parse_expr("QUEUED & !(FAILED | CANCELLED)").unwrap();
+    /// assert_eq!(v.indices_matching_expr(&expr), vec![0]);
+    /// assert!(v.parse_expr("QUEUED & RUNNING").is_err());
+    /// ```
+    pub fn parse_expr(&self, src: &str) -> Result<MaskExpr<B>, MaskExprError> {
+        MaskExpr::parse_with(src, |name| self.mask_for(&[name]))
+    }
+}
+
+impl<'a, B, T> BitmaskVecDeque<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns an iterator over the BitmaskItems whose bitmask satisfies expr, front to back.
+    #[inline]
+    pub fn iter_matching_expr(
+        &'a self,
+        expr: &'a MaskExpr<B>,
+    ) -> impl Iterator<Item = &'a BitmaskItem<B, T>> + 'a {
+        self.iter_with_mask()
+            .filter(move |x| expr.matches(&x.bitmask))
+    }
+
+    /// Returns the number of items whose bitmask satisfies expr.
+    #[inline]
+    pub fn count_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> usize {
+        self.iter_matching_expr(expr).count()
+    }
+}
+
+impl<'a, B, T> BitmaskVecRle<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns an iterator over the (bitmask, T) pairs whose bitmask satisfies expr.
+    #[inline]
+    pub fn iter_matching_expr(
+        &'a self,
+        expr: &'a MaskExpr<B>,
+    ) -> impl Iterator<Item = (&'a B, &'a T)> + 'a {
+        self.iter_with_mask().filter(move |(x, _)| expr.matches(x))
+    }
+
+    /// Returns the number of elements whose bitmask satisfies expr.
+    #[inline]
+    pub fn count_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> usize {
+        self.iter_matching_expr(expr).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask satisfies expr.
+    pub fn indices_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> Vec<usize> {
+        self.iter_with_mask()
+            .enumerate()
+            .filter(|(_, (x, _))| expr.matches(x))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl<'a, B, T> BitmaskVecSoA<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns an iterator over the (bitmask, T) pairs whose bitmask satisfies expr.
+    #[inline]
+    pub fn iter_matching_expr<'e>(
+        &'e self,
+        expr: &'e MaskExpr<B>,
+    ) -> impl Iterator<Item = (&'e B, &'e T)> + 'e {
+        self.masks()
+            .iter()
+            .zip(self.items())
+            .filter(move |(x, _)| expr.matches(x))
+    }
+
+    /// Returns the number of elements whose bitmask satisfies expr. Only the bitmask buffer is scanned.
+    #[inline]
+    pub fn count_matching_expr(&self, expr: &MaskExpr<B>) -> usize {
+        self.masks().iter().filter(|x| expr.matches(x)).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask satisfies expr. Only the bitmask buffer is scanned.
+    pub fn indices_matching_expr(&self, expr: &MaskExpr<B>) -> Vec<usize> {
+        self.masks()
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| expr.matches(x))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+impl<'a, B, T, K> BitmaskSlab<B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+    K: SlabKey,
+{
+    /// Returns an iterator over the (key, BitmaskItem) pairs whose bitmask satisfies expr.
+    #[inline]
+    pub fn iter_matching_expr(
+        &'a self,
+        expr: &'a MaskExpr<B>,
+    ) -> impl Iterator<Item = (K, &'a BitmaskItem<B, T>)> + 'a {
+        self.iter().filter(move |(_, x)| expr.matches(&x.bitmask))
+    }
+
+    /// Returns the number of entries whose bitmask satisfies expr.
+    #[inline]
+    pub fn count_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> usize {
+        self.iter_matching_expr(expr).count()
+    }
+}
+
+impl<'a, B, T> BitmaskTombstoneVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns an iterator over (index, BitmaskItem) of the live entries whose bitmask satisfies expr.
+    #[inline]
+    pub fn iter_matching_expr(
+        &'a self,
+        expr: &'a MaskExpr<B>,
+    ) -> impl Iterator<Item = (usize, &'a BitmaskItem<B, T>)> + 'a {
+        self.iter_with_mask()
+            .filter(move |(_, x)| expr.matches(&x.bitmask))
+    }
+
+    /// Returns the number of live entries whose bitmask satisfies expr.
+    #[inline]
+    pub fn count_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> usize {
+        self.iter_matching_expr(expr).count()
+    }
+
+    /// Returns the indices of the live entries whose bitmask satisfies expr.
+    /// Indices stay valid until the next sweep().
+    #[inline]
+    pub fn indices_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> Vec<usize> {
+        self.iter_matching_expr(expr).map(|(i, _)| i).collect()
+    }
+}
+
+impl<'a, B, T> PersistentBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Copy + PartialEq + BitAnd<Output = B>,
+{
+    /// Returns an iterator over the BitmaskItems whose bitmask satisfies expr.
+    #[inline]
+    pub fn iter_matching_expr(
+        &'a self,
+        expr: &'a MaskExpr<B>,
+    ) -> impl Iterator<Item = &'a BitmaskItem<B, T>> + 'a {
+        self.iter_with_mask()
+            .filter(move |x| expr.matches(&x.bitmask))
+    }
+
+    /// Returns the number of elements whose bitmask satisfies expr.
+    #[inline]
+    pub fn count_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> usize {
+        self.iter_matching_expr(expr).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask satisfies expr.
+    pub fn indices_matching_expr(&'a self, expr: &'a MaskExpr<B>) -> Vec<usize> {
+        self.iter_with_mask()
+            .enumerate()
+            .filter(|(_, x)| expr.matches(&x.bitmask))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask, for items whose bitmask satisfies a MaskExpr.
+pub struct BitmaskVecIterMatchingExpr<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: Iter<'a, BitmaskItem<B, T>>,
+    expr: &'a MaskExpr<B>,
+}
+
+impl<'a, B, T> BitmaskVecIterMatchingExpr<'a, B, T>
+where
+    B: Bitflag,
+{
+    pub fn new(i: Iter<'a, BitmaskItem<B, T>>, expr: &'a MaskExpr<B>) -> Self {
+        Self { inner: i, expr }
+    }
+
+    /// Returns the next item satisfying the expression without consuming it.
+    /// Non-matching items before it are skipped.
+    pub fn peek_matching(&mut self) -> Option<&'a BitmaskItem<B, T>>
+    where
+        B: Copy + Default + PartialEq + BitAnd<Output = B>,
+    {
+        let expr = self.expr;
+        let rest = self.inner.as_slice();
+        let i = rest.iter().position(|x| expr.matches(&x.bitmask))?;
+        self.inner = rest[i..].iter();
+        Some(&rest[i])
+    }
+}
+
+impl<'a, B, T> Iterator for BitmaskVecIterMatchingExpr<'a, B, T>
+where
+    B: Bitflag + Copy + Default + PartialEq + BitAnd<Output = B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let expr = self.expr;
+        self.inner.by_ref().find(|x| expr.matches(&x.bitmask))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_gen_slab::BitmaskGenSlab;
+    use crate::cj_bitmask_indexed_vec::BitmaskIndexedVec;
+    use crate::cj_bitmask_item::BitmaskItem;
+    use crate::cj_bitmask_tombstone_vec::BitmaskTombstoneVec;
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_bitmask_vec_deque::BitmaskVecDeque;
+    use crate::cj_bitmask_vec_rle::BitmaskVecRle;
+    use crate::cj_bitmask_vec_soa::BitmaskVecSoA;
+    use crate::cj_mask_expr::MaskExpr::{self, *};
+    use crate::cj_mask_expr::{MaskExprError, MAX_EXPR_DEPTH};
+    use crate::cj_persistent_bitmask_vec::PersistentBitmaskVec;
+    use crate::cj_sorted_bitmask_vec::SortedBitmaskVec;
+
+    #[test]
+    fn test_mask_expr_matches() {
+        assert!(All(0b00000110u8).matches(&0b00000111));
+        assert!(!All(0b00000110u8).matches(&0b00000101));
+        assert!(Any(0b00000110u8).matches(&0b00000100));
+        assert!(!Any(0b00000110u8).matches(&0b00000001));
+        assert!(Exact(0b00000110u8).matches(&0b00000110));
+        assert!(!Exact(0b00000110u8).matches(&0b00000111));
+    }
+
+    #[test]
+    fn test_mask_expr_operators() {
+        let e: MaskExpr<u8> = All(0b00000001) & !Any(0b00000110);
+        assert_eq!(
+            e,
+            MaskExpr::And(
+                Box::new(All(0b00000001)),
+                Box::new(MaskExpr::Not(Box::new(Any(0b00000110))))
+            )
+        );
+        assert!(e.matches(&0b00000001));
+        assert!(!e.matches(&0b00000011));
+
+        let e = Exact(0u8) | All(0b10000000);
+        assert!(e.matches(&0));
+        assert!(e.matches(&0b10000001));
+        assert!(!e.matches(&0b00000001));
+        assert!(BitmaskItem::new(0b10000000u8, 1).matches_expr(&e));
+    }
+
+    #[test]
+    fn test_mask_expr_containers() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000101, 102);
+        v.push_with_mask(0b00000000, 103);
+        let e = All(0b00000001) & !Any(0b00000110);
+
+        let x = BitmaskIndexedVec::from(v.clone());
+        assert_eq!(x.indices_matching_expr(&e), vec![0]);
+        let x = SortedBitmaskVec::from(v.clone());
+        assert_eq!(x.count_matching_expr(&e), 1);
+        let x = BitmaskVecRle::from(v.clone());
+        assert_eq!(x.indices_matching_expr(&!e.clone()), vec![1, 2, 3]);
+        let x = BitmaskVecSoA::from(v.clone());
+        let items: Vec<i32> = x.iter_matching_expr(&e).map(|(_, x)| *x).collect();
+        assert_eq!(items, vec![100]);
+        let x = PersistentBitmaskVec::from(v.clone());
+        assert_eq!(x.indices_matching_expr(&e), vec![0]);
+        let x = BitmaskVecDeque::from(v.clone());
+        assert_eq!(x.count_matching_expr(&e), 1);
+
+        let mut x = BitmaskTombstoneVec::from_bitmask_vec(v.clone(), 7);
+        x.mark_removed(0);
+        assert_eq!(x.count_matching_expr(&e), 0);
+        assert_eq!(x.indices_matching_expr(&Any(0b00000100)), vec![2]);
+
+        let mut slab = BitmaskGenSlab::<u8, i32>::new();
+        let a = slab.insert_with_mask(0b00000001, 100);
+        slab.insert_with_mask(0b00000011, 101);
+        let keys: Vec<_> = slab.iter_matching_expr(&e).map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![a]);
+    }
+
+    #[test]
+    fn test_mask_expr_bitmask_vec() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000011, 101);
+        v.push_with_mask(0b00000101, 102);
+        v.push_with_mask(0b00000000, 103);
+
+        let e = All(0b00000001) & !Any(0b00000110);
+        assert_eq!(v.count_matching_expr(&e), 1);
+        assert_eq!(v.indices_matching_expr(&!e), vec![1, 2, 3]);
+
+        let e = Any(0b00000110) | Exact(0);
+        let x: Vec<i32> = v.iter_matching_expr(&e).map(|x| x.item).collect();
+        assert_eq!(x, vec![101, 102, 103]);
+    }
+
+    #[test]
+    fn test_mask_expr_peek_matching() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000100, 102);
+
+        let e = Any(0b00000110);
+        let mut iter = v.iter_matching_expr(&e);
+        assert_eq!(iter.peek_matching().map(|x| x.item), Some(101));
+        assert_eq!(iter.peek_matching().map(|x| x.item), Some(101));
+        assert_eq!(iter.next().map(|x| x.item), Some(101));
+        assert_eq!(iter.next().map(|x| x.item), Some(102));
+        assert!(iter.peek_matching().is_none());
+    }
+
+    #[test]
+    fn test_mask_expr_parse() {
+        let lookup = |name: &str| match name {
+            "A" => Some(0b00000001u8),
+            "B" => Some(0b00000010u8),
+            "C_2" => Some(0b00000100u8),
+            _ => None,
+        };
+
+        assert_eq!(MaskExpr::parse_with("A", lookup), Ok(All(0b00000001)));
+        assert_eq!(
+            MaskExpr::parse_with(" A | B & !C_2 ", lookup),
+            Ok(All(0b00000001) | (All(0b00000010) & !All(0b00000100)))
+        );
+        assert_eq!(
+            MaskExpr::parse_with("!(A | B) & C_2", lookup),
+            Ok(!(All(0b00000001) | All(0b00000010)) & All(0b00000100))
+        );
+    }
+
+    #[test]
+    fn test_mask_expr_parse_errors() {
+        let lookup = |name: &str| if name == "A" { Some(1u8) } else { None };
+
+        assert_eq!(
+            MaskExpr::parse_with("A & X", lookup),
+            Err(MaskExprError::UnknownFlag {
+                pos: 4,
+                name: "X".to_string()
+            })
+        );
+        assert_eq!(
+            MaskExpr::parse_with("(A", lookup),
+            Err(MaskExprError::UnexpectedEnd)
+        );
+        assert_eq!(
+            MaskExpr::parse_with("A A", lookup),
+            Err(MaskExprError::UnexpectedChar { pos: 2, ch: 'A' })
+        );
+        assert_eq!(
+            MaskExpr::parse_with("A & ^", lookup),
+            Err(MaskExprError::UnexpectedChar { pos: 4, ch: '^' })
+        );
+        assert_eq!(
+            MaskExpr::parse_with("", lookup),
+            Err(MaskExprError::UnexpectedEnd)
+        );
+        assert_eq!(
+            MaskExprError::UnknownFlag {
+                pos: 4,
+                name: "X".to_string()
+            }
+            .to_string(),
+            "unknown flag 'X' at position 4"
+        );
+    }
+
+    #[test]
+    fn test_mask_expr_parse_depth() {
+        let lookup = |name: &str| if name == "A" { Some(1u8) } else { None };
+
+        let nested = "!".repeat(MAX_EXPR_DEPTH) + "A";
+        assert!(MaskExpr::parse_with(&nested, lookup).is_ok());
+        let src = "(".repeat(MAX_EXPR_DEPTH) + "A" + &")".repeat(MAX_EXPR_DEPTH);
+        assert!(MaskExpr::parse_with(&src, lookup).is_ok());
+
+        let src = "(".repeat(MAX_EXPR_DEPTH) + "!A" + &")".repeat(MAX_EXPR_DEPTH);
+        assert_eq!(
+            MaskExpr::parse_with(&src, lookup),
+            Err(MaskExprError::TooDeep {
+                pos: MAX_EXPR_DEPTH
+            })
+        );
+        let src = "(".repeat(100_000);
+        assert_eq!(
+            MaskExpr::parse_with(&src, lookup),
+            Err(MaskExprError::TooDeep {
+                pos: MAX_EXPR_DEPTH
+            })
+        );
+    }
+
+    #[test]
+    fn test_mask_expr_parse_chain_depth() {
+        let lookup = |name: &str| if name == "A" { Some(1u8) } else { None };
+
+        let src = String::from("A") + &"&A".repeat(MAX_EXPR_DEPTH);
+        assert!(MaskExpr::parse_with(&src, lookup).is_ok());
+        let src = String::from("A") + &"|A".repeat(MAX_EXPR_DEPTH);
+        assert!(MaskExpr::parse_with(&src, lookup).is_ok());
+
+        // a flat chain builds a left-nested tree, so it is limited like nesting
+        let src = String::from("A") + &"&A".repeat(MAX_EXPR_DEPTH + 1);
+        assert_eq!(
+            MaskExpr::parse_with(&src, lookup),
+            Err(MaskExprError::TooDeep {
+                pos: 1 + 2 * MAX_EXPR_DEPTH
+            })
+        );
+        let src = String::from("A") + &"&A".repeat(200_000);
+        assert!(matches!(
+            MaskExpr::parse_with(&src, lookup),
+            Err(MaskExprError::TooDeep { .. })
+        ));
+        let src = String::from("A") + &"|A&A".repeat(100_000);
+        assert!(matches!(
+            MaskExpr::parse_with(&src, lookup),
+            Err(MaskExprError::TooDeep { .. })
+        ));
+    }
+
+    #[test]
+    fn test_mask_expr_parse_expr() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.name_bit(0, "QUEUED");
+        v.name_bit(3, "FAILED");
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00001001, 101);
+
+        let e = v.parse_expr("QUEUED & !FAILED").unwrap();
+        assert_eq!(e, All(0b00000001) & !All(0b00001000));
+        assert_eq!(v.count_matching_expr(&e), 1);
+    }
+}
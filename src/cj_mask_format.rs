@@ -0,0 +1,191 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+/// How format_mask() and DisplayMask render a bitmask.<br>
+///
+/// radix is 2, 8 or 16. Digits are split into groups of group_size with an underscore, counting
+/// from the least significant digit, or not split at all when group_size is 0. Leading zeros are
+/// added until at least min_width digits are shown; padded() sets min_width to the full width
+/// of the bitmask type.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// assert_eq!(format_mask(&0b00000110u8, MaskFormat::BINARY), "110");
+/// assert_eq!(format_mask(&0b00000110u8, MaskFormat::BINARY.padded::<u8>()), "0000_0110");
+/// assert_eq!(format_mask(&0xBEEFu32, MaskFormat::HEX.padded::<u32>()), "0000_BEEF");
+///
+/// let f = MaskFormat {
+///     radix: 8,
+///     group_size: 0,
+///     min_width: 4,
+/// };
+/// assert_eq!(format_mask(&0o17u16, f), "0017");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaskFormat {
+    pub radix: u32,
+    pub group_size: usize,
+    pub min_width: usize,
+}
+
+impl MaskFormat {
+    /// Binary in groups of 4 digits, without leading zeros.
+    pub const BINARY: Self = Self {
+        radix: 2,
+        group_size: 4,
+        min_width: 0,
+    };
+
+    /// Upper case hex in groups of 4 digits, without leading zeros.
+    pub const HEX: Self = Self {
+        radix: 16,
+        group_size: 4,
+        min_width: 0,
+    };
+
+    /// Returns self with min_width set to the number of digits needed for every bit of B.
+    #[inline]
+    pub fn padded<B: BitmaskBits>(self) -> Self {
+        Self {
+            min_width: B::BITS.div_ceil(self.digit_bits()),
+            ..self
+        }
+    }
+
+    #[inline]
+    fn digit_bits(&self) -> usize {
+        assert!(
+            matches!(self.radix, 2 | 8 | 16),
+            "radix must be 2, 8 or 16, not {}",
+            self.radix
+        );
+        self.radix.trailing_zeros() as usize
+    }
+}
+
+impl Default for MaskFormat {
+    fn default() -> Self {
+        Self::BINARY
+    }
+}
+
+/// Returns bitmask rendered as described by format.
+/// # Panics
+/// Panics if format.radix is not 2, 8 or 16.
+pub fn format_mask<B: BitmaskBits>(bitmask: &B, format: MaskFormat) -> String {
+    let mut s = String::new();
+    write_mask(&mut s, bitmask, format).expect("writing to a String does not fail");
+    s
+}
+
+fn write_mask<B, W>(w: &mut W, bitmask: &B, format: MaskFormat) -> core::fmt::Result
+where
+    B: BitmaskBits,
+    W: core::fmt::Write,
+{
+    let digit_bits = format.digit_bits();
+    // least significant digit first
+    let mut digits: Vec<u32> = (0..B::BITS.div_ceil(digit_bits))
+        .map(|d| {
+            (0..digit_bits)
+                .map(|k| d * digit_bits + k)
+                .filter(|&bit| bit < B::BITS && bitmask.has_bit(bit))
+                .fold(0, |acc, bit| acc | 1 << (bit % digit_bits))
+        })
+        .collect();
+    let significant = digits.iter().rposition(|x| *x != 0).map_or(1, |x| x + 1);
+    digits.resize(significant.max(format.min_width), 0);
+
+    for (i, x) in digits.iter().enumerate().rev() {
+        let c = char::from_digit(*x, format.radix).map_or('0', |c| c.to_ascii_uppercase());
+        w.write_char(c)?;
+        if format.group_size != 0 && i != 0 && i.is_multiple_of(format.group_size) {
+            w.write_char('_')?;
+        }
+    }
+    Ok(())
+}
+
+/// Wrapper that implements Display for a bitmask using a MaskFormat, for use in format strings
+/// and log lines.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let flags = 0b0010_0110u16;
+/// let line = format!("flags={}", DisplayMask::new(&flags, MaskFormat::BINARY));
+/// assert_eq!(line, "flags=10_0110");
+/// ```
+pub struct DisplayMask<'m, B>
+where
+    B: BitmaskBits,
+{
+    bitmask: &'m B,
+    format: MaskFormat,
+}
+
+impl<'m, B> DisplayMask<'m, B>
+where
+    B: BitmaskBits,
+{
+    pub fn new(bitmask: &'m B, format: MaskFormat) -> Self {
+        Self { bitmask, format }
+    }
+}
+
+impl<B> Display for DisplayMask<'_, B>
+where
+    B: BitmaskBits,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write_mask(f, self.bitmask, self.format)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_bits::BitmaskBits;
+    use crate::cj_mask_format::{format_mask, DisplayMask, MaskFormat};
+    use crate::cj_wide_mask::WideMask;
+
+    #[test]
+    fn test_format_mask() {
+        assert_eq!(format_mask(&0u8, MaskFormat::BINARY), "0");
+        assert_eq!(format_mask(&0u8, MaskFormat::HEX.padded::<u8>()), "00");
+        assert_eq!(
+            format_mask(&0b1_0110_1001u16, MaskFormat::BINARY),
+            "1_0110_1001"
+        );
+        assert_eq!(
+            format_mask(&u64::MAX, MaskFormat::HEX),
+            "FFFF_FFFF_FFFF_FFFF"
+        );
+        assert_eq!(
+            format_mask(&0o755u16, MaskFormat::default().padded::<u16>()),
+            "0000_0001_1110_1101"
+        );
+        let octal = MaskFormat {
+            radix: 8,
+            group_size: 3,
+            min_width: 0,
+        };
+        assert_eq!(format_mask(&u8::MAX, octal), "377");
+        assert_eq!(format_mask(&u8::MAX, octal.padded::<u8>()), "377");
+        assert_eq!(format_mask(&1u16, octal.padded::<u16>()), "000_001");
+
+        let wide = WideMask::<4>::bit(200);
+        assert_eq!(format_mask(&wide, MaskFormat::HEX).len(), 51 + 12);
+        assert!(format_mask(&wide, MaskFormat::HEX).starts_with("100_0000_"));
+        assert_eq!(DisplayMask::new(&0x1Fu8, MaskFormat::HEX).to_string(), "1F");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_format_mask_radix() {
+        let f = MaskFormat {
+            radix: 10,
+            group_size: 0,
+            min_width: 0,
+        };
+        format_mask(&10u8, f);
+    }
+}
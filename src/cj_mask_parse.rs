@@ -0,0 +1,146 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+/// Error returned by parse_mask().
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaskParseError {
+    /// The string has no digits.
+    Empty,
+    /// ch at byte position pos is not a digit of the radix.
+    InvalidDigit { pos: usize, ch: char },
+    /// The value has bits past the width of the bitmask type.
+    Overflow,
+}
+
+impl Display for MaskParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MaskParseError::Empty => f.write_str("bitmask has no digits"),
+            MaskParseError::InvalidDigit { pos, ch } => {
+                write!(f, "invalid digit '{ch}' at position {pos}")
+            }
+            MaskParseError::Overflow => f.write_str("bitmask is too large for its type"),
+        }
+    }
+}
+
+impl std::error::Error for MaskParseError {}
+
+/// Parses a bitmask literal: binary with a 0b prefix, hex with a 0x prefix, or decimal.
+/// Underscores between digits are ignored. Decimal literals are limited to 128 bits.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// assert_eq!(parse_mask::<u8>("0b1010"), Ok(0b1010));
+/// assert_eq!(parse_mask::<u16>("0x1F"), Ok(0x1F));
+/// assert_eq!(parse_mask::<u32>("42"), Ok(42));
+/// assert_eq!(parse_mask::<u8>("0b0000_0110"), Ok(0b0000_0110));
+/// assert_eq!(parse_mask::<u8>("0x100"), Err(MaskParseError::Overflow));
+///
+/// // as the flag lookup of a MaskExpr, so expressions can hold literals
+/// let expr = MaskExpr::<u8>::parse_with("0b0001 & !0x80", |x| parse_mask(x).ok()).unwrap();
+/// assert!(expr.matches(&0b00000011));
+/// ```
+pub fn parse_mask<B: BitmaskBits>(s: &str) -> Result<B, MaskParseError> {
+    let (start, radix) = match s.get(..2) {
+        Some("0b" | "0B") => (2, 2u32),
+        Some("0x" | "0X") => (2, 16u32),
+        _ => (0, 10u32),
+    };
+
+    // digit values, most significant first
+    let mut digits = Vec::with_capacity(s.len() - start);
+    for (pos, ch) in s[start..].char_indices() {
+        if ch == '_' {
+            continue;
+        }
+        match ch.to_digit(radix) {
+            Some(d) => digits.push(d),
+            None => {
+                return Err(MaskParseError::InvalidDigit {
+                    pos: start + pos,
+                    ch,
+                })
+            }
+        }
+    }
+    if digits.is_empty() {
+        return Err(MaskParseError::Empty);
+    }
+
+    let mut bitmask = B::default();
+    let mut set = |bit: usize| {
+        if bit < B::BITS {
+            bitmask = bitmask | B::bit(bit);
+            Ok(())
+        } else {
+            Err(MaskParseError::Overflow)
+        }
+    };
+    if radix == 10 {
+        let value = digits.iter().try_fold(0u128, |acc, d| {
+            acc.checked_mul(10)
+                .and_then(|x| x.checked_add(*d as u128))
+                .ok_or(MaskParseError::Overflow)
+        })?;
+        for bit in (0..128).filter(|i| (value >> i) & 1 == 1) {
+            set(bit)?;
+        }
+    } else {
+        let digit_bits = radix.trailing_zeros() as usize;
+        for (i, d) in digits.iter().rev().enumerate() {
+            for k in (0..digit_bits).filter(|k| (d >> k) & 1 == 1) {
+                set(i * digit_bits + k)?;
+            }
+        }
+    }
+    Ok(bitmask)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_bits::BitmaskBits;
+    use crate::cj_mask_parse::{parse_mask, MaskParseError};
+    use crate::cj_wide_mask::WideMask;
+
+    #[test]
+    fn test_parse_mask() {
+        assert_eq!(parse_mask::<u8>("0B1_0_1"), Ok(0b101));
+        assert_eq!(parse_mask::<u8>("0xff"), Ok(0xFF));
+        assert_eq!(parse_mask::<u8>("0b000000001"), Ok(1));
+        assert_eq!(parse_mask::<u8>("255"), Ok(255));
+        assert_eq!(parse_mask::<u8>("256"), Err(MaskParseError::Overflow));
+        assert_eq!(parse_mask::<u128>(&u128::MAX.to_string()), Ok(u128::MAX));
+        assert_eq!(
+            parse_mask::<u128>("340282366920938463463374607431768211456"),
+            Err(MaskParseError::Overflow)
+        );
+        assert_eq!(
+            parse_mask::<WideMask<4>>("0x1_0000_0000_0000_0000_0000_0000_0000_0000"),
+            Ok(WideMask::bit(128))
+        );
+    }
+
+    #[test]
+    fn test_parse_mask_errors() {
+        assert_eq!(parse_mask::<u8>(""), Err(MaskParseError::Empty));
+        assert_eq!(parse_mask::<u8>("0x"), Err(MaskParseError::Empty));
+        assert_eq!(parse_mask::<u8>("0b__"), Err(MaskParseError::Empty));
+        assert_eq!(
+            parse_mask::<u8>("0b102"),
+            Err(MaskParseError::InvalidDigit { pos: 4, ch: '2' })
+        );
+        assert_eq!(
+            parse_mask::<u8>("-1"),
+            Err(MaskParseError::InvalidDigit { pos: 0, ch: '-' })
+        );
+        assert_eq!(
+            parse_mask::<u8>(" 1"),
+            Err(MaskParseError::InvalidDigit { pos: 0, ch: ' ' })
+        );
+        assert_eq!(
+            MaskParseError::InvalidDigit { pos: 4, ch: '2' }.to_string(),
+            "invalid digit '2' at position 4"
+        );
+    }
+}
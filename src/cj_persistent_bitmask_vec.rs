@@ -0,0 +1,437 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::Index;
+use core::slice::Iter;
+
+/// Number of items held by each shared chunk of a PersistentBitmaskVec.
+pub const PERSISTENT_CHUNK_LEN: usize = 64;
+
+/// PersistentBitmaskVec is an immutable vec that pairs bitmasks with T, where every change
+/// returns a new handle sharing structure with the old one.<br>
+///
+/// Items are stored in chunks of PERSISTENT_CHUNK_LEN held behind an Arc. push(), set_mask() and
+/// the other updates copy only the chunk they touch plus the list of chunk pointers, so keeping
+/// many historical versions costs far less than cloning the whole vec for each one.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let v1 = PersistentBitmaskVec::<u8, i32>::new()
+///     .push_with_mask(0b00000001, 100)
+///     .push_with_mask(0b00000010, 101);
+///
+/// let v2 = v1.set_mask(0, 0b00000011);
+/// let v3 = v2.push(102);
+///
+/// assert_eq!(v1.count_matching(&0b00000010), 1);
+/// assert_eq!(v2.count_matching(&0b00000010), 2);
+/// assert_eq!(v3.len(), 3);
+/// assert_eq!(v1.len(), 2);
+/// ```
+pub struct PersistentBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    chunks: Vec<Arc<Vec<BitmaskItem<B, T>>>>,
+    len: usize,
+}
+
+impl<'a, B, T> PersistentBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of chunks this vec shares with other.
+    pub fn shared_chunks(&self, other: &Self) -> usize {
+        self.chunks
+            .iter()
+            .zip(other.chunks.iter())
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count()
+    }
+
+    /// Returns T at index, or None if index is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.get_with_mask(index).map(|x| &x.item)
+    }
+
+    /// Returns the BitmaskItem at index, or None if index is out of bounds.
+    #[inline]
+    pub fn get_with_mask(&self, index: usize) -> Option<&BitmaskItem<B, T>> {
+        if index < self.len {
+            Some(&self.chunks[index / PERSISTENT_CHUNK_LEN][index % PERSISTENT_CHUNK_LEN])
+        } else {
+            None
+        }
+    }
+
+    /// Returns a new vec with T and a default bitmask of zero appended.
+    #[inline]
+    pub fn push(&self, value: T) -> Self
+    where
+        T: Clone,
+    {
+        self.push_with_mask(B::default(), value)
+    }
+
+    /// Returns a new vec with T and bitmask appended.
+    pub fn push_with_mask(&self, bitmask: B, value: T) -> Self
+    where
+        T: Clone,
+    {
+        let mut v = self.clone();
+        let item = BitmaskItem::new(bitmask, value);
+        match v.chunks.last_mut() {
+            Some(chunk) if chunk.len() < PERSISTENT_CHUNK_LEN => Arc::make_mut(chunk).push(item),
+            _ => {
+                let mut chunk = Vec::with_capacity(PERSISTENT_CHUNK_LEN);
+                chunk.push(item);
+                v.chunks.push(Arc::new(chunk));
+            }
+        }
+        v.len += 1;
+        v
+    }
+
+    /// Returns a new vec without the last element, or None if the vector is empty.
+    pub fn pop(&self) -> Option<Self>
+    where
+        T: Clone,
+    {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.truncate(self.len - 1))
+        }
+    }
+
+    /// Returns a new vec keeping the first len elements.
+    pub fn truncate(&self, len: usize) -> Self
+    where
+        T: Clone,
+    {
+        let mut v = self.clone();
+        if len < v.len {
+            v.chunks.truncate(len.div_ceil(PERSISTENT_CHUNK_LEN));
+            if !len.is_multiple_of(PERSISTENT_CHUNK_LEN) {
+                if let Some(chunk) = v.chunks.last_mut() {
+                    Arc::make_mut(chunk).truncate(len % PERSISTENT_CHUNK_LEN);
+                }
+            }
+            v.len = len;
+        }
+        v
+    }
+
+    /// Returns a new vec with the bitmask at index replaced.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    pub fn set_mask(&self, index: usize, bitmask: B) -> Self
+    where
+        T: Clone,
+    {
+        let mut v = self.clone();
+        v.item_mut(index).bitmask = bitmask;
+        v
+    }
+
+    /// Returns a new vec with T at index replaced, keeping its bitmask.
+    /// # Panics
+    /// Panics if index is out of bounds.
+    pub fn set(&self, index: usize, value: T) -> Self
+    where
+        T: Clone,
+    {
+        let mut v = self.clone();
+        v.item_mut(index).item = value;
+        v
+    }
+
+    /// Returns a PersistentBitmaskVecIter for iterating over T.
+    #[inline]
+    pub fn iter(&'a self) -> PersistentBitmaskVecIter<'a, B, T> {
+        PersistentBitmaskVecIter {
+            inner: self.iter_with_mask(),
+        }
+    }
+
+    /// Returns a PersistentBitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> PersistentBitmaskVecIterWithMask<'a, B, T> {
+        PersistentBitmaskVecIterWithMask {
+            chunks: self.chunks.iter(),
+            current: [].iter(),
+        }
+    }
+
+    /// Returns a PersistentBitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> PersistentBitmaskVecIterMatching<'a, B, T> {
+        PersistentBitmaskVecIterMatching {
+            inner: self.iter_with_mask(),
+            mask,
+        }
+    }
+
+    /// Returns the number of elements whose bitmask matches mask.
+    #[inline]
+    pub fn count_matching(&'a self, mask: &'a B) -> usize {
+        self.iter_matching(mask).count()
+    }
+
+    /// Returns the indices of the elements whose bitmask matches mask.
+    pub fn indices_matching(&'a self, mask: &'a B) -> Vec<usize> {
+        self.iter_with_mask()
+            .enumerate()
+            .filter(|(_, x)| x.matches_mask(mask))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Copies the items into a new BitmaskVec.
+    pub fn to_bitmask_vec(&self) -> BitmaskVec<B, T>
+    where
+        T: Clone,
+    {
+        let mut v = BitmaskVec::with_capacity(self.len);
+        for x in self.chunks.iter() {
+            v.as_mut_vec().extend_from_slice(x);
+        }
+        v
+    }
+
+    #[inline]
+    fn item_mut(&mut self, index: usize) -> &mut BitmaskItem<B, T>
+    where
+        T: Clone,
+    {
+        assert!(
+            index < self.len,
+            "index {index} is out of bounds for length {}",
+            self.len
+        );
+        &mut Arc::make_mut(&mut self.chunks[index / PERSISTENT_CHUNK_LEN])
+            [index % PERSISTENT_CHUNK_LEN]
+    }
+}
+
+impl<B, T> Clone for PersistentBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    /// Returns a new handle sharing every chunk.
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<'a, B, T> Default for PersistentBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for PersistentBitmaskVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn from(mut value: BitmaskVec<B, T>) -> Self {
+        let len = value.len();
+        let mut chunks = Vec::with_capacity(len.div_ceil(PERSISTENT_CHUNK_LEN));
+        let mut items = value.drain(..);
+        loop {
+            let chunk: Vec<_> = items.by_ref().take(PERSISTENT_CHUNK_LEN).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            chunks.push(Arc::new(chunk));
+        }
+        Self { chunks, len }
+    }
+}
+
+impl<B, T> Index<usize> for PersistentBitmaskVec<B, T>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        assert!(
+            index < self.len,
+            "index {index} is out of bounds for length {}",
+            self.len
+        );
+        &self.chunks[index / PERSISTENT_CHUNK_LEN][index % PERSISTENT_CHUNK_LEN].item
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem, containing both T and bitmask, from a PersistentBitmaskVec.
+pub struct PersistentBitmaskVecIterWithMask<'a, B, T>
+where
+    B: Bitflag,
+{
+    chunks: Iter<'a, Arc<Vec<BitmaskItem<B, T>>>>,
+    current: Iter<'a, BitmaskItem<B, T>>,
+}
+
+impl<'a, B, T> Iterator for PersistentBitmaskVecIterWithMask<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(x) = self.current.next() {
+                return Some(x);
+            }
+            self.current = self.chunks.next()?.iter();
+        }
+    }
+}
+
+// =================================================================================================
+/// Iter that returns T from a PersistentBitmaskVec.
+pub struct PersistentBitmaskVecIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: PersistentBitmaskVecIterWithMask<'a, B, T>,
+}
+
+impl<'a, B, T> Iterator for PersistentBitmaskVecIter<'a, B, T>
+where
+    B: Bitflag,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|x| &x.item)
+    }
+}
+
+// =================================================================================================
+/// Iter that returns BitmaskItem from a PersistentBitmaskVec for items whose bitmask matches mask.
+pub struct PersistentBitmaskVecIterMatching<'a, B, T>
+where
+    B: Bitflag,
+{
+    inner: PersistentBitmaskVecIterWithMask<'a, B, T>,
+    mask: &'a B,
+}
+
+impl<'a, B, T> Iterator for PersistentBitmaskVecIterMatching<'a, B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Item = &'a BitmaskItem<B, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mask = self.mask;
+        self.inner.by_ref().find(|x| x.matches_mask(mask))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_persistent_bitmask_vec::{PersistentBitmaskVec, PERSISTENT_CHUNK_LEN};
+
+    fn sample(len: usize) -> PersistentBitmaskVec<u8, usize> {
+        let mut v = PersistentBitmaskVec::new();
+        for i in 0..len {
+            v = v.push_with_mask((i % 4) as u8, i);
+        }
+        v
+    }
+
+    #[test]
+    fn test_persistent_push_shares_structure() {
+        let v1 = sample(PERSISTENT_CHUNK_LEN * 3);
+        let v2 = v1.push(500);
+
+        assert_eq!(v1.len(), PERSISTENT_CHUNK_LEN * 3);
+        assert_eq!(v2.len(), PERSISTENT_CHUNK_LEN * 3 + 1);
+        assert_eq!(v2.shared_chunks(&v1), 3);
+        assert_eq!(v2[PERSISTENT_CHUNK_LEN * 3], 500);
+        assert_eq!(v1.get(PERSISTENT_CHUNK_LEN * 3), None);
+    }
+
+    #[test]
+    fn test_persistent_set_mask() {
+        let v1 = sample(PERSISTENT_CHUNK_LEN * 2);
+        let v2 = v1.set_mask(PERSISTENT_CHUNK_LEN + 1, 0b10000000);
+        let v3 = v2.set(0, 1000);
+
+        assert_eq!(v2.shared_chunks(&v1), 1);
+        assert_eq!(v3.shared_chunks(&v1), 0);
+        assert_eq!(v1.count_matching(&0b10000000), 0);
+        assert_eq!(
+            v2.indices_matching(&0b10000000),
+            vec![PERSISTENT_CHUNK_LEN + 1]
+        );
+        assert_eq!(v3[0], 1000);
+        assert_eq!(v2[0], 0);
+    }
+
+    #[test]
+    fn test_persistent_truncate_pop() {
+        let v1 = sample(PERSISTENT_CHUNK_LEN + 2);
+        let v2 = v1.pop().unwrap();
+        let v3 = v1.truncate(PERSISTENT_CHUNK_LEN);
+
+        assert_eq!(v2.len(), PERSISTENT_CHUNK_LEN + 1);
+        assert_eq!(v3.len(), PERSISTENT_CHUNK_LEN);
+        assert_eq!(v3.shared_chunks(&v1), 1);
+        assert_eq!(v3.iter().count(), PERSISTENT_CHUNK_LEN);
+        assert_eq!(v1.len(), PERSISTENT_CHUNK_LEN + 2);
+        assert!(PersistentBitmaskVec::<u8, i32>::new().pop().is_none());
+
+        let v4 = v3.truncate(1).push(7);
+        assert_eq!(v4.iter().copied().collect::<Vec<_>>(), vec![0, 7]);
+    }
+
+    #[test]
+    fn test_persistent_bitmask_vec_conversion() {
+        let mut b = BitmaskVec::<u8, usize>::new();
+        for i in 0..PERSISTENT_CHUNK_LEN + 5 {
+            b.push_with_mask((i % 2) as u8, i);
+        }
+        let v = PersistentBitmaskVec::from(b);
+        assert_eq!(v.len(), PERSISTENT_CHUNK_LEN + 5);
+        assert_eq!(v[PERSISTENT_CHUNK_LEN + 4], PERSISTENT_CHUNK_LEN + 4);
+
+        let b = v.to_bitmask_vec();
+        assert_eq!(b.len(), v.len());
+        assert_eq!(b.count_matching(&1), v.count_matching(&1));
+        assert_eq!(v.iter_matching(&1).count(), (PERSISTENT_CHUNK_LEN + 5) / 2);
+    }
+}
@@ -0,0 +1,350 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMut, BitmaskVecIterWithMask,
+    BitmaskVecIterWithMaskMut,
+};
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{AddAssign, Index, IndexMut};
+use smallvec::SmallVec;
+
+/// SmallBitmaskVec is a BitmaskVec that stores up to N items inline before spilling to the heap.<br>
+///
+/// Useful when many small vecs are created and the per-vec heap allocation would dominate.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = SmallBitmaskVec::<u8, i32, 4>::new();
+/// v.push_with_mask(0b00000000, 100);
+/// v.push_with_mask(0b00000010, 101);
+/// v.push_with_mask(0b00000011, 102);
+/// v.push(103);
+/// assert!(!v.spilled());
+///
+/// let mut count = 0;
+/// let mut iter = v.iter_with_mask();
+/// while let Some(pair) = iter.filter_mask(&0b00000010) {
+///     assert!([101, 102].contains(&pair.item));
+///     count += 1;
+/// }
+/// assert_eq!(count, 2);
+/// ```
+pub struct SmallBitmaskVec<B, T, const N: usize>
+where
+    B: Bitflag,
+{
+    inner: SmallVec<[BitmaskItem<B, T>; N]>,
+}
+
+impl<'a, B, T, const N: usize> SmallBitmaskVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    pub fn new() -> Self {
+        Self {
+            inner: SmallVec::new(),
+        }
+    }
+
+    /// Constructs a new, empty vec with at least the specified capacity.
+    /// Capacities up to N do not allocate.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: SmallVec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of elements the vector can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Returns true if the items have spilled from inline storage onto the heap.
+    #[inline]
+    pub fn spilled(&self) -> bool {
+        self.inner.spilled()
+    }
+
+    /// Extracts a slice containing the entire vector.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Extracts a mutable slice containing the entire vector.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [BitmaskItem<B, T>] {
+        self.inner.as_mut_slice()
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Inserts an element with default bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert(&mut self, index: usize, value: T) {
+        self.inner
+            .insert(index, BitmaskItem::new(B::default(), value));
+    }
+
+    /// Inserts an element and bitmask at position index within the vector, shifting all elements after it to the right.
+    #[inline]
+    pub fn insert_with_mask(&mut self, index: usize, bitmask: B, value: T) {
+        self.inner.insert(index, BitmaskItem::new(bitmask, value));
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Removes and returns the element without bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        let x = self.inner.remove(index);
+        x.item
+    }
+
+    /// Removes and returns the element and bitmask at position index within the vector, shifting all elements after it to the left
+    #[inline]
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        self.inner.remove(index)
+    }
+
+    /// Reserves capacity for at least additional more elements.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    /// Shrinks the capacity as much as possible, moving items back inline if they fit.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit();
+    }
+
+    /// Shortens the vector, keeping the first len elements and dropping the rest
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.inner.truncate(len);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.inner.push(BitmaskItem::new(B::default(), value));
+    }
+
+    /// Pushes T and the supplied bitmask
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.inner.push(BitmaskItem::new(bitmask, value));
+    }
+
+    /// Pops T from the vec without the bitmask.  If both T and bitmask are wanted,
+    /// use pop_with_mask() instead.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.inner.pop().map(|x| x.item)
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        self.inner.pop()
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T.
+    /// * this iter excludes bitmask. Use iter_with_mask() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter(&'a mut self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask(&'a mut self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMut for mutable iteration over T.
+    /// * this iter excludes bitmask. Use iter_with_mask_mut() instead if both T and bitmask are wanted.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecIterMut<'a, B, T> {
+        BitmaskVecIterMut::new(self.inner.iter_mut())
+    }
+
+    /// Returns a BitmaskVecIterWithMaskMut for mutable iteration over T and bitmask.
+    #[inline]
+    pub fn iter_with_mask_mut(&'a mut self) -> BitmaskVecIterWithMaskMut<'a, B, T> {
+        BitmaskVecIterWithMaskMut::new(self.inner.iter_mut())
+    }
+
+    /// Moves all items into a heap backed BitmaskVec.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.inner.len());
+        for x in self.inner {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<'a, B, T, const N: usize> Default for SmallBitmaskVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T, const N: usize> Index<usize> for SmallBitmaskVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+impl<'a, B, T, const N: usize> IndexMut<usize> for SmallBitmaskVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index].item
+    }
+}
+
+impl<'a, B, T, const N: usize> AddAssign<(B, T)> for SmallBitmaskVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: (B, T)) {
+        self.push_with_mask(rhs.0, rhs.1);
+    }
+}
+
+impl<'a, B, T, const N: usize> AddAssign<T> for SmallBitmaskVec<B, T, N>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+{
+    #[inline(always)]
+    fn add_assign(&mut self, rhs: T) {
+        self.push(rhs);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_small_bitmask_vec::SmallBitmaskVec;
+
+    #[test]
+    fn test_small_bitmask_vec_push() {
+        let mut v = SmallBitmaskVec::<u8, i32, 4>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(1, 400);
+        v.push(0);
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[1], 400);
+        assert!(!v.spilled());
+    }
+
+    #[test]
+    fn test_small_bitmask_vec_spill() {
+        let mut v = SmallBitmaskVec::<u8, i32, 2>::new();
+        v += (0b00000001, 100);
+        v += (0b00000010, 101);
+        assert!(!v.spilled());
+        v += 102;
+        assert!(v.spilled());
+        assert_eq!(v.len(), 3);
+
+        v.truncate(2);
+        v.shrink_to_fit();
+        assert!(!v.spilled());
+    }
+
+    #[test]
+    fn test_small_bitmask_vec_pop_with_mask() {
+        let mut v = SmallBitmaskVec::<u8, i32, 4>::new();
+        v.push_with_mask(0, 100);
+        v.push_with_mask(2, 999);
+
+        let x = v.pop_with_mask().unwrap();
+        assert_eq!(x.bitmask, 0b00000010);
+        assert_eq!(x.item, 999);
+        assert_eq!(v.pop(), Some(100));
+        assert_eq!(v.pop(), None);
+    }
+
+    #[test]
+    fn test_small_bitmask_vec_insert_remove() {
+        let mut v = SmallBitmaskVec::<u8, i32, 4>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.insert(1, 500);
+        v.insert_with_mask(0, 0b11000000, 600);
+
+        assert_eq!(v.len(), 4);
+        assert_eq!(v.remove(2), 500);
+        let x = v.remove_with_mask(0);
+        assert_eq!(x.bitmask, 0b11000000);
+        assert_eq!(x.item, 600);
+    }
+
+    #[test]
+    fn test_small_bitmask_vec_filter() {
+        let mut v = SmallBitmaskVec::<u8, i32, 8>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+        v.push_with_mask(0b00000010, 102);
+        v.push_with_mask(0b00000100, 103);
+        v.push_with_mask(0b00000011, 104);
+
+        let mut count = 0;
+        let mut z = v.iter_with_mask();
+        while let Some(pair) = z.filter_mask(&0b00000010) {
+            assert!([101, 102, 104].contains(&pair.item));
+            count += 1;
+        }
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_small_bitmask_vec_iter_mut() {
+        let mut v = SmallBitmaskVec::<u8, i32, 4>::new();
+        v.push_with_mask(0b00000000, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        for x in v.iter_mut() {
+            *x *= 2;
+        }
+        let total: i32 = v.iter().sum();
+        assert_eq!(total, 402);
+    }
+
+    #[test]
+    fn test_small_bitmask_vec_into_bitmask_vec() {
+        let mut v = SmallBitmaskVec::<u8, i32, 4>::new();
+        v.push_with_mask(0b00000001, 100);
+        v.push_with_mask(0b00000010, 101);
+
+        let mut v2 = v.into_bitmask_vec();
+        assert_eq!(v2.len(), 2);
+        assert_eq!(v2.iter_with_mask().nth(1).unwrap().bitmask, 0b00000010);
+    }
+}
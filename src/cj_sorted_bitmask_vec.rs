@@ -0,0 +1,313 @@
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::{
+    BitmaskVec, BitmaskVecIter, BitmaskVecIterMatching, BitmaskVecIterMut, BitmaskVecIterWithMask,
+};
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Bound, Index, Range, RangeBounds};
+
+/// SortedBitmaskVec pairs bitmasks with T and keeps the items sorted by a key derived from the
+/// bitmask, the bitmask itself by default.<br>
+///
+/// Items can only be added through methods that preserve the order, and bitmasks cannot be
+/// mutated in place, so range_matching() can find a key range with a binary search instead
+/// of a scan. Items with equal keys keep their insertion order.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = SortedBitmaskVec::<u8, i32>::new();
+/// v.insert_with_mask(0b00000100, 100);
+/// v.insert_with_mask(0b00000001, 101);
+/// v.insert_with_mask(0b00000010, 102);
+/// v.insert_with_mask(0b00001000, 103);
+///
+/// let x: Vec<_> = v.range_matching(0b00000010..=0b00000100).map(|x| x.item).collect();
+/// assert_eq!(x, vec![102, 100]);
+/// ```
+pub struct SortedBitmaskVec<B, T, K = B>
+where
+    B: Bitflag,
+{
+    inner: Vec<BitmaskItem<B, T>>,
+    key: fn(&B) -> K,
+}
+
+impl<'a, B, T> SortedBitmaskVec<B, T, B>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    /// Constructs a new, empty vec sorted by bitmask.
+    pub fn new() -> Self {
+        Self::with_key(B::clone)
+    }
+}
+
+impl<'a, B, T, K> SortedBitmaskVec<B, T, K>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default,
+    K: Ord,
+{
+    /// Constructs a new, empty vec sorted by key(bitmask).
+    pub fn with_key(key: fn(&B) -> K) -> Self {
+        Self {
+            inner: Vec::new(),
+            key,
+        }
+    }
+
+    /// Builds a sorted vec from a BitmaskVec using key(bitmask). The sort is stable.
+    pub fn from_bitmask_vec(mut value: BitmaskVec<B, T>, key: fn(&B) -> K) -> Self {
+        let mut inner: Vec<_> = value.drain(..).collect();
+        inner.sort_by_key(|x| key(&x.bitmask));
+        Self { inner, key }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns true if the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Extracts a slice containing the entire vector, in sorted order.
+    #[inline]
+    pub fn as_slice(&self) -> &[BitmaskItem<B, T>] {
+        self.inner.as_slice()
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Inserts T with a default bitmask of zero at its sorted position. Returns the insertion index.
+    #[inline]
+    pub fn insert(&mut self, value: T) -> usize {
+        self.insert_with_mask(B::default(), value)
+    }
+
+    /// Inserts T and bitmask after every item with a less than or equal key. Returns the insertion index.
+    pub fn insert_with_mask(&mut self, bitmask: B, value: T) -> usize {
+        let k = (self.key)(&bitmask);
+        let index = self.inner.partition_point(|x| (self.key)(&x.bitmask) <= k);
+        self.inner.insert(index, BitmaskItem::new(bitmask, value));
+        index
+    }
+
+    /// Removes and returns T at index, shifting all elements after it to the left.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> T {
+        self.inner.remove(index).item
+    }
+
+    /// Removes and returns the BitmaskItem at index, shifting all elements after it to the left.
+    #[inline]
+    pub fn remove_with_mask(&mut self, index: usize) -> BitmaskItem<B, T> {
+        self.inner.remove(index)
+    }
+
+    /// Pops the item with the greatest key, without the bitmask.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.inner.pop().map(|x| x.item)
+    }
+
+    /// Pops the BitmaskItem with the greatest key.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        self.inner.pop()
+    }
+
+    /// Returns the index range of the items whose key falls within range.
+    pub fn range_indices<R>(&self, range: R) -> Range<usize>
+    where
+        R: RangeBounds<K>,
+    {
+        let key = self.key;
+        let start = match range.start_bound() {
+            Bound::Included(s) => self.inner.partition_point(|x| key(&x.bitmask) < *s),
+            Bound::Excluded(s) => self.inner.partition_point(|x| key(&x.bitmask) <= *s),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(e) => self.inner.partition_point(|x| key(&x.bitmask) <= *e),
+            Bound::Excluded(e) => self.inner.partition_point(|x| key(&x.bitmask) < *e),
+            Bound::Unbounded => self.inner.len(),
+        };
+        start..end.max(start)
+    }
+
+    /// Returns a BitmaskVecIterWithMask over the items whose key falls within range.
+    /// The range is located with a binary search.
+    pub fn range_matching<R>(&'a self, range: R) -> BitmaskVecIterWithMask<'a, B, T>
+    where
+        R: RangeBounds<K>,
+    {
+        let r = self.range_indices(range);
+        BitmaskVecIterWithMask::new(self.inner[r].iter())
+    }
+
+    /// Returns a BitmaskVecIter for iterating over T in sorted order.
+    #[inline]
+    pub fn iter(&'a self) -> BitmaskVecIter<'a, B, T> {
+        BitmaskVecIter::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterWithMask for iterating over T and bitmask in sorted order.
+    #[inline]
+    pub fn iter_with_mask(&'a self) -> BitmaskVecIterWithMask<'a, B, T> {
+        BitmaskVecIterWithMask::new(self.inner.iter())
+    }
+
+    /// Returns a BitmaskVecIterMut for mutably iterating over T. Bitmasks stay read only to keep the order.
+    #[inline]
+    pub fn iter_mut(&'a mut self) -> BitmaskVecIterMut<'a, B, T> {
+        BitmaskVecIterMut::new(self.inner.iter_mut())
+    }
+
+    /// Returns a BitmaskVecIterMatching for iterating over the BitmaskItems whose bitmask matches mask.
+    #[inline]
+    pub fn iter_matching(&'a self, mask: &'a B) -> BitmaskVecIterMatching<'a, B, T> {
+        BitmaskVecIterMatching::new(self.inner.iter(), mask)
+    }
+
+    /// Returns the number of items whose bitmask matches mask.
+    pub fn count_matching(&self, mask: &'a B) -> usize {
+        self.inner.iter().filter(|x| x.matches_mask(mask)).count()
+    }
+
+    /// Moves the items into a BitmaskVec, in sorted order.
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        let mut v = BitmaskVec::with_capacity(self.inner.len());
+        for x in self.inner {
+            v.push_with_mask(x.bitmask, x.item);
+        }
+        v
+    }
+}
+
+impl<'a, B, T> Default for SortedBitmaskVec<B, T, B>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, B, T> From<BitmaskVec<B, T>> for SortedBitmaskVec<B, T, B>
+where
+    B: Bitflag + CjMatchesMask<'a, B> + Clone + Default + Ord,
+{
+    /// Sorts the items by bitmask. The sort is stable.
+    fn from(value: BitmaskVec<B, T>) -> Self {
+        Self::from_bitmask_vec(value, B::clone)
+    }
+}
+
+impl<B, T, K> Index<usize> for SortedBitmaskVec<B, T, K>
+where
+    B: Bitflag,
+{
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index].item
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_sorted_bitmask_vec::SortedBitmaskVec;
+
+    #[test]
+    fn test_sorted_bitmask_vec_insert() {
+        let mut v = SortedBitmaskVec::<u8, i32>::new();
+        v.insert_with_mask(0b00000100, 100);
+        v.insert_with_mask(0b00000001, 101);
+        v.insert_with_mask(0b00000010, 102);
+        v.insert_with_mask(0b00000010, 103);
+        v.insert(104);
+
+        // equal masks keep their insertion order
+        assert_eq!(
+            v.iter().copied().collect::<Vec<_>>(),
+            vec![104, 101, 102, 103, 100]
+        );
+        assert_eq!(v[0], 104);
+        assert_eq!(v.len(), 5);
+    }
+
+    #[test]
+    fn test_sorted_bitmask_vec_range_matching() {
+        let mut v = SortedBitmaskVec::<u8, i32>::new();
+        v.insert_with_mask(0b00000100, 103);
+        v.insert_with_mask(0b00000010, 101);
+        v.insert_with_mask(0b00000001, 100);
+        v.insert_with_mask(0b00000010, 102);
+
+        let x: Vec<_> = v.range_matching(0b00000010..).map(|x| x.item).collect();
+        assert_eq!(x, vec![101, 102, 103]);
+        let x: Vec<_> = v.range_matching(..0b00000010).map(|x| x.item).collect();
+        assert_eq!(x, vec![100]);
+        assert_eq!(v.range_indices(0b00000010..=0b00000010), 1..3);
+        assert_eq!(v.range_indices(0b00001000..), 4..4);
+        assert_eq!(v.range_matching(0b00000011..0b00000100).count(), 0);
+
+        let mut iter = v.range_matching(..);
+        assert_eq!(iter.filter_mask(&0b00000100).unwrap().item, 103);
+    }
+
+    #[test]
+    fn test_sorted_bitmask_vec_with_key() {
+        let mut v = SortedBitmaskVec::<u8, i32, u32>::with_key(|b| b.count_ones());
+        v.insert_with_mask(0b00000111, 100);
+        v.insert_with_mask(0b00000001, 101);
+        v.insert_with_mask(0b10000001, 102);
+        v.insert_with_mask(0b01000000, 103);
+
+        assert_eq!(
+            v.iter().copied().collect::<Vec<_>>(),
+            vec![101, 103, 102, 100]
+        );
+        let x: Vec<_> = v.range_matching(2..).map(|x| x.item).collect();
+        assert_eq!(x, vec![102, 100]);
+        assert_eq!(v.pop(), Some(100));
+    }
+
+    #[test]
+    fn test_sorted_bitmask_vec_remove() {
+        let mut v = SortedBitmaskVec::<u8, i32>::new();
+        v.insert_with_mask(0b00000100, 102);
+        v.insert_with_mask(0b00000001, 100);
+        v.insert_with_mask(0b00000010, 101);
+        v.insert(103);
+
+        assert_eq!(v.remove(0), 103);
+        assert_eq!(v.remove_with_mask(0).bitmask, 0b00000001);
+        assert_eq!(v.pop_with_mask().unwrap().item, 102);
+        for x in v.iter_mut() {
+            *x += 1;
+        }
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![102]);
+        assert_eq!(v.count_matching(&0b00000010), 1);
+    }
+
+    #[test]
+    fn test_sorted_bitmask_vec_conversion() {
+        let mut v = BitmaskVec::<u8, i32>::new();
+        v.push_with_mask(0b00000100, 100);
+        v.push_with_mask(0b00000001, 101);
+        v.push_with_mask(0b00000100, 102);
+
+        let s = SortedBitmaskVec::from(v);
+        assert_eq!(s.iter_matching(&0b00000100).count(), 2);
+        let v = s.into_bitmask_vec();
+        assert_eq!(v, vec![101, 100, 102]);
+    }
+}
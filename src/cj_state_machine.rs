@@ -0,0 +1,401 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use crate::cj_bitmask_item::BitmaskItem;
+use crate::cj_bitmask_vec::BitmaskVec;
+use crate::cj_error::Error;
+use alloc::vec::Vec;
+use cj_common::cj_binary::bitbuf::*;
+use core::ops::{Deref, Index, IndexMut};
+
+/// StateMachine lists the allowed moves between the states of a workflow, where each state is
+/// a bitmask.<br>
+///
+/// The bits used by any state are the state bits; the state of a bitmask is its state bits,
+/// and any other bits (flags such as URGENT) are ignored when checking a move. Moving to the
+/// same state is always allowed. BitmaskStateVec only makes the moves its StateMachine allows.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// const QUEUED: u8 = 0b00000001;
+/// const RUNNING: u8 = 0b00000010;
+/// const COMPLETED: u8 = 0b00000100;
+/// const URGENT: u8 = 0b10000000;
+///
+/// let machine = StateMachine::new()
+///     .allow(QUEUED, RUNNING)
+///     .allow(RUNNING, COMPLETED)
+///     .allow(RUNNING, QUEUED);
+/// assert!(machine.allows(&(QUEUED | URGENT), &RUNNING));
+/// assert!(!machine.allows(&COMPLETED, &RUNNING));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateMachine<B> {
+    state_bits: B,
+    transitions: Vec<(B, B)>,
+}
+
+impl<B> StateMachine<B>
+where
+    B: BitmaskBits,
+{
+    /// Creates a StateMachine with no states. Until moves are added with allow() it has no
+    /// state bits, so every change is allowed.
+    pub fn new() -> Self {
+        Self {
+            state_bits: B::default(),
+            transitions: Vec::new(),
+        }
+    }
+
+    /// Returns self with the move from state from to state to allowed.
+    pub fn allow(mut self, from: B, to: B) -> Self {
+        self.state_bits = self.state_bits | from | to;
+        if !self.transitions.contains(&(from, to)) {
+            self.transitions.push((from, to));
+        }
+        self
+    }
+
+    /// Returns the bits used by any state.
+    #[inline]
+    pub fn state_bits(&self) -> B {
+        self.state_bits
+    }
+
+    /// Returns the state of bitmask, its bits that are state bits.
+    #[inline]
+    pub fn state_of(&self, bitmask: &B) -> B {
+        *bitmask & self.state_bits
+    }
+
+    /// Returns the allowed moves as (from, to) pairs, in the order they were added.
+    #[inline]
+    pub fn transitions(&self) -> &[(B, B)] {
+        &self.transitions
+    }
+
+    /// Returns true if a bitmask may change from from to to: both have the same state, or the
+    /// move between their states is allowed.
+    pub fn allows(&self, from: &B, to: &B) -> bool {
+        let from = self.state_of(from);
+        let to = self.state_of(to);
+        from == to || self.transitions.contains(&(from, to))
+    }
+}
+
+impl<B> Default for StateMachine<B>
+where
+    B: BitmaskBits,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// BitmaskStateVec is a BitmaskVec bound to a StateMachine, so the bitmasks of its elements
+/// only make the moves the machine allows.<br>
+///
+/// set_mask() and transition() panic on a move the machine does not allow, and try_set_mask()
+/// and try_transition() return Error::IllegalTransition instead, leaving the vec unchanged.
+/// Bitmasks of new elements are not checked. The BitmaskVec is available read only through
+/// Deref; items can be changed with IndexMut, get_mut() and iter_mut().
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// const QUEUED: u8 = 0b00000001;
+/// const RUNNING: u8 = 0b00000010;
+/// const COMPLETED: u8 = 0b00000100;
+///
+/// let machine = StateMachine::new()
+///     .allow(QUEUED, RUNNING)
+///     .allow(RUNNING, COMPLETED);
+/// let mut v = BitmaskStateVec::<u8, i32>::new(machine);
+/// v.push_with_mask(QUEUED, 100);
+/// v.push_with_mask(COMPLETED, 101);
+///
+/// assert_eq!(v.try_transition(&QUEUED, &RUNNING), Ok(1));
+/// assert_eq!(
+///     v.try_set_mask(1, RUNNING),
+///     Err(Error::IllegalTransition { index: 1 })
+/// );
+/// assert_eq!(v.count_matching(&RUNNING), 1);
+/// ```
+pub struct BitmaskStateVec<B, T>
+where
+    B: Bitflag,
+{
+    inner: BitmaskVec<B, T>,
+    machine: StateMachine<B>,
+}
+
+impl<'a, B, T> BitmaskStateVec<B, T>
+where
+    B: BitmaskBits + Bitflag + CjMatchesMask<'a, B>,
+{
+    /// Creates an empty vec bound to machine.
+    pub fn new(machine: StateMachine<B>) -> Self {
+        Self::from_bitmask_vec(BitmaskVec::new(), machine)
+    }
+
+    /// Binds vec to machine. The bitmasks already in vec are not checked.
+    pub fn from_bitmask_vec(vec: BitmaskVec<B, T>, machine: StateMachine<B>) -> Self {
+        Self {
+            inner: vec,
+            machine,
+        }
+    }
+
+    /// Returns the StateMachine the vec is bound to.
+    #[inline]
+    pub fn machine(&self) -> &StateMachine<B> {
+        &self.machine
+    }
+
+    /// Pushes T and a default bitmask of zero.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.inner.push(value);
+    }
+
+    /// Pushes T and the supplied bitmask.
+    /// # Panics
+    /// Panics if bitmask has bits outside the schema of the vec.
+    #[inline]
+    pub fn push_with_mask(&mut self, bitmask: B, value: T) {
+        self.inner.push_with_mask(bitmask, value);
+    }
+
+    /// Pops T from the vec without the bitmask.
+    #[inline]
+    pub fn pop(&mut self) -> Option<T> {
+        self.inner.pop()
+    }
+
+    /// Pops BitmaskItem containing both T and bitmask from the vec.
+    #[inline]
+    pub fn pop_with_mask(&mut self) -> Option<BitmaskItem<B, T>> {
+        self.inner.pop_with_mask()
+    }
+
+    /// Clears the vector, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
+    /// Returns a mutable reference to the T at index, or None if index is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.inner.as_mut_vec().get_mut(index).map(|x| &mut x.item)
+    }
+
+    /// Returns an iterator over mutable references to T. Bitmasks are left untouched.
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.inner.as_mut_vec().iter_mut().map(|x| &mut x.item)
+    }
+
+    /// Replaces the bitmask at index.
+    /// # Panics
+    /// Panics if try_set_mask() would return an Error.
+    #[inline]
+    pub fn set_mask(&mut self, index: usize, bitmask: B) {
+        if let Err(e) = self.try_set_mask(index, bitmask) {
+            panic!("{e}");
+        }
+    }
+
+    /// Replaces the bitmask at index, returning an Error if index is out of bounds, bitmask has
+    /// bits outside the schema of the vec, or the machine does not allow the move from the
+    /// current bitmask to bitmask.
+    pub fn try_set_mask(&mut self, index: usize, bitmask: B) -> Result<(), Error> {
+        self.inner.check_set_mask(index, &bitmask)?;
+        if !self
+            .machine
+            .allows(&self.inner.as_slice()[index].bitmask, &bitmask)
+        {
+            return Err(Error::IllegalTransition { index });
+        }
+        self.inner.set_mask(index, bitmask);
+        Ok(())
+    }
+
+    /// Moves every element whose bitmask matches from_mask to to_mask, as
+    /// BitmaskVec::transition() does. Returns the number of elements changed.
+    /// # Panics
+    /// Panics if try_transition() would return an Error.
+    pub fn transition(&mut self, from_mask: &'a B, to_mask: &B) -> usize {
+        match self.try_transition(from_mask, to_mask) {
+            Ok(count) => count,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Same as transition(), but returns an Error if to_mask has bits outside the schema of the
+    /// vec or the machine does not allow the move for a matching element. No element is
+    /// changed when an Error is returned.
+    pub fn try_transition(&mut self, from_mask: &'a B, to_mask: &B) -> Result<usize, Error> {
+        if !self.inner.allows_mask(to_mask) {
+            return Err(Error::OutsideSchema);
+        }
+        let moved = |bitmask: B| (bitmask & !*from_mask) | *to_mask;
+        if let Some(index) = self.inner.as_slice().iter().position(|x| {
+            x.bitmask.matches_mask(from_mask) && !self.machine.allows(&x.bitmask, &moved(x.bitmask))
+        }) {
+            return Err(Error::IllegalTransition { index });
+        }
+        Ok(self.inner.transition(from_mask, to_mask))
+    }
+
+    /// Unbinds the vec, returning the BitmaskVec.
+    #[inline]
+    pub fn into_bitmask_vec(self) -> BitmaskVec<B, T> {
+        self.inner
+    }
+}
+
+impl<B, T> Deref for BitmaskStateVec<B, T>
+where
+    B: Bitflag,
+{
+    type Target = BitmaskVec<B, T>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<'a, B, T> Index<usize> for BitmaskStateVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.inner[index]
+    }
+}
+
+impl<'a, B, T> IndexMut<usize> for BitmaskStateVec<B, T>
+where
+    B: Bitflag + CjMatchesMask<'a, B>,
+{
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.inner[index]
+    }
+}
+
+impl<B, T> Clone for BitmaskStateVec<B, T>
+where
+    B: Bitflag + Clone,
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            machine: self.machine.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_error::Error;
+    use crate::cj_state_machine::{BitmaskStateVec, StateMachine};
+
+    const QUEUED: u8 = 0b00000001;
+    const RUNNING: u8 = 0b00000010;
+    const COMPLETED: u8 = 0b00000100;
+    const URGENT: u8 = 0b10000000;
+
+    fn machine() -> StateMachine<u8> {
+        StateMachine::new()
+            .allow(QUEUED, RUNNING)
+            .allow(RUNNING, COMPLETED)
+            .allow(RUNNING, COMPLETED)
+    }
+
+    #[test]
+    fn test_state_machine() {
+        let m = machine();
+        assert_eq!(m.state_bits(), QUEUED | RUNNING | COMPLETED);
+        assert_eq!(m.transitions(), &[(QUEUED, RUNNING), (RUNNING, COMPLETED)]);
+        assert_eq!(m.state_of(&(RUNNING | URGENT)), RUNNING);
+        assert!(m.allows(&QUEUED, &(RUNNING | URGENT)));
+        assert!(m.allows(&COMPLETED, &(COMPLETED | URGENT)));
+        assert!(!m.allows(&QUEUED, &COMPLETED));
+        assert!(!m.allows(&COMPLETED, &RUNNING));
+        // no state bits, so no bit belongs to a state
+        assert!(StateMachine::new().allows(&QUEUED, &RUNNING));
+    }
+
+    #[test]
+    fn test_bitmask_state_vec() {
+        let mut b = BitmaskVec::<u8, i32>::new();
+        b.push_with_mask(QUEUED, 100);
+        b.push_with_mask(QUEUED | URGENT, 101);
+        b.push_with_mask(COMPLETED, 102);
+        let mut v = BitmaskStateVec::from_bitmask_vec(b, machine());
+
+        assert_eq!(
+            v.try_transition(&QUEUED, &COMPLETED),
+            Err(Error::IllegalTransition { index: 0 })
+        );
+        assert_eq!(v.count_matching(&QUEUED), 2);
+        assert_eq!(v.try_transition(&QUEUED, &RUNNING), Ok(2));
+        assert_eq!(v.as_slice()[1].bitmask, RUNNING | URGENT);
+
+        assert_eq!(
+            v.try_set_mask(2, RUNNING),
+            Err(Error::IllegalTransition { index: 2 })
+        );
+        assert_eq!(
+            v.try_set_mask(3, RUNNING),
+            Err(Error::IndexOutOfBounds { index: 3, len: 3 })
+        );
+        assert_eq!(v.try_set_mask(2, COMPLETED | URGENT), Ok(()));
+        v.set_mask(0, COMPLETED);
+
+        v[0] += 1;
+        assert_eq!(v[0], 101);
+        assert_eq!(v.machine(), &machine());
+        let mut b = v.into_bitmask_vec();
+        b.set_mask(0, QUEUED);
+        assert_eq!(b.transition(&COMPLETED, &RUNNING), 1);
+    }
+
+    #[test]
+    fn test_bitmask_state_vec_schema() {
+        let mut b = BitmaskVec::<u8, i32>::new();
+        b.set_schema(QUEUED | RUNNING | COMPLETED);
+        b.push_with_mask(QUEUED, 100);
+        let mut v = BitmaskStateVec::from_bitmask_vec(b, machine());
+
+        assert_eq!(
+            v.try_set_mask(0, RUNNING | URGENT),
+            Err(Error::OutsideSchema)
+        );
+        assert_eq!(
+            v.try_transition(&QUEUED, &(RUNNING | URGENT)),
+            Err(Error::OutsideSchema)
+        );
+        assert_eq!(v.as_slice()[0].bitmask, QUEUED);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_state_vec_set_mask() {
+        let mut v = BitmaskStateVec::<u8, i32>::new(machine());
+        v.push_with_mask(COMPLETED, 100);
+        v.set_mask(0, RUNNING);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bitmask_state_vec_transition() {
+        let mut v = BitmaskStateVec::<u8, i32>::new(machine());
+        v.push_with_mask(COMPLETED, 100);
+        v.transition(&COMPLETED, &QUEUED);
+    }
+}
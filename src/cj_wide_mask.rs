@@ -0,0 +1,266 @@
+use crate::cj_bitmask_bits::BitmaskBits;
+use alloc::string::String;
+use cj_common::cj_binary::bitbuf::*;
+use core::cmp::Ordering;
+use core::fmt::{Binary, Formatter};
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+/// WideMask is a bitmask of N * 64 bits, for use as B when more than 128 flags are needed.<br>
+///
+/// Bit 0 is the lowest bit of the first word. WideMasks order like the unsigned integers, by
+/// their highest differing bit. WideMask supports the same bit operators as the
+/// unsigned integers, so it works with BitmaskVec and the other containers of this crate.
+/// ```
+/// # use cj_bitmask_vec::prelude::*;
+/// let mut v = BitmaskVec::<WideMask<4>, i32>::new();
+/// v.push_with_mask(WideMask::bit(200), 100);
+/// v.push_with_mask(WideMask::bit(200) | WideMask::bit(3), 101);
+/// v.push_with_mask(WideMask::bit(3), 102);
+///
+/// assert_eq!(v.count_matching(&WideMask::bit(200)), 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WideMask<const N: usize>([u64; N]);
+
+impl<const N: usize> WideMask<N> {
+    /// Creates a WideMask from its words, lowest word first.
+    #[inline]
+    pub const fn new(words: [u64; N]) -> Self {
+        Self(words)
+    }
+
+    /// Returns a WideMask with no bits set.
+    #[inline]
+    pub const fn zero() -> Self {
+        Self([0; N])
+    }
+
+    /// Returns the words of the mask, lowest word first.
+    #[inline]
+    pub const fn words(&self) -> &[u64; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> Default for WideMask<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl<const N: usize> From<[u64; N]> for WideMask<N> {
+    #[inline]
+    fn from(value: [u64; N]) -> Self {
+        Self(value)
+    }
+}
+
+impl<const N: usize> From<WideMask<N>> for [u64; N] {
+    #[inline]
+    fn from(value: WideMask<N>) -> Self {
+        value.0
+    }
+}
+
+impl<const N: usize> Ord for WideMask<N> {
+    /// Compares the words from the highest down, so the highest differing bit decides.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl<const N: usize> PartialOrd for WideMask<N> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const N: usize> Bitflag for WideMask<N> {
+    #[inline]
+    fn get_bit(&self, bit: usize) -> bool {
+        self.has_bit(bit)
+    }
+
+    #[inline]
+    fn set_bit(&mut self, bit: usize, value: bool) {
+        let word = &mut self.0[bit / 64];
+        if value {
+            *word |= 1 << (bit % 64);
+        } else {
+            *word &= !(1 << (bit % 64));
+        }
+    }
+}
+
+impl<'a, const N: usize> CjMatchesMask<'a, WideMask<N>> for WideMask<N> {
+    #[inline]
+    fn matches_mask(&self, mask: &WideMask<N>) -> bool {
+        self.0.iter().zip(mask.0.iter()).all(|(a, m)| a & m == *m)
+    }
+
+    #[inline]
+    fn as_mask_matches(&self, value: &WideMask<N>) -> bool {
+        value.matches_mask(self)
+    }
+}
+
+impl<const N: usize> BitmaskBits for WideMask<N> {
+    const BITS: usize = N * 64;
+
+    #[inline]
+    fn bit(bit: usize) -> Self {
+        let mut x = Self::zero();
+        x.0[bit / 64] = 1 << (bit % 64);
+        x
+    }
+
+    #[inline]
+    fn has_bit(&self, bit: usize) -> bool {
+        (self.0[bit / 64] >> (bit % 64)) & 1 == 1
+    }
+
+    #[inline]
+    fn count_bits(&self) -> u32 {
+        self.0.iter().map(|x| x.count_ones()).sum()
+    }
+}
+
+macro_rules! impl_wide_mask_op {
+    ($($op:ident $f:ident $op_assign:ident $f_assign:ident $sym:tt),*) => {$(
+        impl<const N: usize> $op for WideMask<N> {
+            type Output = Self;
+
+            #[inline]
+            fn $f(mut self, rhs: Self) -> Self::Output {
+                self.$f_assign(rhs);
+                self
+            }
+        }
+
+        impl<const N: usize> $op_assign for WideMask<N> {
+            #[inline]
+            fn $f_assign(&mut self, rhs: Self) {
+                for (a, b) in self.0.iter_mut().zip(rhs.0) {
+                    *a = *a $sym b;
+                }
+            }
+        }
+    )*};
+}
+
+impl_wide_mask_op!(
+    BitAnd bitand BitAndAssign bitand_assign &,
+    BitOr bitor BitOrAssign bitor_assign |,
+    BitXor bitxor BitXorAssign bitxor_assign ^
+);
+
+impl<const N: usize> Not for WideMask<N> {
+    type Output = Self;
+
+    #[inline]
+    fn not(mut self) -> Self::Output {
+        for x in self.0.iter_mut() {
+            *x = !*x;
+        }
+        self
+    }
+}
+
+impl<const N: usize> Binary for WideMask<N> {
+    /// Formats like the unsigned integers: highest set bit first, honouring width, fill and `#`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut s = String::with_capacity(N * 64);
+        for bit in (0..N * 64).rev() {
+            if s.is_empty() && !self.has_bit(bit) {
+                continue;
+            }
+            s.push(if self.has_bit(bit) { '1' } else { '0' });
+        }
+        if s.is_empty() {
+            s.push('0');
+        }
+        f.pad_integral(true, "0b", &s)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cj_bitmask_bits::BitmaskBits;
+    use crate::cj_bitmask_vec::BitmaskVec;
+    use crate::cj_wide_mask::WideMask;
+    use cj_common::prelude::{Bitflag, CjMatchesMask};
+    use core::cmp::Ordering;
+
+    #[test]
+    fn test_wide_mask_bits() {
+        let mut x = WideMask::<3>::zero();
+        x.set_bit(130, true);
+        x.set_bit(2, true);
+
+        assert!(x.get_bit(130));
+        assert_eq!(x, WideMask::bit(130) | WideMask::bit(2));
+        assert_eq!(x.words(), &[0b100, 0, 0b100]);
+        assert_eq!(x.count_bits(), 2);
+        assert_eq!(x.set_bits().collect::<Vec<_>>(), vec![2, 130]);
+        assert_eq!(<WideMask<3> as BitmaskBits>::BITS, 192);
+
+        x.set_bit(130, false);
+        assert_eq!(x, WideMask::new([0b100, 0, 0]));
+    }
+
+    #[test]
+    fn test_wide_mask_ops() {
+        let a = WideMask::new([0b0011, 1]);
+        let b = WideMask::new([0b0110, 0]);
+
+        assert_eq!(a & b, WideMask::new([0b0010, 0]));
+        assert_eq!(a | b, WideMask::new([0b0111, 1]));
+        assert_eq!(a ^ b, WideMask::new([0b0101, 1]));
+        assert_eq!(!WideMask::<2>::zero(), WideMask::new([u64::MAX, u64::MAX]));
+        assert!(a.matches_mask(&WideMask::new([0b0001, 1])));
+        assert!(!a.matches_mask(&b));
+    }
+
+    #[test]
+    fn test_wide_mask_ord() {
+        // the high word decides, as for the unsigned integers
+        assert!(WideMask::new([0, 1]) > WideMask::new([u64::MAX, 0]));
+        assert!(WideMask::new([1, 1]) > WideMask::new([0, 1]));
+        assert_eq!(
+            WideMask::<2>::bit(64).cmp(&WideMask::bit(64)),
+            Ordering::Equal
+        );
+
+        let mut v = BitmaskVec::<WideMask<2>, i32>::new();
+        v.push_with_mask(WideMask::bit(64), 100);
+        v.push_with_mask(WideMask::bit(3), 101);
+        v.push_with_mask(WideMask::bit(0) | WideMask::bit(64), 102);
+        v.sort_by_mask();
+        assert_eq!(v.iter().copied().collect::<Vec<_>>(), vec![101, 100, 102]);
+    }
+
+    #[test]
+    fn test_wide_mask_binary() {
+        assert_eq!(format!("{:b}", WideMask::<2>::zero()), "0");
+        assert_eq!(format!("{:#b}", WideMask::new([0b101, 0])), "0b101");
+        assert_eq!(format!("{:08b}", WideMask::new([0b101, 0])), "00000101");
+        assert_eq!(
+            format!("{:b}", WideMask::<2>::bit(64)),
+            format!("1{:064b}", 0)
+        );
+    }
+
+    #[test]
+    fn test_wide_mask_bitmask_vec() {
+        let mut v = BitmaskVec::<WideMask<4>, i32>::new();
+        v.push_with_mask(WideMask::bit(255), 100);
+        v.push_with_mask(WideMask::bit(255) | WideMask::bit(1), 101);
+        v.push(102);
+
+        assert_eq!(v.count_matching(&WideMask::bit(255)), 2);
+        assert_eq!(v.indices_matching(&WideMask::bit(1)), vec![1]);
+        assert_eq!(v.mask_union(), WideMask::bit(255) | WideMask::bit(1));
+    }
+}
@@ -153,15 +153,171 @@
 //!     assert_eq!(total_2, total * 2);
 //! ```
 
+extern crate alloc;
+
+/// arbitrary crate support for fuzzing (requires the arbitrary feature)
+#[cfg(feature = "arbitrary")]
+pub mod cj_bitmask_arbitrary;
+/// Fixed capacity, stack allocated BitmaskVec
+pub mod cj_bitmask_array_vec;
+/// Arrow RecordBatch conversion (requires the arrow feature)
+#[cfg(feature = "arrow")]
+pub mod cj_bitmask_arrow;
+/// bitflags crate interoperability (requires the bitflags feature)
+#[cfg(feature = "bitflags")]
+pub mod cj_bitmask_bitflags;
+/// Bit level operations on bitmask types
+pub mod cj_bitmask_bits;
+/// BitmaskVec with maintained per bit counts
+pub mod cj_bitmask_counted_vec;
+/// Cursor for structural edits while walking a BitmaskVec
+pub mod cj_bitmask_cursor;
+/// Queue of changes applied to a BitmaskVec in one call
+pub mod cj_bitmask_deferred;
+/// Diff and patch between BitmaskVecs
+pub mod cj_bitmask_diff;
+/// C functions over BitmaskVec with byte payloads (requires the ffi feature)
+#[cfg(feature = "ffi")]
+pub mod cj_bitmask_ffi;
+/// Arena of BitmaskItem with generational keys
+pub mod cj_bitmask_gen_slab;
+/// T bucketed by exact bitmask value
+pub mod cj_bitmask_group_map;
+/// BitmaskVec with a maintained bit to index reverse index
+pub mod cj_bitmask_indexed_vec;
 /// struct that pairs bitmask with T
 pub mod cj_bitmask_item;
+/// Binary heap of BitmaskItem ordered by mask priority
+pub mod cj_bitmask_priority_queue;
+/// proptest strategies for BitmaskItem and BitmaskVec (requires the proptest feature)
+#[cfg(feature = "proptest")]
+pub mod cj_bitmask_proptest;
+/// quickcheck Arbitrary for BitmaskItem and BitmaskVec (requires the quickcheck feature)
+#[cfg(feature = "quickcheck")]
+pub mod cj_bitmask_quickcheck;
+/// Random sampling and shuffling (requires the rand feature)
+#[cfg(feature = "rand")]
+pub mod cj_bitmask_rand;
+/// Parallel sorting and bulk mask operations (requires the rayon feature)
+#[cfg(feature = "rayon")]
+pub mod cj_bitmask_rayon;
+/// Arena of BitmaskItem with stable keys
+pub mod cj_bitmask_slab;
+/// slotmap SecondaryMap integration (requires the slotmap feature)
+#[cfg(feature = "slotmap")]
+pub mod cj_bitmask_slotmap;
+/// BitmaskVec with per block mask summaries for skipping non-matching blocks
+pub mod cj_bitmask_summary_vec;
+/// BitmaskVec with O(1) removal by tombstone bit
+pub mod cj_bitmask_tombstone_vec;
+/// BitmaskVec that records modified indices
+pub mod cj_bitmask_tracked_vec;
+/// All-or-nothing changes to a BitmaskVec
+pub mod cj_bitmask_transaction;
 /// Vec of BitmaskItem
 pub mod cj_bitmask_vec;
+/// Double-ended queue of BitmaskItem
+pub mod cj_bitmask_vec_deque;
+/// BitmaskVec variant run-length encoding its bitmasks
+pub mod cj_bitmask_vec_rle;
+/// BitmaskVec variant storing bitmasks and T in separate buffers
+pub mod cj_bitmask_vec_soa;
+/// Mask column shared by typed item columns, queried as tuples
+pub mod cj_bitmask_world;
+/// Thread safe append only BitmaskVec
+pub mod cj_concurrent_bitmask_vec;
+/// Copy-on-write BitmaskVec with O(1) clones
+pub mod cj_cow_bitmask_vec;
+/// BitmaskVec variant with bitmask width chosen at runtime
+pub mod cj_dyn_bitmask_vec;
+/// Error returned by the fallible BitmaskVec methods
+pub mod cj_error;
+/// Immutable, cheaply shareable BitmaskVec snapshot
+pub mod cj_frozen_bitmask_vec;
+/// Compound boolean filters over bitmasks
+pub mod cj_mask_expr;
+/// Binary, octal and hex rendering of bitmasks
+pub mod cj_mask_format;
+/// Parsing of binary, hex and decimal bitmask literals
+pub mod cj_mask_parse;
+/// Immutable BitmaskVec whose updates share structure with the previous version
+pub mod cj_persistent_bitmask_vec;
+/// BitmaskVec with inline storage for small sizes (requires the smallvec feature)
+#[cfg(feature = "smallvec")]
+pub mod cj_small_bitmask_vec;
+/// BitmaskVec kept sorted by bitmask
+pub mod cj_sorted_bitmask_vec;
+/// Allowed moves between bitmask states, and a BitmaskVec that only makes those moves
+pub mod cj_state_machine;
+/// Bitmask type wider than 128 bits
+pub mod cj_wide_mask;
+
+/// Derives named bits of an unsigned integer bitmask for a fieldless enum (requires the derive feature)
+/// ```
+/// use cj_bitmask_vec::prelude::*;
+///
+/// #[derive(BitmaskFlags, Clone, Copy)]
+/// #[repr(u8)]
+/// enum State {
+///     Queued,
+///     Running,
+///     Failed,
+/// }
+///
+/// let mut v = BitmaskVec::<u8, i32>::new();
+/// v.push_with_mask(State::Queued.bit(), 100);
+/// v.push_with_mask(State::Running | State::Failed, 101);
+///
+/// assert_eq!(v.count_matching(&State::Failed.bit()), 1);
+/// ```
+#[cfg(feature = "derive")]
+pub use cj_bitmask_vec_derive::BitmaskFlags;
 
 /// easiest way to import all functionality
 pub mod prelude {
+    pub use crate::cj_bitmask_array_vec::*;
+    #[cfg(feature = "arrow")]
+    pub use crate::cj_bitmask_arrow::*;
+    pub use crate::cj_bitmask_bits::*;
+    pub use crate::cj_bitmask_counted_vec::*;
+    pub use crate::cj_bitmask_cursor::*;
+    pub use crate::cj_bitmask_deferred::*;
+    pub use crate::cj_bitmask_diff::*;
+    pub use crate::cj_bitmask_gen_slab::*;
+    pub use crate::cj_bitmask_group_map::*;
+    pub use crate::cj_bitmask_indexed_vec::*;
     pub use crate::cj_bitmask_item::*;
+    pub use crate::cj_bitmask_priority_queue::*;
+    #[cfg(feature = "proptest")]
+    pub use crate::cj_bitmask_proptest::*;
+    pub use crate::cj_bitmask_slab::*;
+    #[cfg(feature = "slotmap")]
+    pub use crate::cj_bitmask_slotmap::*;
+    pub use crate::cj_bitmask_summary_vec::*;
+    pub use crate::cj_bitmask_tombstone_vec::*;
+    pub use crate::cj_bitmask_tracked_vec::*;
+    pub use crate::cj_bitmask_transaction::*;
     pub use crate::cj_bitmask_vec::*;
+    pub use crate::cj_bitmask_vec_deque::*;
+    pub use crate::cj_bitmask_vec_rle::*;
+    pub use crate::cj_bitmask_vec_soa::*;
+    pub use crate::cj_bitmask_world::*;
+    pub use crate::cj_concurrent_bitmask_vec::*;
+    pub use crate::cj_cow_bitmask_vec::*;
+    pub use crate::cj_dyn_bitmask_vec::*;
+    pub use crate::cj_error::*;
+    pub use crate::cj_frozen_bitmask_vec::*;
+    pub use crate::cj_mask_expr::*;
+    pub use crate::cj_mask_format::*;
+    pub use crate::cj_mask_parse::*;
+    pub use crate::cj_persistent_bitmask_vec::*;
+    #[cfg(feature = "smallvec")]
+    pub use crate::cj_small_bitmask_vec::*;
+    pub use crate::cj_sorted_bitmask_vec::*;
+    pub use crate::cj_state_machine::*;
+    pub use crate::cj_wide_mask::*;
+    #[cfg(feature = "derive")]
+    pub use crate::BitmaskFlags;
 }
 
 #[doc = include_str!("../README.md")]